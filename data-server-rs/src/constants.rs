@@ -0,0 +1,5 @@
+pub const DEFAULT_LISTEN_ADDR: &str = "127.0.0.1:3000";
+pub const DEFAULT_DB_PATH: &str = "./data/db.json";
+
+/// Name of the collection served at `/data`, which is the key of the record array in the db file
+pub const COLLECTION: &str = "data";
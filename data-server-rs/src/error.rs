@@ -0,0 +1,2 @@
+#[allow(unused)]
+pub use anyhow::{anyhow, bail, ensure, Context, Result};
@@ -0,0 +1,199 @@
+use crate::{constants::COLLECTION, log::*, store::Store};
+use axum::{
+  extract::{Path, Query, State},
+  http::StatusCode,
+  response::{IntoResponse, Response},
+  routing::get,
+  Json, Router,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+pub type SharedStore = Arc<Mutex<Store>>;
+
+/// Page size when only `_page` is given, as in json-server
+const DEFAULT_PAGE_LIMIT: usize = 10;
+const TOTAL_COUNT_HEADER: &str = "x-total-count";
+
+#[derive(Debug)]
+pub enum ApiError {
+  NotFound,
+  BadRequest(&'static str),
+  Internal(anyhow::Error),
+}
+impl IntoResponse for ApiError {
+  fn into_response(self) -> Response {
+    match self {
+      ApiError::NotFound => (StatusCode::NOT_FOUND, Json(Value::Object(Default::default()))).into_response(),
+      ApiError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg).into_response(),
+      ApiError::Internal(e) => {
+        error!("{e}");
+        (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error").into_response()
+      }
+    }
+  }
+}
+
+/// Routes compatible with json-server for the `data` collection
+pub fn router(store: SharedStore) -> Router {
+  Router::new()
+    .route(&format!("/{COLLECTION}"), get(list_records).post(create_record))
+    .route(
+      &format!("/{COLLECTION}/{{id}}"),
+      get(get_record).put(update_record).delete(delete_record),
+    )
+    .with_state(store)
+}
+
+/// Paging by `_page` (from 1) and `_limit` as in json-server 0.17
+#[derive(Deserialize, Debug)]
+struct ListQuery {
+  _page: Option<usize>,
+  _limit: Option<usize>,
+}
+
+async fn list_records(State(store): State<SharedStore>, Query(query): Query<ListQuery>) -> impl IntoResponse {
+  let records = store.lock().await.list();
+  let total = records.len();
+  let (start, limit) = match (query._page, query._limit) {
+    (Some(page), limit) => {
+      let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+      (page.max(1).saturating_sub(1).saturating_mul(limit), limit)
+    }
+    (None, Some(limit)) => (0, limit),
+    (None, None) => (0, total),
+  };
+  let page = records.into_iter().skip(start).take(limit).collect::<Vec<_>>();
+  ([(TOTAL_COUNT_HEADER, total.to_string())], Json(page))
+}
+
+async fn get_record(State(store): State<SharedStore>, Path(id): Path<String>) -> Result<Json<Value>, ApiError> {
+  let id = parse_id(&id)?;
+  store.lock().await.get(id).map(Json).ok_or(ApiError::NotFound)
+}
+
+async fn update_record(
+  State(store): State<SharedStore>,
+  Path(id): Path<String>,
+  Json(body): Json<Value>,
+) -> Result<Json<Value>, ApiError> {
+  let id = parse_id(&id)?;
+  let Value::Object(record) = body else {
+    return Err(ApiError::BadRequest("Record must be a JSON object"));
+  };
+  let record = store.lock().await.update(id, record).map_err(ApiError::Internal)?;
+  let record = record.ok_or(ApiError::NotFound)?;
+  debug!("Updated: {record}");
+  Ok(Json(record))
+}
+
+async fn delete_record(State(store): State<SharedStore>, Path(id): Path<String>) -> Result<Json<Value>, ApiError> {
+  let id = parse_id(&id)?;
+  let record = store.lock().await.remove(id).map_err(ApiError::Internal)?;
+  let record = record.ok_or(ApiError::NotFound)?;
+  debug!("Deleted: {record}");
+  // json-server responds with an empty object
+  Ok(Json(Value::Object(Default::default())))
+}
+
+fn parse_id(id: &str) -> Result<u64, ApiError> {
+  id.parse::<u64>().map_err(|_| ApiError::NotFound)
+}
+
+async fn create_record(
+  State(store): State<SharedStore>,
+  Json(body): Json<Value>,
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+  let Value::Object(record) = body else {
+    return Err(ApiError::BadRequest("Record must be a JSON object"));
+  };
+  let record = store.lock().await.insert(record).map_err(ApiError::Internal)?;
+  debug!("Registered: {record}");
+  Ok((StatusCode::CREATED, Json(record)))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use axum::{body::Body, http::Request};
+  use http_body_util::BodyExt;
+  use serde_json::json;
+  use tower::ServiceExt;
+
+  async fn call(router: &Router, method: &str, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+    let req = Request::builder().method(method).uri(uri);
+    let req = match body {
+      Some(b) => req
+        .header("content-type", "application/json")
+        .body(Body::from(b.to_string())),
+      None => req.body(Body::empty()),
+    }
+    .unwrap();
+    let res = router.clone().oneshot(req).await.unwrap();
+    let status = res.status();
+    let bytes = res.into_body().collect().await.unwrap().to_bytes();
+    (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+  }
+
+  #[tokio::test]
+  async fn post_and_get() {
+    let router = router(Arc::new(Mutex::new(Store::new_in_memory())));
+
+    let body = json!({"data": "abc", "iv": "def", "salt": "ghi"});
+    let (status, res) = call(&router, "POST", "/data", Some(body)).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(res["id"], 1);
+
+    let (status, res) = call(&router, "GET", "/data/1", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(res, json!({"id": 1, "data": "abc", "iv": "def", "salt": "ghi"}));
+
+    let (status, res) = call(&router, "GET", "/data", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(res.as_array().unwrap().len(), 1);
+
+    let (status, _) = call(&router, "GET", "/data/2", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = call(&router, "GET", "/data/abc", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = call(&router, "POST", "/data", Some(json!(["not", "object"]))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+  }
+
+  #[tokio::test]
+  async fn page_put_and_delete() {
+    let router = router(Arc::new(Mutex::new(Store::new_in_memory())));
+    for i in 0..5 {
+      call(&router, "POST", "/data", Some(json!({ "data": i }))).await;
+    }
+
+    let (_, res) = call(&router, "GET", "/data?_page=2&_limit=2", None).await;
+    assert_eq!(res, json!([{"data": 2, "id": 3}, {"data": 3, "id": 4}]));
+    let (_, res) = call(&router, "GET", "/data?_page=3&_limit=2", None).await;
+    assert_eq!(res.as_array().unwrap().len(), 1);
+    let (_, res) = call(&router, "GET", "/data?_page=4&_limit=2", None).await;
+    assert_eq!(res, json!([]));
+    let (_, res) = call(&router, "GET", "/data?_limit=3", None).await;
+    assert_eq!(res.as_array().unwrap().len(), 3);
+
+    let req = Request::builder().uri("/data?_page=1").body(Body::empty()).unwrap();
+    let res = router.clone().oneshot(req).await.unwrap();
+    assert_eq!(res.headers()[TOTAL_COUNT_HEADER], "5");
+
+    let (status, res) = call(&router, "PUT", "/data/2", Some(json!({"data": "new"}))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(res, json!({"data": "new", "id": 2}));
+    let (status, _) = call(&router, "PUT", "/data/9", Some(json!({"data": "new"}))).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, res) = call(&router, "DELETE", "/data/2", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(res, json!({}));
+    let (status, _) = call(&router, "GET", "/data/2", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = call(&router, "DELETE", "/data/2", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+  }
+}
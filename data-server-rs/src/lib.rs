@@ -0,0 +1,35 @@
+//! Local data server implementing the `/data` contract of json-server used by sample-03 and sample-04,
+//! so that their Rust clients work and can be tested without Node.js.
+
+mod constants;
+mod error;
+mod handler;
+mod log;
+mod store;
+
+pub use crate::{
+  constants::{DEFAULT_DB_PATH, DEFAULT_LISTEN_ADDR},
+  handler::{router, SharedStore},
+  log::init_logger,
+  store::Store,
+};
+use crate::{error::*, log::*};
+use std::sync::Arc;
+use tokio::{net::TcpListener, sync::Mutex};
+
+/// Serve the store on the given listener until the server is down
+pub async fn serve(listener: TcpListener, store: Store) -> Result<()> {
+  info!("Listening on {}", listener.local_addr()?);
+  let router = router(Arc::new(Mutex::new(store)));
+  axum::serve(listener, router.into_make_service()).await?;
+  Ok(())
+}
+
+/// Spawn a server with an in-memory store on an ephemeral local port, and return the URL of `/data`.
+/// This is mainly for integration tests of the clients.
+pub async fn spawn_in_memory() -> Result<String> {
+  let listener = TcpListener::bind("127.0.0.1:0").await?;
+  let addr = listener.local_addr()?;
+  tokio::spawn(serve(listener, Store::new_in_memory()));
+  Ok(format!("http://{addr}/{}", constants::COLLECTION))
+}
@@ -0,0 +1,35 @@
+use clap::Parser;
+use data_server::{init_logger, serve, Store, DEFAULT_DB_PATH, DEFAULT_LISTEN_ADDR};
+use std::{net::SocketAddr, path::PathBuf};
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct ClapArgs {
+  /// Listen socket
+  #[arg(short, long, default_value = DEFAULT_LISTEN_ADDR)]
+  listen_address: String,
+
+  /// JSON file storing records, in the layout of sample-03/data/db.template.json (created if absent)
+  #[arg(short, long, default_value = DEFAULT_DB_PATH)]
+  db: PathBuf,
+
+  /// Keep records only in memory instead of the JSON file
+  #[arg(short, long, action = clap::ArgAction::SetTrue)]
+  in_memory: bool,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+  let _ = include_str!("../Cargo.toml");
+  init_logger();
+  let args = ClapArgs::parse();
+
+  let listen_socket = args.listen_address.parse::<SocketAddr>()?;
+  let store = if args.in_memory {
+    Store::new_in_memory()
+  } else {
+    Store::open(&args.db)?
+  };
+  let listener = tokio::net::TcpListener::bind(listen_socket).await?;
+  serve(listener, store).await
+}
@@ -0,0 +1,198 @@
+use crate::{constants::COLLECTION, error::*};
+use serde_json::{Map, Value};
+use std::path::{Path, PathBuf};
+
+/// Records kept in the layout of `sample-03/data/db.template.json`, i.e., `{"data": [...], ...}`.
+/// Each record in `data` is a JSON object with a numeric `id` assigned by the store.
+/// Other top-level entries like `author` are preserved as they are.
+#[derive(Debug)]
+pub struct Store {
+  db: Map<String, Value>,
+  path: Option<PathBuf>,
+}
+
+impl Store {
+  /// Store that is never written to a file
+  pub fn new_in_memory() -> Self {
+    let mut db = Map::new();
+    db.insert(COLLECTION.to_string(), Value::Array(vec![]));
+    Self { db, path: None }
+  }
+
+  /// Load the db file, or create it with an empty collection if it does not exist
+  pub fn open(path: &Path) -> Result<Self> {
+    if !path.exists() {
+      let store = Self {
+        path: Some(path.to_path_buf()),
+        ..Self::new_in_memory()
+      };
+      store.persist()?;
+      return Ok(store);
+    }
+    let content = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let Value::Object(mut db) = serde_json::from_str::<Value>(&content)? else {
+      bail!("{} is not a JSON object", path.display());
+    };
+    match db.get(COLLECTION) {
+      None => {
+        db.insert(COLLECTION.to_string(), Value::Array(vec![]));
+      }
+      Some(Value::Array(records)) => {
+        ensure!(
+          records.iter().all(|r| r.get("id").and_then(Value::as_u64).is_some()),
+          "Every record in \"{COLLECTION}\" must have a numeric id"
+        );
+      }
+      Some(_) => bail!("\"{COLLECTION}\" in {} is not an array", path.display()),
+    }
+    Ok(Self {
+      db,
+      path: Some(path.to_path_buf()),
+    })
+  }
+
+  pub fn list(&self) -> Vec<Value> {
+    self.records().to_vec()
+  }
+
+  pub fn get(&self, id: u64) -> Option<Value> {
+    self.records().iter().find(|r| record_id(r) == Some(id)).cloned()
+  }
+
+  /// Register a new record with the next id, and return it as stored
+  pub fn insert(&mut self, mut record: Map<String, Value>) -> Result<Value> {
+    let id = self.records().iter().filter_map(record_id).max().unwrap_or(0) + 1;
+    record.insert("id".to_string(), Value::from(id));
+    let record = Value::Object(record);
+    self.records_mut().push(record.clone());
+    self.persist()?;
+    Ok(record)
+  }
+
+  /// Replace the record of `id` keeping the id, and return it as stored if exists
+  pub fn update(&mut self, id: u64, mut record: Map<String, Value>) -> Result<Option<Value>> {
+    let Some(slot) = self.records_mut().iter_mut().find(|r| record_id(r) == Some(id)) else {
+      return Ok(None);
+    };
+    record.insert("id".to_string(), Value::from(id));
+    *slot = Value::Object(record);
+    let record = slot.clone();
+    self.persist()?;
+    Ok(Some(record))
+  }
+
+  /// Remove the record of `id`, and return it if exists
+  pub fn remove(&mut self, id: u64) -> Result<Option<Value>> {
+    let Some(pos) = self.records().iter().position(|r| record_id(r) == Some(id)) else {
+      return Ok(None);
+    };
+    let record = self.records_mut().remove(pos);
+    self.persist()?;
+    Ok(Some(record))
+  }
+
+  fn records(&self) -> &Vec<Value> {
+    match self.db.get(COLLECTION) {
+      Some(Value::Array(records)) => records,
+      _ => unreachable!("collection is validated on open"),
+    }
+  }
+
+  fn records_mut(&mut self) -> &mut Vec<Value> {
+    match self.db.get_mut(COLLECTION) {
+      Some(Value::Array(records)) => records,
+      _ => unreachable!("collection is validated on open"),
+    }
+  }
+
+  /// Write the whole db to a temporary file and rename it, so that the db file is never left half-written
+  fn persist(&self) -> Result<()> {
+    let Some(path) = &self.path else {
+      return Ok(());
+    };
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+      std::fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, serde_json::to_string_pretty(&self.db)?)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+  }
+}
+
+fn record_id(record: &Value) -> Option<u64> {
+  record.get("id").and_then(Value::as_u64)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  fn object(v: Value) -> Map<String, Value> {
+    v.as_object().unwrap().clone()
+  }
+
+  #[test]
+  fn insert_and_get() -> Result<()> {
+    let mut store = Store::new_in_memory();
+    let r1 = store.insert(object(json!({"data": "hello"})))?;
+    let r2 = store.insert(object(json!({"data": "world", "id": 100})))?;
+    assert_eq!(r1, json!({"data": "hello", "id": 1}));
+    assert_eq!(r2, json!({"data": "world", "id": 2}));
+    assert_eq!(store.get(2), Some(r2));
+    assert_eq!(store.get(3), None);
+    assert_eq!(store.list().len(), 2);
+    Ok(())
+  }
+
+  #[test]
+  fn update_and_remove() -> Result<()> {
+    let mut store = Store::new_in_memory();
+    store.insert(object(json!({"data": "hello"})))?;
+    store.insert(object(json!({"data": "world"})))?;
+
+    let updated = store.update(1, object(json!({"data": "bye", "id": 5})))?;
+    assert_eq!(updated, Some(json!({"data": "bye", "id": 1})));
+    assert_eq!(store.get(1), updated);
+    assert_eq!(store.update(3, object(json!({"data": "none"})))?, None);
+
+    assert_eq!(store.remove(2)?, Some(json!({"data": "world", "id": 2})));
+    assert_eq!(store.remove(2)?, None);
+    assert_eq!(store.list().len(), 1);
+    // as in json-server, the next id follows the largest remaining one
+    assert_eq!(store.insert(object(json!({"data": "again"})))?["id"], 2);
+    Ok(())
+  }
+
+  #[test]
+  fn persist_in_template_layout() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("data-server-test-{}", std::process::id()));
+    let path = dir.join("db.json");
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(
+      &path,
+      r#"{"data": [{"id": 3, "data": "x"}], "author": {"name": "jun kurihara"}}"#,
+    )?;
+
+    let mut store = Store::open(&path)?;
+    let record = store.insert(object(json!({"data": "y", "iv": "z"})))?;
+    assert_eq!(record["id"], 4);
+
+    let reopened = Store::open(&path)?;
+    assert_eq!(reopened.list().len(), 2);
+    assert_eq!(reopened.get(4), Some(record));
+    let db: Value = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+    assert_eq!(db["author"]["name"], "jun kurihara");
+
+    std::fs::write(&path, r#"{"data": {}}"#)?;
+    assert!(Store::open(&path).is_err());
+
+    std::fs::remove_dir_all(&dir)?;
+    let store = Store::open(&path)?;
+    assert!(store.list().is_empty());
+    assert!(path.exists());
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+  }
+}
@@ -0,0 +1,51 @@
+//! Bit-flipping demonstration. CBC decrypts a block as `D(C[i]) xor C[i-1]` with `C[-1]` the IV, so flipping
+//! a bit of the IV or of the previous ciphertext block flips the same bit of the plaintext, at the cost of
+//! garbling the previous block in the latter case. Nothing detects the edit unless the object is authenticated.
+
+use crate::error::*;
+use base64::{engine::general_purpose, Engine as _};
+use seceng_crypto::{envelope::Envelope, symmetric::Algorithm};
+use serde_json::Value;
+
+const BLOCK_LEN: usize = 16;
+const GCM_TAG_LEN: usize = 16;
+
+/// Object edited without the key so that `known` at `offset` of its plaintext decrypts into `desired`,
+/// and the indices of the plaintext blocks garbled by the edit
+pub fn flip(record: &Value, known: &[u8], desired: &[u8], offset: usize) -> Result<(Value, Vec<usize>)> {
+  ensure!(
+    known.len() == desired.len(),
+    "Known and desired plaintexts must have the same length"
+  );
+  let envelope = Envelope::upgrade_json(record)?;
+  let (cipher, encrypted) = (envelope.cipher()?, envelope.encrypted());
+  let (mut iv, mut data) = (encrypted.iv, encrypted.data);
+  let mut garbled = vec![];
+  for (i, delta) in known.iter().zip(desired).map(|(k, d)| k ^ d).enumerate() {
+    let pos = offset + i;
+    match cipher {
+      Algorithm::AesCbc => {
+        let (block, index) = (pos / BLOCK_LEN, pos % BLOCK_LEN);
+        ensure!(block < data.len() / BLOCK_LEN, "Offset beyond the ciphertext");
+        if block == 0 {
+          iv[index] ^= delta;
+        } else {
+          data[(block - 1) * BLOCK_LEN + index] ^= delta;
+          if delta != 0 && !garbled.contains(&(block - 1)) {
+            garbled.push(block - 1);
+          }
+        }
+      }
+      // The keystream of the counter mode is XORed byte by byte, but the tag no longer matches
+      Algorithm::AesGcm => {
+        ensure!(pos + GCM_TAG_LEN < data.len(), "Offset beyond the ciphertext");
+        data[pos] ^= delta;
+      }
+    }
+  }
+
+  let mut modified = record.clone();
+  modified["iv"] = general_purpose::STANDARD.encode(&iv).into();
+  modified["data"] = general_purpose::STANDARD.encode(&data).into();
+  Ok((modified, garbled))
+}
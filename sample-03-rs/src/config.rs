@@ -1,4 +1,6 @@
-use clap::{ArgAction, Parser, Subcommand};
+use crate::oracle::{DEFAULT_ORACLE_ADDR, DEFAULT_ORACLE_URL};
+use clap::{ArgAction, Args, Parser, Subcommand};
+use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -7,6 +9,7 @@ pub struct ClapArgs {
   pub subcommand: SubCommands,
 }
 
+#[allow(non_snake_case, non_camel_case_types)]
 #[derive(Debug, Subcommand)]
 pub enum SubCommands {
   /// Get ciphertext or plaintext object from the json server
@@ -18,13 +21,12 @@ pub enum SubCommands {
     #[arg(short, long)]
     key: Option<String>,
 
-    /// Decrypt given data by AES-CBC
+    /// Decrypt given data by the algorithm recorded in the object (AES-CBC if absent)
     #[arg(short, long, action = ArgAction::SetTrue)]
     decrypt: bool,
 
-    /// Get from the preset remote server (e2e.secarchlab.net) otherwise localhost:3000
-    #[arg(short, long, action = ArgAction::SetTrue)]
-    remote: bool,
+    #[command(flatten)]
+    server: ServerArgs,
   },
   /// Post ciphertext or plaintext object to the json server
   Post {
@@ -35,12 +37,147 @@ pub enum SubCommands {
     #[arg(short, long)]
     key: Option<String>,
 
-    /// Encrypt given data by AES-CBC
+    /// Encrypt given data by AES-CBC or AES-GCM
     #[arg(short, long, action = ArgAction::SetTrue)]
     encrypt: bool,
 
-    /// Post to the preset remote server (e2e.secarchlab.net) otherwise localhost:3000
+    /// Encryption algorithm, either 'AES-CBC' or 'AES-GCM' (AES-GCM detects tampering) [default: the profile's cipher or AES-CBC]
+    #[arg(short, long)]
+    alg: Option<String>,
+
+    /// Post the ciphertext as a versioned envelope instead of the legacy object of sample-03
+    #[arg(short = 'E', long, action = ArgAction::SetTrue)]
+    envelope: bool,
+
+    /// Calibrate the PBKDF2 iteration count so that the key derivation takes about this time on this machine (envelope only)
+    #[arg(long, value_name = "MS", requires = "envelope")]
+    kdf_target_ms: Option<u64>,
+
+    #[command(flatten)]
+    server: ServerArgs,
+  },
+  /// List objects on the json server page by page, showing whether and how each is encrypted
+  List {
+    /// Number of objects fetched per page
+    #[arg(short, long, default_value = "10")]
+    limit: usize,
+
+    #[command(flatten)]
+    server: ServerArgs,
+  },
+  /// Re-encrypt an object with a fresh IV and salt, optionally replacing its data, and put it back to the json server
+  Update {
+    /// Id number of the target data on the server
+    id: usize,
+
+    /// New plaintext data string (the current data is kept if absent)
+    data: Option<String>,
+
+    /// Key string, required for encrypted objects
+    #[arg(short, long)]
+    key: Option<String>,
+
+    /// Encryption algorithm, either 'AES-CBC' or 'AES-GCM' [default: the algorithm recorded in the object]
+    #[arg(short, long)]
+    alg: Option<String>,
+
+    /// Upgrade the object to a versioned envelope [default: kept in the current format]
+    #[arg(short = 'E', long, action = ArgAction::SetTrue)]
+    envelope: bool,
+
+    #[command(flatten)]
+    server: ServerArgs,
+  },
+  /// Delete an object from the json server
+  Delete {
+    /// Id number of the target data on the server
+    id: usize,
+
+    #[command(flatten)]
+    server: ServerArgs,
+  },
+  /// Benchmark PBKDF2-SHA256 and pick its iteration count for a target derivation time on this machine
+  Calibrate_Kdf {
+    /// Target time of a key derivation in milliseconds
+    #[arg(short, long, value_name = "MS", default_value = "250")]
+    target_ms: u64,
+  },
+  /// Edit an encrypted object without the key so that a known part of its plaintext decrypts into a chosen text
+  Flip_Bits {
+    /// Id number of the target data on the server
+    id: usize,
+
+    /// Known plaintext at the offset
+    #[arg(long)]
+    known: String,
+
+    /// Desired plaintext replacing the known one, of the same length
+    #[arg(long)]
+    desired: String,
+
+    /// Byte offset of the known plaintext in the data
+    #[arg(short, long, default_value = "0")]
+    offset: usize,
+
+    /// Put the modified object back to the json server instead of only printing it
     #[arg(short, long, action = ArgAction::SetTrue)]
-    remote: bool,
+    write: bool,
+
+    #[command(flatten)]
+    server: ServerArgs,
   },
+  /// Encrypt the pixels of a binary PPM/PGM image by AES-ECB, AES-CBC and AES-CTR, and write the three images
+  Visualize_Modes {
+    /// Binary PPM (P6) or PGM (P5) image
+    input: PathBuf,
+
+    /// Key string
+    #[arg(short, long)]
+    key: String,
+
+    /// Directory of the output images [default: the directory of the input]
+    #[arg(short, long)]
+    out_dir: Option<PathBuf>,
+  },
+  /// Serve a padding oracle, which holds the key and answers only whether a posted object decrypts
+  Padding_Oracle {
+    /// Key string
+    #[arg(short, long)]
+    key: String,
+
+    /// Listen socket of the oracle
+    #[arg(short, long, default_value = DEFAULT_ORACLE_ADDR)]
+    listen_address: String,
+  },
+  /// Recover the plaintext of an AES-CBC object without the key by querying the padding oracle
+  Padding_Oracle_Attack {
+    /// Id number of the target data on the server
+    id: usize,
+
+    /// URL of the padding oracle
+    #[arg(short, long, default_value = DEFAULT_ORACLE_URL)]
+    oracle: String,
+
+    #[command(flatten)]
+    server: ServerArgs,
+  },
+}
+
+#[derive(Debug, Args)]
+pub struct ServerArgs {
+  /// Use the preset remote server (e2e.secarchlab.net) otherwise the profile's server or localhost:3000
+  #[arg(short, long, action = ArgAction::SetTrue)]
+  pub remote: bool,
+
+  /// URL of the data endpoint like http://localhost:3000/data, taking precedence over --remote and the profile
+  #[arg(short, long)]
+  pub server: Option<String>,
+
+  /// Name of the profile to use [default: the default profile in the profiles file]
+  #[arg(short = 'P', long)]
+  pub profile: Option<String>,
+
+  /// Profiles file [default: ~/.config/seceng/profiles.toml]
+  #[arg(long)]
+  pub profiles: Option<PathBuf>,
 }
@@ -1,21 +1,23 @@
+mod bitflip;
 mod config;
-mod crypto;
 mod error;
-mod key;
+mod oracle;
+mod visualize;
 
-use crate::{
-  crypto::{decrypt, encrypt, Encrypted},
-  error::*,
-  key::BinaryKey,
-};
+use crate::error::*;
 use base64::{engine::general_purpose, Engine as _};
 use clap::Parser;
-use config::{ClapArgs, SubCommands};
-use serde::Deserialize;
-use std::collections::HashMap;
-
-const LOCAL_SRV: &str = "http://localhost:3000/data";
-const REMOTE_SRV: &str = "https://e2e.secarchlab.net/data";
+use config::{ClapArgs, ServerArgs, SubCommands};
+use seceng_client::profile::{Profile, Profiles};
+use seceng_crypto::{
+  calibrate::{calibrate_pbkdf2, measure},
+  envelope::{Envelope, KeyAgreement, Sample03Object, Sample04Object, WrappedKey},
+  kdf::{random_salt, BinaryKey, KdfHash, KdfParams, ITERATION},
+  symmetric::{Algorithm, KEY_LEN},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::HashSet, time::Duration};
 
 #[tokio::main]
 pub async fn main() -> Result<()> {
@@ -27,84 +29,416 @@ pub async fn main() -> Result<()> {
       id,
       key,
       decrypt,
-      remote,
+      server,
     } => {
       if (key.is_none() && *decrypt) || (key.is_some() && !*decrypt) {
         bail!("when -d is specified, -k must be simultaneously specified")
       }
-      get_data(id, key.as_ref().map(|x| x.as_str()), remote).await?;
+      let (profile, client) = load_profile(server)?;
+      let srv = profile.server_url(server.server.as_deref(), server.remote);
+      get_data(id, key.as_ref().map(|x| x.as_str()), &client, &srv).await?;
     }
     SubCommands::Post {
       data,
       key,
       encrypt,
-      remote,
+      alg,
+      envelope,
+      kdf_target_ms,
+      server,
     } => {
       if (key.is_none() && *encrypt) || (key.is_some() && !*encrypt) {
         bail!("when -e is specified, -k must be simultaneously specified")
       }
-      post_data(data, key.as_ref().map(|x| x.as_str()), remote).await?;
+      let (profile, client) = load_profile(server)?;
+      let srv = profile.server_url(server.server.as_deref(), server.remote);
+      let alg = match alg.as_ref().or(profile.cipher.as_ref()) {
+        Some(alg) => alg.parse::<Algorithm>()?,
+        None => Algorithm::default(),
+      };
+      // Plaintext is posted without any key to derive, so there is nothing to calibrate
+      let iterations = match (kdf_target_ms, key) {
+        (Some(ms), Some(_)) => calibrate_iterations(&Duration::from_millis(*ms))?.0,
+        _ => ITERATION,
+      };
+      let kdf = KdfParams::new_pbkdf2(&random_salt(), &KdfHash::Sha256, iterations);
+      post_data(
+        data,
+        key.as_ref().map(|x| x.as_str()),
+        &alg,
+        &kdf,
+        *envelope,
+        &client,
+        &srv,
+      )
+      .await?;
+    }
+    SubCommands::List { limit, server } => {
+      let (profile, client) = load_profile(server)?;
+      let srv = profile.server_url(server.server.as_deref(), server.remote);
+      list_data(limit, &client, &srv).await?;
+    }
+    SubCommands::Update {
+      id,
+      data,
+      key,
+      alg,
+      envelope,
+      server,
+    } => {
+      let (profile, client) = load_profile(server)?;
+      let srv = profile.server_url(server.server.as_deref(), server.remote);
+      let alg = alg.as_ref().map(|alg| alg.parse::<Algorithm>()).transpose()?;
+      update_data(
+        id,
+        data.as_ref().map(|x| x.as_str()),
+        key.as_ref().map(|x| x.as_str()),
+        alg.as_ref(),
+        *envelope,
+        &client,
+        &srv,
+      )
+      .await?;
+    }
+    SubCommands::Delete { id, server } => {
+      let (profile, client) = load_profile(server)?;
+      let srv = profile.server_url(server.server.as_deref(), server.remote);
+      delete_data(id, &client, &srv).await?;
+    }
+    SubCommands::Calibrate_Kdf { target_ms } => {
+      calibrate_iterations(&Duration::from_millis(*target_ms))?;
+    }
+    SubCommands::Flip_Bits {
+      id,
+      known,
+      desired,
+      offset,
+      write,
+      server,
+    } => {
+      let (profile, client) = load_profile(server)?;
+      let srv = profile.server_url(server.server.as_deref(), server.remote);
+      flip_data(id, known, desired, *offset, *write, &client, &srv).await?;
+    }
+    SubCommands::Visualize_Modes { input, key, out_dir } => {
+      let key = BinaryKey::try_new_pbkdf2(key, KEY_LEN, None, None, None)?;
+      visualize::visualize(input, &key.key, out_dir.as_deref())?;
+    }
+    SubCommands::Padding_Oracle { key, listen_address } => {
+      let listener = tokio::net::TcpListener::bind(listen_address.as_str()).await?;
+      oracle::serve(listener, key).await?;
+    }
+    SubCommands::Padding_Oracle_Attack { id, oracle, server } => {
+      let (profile, client) = load_profile(server)?;
+      let srv = profile.server_url(server.server.as_deref(), server.remote);
+      attack_data(id, oracle, &client, &srv).await?;
     }
   }
 
   Ok(())
 }
 
-async fn post_data(data: &str, key: Option<&str>, remote: &bool) -> Result<()> {
-  let mut body = HashMap::new();
-  match key {
-    Some(key) => {
-      println!("Encrypt data");
-      let binary_key = BinaryKey::try_new(key, 32, None)?;
-      let encrypted = encrypt(data.as_bytes(), &binary_key, None)?;
-      let data = encrypted.data_to_base64();
-      let iv = encrypted.iv_to_base64();
-      body.insert("data", data);
-      body.insert("iv", iv);
-      body.insert("salt", binary_key.salt);
-    }
-    None => {
-      body.insert("data", data.to_string());
-    }
-  }
+fn load_profile(server: &ServerArgs) -> Result<(Profile, reqwest::Client)> {
+  let profile = Profiles::load(server.profiles.as_deref())?.select(server.profile.as_deref())?;
+  let client = profile.build_client()?;
+  Ok((profile, client))
+}
 
-  let client = reqwest::Client::new();
-  let srv = if *remote { REMOTE_SRV } else { LOCAL_SRV };
-  let res = client.post(srv).json(&body).send().await?;
+async fn post_data(
+  data: &str,
+  key: Option<&str>,
+  alg: &Algorithm,
+  kdf: &KdfParams,
+  envelope: bool,
+  client: &reqwest::Client,
+  srv: &str,
+) -> Result<usize> {
+  let body = new_post_request(data, key, alg, kdf, envelope)?;
+
+  let res = client.post(srv).json(&body).send().await?.error_for_status()?;
   let post_res = res.json::<PostResponse>().await?;
   println!("Registered id: {:?}", post_res.id);
 
+  Ok(post_res.id)
+}
+
+async fn get_data(id: &usize, key: Option<&str>, client: &reqwest::Client, srv: &str) -> Result<String> {
+  let record = fetch_record(id, client, srv).await?;
+  let plaintext = decrypt_response(&record, key)?;
+  let retrieved_data = String::from_utf8_lossy(&plaintext).into_owned();
+  if std::str::from_utf8(&plaintext).is_err() {
+    // e.g., a block garbled by bit flipping
+    println!("Retrieved data is not valid UTF-8, shown with U+FFFD in place of the invalid bytes");
+  }
+  println!("Retrieved data: {retrieved_data}");
+  Ok(retrieved_data)
+}
+
+/// Decrypt the object with the given key, and put it back encrypted by a fresh IV and salt under the same KDF.
+/// Envelopes stay envelopes, and legacy objects are upgraded to envelopes if `envelope`.
+async fn update_data(
+  id: &usize,
+  data: Option<&str>,
+  key: Option<&str>,
+  alg: Option<&Algorithm>,
+  envelope: bool,
+  client: &reqwest::Client,
+  srv: &str,
+) -> Result<()> {
+  let record = fetch_record(id, client, srv).await?;
+  let recorded = record.envelope()?;
+  let recorded_alg = recorded.as_ref().map(|env| env.cipher()).transpose()?;
+  let fresh = KdfParams::new_pbkdf2(&random_salt(), &KdfHash::Sha256, ITERATION);
+  let kdf = match recorded {
+    Some(env) => KdfParams {
+      salt: fresh.salt,
+      ..env.kdf
+    },
+    None => fresh,
+  };
+  let current = decrypt_response(&record, key)?;
+  let data = match data {
+    Some(data) => data.to_string(),
+    None => String::from_utf8(current).context("Current data is not valid UTF-8")?,
+  };
+  let alg = alg.or(recorded_alg.as_ref()).copied().unwrap_or_default();
+  let envelope = envelope || record.v.is_some();
+
+  let body = new_post_request(&data, key, &alg, &kdf, envelope)?;
+  client
+    .put(format!("{srv}/{id}"))
+    .json(&body)
+    .send()
+    .await?
+    .error_for_status()?;
+  println!("Updated id: {id}");
+  Ok(())
+}
+
+/// Edit an encrypted object so that `known` at `offset` decrypts into `desired`, and put it back if `write`
+async fn flip_data(
+  id: &usize,
+  known: &str,
+  desired: &str,
+  offset: usize,
+  write: bool,
+  client: &reqwest::Client,
+  srv: &str,
+) -> Result<Value> {
+  let record = fetch_value(id, client, srv).await?;
+  let (modified, garbled) = bitflip::flip(&record, known.as_bytes(), desired.as_bytes(), offset)?;
+  println!("Modified iv: {}", modified["iv"]);
+  println!("Modified data: {}", modified["data"]);
+  if !garbled.is_empty() {
+    println!("Plaintext blocks {garbled:?} are garbled by the edit");
+  }
+  if write {
+    client
+      .put(format!("{srv}/{id}"))
+      .json(&modified)
+      .send()
+      .await?
+      .error_for_status()?;
+    println!("Updated id: {id}");
+  }
+  Ok(modified)
+}
+
+/// Recover the data of an encrypted object through the padding oracle, without the key
+async fn attack_data(id: &usize, oracle: &str, client: &reqwest::Client, srv: &str) -> Result<oracle::Recovered> {
+  let record = fetch_value(id, client, srv).await?;
+  let recovered = oracle::attack(&record, client, oracle).await?;
+  println!("Recovered data: {}", String::from_utf8_lossy(&recovered.plaintext));
+  println!("Oracle queries: {}", recovered.queries);
+  Ok(recovered)
+}
+
+async fn delete_data(id: &usize, client: &reqwest::Client, srv: &str) -> Result<()> {
+  client.delete(format!("{srv}/{id}")).send().await?.error_for_status()?;
+  println!("Deleted id: {id}");
   Ok(())
 }
 
-async fn get_data(id: &usize, key: Option<&str>, remote: &bool) -> Result<()> {
-  let client = reqwest::Client::new();
-  let srv = if *remote { REMOTE_SRV } else { LOCAL_SRV };
-  let res = client.get(format!("{srv}/{id}")).send().await?;
-  let get_res = res.json::<GetResponse>().await?;
-
-  let retrieved_data = match (key, get_res.salt, get_res.iv) {
-    (Some(key), Some(salt), Some(iv)) => {
-      println!("Decrypt data");
-      let binary_data = general_purpose::STANDARD.decode(get_res.data)?;
-      let binary_key = BinaryKey::try_new(key, 32, Some(&salt))?;
-      let binary_iv = general_purpose::STANDARD.decode(iv)?;
-      let dec = decrypt(
-        &Encrypted {
-          data: binary_data,
-          iv: binary_iv,
-        },
-        &binary_key,
-      )?;
-      String::from_utf8(dec)?
-    }
-    (None, None, None) => get_res.data,
+/// Fetch all the objects page by page, and return their ids with how they are encrypted
+async fn list_data(limit: &usize, client: &reqwest::Client, srv: &str) -> Result<Vec<(usize, String)>> {
+  ensure!(*limit > 0, "Page size must be positive");
+  let mut listed = vec![];
+  let mut ids = HashSet::new();
+  for page in 1.. {
+    let res = client
+      .get(format!("{srv}?_page={page}&_limit={limit}"))
+      .send()
+      .await?
+      .error_for_status()?;
+    let total = total_count(&res);
+    let records = res.json::<Vec<Value>>().await?;
+    let mut new_ids = false;
+    for record in &records {
+      let (id, description) = describe_record(record);
+      if !ids.insert(id) {
+        continue;
+      }
+      println!("{id}: {description}");
+      listed.push((id, description));
+      new_ids = true;
+    }
+    // A server ignoring the paging returns the same objects again, so stop also at a page without new ones
+    if records.len() < *limit || !new_ids || total.is_some_and(|total| listed.len() >= total) {
+      break;
+    }
+  }
+  Ok(listed)
+}
+
+/// Number of all the objects in `x-total-count` of the data server, if any
+fn total_count(res: &reqwest::Response) -> Option<usize> {
+  res.headers().get("x-total-count")?.to_str().ok()?.parse().ok()
+}
+
+async fn fetch_record(id: &usize, client: &reqwest::Client, srv: &str) -> Result<GetResponse> {
+  let res = client.get(format!("{srv}/{id}")).send().await?.error_for_status()?;
+  res
+    .json::<GetResponse>()
+    .await
+    .with_context(|| format!("Malformed object of id {id}"))
+}
+
+/// Object as is for the labs, which forge the fields of objects in any format of the json server
+async fn fetch_value(id: &usize, client: &reqwest::Client, srv: &str) -> Result<Value> {
+  let res = client.get(format!("{srv}/{id}")).send().await?.error_for_status()?;
+  Ok(res.json::<Value>().await?)
+}
+
+/// Plaintext object, or ciphertext as an envelope or a legacy object of sample-03.
+/// Legacy objects take only PBKDF2-SHA256 with 2048 iterations as `kdf`.
+fn new_post_request(
+  data: &str,
+  key: Option<&str>,
+  alg: &Algorithm,
+  kdf: &KdfParams,
+  envelope: bool,
+) -> Result<PostRequest> {
+  let Some(key) = key else {
+    return Ok(PostRequest {
+      data: data.to_string(),
+      ..Default::default()
+    });
+  };
+  println!("Encrypt data with {alg}");
+  let sealed = Envelope::seal(data.as_bytes(), key, kdf, alg, false)?;
+  if envelope {
+    Ok(PostRequest::from(&sealed))
+  } else {
+    Ok(PostRequest::from(Sample03Object::try_from(&sealed)?))
+  }
+}
+
+fn decrypt_response(record: &GetResponse, key: Option<&str>) -> Result<Vec<u8>> {
+  let retrieved_data = match (key, record.envelope()?) {
+    (Some(key), Some(envelope)) => {
+      println!("Decrypt data with {}", envelope.cipher);
+      envelope.open(key)?
+    }
+    (None, None) => record.data.as_bytes().to_vec(),
     _ => {
-      bail!("Invalid data format or ungiven key for the id: {}", get_res.id)
+      bail!("Invalid data format or ungiven key for the id: {}", record.id)
     }
   };
-  println!("Retrieved data: {retrieved_data}");
-  Ok(())
+  Ok(retrieved_data)
+}
+
+/// PBKDF2-SHA256 iteration count calibrated to the target time, printed with the measured time
+fn calibrate_iterations(target: &Duration) -> Result<(u32, Duration)> {
+  let iterations = calibrate_pbkdf2(&KdfHash::Sha256, *target)?;
+  let elapsed = measure(&KdfParams::new_pbkdf2(&random_salt(), &KdfHash::Sha256, iterations))?;
+  println!(
+    "PBKDF2 (SHA-256, {iterations} iterations): {} ms for the target {} ms",
+    elapsed.as_millis(),
+    target.as_millis()
+  );
+  Ok((iterations, elapsed))
+}
+
+/// Envelope of an object in any format of the json server, upgraded from a legacy object if needed,
+/// or None for plaintext
+fn encrypted_record(record: &Value) -> Result<Option<Envelope>> {
+  if Envelope::is_envelope(record) || record.get("iv").is_some() {
+    Ok(Some(Envelope::upgrade_json(record)?))
+  } else {
+    Ok(None)
+  }
+}
+
+/// Id and a short description of the object, tolerating objects posted by other clients
+fn describe_record(record: &Value) -> (usize, String) {
+  let id = record.get("id").and_then(Value::as_u64).unwrap_or_default() as usize;
+  let description = match encrypted_record(record) {
+    Ok(Some(envelope)) => {
+      let kdf = envelope.kdf.algorithm().map(|kdf| kdf.to_string()).unwrap_or_default();
+      match Envelope::is_envelope(record) {
+        true => format!(
+          "encrypted with {} (key derived by {kdf}, envelope v{})",
+          envelope.cipher, envelope.v
+        ),
+        false => format!("encrypted with {} (key derived by {kdf})", envelope.cipher),
+      }
+    }
+    Ok(None) => "plaintext".to_string(),
+    Err(e) => format!("unknown format ({e})"),
+  };
+  (id, description)
+}
+
+/// Plaintext object, legacy object of sample-03 or envelope, whose binary fields are Base64 strings
+#[derive(Serialize, Debug, Default)]
+struct PostRequest {
+  pub data: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub iv: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub salt: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub alg: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub v: Option<u32>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub cipher: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub kdf: Option<KdfParams>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub ka: Option<KeyAgreement>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub kw: Option<WrappedKey>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub mac: Option<String>,
+}
+
+impl From<Sample03Object> for PostRequest {
+  fn from(object: Sample03Object) -> Self {
+    Self {
+      data: object.data,
+      iv: Some(object.iv),
+      salt: Some(object.salt),
+      alg: object.alg,
+      ..Default::default()
+    }
+  }
+}
+
+impl From<&Envelope> for PostRequest {
+  fn from(envelope: &Envelope) -> Self {
+    Self {
+      data: general_purpose::STANDARD.encode(&envelope.data),
+      iv: Some(general_purpose::STANDARD.encode(&envelope.iv)),
+      v: Some(envelope.v),
+      cipher: Some(envelope.cipher.clone()),
+      kdf: Some(envelope.kdf.clone()),
+      ka: envelope.ka.clone(),
+      kw: envelope.kw.clone(),
+      mac: envelope.mac.as_ref().map(|mac| general_purpose::STANDARD.encode(mac)),
+      ..Default::default()
+    }
+  }
 }
 
 #[derive(Deserialize, Debug)]
@@ -112,10 +446,453 @@ struct PostResponse {
   pub id: usize,
 }
 
+/// Object posted by `PostRequest`, or a legacy object of sample-04 with `kdfParams`.
+/// Unknown fields and fields of the wrong types fail here, and fields of other formats mixed in fail in `envelope`.
 #[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
 struct GetResponse {
   pub id: usize,
   pub data: String,
   pub iv: Option<String>,
   pub salt: Option<String>,
+  pub alg: Option<String>,
+  pub v: Option<u32>,
+  pub cipher: Option<String>,
+  pub kdf: Option<KdfParams>,
+  pub ka: Option<KeyAgreement>,
+  pub kw: Option<WrappedKey>,
+  pub mac: Option<String>,
+  #[serde(rename = "kdfParams")]
+  pub kdf_params: Option<KdfParams>,
+}
+
+impl GetResponse {
+  /// Envelope of the encrypted object, upgraded from a legacy object if needed, or None for plaintext
+  fn envelope(&self) -> Result<Option<Envelope>> {
+    let sample03 = self.salt.is_some() || self.alg.is_some();
+    let enveloped = self.cipher.is_some() || self.kdf.is_some() || self.ka.is_some() || self.kw.is_some();
+    let envelope = match (self.v, &self.iv, &self.kdf_params) {
+      (None, None, None) => {
+        ensure!(
+          !sample03 && !enveloped && self.mac.is_none(),
+          "Plaintext object with fields of an encrypted one"
+        );
+        return Ok(None);
+      }
+      (Some(v), Some(iv), None) => {
+        ensure!(!sample03, "Envelope with fields of a legacy object");
+        Envelope {
+          v,
+          cipher: self.cipher.clone().context("No cipher in the envelope")?,
+          kdf: self.kdf.clone().context("No kdf in the envelope")?,
+          ka: self.ka.clone(),
+          kw: self.kw.clone(),
+          iv: general_purpose::STANDARD.decode(iv)?,
+          data: general_purpose::STANDARD.decode(&self.data)?,
+          mac: self
+            .mac
+            .as_ref()
+            .map(|mac| general_purpose::STANDARD.decode(mac))
+            .transpose()?,
+        }
+      }
+      (None, Some(iv), Some(kdf_params)) => {
+        ensure!(
+          !sample03 && !enveloped,
+          "Object of sample-04 with fields of other formats"
+        );
+        Envelope::try_from(Sample04Object {
+          data: self.data.clone(),
+          iv: iv.clone(),
+          mac: self.mac.clone(),
+          kdfParams: kdf_params.clone(),
+        })?
+      }
+      (None, Some(iv), None) => {
+        ensure!(
+          !enveloped && self.mac.is_none(),
+          "Object of sample-03 with fields of other formats"
+        );
+        Envelope::try_from(Sample03Object {
+          data: self.data.clone(),
+          iv: iv.clone(),
+          salt: self.salt.clone().context("No salt in the object")?,
+          alg: self.alg.clone(),
+        })?
+      }
+      _ => bail!("Object mixing the formats of an envelope and sample-04, or without iv"),
+    };
+    envelope.validate()?;
+    Ok(Some(envelope))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  fn default_kdf() -> KdfParams {
+    KdfParams::new_pbkdf2(&random_salt(), &KdfHash::Sha256, ITERATION)
+  }
+
+  #[tokio::test]
+  async fn post_get_with_local_server() -> Result<()> {
+    let srv = data_server::spawn_in_memory().await?;
+    let client = &reqwest::Client::new();
+
+    let id = post_data(
+      "plain text",
+      None,
+      &Algorithm::AesCbc,
+      &default_kdf(),
+      false,
+      client,
+      &srv,
+    )
+    .await?;
+    assert_eq!(get_data(&id, None, client, &srv).await?, "plain text");
+
+    for alg in [Algorithm::AesCbc, Algorithm::AesGcm] {
+      let id = post_data("secret text", Some("my key"), &alg, &default_kdf(), false, client, &srv).await?;
+      assert_eq!(get_data(&id, Some("my key"), client, &srv).await?, "secret text");
+      assert!(get_data(&id, None, client, &srv).await.is_err());
+    }
+    let id = post_data(
+      "secret text",
+      Some("my key"),
+      &Algorithm::AesGcm,
+      &default_kdf(),
+      false,
+      client,
+      &srv,
+    )
+    .await?;
+    assert!(get_data(&id, Some("wrong key"), client, &srv).await.is_err());
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn list_update_delete_with_local_server() -> Result<()> {
+    let srv = data_server::spawn_in_memory().await?;
+    let client = &reqwest::Client::new();
+
+    let plain = post_data(
+      "plain text",
+      None,
+      &Algorithm::AesCbc,
+      &default_kdf(),
+      false,
+      client,
+      &srv,
+    )
+    .await?;
+    let cbc = post_data(
+      "secret text",
+      Some("my key"),
+      &Algorithm::AesCbc,
+      &default_kdf(),
+      false,
+      client,
+      &srv,
+    )
+    .await?;
+    let gcm = post_data(
+      "secret text",
+      Some("my key"),
+      &Algorithm::AesGcm,
+      &default_kdf(),
+      false,
+      client,
+      &srv,
+    )
+    .await?;
+
+    let listed = list_data(&2, client, &srv).await?;
+    assert_eq!(listed.len(), 3);
+    assert_eq!(listed[0], (plain, "plaintext".to_string()));
+    assert!(listed[1].1.contains("AES-CBC"));
+    assert!(listed[2].1.contains("AES-GCM"));
+
+    let before = fetch_record(&cbc, client, &srv).await?;
+    update_data(&cbc, None, Some("my key"), None, false, client, &srv).await?;
+    let after = fetch_record(&cbc, client, &srv).await?;
+    assert_ne!(before.iv, after.iv);
+    assert_ne!(before.salt, after.salt);
+    assert_eq!(after.alg, None);
+    assert_eq!(get_data(&cbc, Some("my key"), client, &srv).await?, "secret text");
+    assert!(update_data(&cbc, None, Some("wrong key"), None, false, client, &srv)
+      .await
+      .is_err());
+
+    update_data(
+      &cbc,
+      Some("new text"),
+      Some("my key"),
+      Some(&Algorithm::AesGcm),
+      false,
+      client,
+      &srv,
+    )
+    .await?;
+    assert_eq!(fetch_record(&cbc, client, &srv).await?.alg.as_deref(), Some("AES-GCM"));
+    assert_eq!(get_data(&cbc, Some("my key"), client, &srv).await?, "new text");
+    update_data(&plain, Some("new plain text"), None, None, false, client, &srv).await?;
+    assert_eq!(get_data(&plain, None, client, &srv).await?, "new plain text");
+
+    delete_data(&gcm, client, &srv).await?;
+    assert!(get_data(&gcm, Some("my key"), client, &srv).await.is_err());
+    assert!(delete_data(&gcm, client, &srv).await.is_err());
+    assert_eq!(list_data(&10, client, &srv).await?.len(), 2);
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn list_with_server_ignoring_paging() -> Result<()> {
+    // Every page is the full list, with and without `x-total-count`
+    let records = json!([{ "id": 1, "data": "a" }, { "id": 2, "data": "b" }, { "id": 3, "data": "c" }]);
+    for total in [Some("3"), None] {
+      let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+      let srv = format!("http://{}/data", listener.local_addr()?);
+      let body = records.clone();
+      let router = axum::Router::new().route(
+        "/data",
+        axum::routing::get(move || async move {
+          let headers = total.map(|total| [("x-total-count", total)]).into_iter().flatten();
+          (axum::response::AppendHeaders(headers), axum::Json(body))
+        }),
+      );
+      tokio::spawn(async move { axum::serve(listener, router).await });
+      let listed = list_data(&2, &reqwest::Client::new(), &srv).await?;
+      assert_eq!(listed.iter().map(|(id, _)| *id).collect::<Vec<_>>(), [1, 2, 3]);
+    }
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn calibrated_kdf_with_local_server() -> Result<()> {
+    let srv = data_server::spawn_in_memory().await?;
+    let client = &reqwest::Client::new();
+
+    let (iterations, _) = calibrate_iterations(&Duration::from_millis(5))?;
+    let kdf = KdfParams::new_pbkdf2(&random_salt(), &KdfHash::Sha256, iterations);
+    let id = post_data(
+      "secret text",
+      Some("my key"),
+      &Algorithm::AesGcm,
+      &kdf,
+      true,
+      client,
+      &srv,
+    )
+    .await?;
+    update_data(&id, Some("new text"), Some("my key"), None, false, client, &srv).await?;
+    let recorded = fetch_record(&id, client, &srv).await?.kdf.context("No kdf")?;
+    assert_eq!(recorded.iterationCount, Some(iterations as usize));
+    assert_ne!(recorded.salt, kdf.salt);
+    assert_eq!(get_data(&id, Some("my key"), client, &srv).await?, "new text");
+
+    // Legacy objects of sample-03 are fixed to 2048 iterations
+    let kdf = KdfParams::new_pbkdf2(&random_salt(), &KdfHash::Sha256, ITERATION + 1);
+    assert!(post_data(
+      "secret text",
+      Some("my key"),
+      &Algorithm::AesCbc,
+      &kdf,
+      false,
+      client,
+      &srv
+    )
+    .await
+    .is_err());
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn envelope_with_local_server() -> Result<()> {
+    let srv = data_server::spawn_in_memory().await?;
+    let client = &reqwest::Client::new();
+
+    let id = post_data(
+      "secret text",
+      Some("my key"),
+      &Algorithm::AesGcm,
+      &default_kdf(),
+      true,
+      client,
+      &srv,
+    )
+    .await?;
+    let record = fetch_record(&id, client, &srv).await?;
+    assert_eq!(record.v, Some(1));
+    assert_eq!(record.cipher.as_deref(), Some("AES-GCM"));
+    assert_eq!(get_data(&id, Some("my key"), client, &srv).await?, "secret text");
+    assert!(list_data(&10, client, &srv).await?[0].1.contains("envelope v1"));
+
+    // Legacy objects are upgraded only on request, and envelopes stay envelopes
+    let legacy = post_data(
+      "secret text",
+      Some("my key"),
+      &Algorithm::AesCbc,
+      &default_kdf(),
+      false,
+      client,
+      &srv,
+    )
+    .await?;
+    update_data(&legacy, None, Some("my key"), None, false, client, &srv).await?;
+    assert_eq!(fetch_record(&legacy, client, &srv).await?.v, None);
+    update_data(&legacy, None, Some("my key"), None, true, client, &srv).await?;
+    let record = fetch_record(&legacy, client, &srv).await?;
+    assert_eq!(record.cipher.as_deref(), Some("AES-CBC"));
+    update_data(&legacy, Some("new text"), Some("my key"), None, false, client, &srv).await?;
+    assert_eq!(fetch_record(&legacy, client, &srv).await?.v, Some(1));
+    assert_eq!(get_data(&legacy, Some("my key"), client, &srv).await?, "new text");
+
+    let sealed = Envelope::seal(b"secret text", "my key", &default_kdf(), &Algorithm::AesCbc, true)?;
+    assert_eq!(
+      serde_json::to_value(PostRequest::from(&sealed))?,
+      sealed.to_json_value()?
+    );
+    // Objects of sample-04 are read as well
+    let object = Sample04Object::try_from(&sealed)?;
+    let res = client.post(&srv).json(&object).send().await?.error_for_status()?;
+    let id = res.json::<PostResponse>().await?.id;
+    assert_eq!(get_data(&id, Some("my key"), client, &srv).await?, "secret text");
+
+    // Malformed objects fail at deserialization, and objects mixing the formats when upgraded
+    let malformed = [
+      json!({ "data": "text", "ivv": "AAAAAAAAAAAAAAAAAAAAAA==" }),
+      json!({ "data": "text", "v": "1" }),
+      json!({ "data": "text", "v": 1, "iv": "AAAAAAAAAAAAAAAAAAAAAA==", "cipher": "AES-CBC", "kdf": { "salt": "AAAA", "iteratons": 2048 } }),
+      json!({ "data": "text", "salt": "AAAA" }),
+      json!({ "data": "text", "v": 1, "iv": "AAAAAAAAAAAAAAAAAAAAAA==", "kdfParams": { "salt": "AAAA", "iterationCount": 2048 } }),
+    ];
+    for object in malformed {
+      let res = client.post(&srv).json(&object).send().await?.error_for_status()?;
+      let id = res.json::<PostResponse>().await?.id;
+      assert!(get_data(&id, Some("my key"), client, &srv).await.is_err(), "{object}");
+      assert!(get_data(&id, None, client, &srv).await.is_err(), "{object}");
+    }
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn padding_oracle_with_local_server() -> Result<()> {
+    let srv = data_server::spawn_in_memory().await?;
+    let client = &reqwest::Client::new();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let oracle = format!("http://{}", listener.local_addr()?);
+    tokio::spawn(async move { oracle::serve(listener, "my key").await });
+
+    let text = "secret text longer than a block";
+    for envelope in [false, true] {
+      let id = post_data(
+        text,
+        Some("my key"),
+        &Algorithm::AesCbc,
+        &default_kdf(),
+        envelope,
+        client,
+        &srv,
+      )
+      .await?;
+      let recovered = attack_data(&id, &oracle, client, &srv).await?;
+      assert_eq!(recovered.plaintext, text.as_bytes());
+      assert!(recovered.queries >= 32);
+    }
+
+    // The oracle derives no key costlier than the default of the lab
+    let id = post_data(
+      text,
+      Some("my key"),
+      &Algorithm::AesCbc,
+      &default_kdf(),
+      true,
+      client,
+      &srv,
+    )
+    .await?;
+    let mut costly = fetch_value(&id, client, &srv).await?;
+    costly["kdf"]["iterationCount"] = (ITERATION + 1).into();
+    let res = client.post(&oracle).json(&costly).send().await?;
+    assert_eq!(res.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+
+    // Forged AES-GCM ciphertexts under the original nonce are well-formed but never pass the tag,
+    // so the attack exhausts the guesses of the first byte
+    let id = post_data(
+      text,
+      Some("my key"),
+      &Algorithm::AesGcm,
+      &default_kdf(),
+      false,
+      client,
+      &srv,
+    )
+    .await?;
+    let err = attack_data(&id, &oracle, client, &srv).await.unwrap_err().to_string();
+    assert!(err.starts_with("No guess of byte 15 gives a valid padding after 256 queries"));
+    let mut malformed = fetch_value(&id, client, &srv).await?;
+    malformed["iv"] = "AAAAAAAAAAAAAAAAAAAAAA==".into();
+    let res = client.post(&oracle).json(&malformed).send().await?;
+    assert_eq!(res.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn flip_bits_with_local_server() -> Result<()> {
+    let srv = data_server::spawn_in_memory().await?;
+    let client = &reqwest::Client::new();
+
+    let text = "role=user;;name=alice;;note=unauthenticated CBC is malleable";
+    for envelope in [false, true] {
+      let id = post_data(
+        text,
+        Some("my key"),
+        &Algorithm::AesCbc,
+        &default_kdf(),
+        envelope,
+        client,
+        &srv,
+      )
+      .await?;
+      flip_data(&id, "role=user;", "role=admin", 0, true, client, &srv).await?;
+      let flipped = get_data(&id, Some("my key"), client, &srv).await?;
+      assert_eq!(flipped, text.replace("role=user;", "role=admin"));
+    }
+
+    // Beyond the first block, the previous ciphertext block is edited and its plaintext is garbled
+    let id = post_data(
+      text,
+      Some("my key"),
+      &Algorithm::AesCbc,
+      &default_kdf(),
+      true,
+      client,
+      &srv,
+    )
+    .await?;
+    assert!(flip_data(&id, "role", "admin", 0, false, client, &srv).await.is_err());
+    flip_data(&id, "CBC", "GCM", 44, true, client, &srv).await?;
+    // The garbled block may be invalid UTF-8, which is shown lossily, whereas the other blocks are ASCII
+    let flipped = get_data(&id, Some("my key"), client, &srv).await?;
+    assert!(flipped.starts_with(&text[..16]));
+    assert!(flipped.ends_with(&text.replace("CBC", "GCM")[32..]));
+    assert!(!flipped.contains(&text[16..32]));
+
+    // AES-GCM rejects the edited object
+    let id = post_data(
+      text,
+      Some("my key"),
+      &Algorithm::AesGcm,
+      &default_kdf(),
+      false,
+      client,
+      &srv,
+    )
+    .await?;
+    flip_data(&id, "role=user;", "role=admin", 0, true, client, &srv).await?;
+    assert!(get_data(&id, Some("my key"), client, &srv).await.is_err());
+    Ok(())
+  }
 }
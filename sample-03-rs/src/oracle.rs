@@ -0,0 +1,196 @@
+//! Padding oracle lab. The oracle holds the key and answers only whether a posted object decrypts,
+//! which for AES-CBC means whether its PKCS#7 padding is valid. That single bit is enough to recover
+//! the plaintext without the key, whereas AES-GCM rejects every forged ciphertext and leaks nothing.
+
+use crate::error::*;
+use axum::{extract::State, http::StatusCode, routing::post, Json, Router};
+use base64::{engine::general_purpose, Engine as _};
+use seceng_crypto::{
+  envelope::Envelope,
+  kdf::{BinaryKey, KdfAlgorithm, KdfParams, ITERATION},
+  symmetric::{Algorithm, KEY_LEN},
+  util::ToHexString,
+};
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+
+pub const DEFAULT_ORACLE_ADDR: &str = "127.0.0.1:3001";
+pub const DEFAULT_ORACLE_URL: &str = "http://127.0.0.1:3001";
+const BLOCK_LEN: usize = 16;
+
+struct Oracle {
+  secret: String,
+  /// Key of the last KDF params, so that the queries of an attack on an object cost a single decryption each.
+  /// A single entry cannot be grown by posting objects with fresh salts.
+  key: Mutex<Option<(KdfParams, BinaryKey)>>,
+}
+
+impl Oracle {
+  /// Whether the object decrypts with the key, and nothing else.
+  /// A malformed object is an error, which anyone can tell without the key.
+  fn decrypts(&self, record: &Value) -> Result<bool> {
+    let envelope = Envelope::upgrade_json(record)?;
+    envelope.validate()?;
+    check_cost(&envelope.kdf)?;
+    if envelope.mac.is_some() {
+      return Ok(envelope.open(&self.secret).is_ok());
+    }
+    let mut cached = self.key.lock().unwrap();
+    let (_, key) = match cached.take() {
+      Some((kdf, key)) if kdf == envelope.kdf => cached.insert((kdf, key)),
+      _ => {
+        let key = BinaryKey::try_from_kdf_params(&self.secret, KEY_LEN, &envelope.kdf)?;
+        cached.insert((envelope.kdf.clone(), key))
+      }
+    };
+    Ok(envelope.cipher()?.decrypt(&envelope.encrypted(), &key.key).is_ok())
+  }
+}
+
+/// Only PBKDF2 of at most the default iteration count of the lab is derived,
+/// so that a query cannot make the oracle spend more than a usual decryption
+fn check_cost(kdf: &KdfParams) -> Result<()> {
+  ensure!(
+    kdf.algorithm()? == KdfAlgorithm::Pbkdf2,
+    "The oracle only derives keys by PBKDF2"
+  );
+  ensure!(
+    kdf.iterationCount.is_some_and(|iter| iter <= ITERATION as usize),
+    "The oracle derives keys by at most {ITERATION} iterations"
+  );
+  Ok(())
+}
+
+async fn answer(State(oracle): State<Arc<Oracle>>, Json(record): Json<Value>) -> (StatusCode, &'static str) {
+  match oracle.decrypts(&record) {
+    Ok(true) => (StatusCode::OK, "valid padding"),
+    Ok(false) => (StatusCode::BAD_REQUEST, "invalid padding"),
+    Err(_) => (StatusCode::UNPROCESSABLE_ENTITY, "malformed object"),
+  }
+}
+
+/// Serve the oracle at `/` of the listener, accepting objects in any format of the json server
+pub async fn serve(listener: TcpListener, secret: &str) -> Result<()> {
+  let oracle = Arc::new(Oracle {
+    secret: secret.to_string(),
+    key: Mutex::new(None),
+  });
+  let router = Router::new().route("/", post(answer)).with_state(oracle);
+  println!("Padding oracle listening on http://{}", listener.local_addr()?);
+  axum::serve(listener, router.into_make_service()).await?;
+  Ok(())
+}
+
+/// Plaintext recovered by the attack with the number of oracle queries
+#[derive(Debug)]
+pub struct Recovered {
+  pub plaintext: Vec<u8>,
+  pub queries: usize,
+}
+
+struct Attacker<'a> {
+  client: &'a reqwest::Client,
+  oracle: &'a str,
+  /// The target object, whose `iv` and `data` are replaced in each query
+  record: Value,
+  /// Nonce of an AES-GCM object, which is kept as is while the forged block is put into the ciphertext
+  gcm_nonce: Option<Vec<u8>>,
+  queries: usize,
+}
+
+impl Attacker<'_> {
+  async fn query(&mut self, iv: &[u8], block: &[u8]) -> Result<bool> {
+    self.queries += 1;
+    let (iv, data) = match &self.gcm_nonce {
+      Some(nonce) => (nonce.as_slice(), [iv, block].concat()),
+      None => (iv, block.to_vec()),
+    };
+    self.record["iv"] = general_purpose::STANDARD.encode(iv).into();
+    self.record["data"] = general_purpose::STANDARD.encode(data).into();
+    let res = self.client.post(self.oracle).json(&self.record).send().await?;
+    match res.status() {
+      StatusCode::OK => Ok(true),
+      StatusCode::BAD_REQUEST => Ok(false),
+      StatusCode::UNPROCESSABLE_ENTITY => bail!("Oracle rejected the forged object as malformed"),
+      status => bail!("Unexpected answer from the oracle: {status}"),
+    }
+  }
+
+  /// Decrypt `block` chained from `prev` by forging the IV from the last byte to the first
+  async fn recover_block(&mut self, prev: &[u8], block: &[u8]) -> Result<Vec<u8>> {
+    // Output of the block cipher before XORed with `prev`
+    let mut intermediate = [0u8; BLOCK_LEN];
+    for pos in (0..BLOCK_LEN).rev() {
+      let pad = (BLOCK_LEN - pos) as u8;
+      let mut forged = [0u8; BLOCK_LEN];
+      for (f, i) in forged.iter_mut().zip(&intermediate).skip(pos + 1) {
+        *f = i ^ pad;
+      }
+      let mut found = None;
+      for guess in 0..=u8::MAX {
+        forged[pos] = guess;
+        if !self.query(&forged, block).await? {
+          continue;
+        }
+        // The last byte may also end a longer padding like 02 02, which breaks if the preceding byte changes
+        if pad == 1 && pos > 0 {
+          let mut check = forged;
+          check[pos - 1] ^= 0xff;
+          if !self.query(&check, block).await? {
+            continue;
+          }
+        }
+        found = Some(guess);
+        break;
+      }
+      let Some(guess) = found else {
+        bail!(
+          "No guess of byte {pos} gives a valid padding after {} queries. The oracle leaks nothing, e.g., for AES-GCM",
+          self.queries
+        );
+      };
+      intermediate[pos] = guess ^ pad;
+    }
+    Ok(intermediate.iter().zip(prev).map(|(i, p)| i ^ p).collect())
+  }
+}
+
+/// Recover the plaintext of an AES-CBC object block by block, asking the oracle only about paddings.
+/// The attack is tried on AES-GCM objects as well, forging the ciphertext under the original nonce,
+/// which the oracle never accepts since the tag fails.
+pub async fn attack(record: &Value, client: &reqwest::Client, oracle: &str) -> Result<Recovered> {
+  let envelope = Envelope::upgrade_json(record)?;
+  let encrypted = envelope.encrypted();
+  ensure!(encrypted.data.len() >= BLOCK_LEN, "Ciphertext shorter than a block");
+  let gcm_nonce = (envelope.cipher()? == Algorithm::AesGcm).then(|| encrypted.iv.clone());
+  let mut attacker = Attacker {
+    client,
+    oracle,
+    record: record.clone(),
+    gcm_nonce,
+    queries: 0,
+  };
+  let mut prev = encrypted.iv.clone();
+  let mut plaintext = vec![];
+  for (i, block) in encrypted.data.chunks_exact(BLOCK_LEN).enumerate() {
+    let recovered = attacker.recover_block(&prev, block).await?;
+    println!(
+      "Block {i}: {} ({} queries so far)",
+      recovered.to_hex_string(),
+      attacker.queries
+    );
+    plaintext.extend(recovered);
+    prev = block.to_vec();
+  }
+  let pad = *plaintext.last().unwrap() as usize;
+  ensure!(
+    (1..=BLOCK_LEN).contains(&pad) && plaintext[plaintext.len() - pad..].iter().all(|b| *b as usize == pad),
+    "Recovered an invalid padding"
+  );
+  plaintext.truncate(plaintext.len() - pad);
+  Ok(Recovered {
+    plaintext,
+    queries: attacker.queries,
+  })
+}
@@ -0,0 +1,202 @@
+//! Visualizer of block cipher modes over binary netpbm images, i.e., the "ECB penguin".
+//! Only the raster is encrypted and the header is kept, so that the results are still viewable images.
+//! ECB encrypts equal blocks into equal blocks and leaves the outline of the image, while CBC and CTR do not.
+
+use crate::error::*;
+use aes::{
+  cipher::{BlockEncrypt, KeyInit, KeyIvInit, StreamCipher},
+  Aes256,
+};
+use rand::RngCore;
+use seceng_crypto::symmetric::{encrypt, KEY_LEN};
+use std::{
+  fmt::Display,
+  fs,
+  path::{Path, PathBuf},
+};
+
+const BLOCK_LEN: usize = 16;
+type Aes256Ctr = ctr::Ctr128BE<Aes256>;
+
+/// Binary netpbm image, either PGM (`P5`) or PPM (`P6`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Netpbm {
+  /// Header from the magic number to the single whitespace before the raster
+  pub header: Vec<u8>,
+  pub raster: Vec<u8>,
+}
+
+impl Netpbm {
+  pub fn parse(bytes: &[u8]) -> Result<Self> {
+    let channels = match bytes.get(..2) {
+      Some(b"P5") => 1,
+      Some(b"P6") => 3,
+      _ => bail!("Not a binary PGM (P5) or PPM (P6) image"),
+    };
+    let mut pos = 2;
+    let mut fields = [0usize; 3];
+    for field in fields.iter_mut() {
+      // Whitespace and comments up to the end of line before each of width, height and maxval
+      loop {
+        match bytes.get(pos) {
+          Some(b) if b.is_ascii_whitespace() => pos += 1,
+          Some(b'#') => {
+            while bytes.get(pos).is_some_and(|b| *b != b'\n') {
+              pos += 1;
+            }
+          }
+          _ => break,
+        }
+      }
+      let digits = bytes[pos..].iter().take_while(|b| b.is_ascii_digit()).count();
+      ensure!(digits > 0, "Invalid netpbm header");
+      *field = std::str::from_utf8(&bytes[pos..pos + digits])?.parse()?;
+      pos += digits;
+    }
+    ensure!(
+      bytes.get(pos).is_some_and(u8::is_ascii_whitespace),
+      "Invalid netpbm header"
+    );
+    pos += 1;
+    let [width, height, maxval] = fields;
+    ensure!((1..=u16::MAX as usize).contains(&maxval), "Invalid maxval {maxval}");
+    let sample_len = if maxval < 256 { 1 } else { 2 };
+    let raster_len = width
+      .checked_mul(height)
+      .and_then(|pixels| pixels.checked_mul(channels * sample_len))
+      .with_context(|| format!("Too large image of {width}x{height} pixels"))?;
+    ensure!(
+      bytes.len() - pos >= raster_len,
+      "Raster shorter than {width}x{height} pixels"
+    );
+    Ok(Self {
+      header: bytes[..pos].to_vec(),
+      raster: bytes[pos..pos + raster_len].to_vec(),
+    })
+  }
+
+  pub fn to_bytes(&self) -> Vec<u8> {
+    [self.header.as_slice(), &self.raster].concat()
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+  Ecb,
+  Cbc,
+  Ctr,
+}
+
+impl Display for Mode {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Mode::Ecb => write!(f, "ecb"),
+      Mode::Cbc => write!(f, "cbc"),
+      Mode::Ctr => write!(f, "ctr"),
+    }
+  }
+}
+
+impl Mode {
+  pub const ALL: [Mode; 3] = [Mode::Ecb, Mode::Cbc, Mode::Ctr];
+
+  /// Encrypt the data into the same length. ECB and CBC pad the last partial block with zeros and truncate
+  /// its ciphertext, which is fine for viewing but not decryptable.
+  pub fn encrypt(&self, data: &[u8], key: &[u8]) -> Result<Vec<u8>> {
+    ensure!(key.len() == KEY_LEN, "Invalid key length for AES-256");
+    let mut padded = data.to_vec();
+    padded.resize(data.len().div_ceil(BLOCK_LEN) * BLOCK_LEN, 0);
+    let mut encrypted = match self {
+      Mode::Ecb => {
+        let cipher = Aes256::new_from_slice(key)?;
+        for block in padded.chunks_exact_mut(BLOCK_LEN) {
+          cipher.encrypt_block(block.into());
+        }
+        padded
+      }
+      Mode::Cbc => encrypt(&padded, key, None)?.data,
+      Mode::Ctr => {
+        let mut iv = [0u8; BLOCK_LEN];
+        rand::rng().fill_bytes(&mut iv);
+        let mut buf = data.to_vec();
+        Aes256Ctr::new_from_slices(key, &iv)?.apply_keystream(&mut buf);
+        buf
+      }
+    };
+    encrypted.truncate(data.len());
+    Ok(encrypted)
+  }
+}
+
+/// Encrypt the raster of the image by each mode, and write `<stem>.<mode>.<ext>` to `out_dir`
+pub fn visualize(input: &Path, key: &[u8], out_dir: Option<&Path>) -> Result<Vec<PathBuf>> {
+  let image = Netpbm::parse(&fs::read(input)?)?;
+  let out_dir = match out_dir {
+    Some(dir) => dir.to_path_buf(),
+    None => input.parent().map(Path::to_path_buf).unwrap_or_default(),
+  };
+  let stem = input.file_stem().context("No file name")?.to_string_lossy();
+  let ext = input.extension().map(|e| e.to_string_lossy()).unwrap_or_default();
+  let mut written = vec![];
+  for mode in Mode::ALL {
+    let encrypted = Netpbm {
+      header: image.header.clone(),
+      raster: mode.encrypt(&image.raster, key)?,
+    };
+    let path = out_dir.join(format!("{stem}.{mode}.{ext}"));
+    fs::write(&path, encrypted.to_bytes())?;
+    println!("AES-{}: {}", mode.to_string().to_uppercase(), path.display());
+    written.push(path);
+  }
+  Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::collections::HashSet;
+
+  /// PGM of horizontal stripes, each of which is a run of equal blocks
+  fn stripes() -> Vec<u8> {
+    let (width, height) = (64, 32);
+    let mut bytes = format!("P5\n# stripes\n{width} {height}\n255\n").into_bytes();
+    bytes.extend((0..height).flat_map(|y| vec![if y % 8 < 4 { 0u8 } else { 255 }; width]));
+    bytes
+  }
+
+  fn distinct_blocks(data: &[u8]) -> usize {
+    data.chunks_exact(BLOCK_LEN).collect::<HashSet<_>>().len()
+  }
+
+  #[test]
+  fn parse_netpbm() -> Result<()> {
+    let image = Netpbm::parse(&stripes())?;
+    assert_eq!(image.header, b"P5\n# stripes\n64 32\n255\n");
+    assert_eq!(image.raster.len(), 64 * 32);
+    assert_eq!(image.to_bytes(), stripes());
+
+    let ppm = Netpbm::parse(b"P6 2 1 65535\n0123456789ab trailing")?;
+    assert_eq!(ppm.raster, b"0123456789ab");
+    assert!(Netpbm::parse(b"P3 1 1 255\n0 0 0").is_err());
+    assert!(Netpbm::parse(b"P5 2 2 255\n000").is_err());
+    let overflow = Netpbm::parse(b"P6 4294967296 4294967296 255\n000").unwrap_err();
+    assert!(overflow.to_string().starts_with("Too large image"));
+    Ok(())
+  }
+
+  #[test]
+  fn ecb_leaks_patterns() -> Result<()> {
+    let image = Netpbm::parse(&stripes())?;
+    let key = [7u8; KEY_LEN];
+    assert_eq!(distinct_blocks(&image.raster), 2);
+    let ecb = Mode::Ecb.encrypt(&image.raster, &key)?;
+    assert_eq!(distinct_blocks(&ecb), 2);
+    for mode in [Mode::Cbc, Mode::Ctr] {
+      let encrypted = mode.encrypt(&image.raster, &key)?;
+      assert_eq!(encrypted.len(), image.raster.len());
+      assert_eq!(distinct_blocks(&encrypted), image.raster.len() / BLOCK_LEN);
+    }
+    assert_eq!(Mode::Ctr.encrypt(b"odd length", &key)?.len(), 10);
+    Ok(())
+  }
+}
@@ -1,10 +1,16 @@
-use clap::{ArgAction, Parser, Subcommand};
+use crate::secret::SecretSource;
+use clap::{ArgAction, Args, Parser, Subcommand};
+use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 pub struct ClapArgs {
   #[clap(subcommand)]
   pub subcommand: SubCommands,
+
+  /// Print passwords, master secrets and derived keys for debugging
+  #[arg(long, global = true, action = ArgAction::SetTrue)]
+  pub show_secrets: bool,
 }
 
 #[allow(non_snake_case, non_camel_case_types)]
@@ -15,34 +21,226 @@ pub enum SubCommands {
     /// Id number of the target data on the server
     id: usize,
 
-    /// Password
-    #[arg(short, long)]
-    password: Option<String>,
+    #[command(flatten)]
+    secret: SecretArgs,
 
-    /// Master secret in base64
-    #[arg(short, long)]
-    master: Option<String>,
-
-    /// Get from the preset remote server (e2e.secarchlab.net) otherwise localhost:3000
-    #[arg(short, long, action = ArgAction::SetTrue)]
-    remote: bool,
+    #[command(flatten)]
+    server: ServerArgs,
   },
   /// Post ciphertext or plaintext object to the json server
   Post {
     /// Plaintext data string
     data: String,
 
-    /// Password
+    #[command(flatten)]
+    secret: SecretArgs,
+
+    /// Password-based KDF, either 'PBKDF2', 'Argon2id' or 'scrypt' (ignored with master secret) [default: PBKDF2]
     #[arg(short, long)]
-    password: Option<String>,
+    kdf: Option<String>,
+
+    /// Hash function for PBKDF2 or HKDF, either 'SHA-256', 'SHA-384' or 'SHA-512' [default: SHA-256]
+    #[arg(short = 'H', long)]
+    hash: Option<String>,
 
-    /// Master secret in base64
+    /// Iteration count for PBKDF2 [default: 2048]
     #[arg(short, long)]
-    master: Option<String>,
+    iterations: Option<u32>,
+
+    /// Calibrate the cost of the password-based KDF so that the key derivation takes about this time on this machine
+    #[arg(long, value_name = "MS", conflicts_with = "iterations")]
+    kdf_target_ms: Option<u64>,
 
-    /// Post to the preset remote server (e2e.secarchlab.net) otherwise localhost:3000
+    /// Encrypt-then-MAC: append HMAC-SHA-256 tag over IV and ciphertext with a separately derived MAC key
     #[arg(short, long, action = ArgAction::SetTrue)]
-    remote: bool,
+    etm: bool,
+
+    /// Record label bound into the HKDF info with the key purpose and version (master secret only)
+    #[arg(short, long)]
+    label: Option<String>,
+
+    /// Version bound into the HKDF info along with the label
+    #[arg(long, default_value = "1", requires = "label")]
+    key_version: u32,
+
+    /// Post the ciphertext as a versioned envelope instead of the legacy object of sample-04
+    #[arg(short = 'E', long, action = ArgAction::SetTrue)]
+    envelope: bool,
+
+    /// Encrypt with a random data key wrapped under the derived key, by either 'AES-KW' or 'AES-KWP' (envelope only)
+    #[arg(short = 'W', long, value_name = "ALG", requires = "envelope")]
+    key_wrap: Option<String>,
+
+    #[command(flatten)]
+    server: ServerArgs,
+  },
+  /// List objects on the json server page by page, showing how each is encrypted and with which KDF
+  List {
+    /// Number of objects fetched per page
+    #[arg(short, long, default_value = "10")]
+    limit: usize,
+
+    #[command(flatten)]
+    server: ServerArgs,
+  },
+  /// Re-encrypt an object with a fresh IV and salt, optionally replacing its data, and put it back to the json server
+  Update {
+    /// Id number of the target data on the server
+    id: usize,
+
+    /// New plaintext data string (the current data is kept if absent)
+    data: Option<String>,
+
+    #[command(flatten)]
+    secret: SecretArgs,
+
+    /// Encrypt-then-MAC even if the object has no HMAC-SHA-256 tag
+    #[arg(short, long, action = ArgAction::SetTrue)]
+    etm: bool,
+
+    /// Upgrade the object to a versioned envelope [default: kept in the current format]
+    #[arg(short = 'E', long, action = ArgAction::SetTrue)]
+    envelope: bool,
+
+    #[command(flatten)]
+    server: ServerArgs,
+  },
+  /// Wrap the data key of an object again under a new password or master secret, leaving its ciphertext as is
+  Rewrap {
+    /// Id number of the target data on the server
+    id: usize,
+
+    #[command(flatten)]
+    secret: SecretArgs,
+
+    /// Read the new secret of the same kind as the current one from SOURCE, either 'prompt', 'stdin', 'file:<path>', 'env:<var>' or 'pass:<secret>' (insecure)
+    #[arg(short, long, value_name = "SOURCE")]
+    new: SecretSource,
+
+    /// Replace the version bound into the HKDF info along with the label
+    #[arg(long)]
+    key_version: Option<u32>,
+
+    #[command(flatten)]
+    server: ServerArgs,
+  },
+  /// Re-encrypt every object decryptable by the current password or master secret under a new one, resumably
+  Rotate {
+    #[command(flatten)]
+    secret: SecretArgs,
+
+    /// Read the new secret of the same kind as the current one from SOURCE, either 'prompt', 'stdin', 'file:<path>', 'env:<var>' or 'pass:<secret>' (insecure)
+    #[arg(short, long, value_name = "SOURCE")]
+    new: SecretSource,
+
+    /// Journal of the outcome per object, from which an interrupted rotation is resumed
+    #[arg(short, long, default_value = "cli04-rotate.jsonl")]
+    journal: PathBuf,
+
+    /// Resume the rotation recorded in the journal, skipping the objects already rotated
+    #[arg(long, action = ArgAction::SetTrue)]
+    resume: bool,
+
+    /// Number of objects fetched per page
+    #[arg(short, long, default_value = "10")]
+    limit: usize,
+
+    #[command(flatten)]
+    server: ServerArgs,
+  },
+  /// Delete an object from the json server
+  Delete {
+    /// Id number of the target data on the server
+    id: usize,
+
+    #[command(flatten)]
+    server: ServerArgs,
+  },
+  /// Encrypt a file of any size chunk by chunk by AES-256-GCM with the key derived from the password or master secret
+  Encrypt_File {
+    /// Plaintext file to encrypt
+    input: PathBuf,
+
+    /// Encrypted file to write
+    output: PathBuf,
+
+    #[command(flatten)]
+    secret: SecretArgs,
+
+    /// Password-based KDF, either 'PBKDF2', 'Argon2id' or 'scrypt' (ignored with master secret) [default: PBKDF2]
+    #[arg(short, long)]
+    kdf: Option<String>,
+
+    /// Hash function for PBKDF2 or HKDF, either 'SHA-256', 'SHA-384' or 'SHA-512' [default: SHA-256]
+    #[arg(short = 'H', long)]
+    hash: Option<String>,
+
+    /// Iteration count for PBKDF2 [default: 2048]
+    #[arg(short, long)]
+    iterations: Option<u32>,
+
+    /// Calibrate the cost of the password-based KDF so that the key derivation takes about this time on this machine
+    #[arg(long, value_name = "MS", conflicts_with = "iterations")]
+    kdf_target_ms: Option<u64>,
+
+    /// File label bound into the HKDF info with the key purpose and version (master secret only)
+    #[arg(short, long)]
+    label: Option<String>,
+
+    /// Version bound into the HKDF info along with the label
+    #[arg(long, default_value = "1", requires = "label")]
+    key_version: u32,
+
+    /// Plaintext length of a chunk in bytes
+    #[arg(short, long, value_name = "BYTES", default_value = "65536")]
+    chunk_size: u32,
+  },
+  /// Decrypt a file encrypted by encrypt-file, verifying every chunk and that the file is complete
+  Decrypt_File {
+    /// Encrypted file to decrypt
+    input: PathBuf,
+
+    /// Plaintext file to write, which is left untouched unless the whole file is verified
+    output: PathBuf,
+
+    #[command(flatten)]
+    secret: SecretArgs,
+  },
+  /// Derive a named sub-master secret from a master secret by HKDF
+  Derive_Subkey {
+    /// Read master secret in base64 from SOURCE, either 'prompt', 'stdin', 'file:<path>', 'env:<var>' or 'pass:<secret>' (insecure)
+    #[arg(short, long, value_name = "SOURCE")]
+    master: SecretSource,
+
+    /// Name of the sub-master secret bound into the HKDF info
+    #[arg(short, long)]
+    label: String,
+
+    /// Version bound into the HKDF info along with the label
+    #[arg(long, default_value = "1")]
+    key_version: u32,
+
+    /// Hash function for HKDF, either 'SHA-256', 'SHA-384' or 'SHA-512'
+    #[arg(short = 'H', long, default_value = "SHA-256")]
+    hash: String,
+
+    /// Length of the sub-master secret
+    #[arg(long, default_value = "32")]
+    len: usize,
+  },
+  /// Benchmark the password-based KDFs and pick their costs for a target derivation time on this machine
+  Calibrate_Kdf {
+    /// Password-based KDF, either 'PBKDF2', 'Argon2id' or 'scrypt' [default: all of them]
+    #[arg(short, long)]
+    kdf: Option<String>,
+
+    /// Hash function for PBKDF2, either 'SHA-256', 'SHA-384' or 'SHA-512'
+    #[arg(short = 'H', long, default_value = "SHA-256")]
+    hash: String,
+
+    /// Target time of a key derivation in milliseconds
+    #[arg(short, long, value_name = "MS", default_value = "250")]
+    target_ms: u64,
   },
   /// Generate master secret
   Gen_Secret {
@@ -50,3 +248,34 @@ pub enum SubCommands {
     len: usize,
   },
 }
+
+#[derive(Debug, Args)]
+#[group(required = true, multiple = false)]
+pub struct SecretArgs {
+  /// Read password from SOURCE, either 'prompt', 'stdin', 'file:<path>', 'env:<var>' or 'pass:<password>' (insecure)
+  #[arg(short, long, value_name = "SOURCE")]
+  pub password: Option<SecretSource>,
+
+  /// Read master secret in base64 from SOURCE, either 'prompt', 'stdin', 'file:<path>', 'env:<var>' or 'pass:<secret>' (insecure)
+  #[arg(short, long, value_name = "SOURCE")]
+  pub master: Option<SecretSource>,
+}
+
+#[derive(Debug, Args)]
+pub struct ServerArgs {
+  /// Use the preset remote server (e2e.secarchlab.net) otherwise the profile's server or localhost:3000
+  #[arg(short, long, action = ArgAction::SetTrue)]
+  pub remote: bool,
+
+  /// URL of the data endpoint like http://localhost:3000/data, taking precedence over --remote and the profile
+  #[arg(short, long)]
+  pub server: Option<String>,
+
+  /// Name of the profile to use [default: the default profile in the profiles file]
+  #[arg(short = 'P', long)]
+  pub profile: Option<String>,
+
+  /// Profiles file [default: ~/.config/seceng/profiles.toml]
+  #[arg(long)]
+  pub profiles: Option<PathBuf>,
+}
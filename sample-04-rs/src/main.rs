@@ -1,55 +1,225 @@
 mod config;
-mod crypto;
 mod error;
-mod key;
+mod rotate;
+mod secret;
 
-use crate::{
-  crypto::{decrypt, encrypt, Encrypted},
-  error::*,
-  key::BinaryKey,
-};
+use crate::error::*;
 use base64::{engine::general_purpose, Engine as _};
 use clap::Parser;
-use config::{ClapArgs, SubCommands};
-use serde::{Deserialize, Serialize};
-
-const LOCAL_SRV: &str = "http://localhost:3000/data";
-const REMOTE_SRV: &str = "https://e2e.secarchlab.net/data";
+use config::{ClapArgs, SecretArgs, ServerArgs, SubCommands};
+use rotate::{rotate_record, Journal, Outcome, Report, Rotation};
+use seceng_client::profile::{Profile, Profiles};
+use seceng_crypto::{
+  calibrate::{calibrate, measure},
+  envelope::{Envelope, Sample04Object, WrappedKey},
+  kdf::{
+    derive_subkey, random_salt, Argon2idCost, BinaryKey, HkdfInfo, KdfAlgorithm, KdfHash, KdfParams, ScryptCost,
+    ITERATION,
+  },
+  keywrap::KeyWrapAlgorithm,
+  stream::{decrypt_stream, encrypt_stream, StreamHeader},
+  symmetric::{encrypt, encrypt_then_mac, Algorithm},
+};
+use secret::{Secret, SecretSource};
+use serde::Deserialize;
+use serde_json::Value;
+use std::{
+  collections::HashSet,
+  fs::{self, File},
+  io::{BufReader, BufWriter},
+  path::{Path, PathBuf},
+  time::Duration,
+};
+use zeroize::Zeroizing;
 
 #[tokio::main]
 pub async fn main() -> Result<()> {
   let _ = include_str!("../Cargo.toml");
   let args = ClapArgs::parse();
+  let show_secrets = args.show_secrets;
 
   match &args.subcommand {
-    SubCommands::Get {
-      id,
-      password,
-      master,
-      remote,
-    } => {
-      get_data(
-        id,
-        password.as_ref().map(|x| x.as_str()),
-        master.as_ref().map(|x| x.as_str()),
-        remote,
-      )
-      .await?;
+    SubCommands::Get { id, secret, server } => {
+      if server.remote {
+        println!("Retrieve encrypted data to remote server");
+      }
+      let (profile, client) = load_profile(server)?;
+      let srv = profile.server_url(server.server.as_deref(), server.remote);
+      let secret = read_secret(secret)?;
+      get_data(id, &secret, show_secrets, &client, &srv).await?;
     }
     SubCommands::Post {
       data,
-      password,
-      master,
-      remote,
+      secret,
+      kdf,
+      hash,
+      iterations,
+      kdf_target_ms,
+      etm,
+      label,
+      key_version,
+      envelope,
+      key_wrap,
+      server,
     } => {
+      if server.remote {
+        println!("Register encrypted data to remote server");
+      }
+      let (profile, client) = load_profile(server)?;
+      let srv = profile.server_url(server.server.as_deref(), server.remote);
+      let kdf = kdf.clone().or(profile.kdf).unwrap_or(KdfAlgorithm::Pbkdf2.to_string());
+      let hash = hash.clone().or(profile.hash).unwrap_or(KdfHash::default().to_string());
+      let iterations = iterations.or(profile.iterations).unwrap_or(ITERATION);
+      let etm = *etm || profile.etm.unwrap_or_default();
+      let secret = read_secret(secret)?;
+      let info = label
+        .as_ref()
+        .map(|label| HkdfInfo::try_new(label, *key_version))
+        .transpose()?;
+      let target = kdf_target_ms.map(Duration::from_millis);
+      let kdf_params = new_kdf_params(
+        secret.is_password(),
+        &kdf,
+        &hash,
+        &iterations,
+        target.as_ref(),
+        info.as_ref(),
+      )?;
+      let key_wrap = key_wrap
+        .as_ref()
+        .map(|alg| alg.parse::<KeyWrapAlgorithm>())
+        .transpose()?;
       post_data(
         data,
-        password.as_ref().map(|x| x.as_str()),
-        master.as_ref().map(|x| x.as_str()),
-        remote,
+        &secret,
+        &kdf_params,
+        &etm,
+        *envelope,
+        key_wrap.as_ref(),
+        show_secrets,
+        &client,
+        &srv,
       )
       .await?;
     }
+    SubCommands::List { limit, server } => {
+      let (profile, client) = load_profile(server)?;
+      let srv = profile.server_url(server.server.as_deref(), server.remote);
+      list_data(limit, &client, &srv).await?;
+    }
+    SubCommands::Update {
+      id,
+      data,
+      secret,
+      etm,
+      envelope,
+      server,
+    } => {
+      let (profile, client) = load_profile(server)?;
+      let srv = profile.server_url(server.server.as_deref(), server.remote);
+      let secret = read_secret(secret)?;
+      update_data(
+        id,
+        data.as_ref().map(|x| x.as_str()),
+        &secret,
+        etm,
+        *envelope,
+        show_secrets,
+        &client,
+        &srv,
+      )
+      .await?;
+    }
+    SubCommands::Rewrap {
+      id,
+      secret,
+      new,
+      key_version,
+      server,
+    } => {
+      let (profile, client) = load_profile(server)?;
+      let srv = profile.server_url(server.server.as_deref(), server.remote);
+      let secret = read_secret(secret)?;
+      let new_secret = read_new_secret(&secret, new)?;
+      rewrap_data(id, &secret, &new_secret, *key_version, show_secrets, &client, &srv).await?;
+    }
+    SubCommands::Rotate {
+      secret,
+      new,
+      journal,
+      resume,
+      limit,
+      server,
+    } => {
+      let (profile, client) = load_profile(server)?;
+      let srv = profile.server_url(server.server.as_deref(), server.remote);
+      let secret = read_secret(secret)?;
+      let new_secret = read_new_secret(&secret, new)?;
+      let report = rotate_data(&secret, &new_secret, journal, *resume, limit, &client, &srv).await?;
+      ensure!(
+        report.count("failed") == 0,
+        "{} objects failed to rotate. Run again with --resume to retry them.",
+        report.count("failed")
+      );
+    }
+    SubCommands::Delete { id, server } => {
+      let (profile, client) = load_profile(server)?;
+      let srv = profile.server_url(server.server.as_deref(), server.remote);
+      delete_data(id, &client, &srv).await?;
+    }
+    SubCommands::Encrypt_File {
+      input,
+      output,
+      secret,
+      kdf,
+      hash,
+      iterations,
+      kdf_target_ms,
+      label,
+      key_version,
+      chunk_size,
+    } => {
+      let secret = read_secret(secret)?;
+      let info = label
+        .as_ref()
+        .map(|label| HkdfInfo::try_new(label, *key_version))
+        .transpose()?;
+      let target = kdf_target_ms.map(Duration::from_millis);
+      let kdf_params = new_kdf_params(
+        secret.is_password(),
+        kdf.as_deref().unwrap_or(&KdfAlgorithm::Pbkdf2.to_string()),
+        hash.as_deref().unwrap_or(&KdfHash::default().to_string()),
+        &iterations.unwrap_or(ITERATION),
+        target.as_ref(),
+        info.as_ref(),
+      )?;
+      encrypt_file(input, output, &secret, &kdf_params, *chunk_size, show_secrets)?;
+    }
+    SubCommands::Decrypt_File { input, output, secret } => {
+      let secret = read_secret(secret)?;
+      decrypt_file(input, output, &secret, show_secrets)?;
+    }
+    SubCommands::Derive_Subkey {
+      master,
+      label,
+      key_version,
+      hash,
+      len,
+    } => {
+      let master = master.read("Master secret in Base64: ")?;
+      let master = Zeroizing::new(general_purpose::STANDARD.decode(master.as_str())?);
+      let info = HkdfInfo::try_new(label, *key_version)?;
+      let subkey = derive_subkey(&master, *len, &hash.parse()?, &info)?;
+      let subkey = Zeroizing::new(general_purpose::STANDARD.encode(&subkey));
+      println!("Derived sub-master secret in Base64: {}", subkey.as_str());
+    }
+    SubCommands::Calibrate_Kdf { kdf, hash, target_ms } => {
+      let kdfs = match kdf {
+        Some(kdf) => vec![kdf.parse::<KdfAlgorithm>()?],
+        None => vec![KdfAlgorithm::Pbkdf2, KdfAlgorithm::Argon2id, KdfAlgorithm::Scrypt],
+      };
+      calibrate_kdfs(&kdfs, &hash.parse()?, &Duration::from_millis(*target_ms))?;
+    }
     SubCommands::Gen_Secret { len } => {
       use rand::RngCore;
       let mut buf: Vec<u8> = vec![Default::default(); *len];
@@ -62,149 +232,575 @@ pub async fn main() -> Result<()> {
   Ok(())
 }
 
-async fn post_data(data: &str, pass: Option<&str>, master: Option<&str>, remote: &bool) -> Result<()> {
-  if *remote {
-    println!("Register encrypted data to remote server");
-  }
-  if (pass.is_none() && master.is_none()) && (pass.is_some() && master.is_some()) {
-    bail!("Either one of password or master must be exclusively specified");
+fn load_profile(server: &ServerArgs) -> Result<(Profile, reqwest::Client)> {
+  let profile = Profiles::load(server.profiles.as_deref())?.select(server.profile.as_deref())?;
+  let client = profile.build_client()?;
+  Ok((profile, client))
+}
+
+fn read_secret(args: &SecretArgs) -> Result<Secret> {
+  match (&args.password, &args.master) {
+    (Some(source), None) => Ok(Secret::Password(source.read("Password: ")?)),
+    (None, Some(source)) => Ok(Secret::Master(source.read("Master secret in Base64: ")?)),
+    _ => bail!("Either one of password or master must be exclusively specified"),
   }
+}
+
+/// New secret of the same kind as the current one
+fn read_new_secret(secret: &Secret, source: &SecretSource) -> Result<Secret> {
+  let new_secret = match secret {
+    Secret::Password(_) => Secret::Password(source.read("New password: ")?),
+    Secret::Master(_) => Secret::Master(source.read("New master secret in Base64: ")?),
+  };
+  ensure!(
+    new_secret.as_str() != secret.as_str(),
+    "New secret is the same as the current one"
+  );
+  Ok(new_secret)
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn post_data(
+  data: &str,
+  secret: &Secret,
+  kdf_params: &KdfParams,
+  etm: &bool,
+  envelope: bool,
+  key_wrap: Option<&KeyWrapAlgorithm>,
+  show_secrets: bool,
+  client: &reqwest::Client,
+  srv: &str,
+) -> Result<usize> {
+  print_secret(secret, show_secrets);
   println!("Data: {data}");
 
-  let body = if pass.is_some() {
-    println!("Password: {}", pass.unwrap());
+  let body = new_post_request(data, secret.as_str(), kdf_params, etm, envelope, key_wrap, show_secrets)?;
+  let res = client.post(srv).json(&body).send().await?.error_for_status()?;
+  let post_res = res.json::<PostResponse>().await?;
 
-    let binary_key = BinaryKey::try_new_pbkdf2(pass.unwrap(), 32, None, None)?;
-    let encrypted = encrypt(data.as_bytes(), &binary_key, None)?;
-    let data = encrypted.data_to_base64();
-    let iv = encrypted.iv_to_base64();
+  println!("Registered id: {:?}", post_res.id);
 
-    println!("Derived key and its related params:");
-    println!(
-      "Derived key in Base64: {}",
-      general_purpose::STANDARD.encode(&binary_key.key)
-    );
-    println!("PBKDF2 Param - Salt in Base64: {}", binary_key.salt);
-    println!("PBKDF2 Param - Hash: SHA-256");
-    println!("PBKDF2 Param - Iteration: 2048");
+  Ok(post_res.id)
+}
 
-    PostRequest {
-      data,
-      iv,
-      kdfParams: KdfParams {
-        salt: binary_key.salt,
-        hash: "SHA-256".to_string(),
-        iterationCount: Some(2048),
-      },
-    }
-  } else {
-    println!("Master secret: {}", master.unwrap());
-    let binary_key = BinaryKey::try_new_hdkf(master.unwrap(), 32, None)?;
-    let encrypted = encrypt(data.as_bytes(), &binary_key, None)?;
-    let data = encrypted.data_to_base64();
-    let iv = encrypted.iv_to_base64();
+async fn get_data(
+  id: &usize,
+  secret: &Secret,
+  show_secrets: bool,
+  client: &reqwest::Client,
+  srv: &str,
+) -> Result<String> {
+  println!("Id: {id}");
 
-    println!("Derived key and its related params:");
-    println!(
-      "Derived key in Base64: {}",
-      general_purpose::STANDARD.encode(&binary_key.key)
-    );
-    println!("HKDF Param - Salt in Base64: {}", binary_key.salt);
-    println!("HKDF Param - Hash: SHA-256");
+  let record = Envelope::upgrade_json(&fetch_record(id, client, srv).await?)?;
+  let secret = select_secret(&record.kdf, secret, show_secrets)?;
+  let dec = decrypt_response(&record, secret, show_secrets)?;
+  println!("Decrypted data: {dec}");
+  Ok(dec)
+}
 
-    PostRequest {
-      data,
-      iv,
-      kdfParams: KdfParams {
-        salt: binary_key.salt,
-        hash: "SHA-256".to_string(),
-        iterationCount: None,
-      },
-    }
+/// Decrypt the object, and put it back encrypted under the same KDF params but a fresh IV and salt.
+/// Envelopes stay envelopes, and legacy objects are upgraded to envelopes if `envelope`.
+/// A wrapped data key is replaced with a fresh one wrapped in the same way.
+#[allow(clippy::too_many_arguments)]
+async fn update_data(
+  id: &usize,
+  data: Option<&str>,
+  secret: &Secret,
+  etm: &bool,
+  envelope: bool,
+  show_secrets: bool,
+  client: &reqwest::Client,
+  srv: &str,
+) -> Result<()> {
+  println!("Id: {id}");
+
+  let raw_record = fetch_record(id, client, srv).await?;
+  let envelope = envelope || Envelope::is_envelope(&raw_record);
+  let record = Envelope::upgrade_json(&raw_record)?;
+  let kdf_params = KdfParams {
+    salt: general_purpose::STANDARD.encode(random_salt()),
+    ..record.kdf.clone()
   };
+  let etm = *etm || record.mac.is_some();
+  let secret = select_secret(&kdf_params, secret, show_secrets)?;
+  let current = decrypt_response(&record, secret, show_secrets)?;
+  let data = data.unwrap_or(&current);
+  println!("Data: {data}");
 
-  let client = reqwest::Client::new();
-  let srv = if *remote { REMOTE_SRV } else { LOCAL_SRV };
-  let res = client.post(srv).json::<PostRequest>(&body).send().await?;
-  let post_res = res.json::<PostResponse>().await?;
+  let key_wrap = record.kw.as_ref().map(WrappedKey::alg).transpose()?;
+  let body = new_post_request(
+    data,
+    secret,
+    &kdf_params,
+    &etm,
+    envelope,
+    key_wrap.as_ref(),
+    show_secrets,
+  )?;
+  client
+    .put(format!("{srv}/{id}"))
+    .json(&body)
+    .send()
+    .await?
+    .error_for_status()?;
+  println!("Updated id: {id}");
+  Ok(())
+}
 
-  println!("Registered id: {:?}", post_res.id);
+/// Wrap the data key of the object again under the new secret with a fresh salt, leaving its ciphertext as is.
+/// The version in the HKDF info is replaced with `key_version` if given.
+async fn rewrap_data(
+  id: &usize,
+  secret: &Secret,
+  new_secret: &Secret,
+  key_version: Option<u32>,
+  show_secrets: bool,
+  client: &reqwest::Client,
+  srv: &str,
+) -> Result<()> {
+  println!("Id: {id}");
 
+  let record = Envelope::upgrade_json(&fetch_record(id, client, srv).await?)?;
+  ensure!(
+    record.kw.is_some(),
+    "Object has no wrapped data key. Re-encrypt it by update instead."
+  );
+  let mut info = record.kdf.info.clone();
+  if let Some(version) = key_version {
+    let label = &info
+      .as_ref()
+      .context("Key version is bound into the HKDF info only with a label")?
+      .label;
+    info = Some(HkdfInfo::try_new(label, version)?);
+  }
+  let kdf_params = KdfParams {
+    salt: general_purpose::STANDARD.encode(random_salt()),
+    info,
+    ..record.kdf.clone()
+  };
+  let secret = select_secret(&record.kdf, secret, show_secrets)?;
+  let new_secret = select_secret(&kdf_params, new_secret, show_secrets)?;
+  let rewrapped = record.rewrap(secret, new_secret, &kdf_params)?;
+  println!("Key-encryption key derived by {}", describe_kdf(&kdf_params)?);
+
+  client
+    .put(format!("{srv}/{id}"))
+    .json(&rewrapped.to_json_value()?)
+    .send()
+    .await?
+    .error_for_status()?;
+  println!("Rewrapped id: {id}");
   Ok(())
 }
 
-async fn get_data(id: &usize, pass: Option<&str>, master: Option<&str>, remote: &bool) -> Result<()> {
-  if *remote {
-    println!("Retrieve encrypted data to remote server");
+/// Rotate every object on the server from the old secret to the new one, journaling the outcome per object.
+/// Objects journaled as rotated are skipped if `resume`, and the others are tried again.
+async fn rotate_data(
+  old: &Secret,
+  new: &Secret,
+  journal: &Path,
+  resume: bool,
+  limit: &usize,
+  client: &reqwest::Client,
+  srv: &str,
+) -> Result<Report> {
+  let mut journal = Journal::open(journal, srv, resume)?;
+  let mut report = Report::default();
+  for record in fetch_all(limit, client, srv).await? {
+    let id = record.get("id").and_then(Value::as_u64).context("Object without id")? as usize;
+    if journal.is_done(id) {
+      report.outcomes.push((id, Outcome::Resumed));
+      continue;
+    }
+    let outcome = match rotate_record(&record, old, new) {
+      Ok(Rotation::Reencrypted(body)) => {
+        let res = client.put(format!("{srv}/{id}")).json(&body).send().await;
+        match res.and_then(|res| res.error_for_status()) {
+          Ok(_) => Outcome::Rotated,
+          Err(e) => Outcome::Failed(e.to_string()),
+        }
+      }
+      Ok(Rotation::AlreadyRotated) => Outcome::AlreadyRotated,
+      Ok(Rotation::Skipped(reason)) => Outcome::Skipped(reason),
+      Err(e) => Outcome::Failed(format!("{e:#}")),
+    };
+    println!("{id}: {outcome}");
+    journal.append(id, &outcome)?;
+    report.outcomes.push((id, outcome));
   }
-  if (pass.is_none() && master.is_none()) && (pass.is_some() && master.is_some()) {
-    bail!("Either one of password or master must be exclusively specified");
+  report.print();
+  Ok(report)
+}
+
+async fn delete_data(id: &usize, client: &reqwest::Client, srv: &str) -> Result<()> {
+  client.delete(format!("{srv}/{id}")).send().await?.error_for_status()?;
+  println!("Deleted id: {id}");
+  Ok(())
+}
+
+/// Fetch all the objects page by page, and return their ids with how they are encrypted
+async fn list_data(limit: &usize, client: &reqwest::Client, srv: &str) -> Result<Vec<(usize, String)>> {
+  let mut listed = vec![];
+  for record in &fetch_all(limit, client, srv).await? {
+    let (id, description) = describe_record(record);
+    println!("{id}: {description}");
+    listed.push((id, description));
   }
-  println!("Id: {id}");
+  Ok(listed)
+}
 
-  let client = reqwest::Client::new();
-  let srv = if *remote { REMOTE_SRV } else { LOCAL_SRV };
-  let res = client.get(format!("{srv}/{id}")).send().await?;
-  let get_res = res.json::<GetResponse>().await?;
-  let binary_data = general_purpose::STANDARD.decode(get_res.data)?;
-  let binary_iv = general_purpose::STANDARD.decode(get_res.iv)?;
-  let kdf_params = get_res.kdfParams;
-
-  let binary_key = if pass.is_some() {
-    println!("{:?}", kdf_params);
-    ensure!(
-      kdf_params.iterationCount.is_some(),
-      "Invalid KDF params. Maybe key was derived with HDKF."
-    );
-    println!("Password: {}", pass.unwrap());
-    let iter = kdf_params.iterationCount.unwrap();
-    let k = BinaryKey::try_new_pbkdf2(pass.unwrap(), 32, Some(&kdf_params.salt), None)?;
-    println!("Derived key and its related params:");
-    println!("Derived key in Base64: {}", general_purpose::STANDARD.encode(&k.key));
-    println!("PBKDF2 Param - Salt in Base64: {}", k.salt);
-    println!("PBKDF2 Param - Hash: {}", kdf_params.hash);
-    println!("PBKDF2 Param - Iteration: {}", iter);
-    k
+async fn fetch_all(limit: &usize, client: &reqwest::Client, srv: &str) -> Result<Vec<Value>> {
+  ensure!(*limit > 0, "Page size must be positive");
+  let mut all = vec![];
+  let mut ids = HashSet::new();
+  for page in 1.. {
+    let res = client
+      .get(format!("{srv}?_page={page}&_limit={limit}"))
+      .send()
+      .await?
+      .error_for_status()?;
+    let total = total_count(&res);
+    let records = res.json::<Vec<Value>>().await?;
+    let last = records.len() < *limit;
+    let mut new_ids = false;
+    for record in records {
+      if ids.insert(record.get("id").and_then(Value::as_u64)) {
+        all.push(record);
+        new_ids = true;
+      }
+    }
+    // A server ignoring the paging returns the same objects again, so stop also at a page without new ones
+    if last || !new_ids || total.is_some_and(|total| all.len() >= total) {
+      break;
+    }
+  }
+  Ok(all)
+}
+
+/// Number of all the objects in `x-total-count` of the data server, if any
+fn total_count(res: &reqwest::Response) -> Option<usize> {
+  res.headers().get("x-total-count")?.to_str().ok()?.parse().ok()
+}
+
+async fn fetch_record(id: &usize, client: &reqwest::Client, srv: &str) -> Result<Value> {
+  let res = client.get(format!("{srv}/{id}")).send().await?.error_for_status()?;
+  Ok(res.json::<Value>().await?)
+}
+
+/// Encrypt the file chunk by chunk, and return the plaintext length
+fn encrypt_file(
+  input: &Path,
+  output: &Path,
+  secret: &Secret,
+  kdf_params: &KdfParams,
+  chunk_size: u32,
+  show_secrets: bool,
+) -> Result<u64> {
+  print_secret(secret, show_secrets);
+  let header = StreamHeader::new(kdf_params, chunk_size)?;
+  let binary_key = BinaryKey::try_from_kdf_params(secret.as_str(), 32, kdf_params)?;
+  print_derived_key(&binary_key, kdf_params, show_secrets)?;
+
+  let mut reader = File::open(input).with_context(|| format!("Failed to open {}", input.display()))?;
+  let len = write_atomically(output, |writer| {
+    encrypt_stream(&mut reader, writer, &binary_key.key, &header)
+  })?;
+  println!("Encrypted {len} bytes into {}", output.display());
+  Ok(len)
+}
+
+/// Decrypt the file chunk by chunk, and return the plaintext length
+fn decrypt_file(input: &Path, output: &Path, secret: &Secret, show_secrets: bool) -> Result<u64> {
+  let file = File::open(input).with_context(|| format!("Failed to open {}", input.display()))?;
+  let mut reader = BufReader::new(file);
+  let (header, aad) = StreamHeader::read(&mut reader)?;
+  let secret = select_secret(&header.kdf, secret, show_secrets)?;
+  let binary_key = BinaryKey::try_from_kdf_params(secret, 32, &header.kdf)?;
+  print_derived_key(&binary_key, &header.kdf, show_secrets)?;
+
+  let len = write_atomically(output, |writer| {
+    decrypt_stream(&mut reader, writer, &binary_key.key, &header, &aad)
+  })?;
+  println!("Decrypted {len} bytes into {}", output.display());
+  Ok(len)
+}
+
+/// Write to `<output>.partial` and rename it to `output` only on success, so that no partial output is left
+fn write_atomically(output: &Path, write: impl FnOnce(&mut BufWriter<File>) -> Result<u64>) -> Result<u64> {
+  let mut partial = output.as_os_str().to_owned();
+  partial.push(".partial");
+  let partial = PathBuf::from(partial);
+  let file = File::create(&partial).with_context(|| format!("Failed to create {}", partial.display()))?;
+  let mut writer = BufWriter::new(file);
+  let written = write(&mut writer).and_then(|len| {
+    writer.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+    Ok(len)
+  });
+  match written {
+    Ok(len) => {
+      fs::rename(&partial, output)?;
+      Ok(len)
+    }
+    Err(e) => {
+      let _ = fs::remove_file(&partial);
+      Err(e)
+    }
+  }
+}
+
+/// Ciphertext by AES-CBC as an envelope or a legacy object of sample-04.
+/// With `key_wrap`, the data is encrypted by a random data key wrapped under the derived key, only in an envelope.
+fn new_post_request(
+  data: &str,
+  secret: &str,
+  kdf_params: &KdfParams,
+  etm: &bool,
+  envelope: bool,
+  key_wrap: Option<&KeyWrapAlgorithm>,
+  show_secrets: bool,
+) -> Result<Value> {
+  if let Some(key_wrap) = key_wrap {
+    ensure!(envelope, "Wrapped data key is stored only in an envelope");
+    return new_wrapped_request(data, secret, kdf_params, etm, key_wrap, show_secrets);
+  }
+  let (encrypted, mac) = if *etm {
+    let (enc_key, mac_key) = BinaryKey::try_etm_from_kdf_params(secret, kdf_params)?;
+    print_derived_key(&enc_key, kdf_params, show_secrets)?;
+    let (encrypted, mac) = encrypt_then_mac(data.as_bytes(), &enc_key.key, &mac_key, None)?;
+    println!("HMAC-SHA-256 tag in Base64: {}", general_purpose::STANDARD.encode(&mac));
+    (encrypted, Some(mac))
+  } else {
+    let binary_key = BinaryKey::try_from_kdf_params(secret, 32, kdf_params)?;
+    print_derived_key(&binary_key, kdf_params, show_secrets)?;
+    (encrypt(data.as_bytes(), &binary_key.key, None)?, None)
+  };
+
+  let sealed = Envelope::new(&Algorithm::AesCbc, kdf_params.clone(), None, encrypted, mac);
+  if envelope {
+    sealed.to_json_value()
   } else {
-    println!("Master secret: {}", master.unwrap());
-    ensure!(
-      kdf_params.iterationCount.is_none(),
-      "Invalid KDF params. Maybe key was derived with PBKDF."
+    Ok(serde_json::to_value(Sample04Object::try_from(&sealed)?)?)
+  }
+}
+
+/// Envelope of the data encrypted by a random data key, which is wrapped under the key derived from the secret
+fn new_wrapped_request(
+  data: &str,
+  secret: &str,
+  kdf_params: &KdfParams,
+  etm: &bool,
+  key_wrap: &KeyWrapAlgorithm,
+  show_secrets: bool,
+) -> Result<Value> {
+  let sealed = Envelope::seal_wrapped(data.as_bytes(), secret, kdf_params, &Algorithm::AesCbc, *etm, key_wrap)?;
+  println!("Key-encryption key derived by {}", describe_kdf(kdf_params)?);
+  if show_secrets {
+    let dek_b64 = Zeroizing::new(general_purpose::STANDARD.encode(sealed.unwrap_dek(secret)?));
+    println!("Data key in Base64: {}", dek_b64.as_str());
+  }
+  if let Some(mac) = &sealed.mac {
+    println!("HMAC-SHA-256 tag in Base64: {}", general_purpose::STANDARD.encode(mac));
+  }
+  if let Some(kw) = &sealed.kw {
+    println!(
+      "Data key wrapped by {key_wrap} in Base64: {}",
+      general_purpose::STANDARD.encode(&kw.key)
     );
-    let k = BinaryKey::try_new_hdkf(master.unwrap(), 32, Some(&kdf_params.salt))?;
-    println!("Derived key and its related params:");
-    println!("Derived key in Base64: {}", general_purpose::STANDARD.encode(&k.key));
-    println!("HKDF Param - Salt in Base64: {}", k.salt);
-    println!("HKDF Param - Hash: {}", kdf_params.hash);
-    k
+  }
+  sealed.to_json_value()
+}
+
+/// Decrypt the envelope, which may also come from other clients and be encrypted by AES-GCM
+fn decrypt_response(envelope: &Envelope, secret: &str, show_secrets: bool) -> Result<String> {
+  let kdf_params = &envelope.kdf;
+  println!("{:?}", kdf_params);
+
+  let (dec, key) = envelope.open_with_derived_key(secret)?;
+  print_derived_key(&key, kdf_params, show_secrets)?;
+  if let Some(kw) = &envelope.kw {
+    println!("Data key unwrapped by {}", kw.alg);
+  }
+  if envelope.mac.is_some() {
+    println!("HMAC-SHA-256 tag verified before decryption");
+  }
+  Ok(String::from_utf8(dec)?)
+}
+
+/// Master secret for HKDF, password otherwise
+fn select_secret<'a>(kdf_params: &KdfParams, secret: &'a Secret, show_secrets: bool) -> Result<&'a str> {
+  match (kdf_params.algorithm()?, secret) {
+    (KdfAlgorithm::Hkdf, Secret::Master(_)) => (),
+    (KdfAlgorithm::Hkdf, Secret::Password(_)) => bail!("Invalid KDF params. Maybe key was derived with HDKF."),
+    (_, Secret::Password(_)) => (),
+    (_, Secret::Master(_)) => bail!("Invalid KDF params. Maybe key was derived with password-based KDF."),
+  };
+  print_secret(secret, show_secrets);
+  Ok(secret.as_str())
+}
+
+fn print_secret(secret: &Secret, show_secrets: bool) {
+  if !show_secrets {
+    return;
+  }
+  match secret {
+    Secret::Password(pass) => println!("Password: {}", pass.as_str()),
+    Secret::Master(master) => println!("Master secret: {}", master.as_str()),
+  }
+}
+
+/// Id and a short description of the object, tolerating objects posted by other clients
+fn describe_record(record: &Value) -> (usize, String) {
+  let id = record.get("id").and_then(Value::as_u64).unwrap_or_default() as usize;
+  let description = match Envelope::upgrade_json(record) {
+    Ok(envelope) => {
+      let mac = if envelope.mac.is_some() {
+        " and HMAC-SHA-256"
+      } else {
+        ""
+      };
+      let kdf = describe_kdf(&envelope.kdf).unwrap_or_default();
+      match (Envelope::is_envelope(record), &envelope.kw) {
+        (true, Some(kw)) => format!(
+          "encrypted with {}{mac}, data key wrapped by {} under key derived by {kdf}, envelope v{}",
+          envelope.cipher, kw.alg, envelope.v
+        ),
+        (true, None) => format!(
+          "encrypted with {}{mac}, key derived by {kdf}, envelope v{}",
+          envelope.cipher, envelope.v
+        ),
+        (false, _) => format!("encrypted with {}{mac}, key derived by {kdf}", envelope.cipher),
+      }
+    }
+    Err(_) => "unknown format".to_string(),
   };
+  (id, description)
+}
 
-  let dec = decrypt(
-    &Encrypted {
-      data: binary_data,
-      iv: binary_iv,
+fn describe_kdf(kdf_params: &KdfParams) -> Result<String> {
+  let alg = kdf_params.algorithm()?;
+  let description = match alg {
+    KdfAlgorithm::Pbkdf2 => format!(
+      "{alg} ({}, {} iterations)",
+      kdf_params.hash()?,
+      kdf_params.iterationCount.unwrap_or_default()
+    ),
+    KdfAlgorithm::Hkdf => match &kdf_params.info {
+      Some(info) => format!(
+        "{alg} ({}, label \"{}\" v{})",
+        kdf_params.hash()?,
+        info.label,
+        info.version
+      ),
+      None => format!("{alg} ({})", kdf_params.hash()?),
     },
-    &binary_key,
-  )?;
+    KdfAlgorithm::Argon2id => format!(
+      "{alg} (m={} KiB, t={}, p={})",
+      kdf_params.memoryCost.unwrap_or_default(),
+      kdf_params.iterationCount.unwrap_or_default(),
+      kdf_params.lanes.unwrap_or_default()
+    ),
+    KdfAlgorithm::Scrypt => format!(
+      "{alg} (N={}, r={}, p={})",
+      kdf_params.n.unwrap_or_default(),
+      kdf_params.r.unwrap_or_default(),
+      kdf_params.p.unwrap_or_default()
+    ),
+  };
+  Ok(description)
+}
 
-  println!("Decrypted data: {}", String::from_utf8(dec)?);
-  Ok(())
+/// Build KDF params with a fresh random salt from the command line options.
+/// The cost of the password-based KDF is calibrated to `target` if given.
+fn new_kdf_params(
+  is_password: bool,
+  kdf: &str,
+  hash: &str,
+  iterations: &u32,
+  target: Option<&Duration>,
+  info: Option<&HkdfInfo>,
+) -> Result<KdfParams> {
+  let salt = random_salt();
+  let hash = hash.parse::<KdfHash>()?;
+  if !is_password {
+    ensure!(target.is_none(), "HKDF with master secret has no cost to calibrate");
+    return Ok(KdfParams::new_hkdf(&salt, &hash, info));
+  }
+  ensure!(info.is_none(), "HKDF label is only for master secret");
+  if let Some(target) = target {
+    let kdf_params = calibrate(&kdf.parse()?, &hash, *target)?;
+    println!(
+      "Calibrated to {} ms: {}",
+      target.as_millis(),
+      describe_kdf(&kdf_params)?
+    );
+    return Ok(kdf_params);
+  }
+  match kdf.parse::<KdfAlgorithm>()? {
+    KdfAlgorithm::Pbkdf2 => Ok(KdfParams::new_pbkdf2(&salt, &hash, *iterations)),
+    KdfAlgorithm::Argon2id => Ok(KdfParams::new_argon2id(&salt, &Argon2idCost::default())),
+    KdfAlgorithm::Scrypt => Ok(KdfParams::new_scrypt(&salt, &ScryptCost::default())),
+    KdfAlgorithm::Hkdf => bail!("HKDF is not a password KDF. Use master secret instead."),
+  }
 }
 
-#[allow(non_snake_case)]
-#[derive(Serialize, Debug)]
-struct PostRequest {
-  pub data: String,
-  pub iv: String,
-  pub kdfParams: KdfParams,
+/// Calibrate each KDF to the target time, and print the chosen params with the measured time
+fn calibrate_kdfs(kdfs: &[KdfAlgorithm], hash: &KdfHash, target: &Duration) -> Result<Vec<KdfParams>> {
+  println!("Target time: {} ms", target.as_millis());
+  let mut calibrated = vec![];
+  for kdf in kdfs {
+    let kdf_params = calibrate(kdf, hash, *target)?;
+    let elapsed = measure(&kdf_params)?;
+    println!("{}: {} ms", describe_kdf(&kdf_params)?, elapsed.as_millis());
+    calibrated.push(kdf_params);
+  }
+  Ok(calibrated)
 }
 
-#[allow(non_snake_case)]
-#[derive(Serialize, Deserialize, Debug)]
-struct KdfParams {
-  salt: String,
-  hash: String,
-  iterationCount: Option<usize>,
+/// Print the KDF params, and also the derived key only if `show_secrets`
+fn print_derived_key(binary_key: &BinaryKey, kdf_params: &KdfParams, show_secrets: bool) -> Result<()> {
+  let alg = kdf_params.algorithm()?;
+  println!("Derived key and its related params:");
+  if show_secrets {
+    let key_b64 = Zeroizing::new(general_purpose::STANDARD.encode(&binary_key.key));
+    println!("Derived key in Base64: {}", key_b64.as_str());
+  }
+  println!("{alg} Param - Salt in Base64: {}", binary_key.salt_to_base64());
+  match alg {
+    KdfAlgorithm::Pbkdf2 => {
+      println!("{alg} Param - Hash: {}", kdf_params.hash()?);
+      println!(
+        "{alg} Param - Iteration: {}",
+        kdf_params.iterationCount.unwrap_or_default()
+      );
+    }
+    KdfAlgorithm::Hkdf => {
+      println!("{alg} Param - Hash: {}", kdf_params.hash()?);
+      if let Some(info) = &kdf_params.info {
+        println!("{alg} Param - Label: {}, Version: {}", info.label, info.version);
+      }
+    }
+    KdfAlgorithm::Argon2id => {
+      println!(
+        "{alg} Param - Memory (KiB): {}",
+        kdf_params.memoryCost.unwrap_or_default()
+      );
+      println!(
+        "{alg} Param - Iteration: {}",
+        kdf_params.iterationCount.unwrap_or_default()
+      );
+      println!("{alg} Param - Lanes: {}", kdf_params.lanes.unwrap_or_default());
+    }
+    KdfAlgorithm::Scrypt => {
+      println!(
+        "{alg} Param - N: {}, r: {}, p: {}",
+        kdf_params.n.unwrap_or_default(),
+        kdf_params.r.unwrap_or_default(),
+        kdf_params.p.unwrap_or_default()
+      );
+    }
+  }
+  Ok(())
 }
 
 #[derive(Deserialize, Debug)]
@@ -212,12 +808,434 @@ struct PostResponse {
   pub id: usize,
 }
 
-#[allow(non_snake_case)]
-#[derive(Deserialize, Debug)]
-struct GetResponse {
-  #[allow(dead_code)]
-  pub id: usize,
-  pub data: String,
-  pub iv: String,
-  pub kdfParams: KdfParams,
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn password(pass: &str) -> Secret {
+    Secret::Password(Zeroizing::new(pass.to_string()))
+  }
+
+  fn master(master: &str) -> Secret {
+    Secret::Master(Zeroizing::new(master.to_string()))
+  }
+
+  #[tokio::test]
+  async fn post_get_with_local_server() -> Result<()> {
+    let srv = data_server::spawn_in_memory().await?;
+    let client = &reqwest::Client::new();
+    let master_b64 = general_purpose::STANDARD.encode([0x42u8; 32]);
+
+    let info = HkdfInfo::try_new("record-1", 1)?;
+    let cases = [
+      (true, "PBKDF2", "SHA-256", 2048, None),
+      (true, "PBKDF2", "SHA-512", 3000, None),
+      (false, "HKDF", "SHA-384", 0, None),
+      (false, "HKDF", "SHA-256", 0, Some(&info)),
+    ];
+    for (is_password, kdf, hash, iter, info) in cases {
+      for etm in [false, true] {
+        let kdf_params = new_kdf_params(is_password, kdf, hash, &iter, None, info)?;
+        let (secret, wrong, other) = if is_password {
+          (password("password"), password("wrong"), master(&master_b64))
+        } else {
+          (master(&master_b64), master("d3Jvbmc="), password("password"))
+        };
+        let id = post_data(
+          "secret text",
+          &secret,
+          &kdf_params,
+          &etm,
+          false,
+          None,
+          false,
+          client,
+          &srv,
+        )
+        .await?;
+        assert_eq!(get_data(&id, &secret, false, client, &srv).await?, "secret text");
+        assert!(get_data(&id, &wrong, false, client, &srv).await.is_err());
+        assert!(get_data(&id, &other, false, client, &srv).await.is_err());
+      }
+    }
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn list_update_delete_with_local_server() -> Result<()> {
+    let srv = data_server::spawn_in_memory().await?;
+    let client = &reqwest::Client::new();
+    let master = master(&general_purpose::STANDARD.encode([0x42u8; 32]));
+    let pass = password("password");
+
+    let pbkdf2 = new_kdf_params(true, "PBKDF2", "SHA-512", &3000, None, None)?;
+    let pbkdf2 = post_data("secret text", &pass, &pbkdf2, &false, false, None, false, client, &srv).await?;
+    let hkdf = new_kdf_params(false, "HKDF", "SHA-256", &0, None, None)?;
+    let hkdf = post_data("secret text", &master, &hkdf, &true, false, None, false, client, &srv).await?;
+    let plain = client
+      .post(&srv)
+      .json(&serde_json::json!({"data": "plain text"}))
+      .send()
+      .await?
+      .json::<PostResponse>()
+      .await?
+      .id;
+
+    let listed = list_data(&2, client, &srv).await?;
+    assert_eq!(listed.len(), 3);
+    assert_eq!(
+      listed[0],
+      (
+        pbkdf2,
+        "encrypted with AES-CBC, key derived by PBKDF2 (SHA-512, 3000 iterations)".to_string()
+      )
+    );
+    assert_eq!(
+      listed[1],
+      (
+        hkdf,
+        "encrypted with AES-CBC and HMAC-SHA-256, key derived by HKDF (SHA-256)".to_string()
+      )
+    );
+    assert_eq!(listed[2], (plain, "unknown format".to_string()));
+
+    let before = fetch_record(&pbkdf2, client, &srv).await?;
+    update_data(&pbkdf2, None, &pass, &false, false, false, client, &srv).await?;
+    let after = fetch_record(&pbkdf2, client, &srv).await?;
+    assert_ne!(before["iv"], after["iv"]);
+    assert_ne!(before["kdfParams"]["salt"], after["kdfParams"]["salt"]);
+    assert_eq!(after["kdfParams"]["hash"], before["kdfParams"]["hash"]);
+    assert_eq!(
+      after["kdfParams"]["iterationCount"],
+      before["kdfParams"]["iterationCount"]
+    );
+    assert!(after.get("mac").is_none());
+    assert_eq!(get_data(&pbkdf2, &pass, false, client, &srv).await?, "secret text");
+    assert!(
+      update_data(&pbkdf2, None, &password("wrong"), &false, false, false, client, &srv)
+        .await
+        .is_err()
+    );
+
+    update_data(&pbkdf2, Some("new text"), &pass, &true, false, false, client, &srv).await?;
+    assert!(fetch_record(&pbkdf2, client, &srv).await?.get("mac").is_some());
+    assert_eq!(get_data(&pbkdf2, &pass, false, client, &srv).await?, "new text");
+    update_data(&hkdf, Some("new text"), &master, &false, false, false, client, &srv).await?;
+    assert!(fetch_record(&hkdf, client, &srv).await?.get("mac").is_some());
+    assert_eq!(get_data(&hkdf, &master, false, client, &srv).await?, "new text");
+
+    delete_data(&hkdf, client, &srv).await?;
+    assert!(get_data(&hkdf, &master, false, client, &srv).await.is_err());
+    assert!(delete_data(&hkdf, client, &srv).await.is_err());
+    assert_eq!(list_data(&10, client, &srv).await?.len(), 2);
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn fetch_all_from_server_ignoring_paging() -> Result<()> {
+    use std::io::{Read, Write};
+    // Every page is the full list, with and without `x-total-count`
+    let body = r#"[{"id":1,"data":"a"},{"id":2,"data":"b"},{"id":3,"data":"c"}]"#;
+    for total in ["x-total-count: 3\r\n", ""] {
+      let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+      let srv = format!("http://{}/data", listener.local_addr()?);
+      std::thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+          let _ = stream.read(&mut [0u8; 4096]);
+          let _ = write!(
+            stream,
+            "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\n{total}content-length: {}\r\nconnection: close\r\n\r\n{body}",
+            body.len()
+          );
+        }
+      });
+      let all = fetch_all(&2, &reqwest::Client::new(), &srv).await?;
+      assert_eq!(
+        all.iter().map(|record| record["id"].clone()).collect::<Vec<_>>(),
+        [1, 2, 3]
+      );
+    }
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn calibrated_kdf_with_local_server() -> Result<()> {
+    let srv = data_server::spawn_in_memory().await?;
+    let client = &reqwest::Client::new();
+    let target = Duration::from_millis(5);
+
+    let calibrated = calibrate_kdfs(&[KdfAlgorithm::Pbkdf2], &KdfHash::Sha384, &target)?;
+    assert_eq!(calibrated[0].hash()?, KdfHash::Sha384);
+    let kdf_params = new_kdf_params(true, "PBKDF2", "SHA-256", &ITERATION, Some(&target), None)?;
+    kdf_params.validate()?;
+    let id = post_data(
+      "secret text",
+      &password("password"),
+      &kdf_params,
+      &false,
+      false,
+      None,
+      false,
+      client,
+      &srv,
+    )
+    .await?;
+    let record = fetch_record(&id, client, &srv).await?;
+    assert_eq!(
+      record["kdfParams"]["iterationCount"],
+      kdf_params.iterationCount.unwrap()
+    );
+    assert_eq!(
+      get_data(&id, &password("password"), false, client, &srv).await?,
+      "secret text"
+    );
+    assert!(new_kdf_params(false, "PBKDF2", "SHA-256", &ITERATION, Some(&target), None).is_err());
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn envelope_with_local_server() -> Result<()> {
+    let srv = data_server::spawn_in_memory().await?;
+    let client = &reqwest::Client::new();
+    let master = master(&general_purpose::STANDARD.encode([0x42u8; 32]));
+    let pass = password("password");
+
+    let info = HkdfInfo::try_new("record-1", 1)?;
+    let hkdf = new_kdf_params(false, "HKDF", "SHA-256", &0, None, Some(&info))?;
+    let id = post_data("secret text", &master, &hkdf, &true, true, None, false, client, &srv).await?;
+    let record = fetch_record(&id, client, &srv).await?;
+    assert_eq!(record["v"], 1);
+    assert!(record.get("kdfParams").is_none());
+    assert_eq!(get_data(&id, &master, false, client, &srv).await?, "secret text");
+    assert!(get_data(&id, &pass, false, client, &srv).await.is_err());
+    assert!(list_data(&10, client, &srv).await?[0].1.ends_with("envelope v1"));
+
+    let pbkdf2 = new_kdf_params(true, "PBKDF2", "SHA-256", &2048, None, None)?;
+    let legacy = post_data("secret text", &pass, &pbkdf2, &false, false, None, false, client, &srv).await?;
+    update_data(&legacy, None, &pass, &false, true, false, client, &srv).await?;
+    assert!(Envelope::is_envelope(&fetch_record(&legacy, client, &srv).await?));
+    update_data(&legacy, Some("new text"), &pass, &false, false, false, client, &srv).await?;
+    assert!(Envelope::is_envelope(&fetch_record(&legacy, client, &srv).await?));
+    assert_eq!(get_data(&legacy, &pass, false, client, &srv).await?, "new text");
+
+    // Envelopes by AES-GCM from other clients are also readable
+    let gcm = Envelope::seal(b"from cli03", "password", &pbkdf2, &Algorithm::AesGcm, false)?;
+    let id = client
+      .post(&srv)
+      .json(&gcm.to_json_value()?)
+      .send()
+      .await?
+      .json::<PostResponse>()
+      .await?
+      .id;
+    assert_eq!(get_data(&id, &pass, false, client, &srv).await?, "from cli03");
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn key_wrap_with_local_server() -> Result<()> {
+    let srv = data_server::spawn_in_memory().await?;
+    let client = &reqwest::Client::new();
+    let old_master = master(&general_purpose::STANDARD.encode([0x42u8; 32]));
+    let new_master = master(&general_purpose::STANDARD.encode([0x43u8; 32]));
+    let kw = KeyWrapAlgorithm::AesKw;
+
+    let info = HkdfInfo::try_new("record-1", 1)?;
+    let hkdf = new_kdf_params(false, "HKDF", "SHA-256", &0, None, Some(&info))?;
+    let id = post_data(
+      "secret text",
+      &old_master,
+      &hkdf,
+      &true,
+      true,
+      Some(&kw),
+      false,
+      client,
+      &srv,
+    )
+    .await?;
+    let before = fetch_record(&id, client, &srv).await?;
+    assert_eq!(before["kw"]["alg"], "AES-KW");
+    assert_eq!(get_data(&id, &old_master, false, client, &srv).await?, "secret text");
+    assert!(list_data(&10, client, &srv).await?[0]
+      .1
+      .contains("data key wrapped by AES-KW under key derived by HKDF"));
+
+    rewrap_data(&id, &old_master, &new_master, Some(2), false, client, &srv).await?;
+    let after = fetch_record(&id, client, &srv).await?;
+    for field in ["iv", "data", "mac"] {
+      assert_eq!(after[field], before[field]);
+    }
+    assert_ne!(after["kw"], before["kw"]);
+    assert_ne!(after["kdf"]["salt"], before["kdf"]["salt"]);
+    assert_eq!(after["kdf"]["info"]["version"], 2);
+    assert_eq!(get_data(&id, &new_master, false, client, &srv).await?, "secret text");
+    assert!(get_data(&id, &old_master, false, client, &srv).await.is_err());
+    assert!(rewrap_data(&id, &old_master, &new_master, None, false, client, &srv)
+      .await
+      .is_err());
+
+    update_data(&id, Some("new text"), &new_master, &false, false, false, client, &srv).await?;
+    let updated = fetch_record(&id, client, &srv).await?;
+    assert_eq!(updated["kw"]["alg"], "AES-KW");
+    assert_ne!(updated["kw"], after["kw"]);
+    assert_eq!(get_data(&id, &new_master, false, client, &srv).await?, "new text");
+
+    let pbkdf2 = new_kdf_params(true, "PBKDF2", "SHA-256", &2048, None, None)?;
+    let kwp = KeyWrapAlgorithm::AesKwp;
+    let id = post_data(
+      "secret text",
+      &password("old"),
+      &pbkdf2,
+      &false,
+      true,
+      Some(&kwp),
+      false,
+      client,
+      &srv,
+    )
+    .await?;
+    rewrap_data(&id, &password("old"), &password("new"), None, false, client, &srv).await?;
+    assert_eq!(
+      get_data(&id, &password("new"), false, client, &srv).await?,
+      "secret text"
+    );
+    assert!(
+      rewrap_data(&id, &password("new"), &password("newer"), Some(2), false, client, &srv)
+        .await
+        .is_err()
+    );
+
+    let plain = post_data(
+      "secret text",
+      &password("old"),
+      &pbkdf2,
+      &false,
+      true,
+      None,
+      false,
+      client,
+      &srv,
+    )
+    .await?;
+    assert!(
+      rewrap_data(&plain, &password("old"), &password("new"), None, false, client, &srv)
+        .await
+        .is_err()
+    );
+    assert!(new_post_request("secret text", "old", &pbkdf2, &false, false, Some(&kw), false).is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn encrypt_and_decrypt_file() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("cli04-file-test-{}", std::process::id()));
+    fs::create_dir_all(&dir)?;
+    let (input, encrypted, output) = (dir.join("input"), dir.join("input.enc"), dir.join("output"));
+    let data = (0..100_000u32).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+    fs::write(&input, &data)?;
+
+    let pass = password("password");
+    let pbkdf2 = new_kdf_params(true, "PBKDF2", "SHA-256", &2048, None, None)?;
+    assert_eq!(encrypt_file(&input, &encrypted, &pass, &pbkdf2, 4096, false)?, 100_000);
+    assert_eq!(decrypt_file(&encrypted, &output, &pass, false)?, 100_000);
+    assert_eq!(fs::read(&output)?, data);
+    fs::remove_file(&output)?;
+
+    // Neither a wrong password nor a truncated file leaves any output
+    assert!(decrypt_file(&encrypted, &output, &password("wrong"), false).is_err());
+    let truncated = dir.join("truncated.enc");
+    let bytes = fs::read(&encrypted)?;
+    fs::write(&truncated, &bytes[..bytes.len() - 1000])?;
+    assert!(decrypt_file(&truncated, &output, &pass, false).is_err());
+    assert!(!output.exists() && !dir.join("output.partial").exists());
+
+    let master = master(&general_purpose::STANDARD.encode([0x42u8; 32]));
+    let info = HkdfInfo::try_new("backup", 1)?;
+    let hkdf = new_kdf_params(false, "HKDF", "SHA-256", &0, None, Some(&info))?;
+    encrypt_file(&input, &encrypted, &master, &hkdf, 65536, false)?;
+    assert!(decrypt_file(&encrypted, &output, &pass, false).is_err());
+    decrypt_file(&encrypted, &output, &master, false)?;
+    assert_eq!(fs::read(&output)?, data);
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn rotate_with_local_server() -> Result<()> {
+    let srv = data_server::spawn_in_memory().await?;
+    let client = &reqwest::Client::new();
+    let (old, new) = (password("old"), password("new"));
+    let journal = std::env::temp_dir().join(format!("cli04-rotate-test-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&journal);
+
+    let pbkdf2 = new_kdf_params(true, "PBKDF2", "SHA-256", &2048, None, None)?;
+    let legacy = post_data("legacy", &old, &pbkdf2, &true, false, None, false, client, &srv).await?;
+    let wrapped = post_data(
+      "wrapped",
+      &old,
+      &pbkdf2,
+      &false,
+      true,
+      Some(&KeyWrapAlgorithm::AesKw),
+      false,
+      client,
+      &srv,
+    )
+    .await?;
+    let gcm = Envelope::seal(b"from cli03", "old", &pbkdf2, &Algorithm::AesGcm, false)?;
+    let gcm = client
+      .post(&srv)
+      .json(&gcm.to_json_value()?)
+      .send()
+      .await?
+      .json::<PostResponse>()
+      .await?
+      .id;
+    let others = post_data(
+      "others",
+      &password("other"),
+      &pbkdf2,
+      &false,
+      false,
+      None,
+      false,
+      client,
+      &srv,
+    )
+    .await?;
+    let master = master(&general_purpose::STANDARD.encode([0x42u8; 32]));
+    let hkdf = new_kdf_params(false, "HKDF", "SHA-256", &0, None, None)?;
+    let hkdf = post_data("master", &master, &hkdf, &false, false, None, false, client, &srv).await?;
+
+    let report = rotate_data(&old, &new, &journal, false, &2, client, &srv).await?;
+    assert_eq!((report.count("rotated"), report.count("skipped")), (3, 2));
+    assert_eq!(get_data(&legacy, &new, false, client, &srv).await?, "legacy");
+    assert!(fetch_record(&legacy, client, &srv).await?.get("kdfParams").is_some());
+    assert_eq!(get_data(&wrapped, &new, false, client, &srv).await?, "wrapped");
+    assert_eq!(get_data(&gcm, &new, false, client, &srv).await?, "from cli03");
+    assert_eq!(fetch_record(&gcm, client, &srv).await?["cipher"], "AES-GCM");
+    assert!(get_data(&legacy, &old, false, client, &srv).await.is_err());
+    assert_eq!(
+      get_data(&others, &password("other"), false, client, &srv).await?,
+      "others"
+    );
+    assert_eq!(get_data(&hkdf, &master, false, client, &srv).await?, "master");
+
+    // A new journal is refused, and resuming skips the objects already rotated
+    assert!(rotate_data(&old, &new, &journal, false, &2, client, &srv)
+      .await
+      .is_err());
+    let added = post_data("added", &old, &pbkdf2, &false, false, None, false, client, &srv).await?;
+    let report = rotate_data(&old, &new, &journal, true, &2, client, &srv).await?;
+    assert_eq!((report.count("resumed"), report.count("rotated")), (3, 1));
+    assert_eq!(get_data(&added, &new, false, client, &srv).await?, "added");
+
+    // Objects put back without being journaled are detected by the new secret
+    std::fs::remove_file(&journal)?;
+    let report = rotate_data(&old, &new, &journal, false, &10, client, &srv).await?;
+    assert_eq!((report.count("already rotated"), report.count("rotated")), (4, 0));
+    std::fs::remove_file(&journal)?;
+    Ok(())
+  }
 }
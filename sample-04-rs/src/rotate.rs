@@ -0,0 +1,279 @@
+//! Rotation of the password or master secret over all the objects on the server.
+//! The outcome per object is appended to a journal, so that an interrupted rotation can be resumed
+//! without touching the objects already rotated.
+
+use crate::{error::*, secret::Secret};
+use base64::{engine::general_purpose, Engine as _};
+use seceng_crypto::{
+  envelope::{Envelope, Sample03Object, Sample04Object},
+  kdf::{random_salt, KdfAlgorithm, KdfParams},
+  symmetric::Algorithm,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+  collections::HashSet,
+  fmt::Display,
+  fs::{self, File, OpenOptions},
+  io::Write,
+  path::Path,
+};
+use zeroize::Zeroizing;
+
+/// Object re-encrypted under the new secret, or why it is left as is
+#[derive(Debug)]
+pub enum Rotation {
+  Reencrypted(Value),
+  AlreadyRotated,
+  Skipped(String),
+}
+
+/// Decrypt the object with the old secret, and encrypt it again under the new secret with a fresh salt,
+/// keeping its format, cipher, KDF and MAC. A wrapped data key is just rewrapped.
+pub fn rotate_record(record: &Value, old: &Secret, new: &Secret) -> Result<Rotation> {
+  let Ok(envelope) = Envelope::upgrade_json(record) else {
+    return Ok(Rotation::Skipped("not an encrypted object".to_string()));
+  };
+  let alg = envelope.kdf.algorithm()?;
+  if (alg == KdfAlgorithm::Hkdf) == old.is_password() {
+    return Ok(Rotation::Skipped(format!(
+      "key derived by {alg} from another kind of secret"
+    )));
+  }
+  if envelope.ka.is_some() {
+    return Ok(Rotation::Skipped("key agreed by ECDH".to_string()));
+  }
+  // Without a MAC or a wrapped key, a wrong secret still yields a valid padding of AES-CBC about once in 256 times.
+  // So the plaintext must be a string as posted by cli04, and must not be decrypted by both the secrets.
+  let unauthenticated = envelope.mac.is_none() && envelope.kw.is_none() && envelope.cipher()? == Algorithm::AesCbc;
+  let open = |secret: &Secret| {
+    let plaintext = Zeroizing::new(envelope.open(secret.as_str()).ok()?);
+    (!unauthenticated || std::str::from_utf8(&plaintext).is_ok()).then_some(plaintext)
+  };
+  let Some(plaintext) = open(old) else {
+    return Ok(match open(new) {
+      Some(_) => Rotation::AlreadyRotated,
+      None => Rotation::Skipped("decrypted by neither the old nor the new secret".to_string()),
+    });
+  };
+  if unauthenticated && open(new).is_some() {
+    return Ok(Rotation::Skipped(
+      "decrypted by both the old and the new secret without a MAC".to_string(),
+    ));
+  }
+
+  let kdf = KdfParams {
+    salt: general_purpose::STANDARD.encode(random_salt()),
+    ..envelope.kdf.clone()
+  };
+  let rotated = match &envelope.kw {
+    Some(_) => envelope.rewrap(old.as_str(), new.as_str(), &kdf)?,
+    None => {
+      let etm = envelope.mac.is_some();
+      Envelope::seal(&plaintext, new.as_str(), &kdf, &envelope.cipher()?, etm)?
+    }
+  };
+  let body = if Envelope::is_envelope(record) {
+    rotated.to_json_value()?
+  } else if record.get("kdfParams").is_some() {
+    serde_json::to_value(Sample04Object::try_from(&rotated)?)?
+  } else {
+    serde_json::to_value(Sample03Object::try_from(&rotated)?)?
+  };
+  Ok(Rotation::Reencrypted(body))
+}
+
+/// Outcome of the rotation of an object
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+  Rotated,
+  /// Decrypted only by the new secret, e.g., put back before an interruption but not journaled
+  AlreadyRotated,
+  /// Journaled as rotated in an earlier run
+  Resumed,
+  Skipped(String),
+  Failed(String),
+}
+
+impl Outcome {
+  pub fn kind(&self) -> &'static str {
+    match self {
+      Outcome::Rotated => "rotated",
+      Outcome::AlreadyRotated => "already rotated",
+      Outcome::Resumed => "resumed",
+      Outcome::Skipped(_) => "skipped",
+      Outcome::Failed(_) => "failed",
+    }
+  }
+}
+
+impl Display for Outcome {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Outcome::Skipped(reason) | Outcome::Failed(reason) => write!(f, "{}: {reason}", self.kind()),
+      _ => write!(f, "{}", self.kind()),
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Entry {
+  server: String,
+  id: usize,
+  outcome: String,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  reason: Option<String>,
+}
+
+/// Append-only journal of the outcome per object in JSON lines
+pub struct Journal {
+  file: File,
+  server: String,
+  /// Objects rotated in earlier runs
+  done: HashSet<usize>,
+}
+
+impl Journal {
+  /// Open the journal for the server. An existing journal is refused unless `resume`, so that a new rotation
+  /// is not mistaken for the one recorded in it.
+  pub fn open(path: &Path, server: &str, resume: bool) -> Result<Self> {
+    let content = match fs::read_to_string(path) {
+      Ok(content) => {
+        ensure!(
+          resume,
+          "Journal {} exists. Resume the rotation with --resume, or remove it to start a new one.",
+          path.display()
+        );
+        content
+      }
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+      Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    // The last line may be cut off by an interruption, and is then ignored
+    let done = content
+      .lines()
+      .filter_map(|line| serde_json::from_str::<Entry>(line).ok())
+      .filter(|entry| entry.server == server && matches!(entry.outcome.as_str(), "rotated" | "already rotated"))
+      .map(|entry| entry.id)
+      .collect();
+    let mut file = OpenOptions::new()
+      .create(true)
+      .append(true)
+      .open(path)
+      .with_context(|| format!("Failed to open {}", path.display()))?;
+    if !content.is_empty() && !content.ends_with('\n') {
+      writeln!(file)?;
+    }
+    Ok(Self {
+      file,
+      server: server.to_string(),
+      done,
+    })
+  }
+
+  pub fn is_done(&self, id: usize) -> bool {
+    self.done.contains(&id)
+  }
+
+  /// Append the outcome and flush it to the disk before the next object
+  pub fn append(&mut self, id: usize, outcome: &Outcome) -> Result<()> {
+    let reason = match outcome {
+      Outcome::Skipped(reason) | Outcome::Failed(reason) => Some(reason.clone()),
+      _ => None,
+    };
+    let entry = Entry {
+      server: self.server.clone(),
+      id,
+      outcome: outcome.kind().to_string(),
+      reason,
+    };
+    writeln!(self.file, "{}", serde_json::to_string(&entry)?)?;
+    self.file.sync_data()?;
+    Ok(())
+  }
+}
+
+/// Outcomes of all the objects in the order of the server
+#[derive(Debug, Default)]
+pub struct Report {
+  pub outcomes: Vec<(usize, Outcome)>,
+}
+
+impl Report {
+  pub fn count(&self, kind: &str) -> usize {
+    self
+      .outcomes
+      .iter()
+      .filter(|(_, outcome)| outcome.kind() == kind)
+      .count()
+  }
+
+  pub fn print(&self) {
+    println!("Rotation report:");
+    for kind in ["rotated", "already rotated", "resumed", "skipped", "failed"] {
+      println!("  {kind}: {}", self.count(kind));
+    }
+    for (id, outcome) in &self.outcomes {
+      if matches!(outcome, Outcome::Skipped(_) | Outcome::Failed(_)) {
+        println!("  {id}: {outcome}");
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use seceng_crypto::kdf::KdfHash;
+
+  fn master(seed: u16) -> Secret {
+    let mut master = [0x42u8; 32];
+    master[..2].copy_from_slice(&seed.to_be_bytes());
+    Secret::Master(Zeroizing::new(general_purpose::STANDARD.encode(master)))
+  }
+
+  #[test]
+  fn wrong_secret_with_valid_padding_is_skipped() -> Result<()> {
+    let (old, new) = (master(0), master(1));
+    let kdf = KdfParams::new_hkdf(&random_salt(), &KdfHash::Sha256, None);
+    let record = Envelope::seal(b"text", old.as_str(), &kdf, &Algorithm::AesCbc, false)?.to_json_value()?;
+    assert!(matches!(rotate_record(&record, &old, &new)?, Rotation::Reencrypted(_)));
+
+    // Another secret of the object's owner, which happens to give a valid padding of AES-CBC but garbage
+    let envelope = Envelope::upgrade_json(&record)?;
+    let wrong = (2..)
+      .map(master)
+      .find(|wrong| envelope.open(wrong.as_str()).is_ok())
+      .unwrap();
+    assert!(std::str::from_utf8(&envelope.open(wrong.as_str())?).is_err());
+    assert!(matches!(rotate_record(&record, &wrong, &new)?, Rotation::Skipped(_)));
+    assert!(matches!(rotate_record(&record, &new, &wrong)?, Rotation::Skipped(_)));
+    Ok(())
+  }
+
+  #[test]
+  fn journal_resumes_rotated_objects() -> Result<()> {
+    let path = std::env::temp_dir().join(format!("cli04-journal-test-{}.jsonl", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let srv = "http://localhost:3000/data";
+    let mut journal = Journal::open(&path, srv, false)?;
+    journal.append(1, &Outcome::Rotated)?;
+    journal.append(2, &Outcome::Failed("error".to_string()))?;
+    journal.append(3, &Outcome::AlreadyRotated)?;
+    drop(journal);
+    assert!(Journal::open(&path, srv, false).is_err());
+
+    // An entry cut off by an interruption
+    fs::write(
+      &path,
+      fs::read_to_string(&path)? + r#"{"server":"http://localhost:3000/data","id":4,"#,
+    )?;
+    let mut journal = Journal::open(&path, srv, true)?;
+    assert!(journal.is_done(1) && !journal.is_done(2) && journal.is_done(3) && !journal.is_done(4));
+    journal.append(2, &Outcome::Rotated)?;
+    assert!(Journal::open(&path, srv, true)?.is_done(2));
+    assert!(!Journal::open(&path, "http://example.com/data", true)?.is_done(1));
+    fs::remove_file(&path)?;
+    Ok(())
+  }
+}
@@ -0,0 +1,137 @@
+use crate::error::*;
+use std::{io::BufRead, path::PathBuf, str::FromStr};
+use zeroize::Zeroizing;
+
+/// Where to read a password or master secret from, in the manner of openssl's `-passin`.
+/// Secrets given on the command line leak into shell history and `ps`, so `pass:` is only for testing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SecretSource {
+  /// No-echo prompt on the terminal
+  Prompt,
+  /// First line of stdin
+  Stdin,
+  /// First line of the file
+  File(PathBuf),
+  /// Environment variable
+  Env(String),
+  /// Literal value
+  Pass(String),
+}
+
+impl FromStr for SecretSource {
+  type Err = anyhow::Error;
+  fn from_str(s: &str) -> Result<Self> {
+    match s.split_once(':') {
+      _ if s == "prompt" => Ok(SecretSource::Prompt),
+      _ if s == "stdin" => Ok(SecretSource::Stdin),
+      Some(("file", path)) if !path.is_empty() => Ok(SecretSource::File(PathBuf::from(path))),
+      Some(("env", var)) if !var.is_empty() => Ok(SecretSource::Env(var.to_string())),
+      Some(("pass", value)) => Ok(SecretSource::Pass(value.to_string())),
+      _ => bail!("Unsupported secret source. Use 'prompt', 'stdin', 'file:<path>', 'env:<var>' or 'pass:<secret>'"),
+    }
+  }
+}
+
+impl SecretSource {
+  pub fn read(&self, prompt: &str) -> Result<Zeroizing<String>> {
+    let secret = match self {
+      SecretSource::Prompt => Zeroizing::new(rpassword::prompt_password(prompt)?),
+      SecretSource::Stdin => read_first_line(std::io::stdin().lock())?,
+      SecretSource::File(path) => {
+        let file = std::fs::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        read_first_line(std::io::BufReader::new(file))?
+      }
+      SecretSource::Env(var) => Zeroizing::new(std::env::var(var).with_context(|| format!("{var} is not set"))?),
+      SecretSource::Pass(value) => Zeroizing::new(value.clone()),
+    };
+    ensure!(!secret.is_empty(), "Empty secret");
+    Ok(secret)
+  }
+}
+
+fn read_first_line(mut reader: impl BufRead) -> Result<Zeroizing<String>> {
+  let mut line = Zeroizing::new(String::new());
+  reader.read_line(&mut line)?;
+  let len = line.trim_end_matches(['\r', '\n']).len();
+  line.truncate(len);
+  Ok(line)
+}
+
+/// Password for PBKDF2, Argon2id and scrypt, or master secret in Base64 for HKDF
+pub enum Secret {
+  Password(Zeroizing<String>),
+  Master(Zeroizing<String>),
+}
+
+impl Secret {
+  pub fn is_password(&self) -> bool {
+    matches!(self, Secret::Password(_))
+  }
+
+  pub fn as_str(&self) -> &str {
+    match self {
+      Secret::Password(s) | Secret::Master(s) => s,
+    }
+  }
+}
+
+impl std::fmt::Debug for Secret {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Secret::Password(_) => write!(f, "Password(<redacted>)"),
+      Secret::Master(_) => write!(f, "Master(<redacted>)"),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_secret_source() -> Result<()> {
+    assert_eq!("prompt".parse::<SecretSource>()?, SecretSource::Prompt);
+    assert_eq!("stdin".parse::<SecretSource>()?, SecretSource::Stdin);
+    assert_eq!(
+      "file:/tmp/pass.txt".parse::<SecretSource>()?,
+      SecretSource::File(PathBuf::from("/tmp/pass.txt"))
+    );
+    assert_eq!(
+      "env:PASS".parse::<SecretSource>()?,
+      SecretSource::Env("PASS".to_string())
+    );
+    assert_eq!(
+      "pass:a:b".parse::<SecretSource>()?,
+      SecretSource::Pass("a:b".to_string())
+    );
+    assert!("mypassword".parse::<SecretSource>().is_err());
+    assert!("env:".parse::<SecretSource>().is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn read_secret() -> Result<()> {
+    assert_eq!(
+      read_first_line("p@ss word\r\nsecond line\n".as_bytes())?.as_str(),
+      "p@ss word"
+    );
+    assert_eq!(read_first_line("no newline".as_bytes())?.as_str(), "no newline");
+
+    let path = std::env::temp_dir().join(format!("cli04-secret-test-{}", std::process::id()));
+    std::fs::write(&path, "from file\n")?;
+    assert_eq!(SecretSource::File(path.clone()).read("")?.as_str(), "from file");
+    std::fs::write(&path, "\n")?;
+    assert!(SecretSource::File(path.clone()).read("").is_err());
+    std::fs::remove_file(&path)?;
+    assert!(SecretSource::File(path).read("").is_err());
+
+    assert_eq!(
+      SecretSource::Env("PATH".to_string()).read("")?.as_str(),
+      std::env::var("PATH")?
+    );
+    assert!(SecretSource::Env("CLI04_SECRET_TEST_UNSET".to_string())
+      .read("")
+      .is_err());
+    Ok(())
+  }
+}
@@ -1,4 +1,5 @@
-use clap::{Parser, Subcommand};
+use crate::io::{HexOrFile, Stream};
+use clap::{Args, Parser, Subcommand};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -13,64 +14,211 @@ pub enum SubCommands {
   /// Execute RSAES-OAEP encryption and decryption demo with RSA key generation
   Rsa_Oaep_Demo {
     /// plaintext data string
-    data: String,
+    #[arg(required_unless_present = "input", conflicts_with = "input")]
+    data: Option<String>,
+
+    /// Read plaintext data from FILE, or stdin with '-'
+    #[arg(short, long = "in", value_name = "FILE")]
+    input: Option<Stream>,
   },
   /// Generate RSA Key
   Rsa_Keygen {
     ///  Modulus length like 2048
     #[arg(default_value = "2048")]
     bits: usize,
+
+    /// Output the keys in PEM instead of DER
+    #[arg(long)]
+    pem: bool,
+
+    /// Write the private key to FILE and the public key to FILE.pub, in DER or PEM
+    #[arg(short, long = "out", value_name = "FILE")]
+    output: Option<Stream>,
   },
   /// RSA-OAEP Encryption
   Rsa_Oaep_Encrypt {
-    /// hex DER-formatted public key
+    /// hex DER-formatted public key, or 'file:<path>' of the DER or PEM
     #[arg(short, long = "publicKey")]
-    public_key: String,
+    public_key: HexOrFile,
 
     /// plaintext data string
-    data: String,
+    #[arg(required_unless_present = "input", conflicts_with = "input")]
+    data: Option<String>,
+
+    /// Read plaintext data from FILE, or stdin with '-'
+    #[arg(short, long = "in", value_name = "FILE")]
+    input: Option<Stream>,
+
+    /// Write the binary encrypted data to FILE, or stdout with '-'
+    #[arg(short, long = "out", value_name = "FILE")]
+    output: Option<Stream>,
   },
   /// RSA-OAEP Decryption
   Rsa_Oaep_Decrypt {
-    /// hex DER-formatted private key
+    /// hex DER-formatted private key, or 'file:<path>' of the DER or PEM
     #[arg(short = 's', long = "privateKey")]
-    private_key: String,
+    private_key: HexOrFile,
 
-    /// encrypted data string
-    data: String,
+    /// encrypted data string in hex
+    #[arg(required_unless_present = "input", conflicts_with = "input")]
+    data: Option<String>,
+
+    /// Read binary encrypted data from FILE, or stdin with '-'
+    #[arg(short, long = "in", value_name = "FILE")]
+    input: Option<Stream>,
+
+    /// Write the decrypted data to FILE, or stdout with '-'
+    #[arg(short, long = "out", value_name = "FILE")]
+    output: Option<Stream>,
   },
   /// Generate ECC key pair and check the consistency of ECDH derived bits
   Check_Ecdh,
   /// Generate ECC Key
   Ecc_Keygen {
-    /// Curve name like P-256
+    /// Curve name, either 'P-256', 'P-384', 'P-521', 'secp256k1' or 'X25519'
     #[arg(default_value = "P-256")]
     curve: String,
+
+    /// Output the keys in PEM instead of DER
+    #[arg(long)]
+    pem: bool,
+
+    /// Write the private key to FILE and the public key to FILE.pub, in DER or PEM
+    #[arg(short, long = "out", value_name = "FILE")]
+    output: Option<Stream>,
   },
-  /// ECDH with AES Encryption
+  /// ECDH with AES Encryption, or ECIES with an ephemeral key without the sender's private key
   Ecdh_Aes_Encrypt {
-    /// hex DER-formatted public key
+    /// hex DER-formatted public key, or 'file:<path>' of the DER or PEM
     #[arg(short, long = "publicKey")]
-    public_key: String,
+    public_key: HexOrFile,
 
-    /// hex DER-formatted private key
+    /// hex DER-formatted private key, or 'file:<path>' of the DER or PEM. Encrypted by ECIES if absent.
     #[arg(short = 's', long = "privateKey")]
-    private_key: String,
+    private_key: Option<HexOrFile>,
 
     /// plaintext data string
-    data: String,
+    #[arg(required_unless_present = "input", conflicts_with = "input")]
+    data: Option<String>,
+
+    /// Read plaintext data from FILE, or stdin with '-'
+    #[arg(short, long = "in", value_name = "FILE")]
+    input: Option<Stream>,
+
+    /// output format, either 'legacy' (msgpack of the original sample-05 with bare AES-CBC), or an envelope in 'json',
+    /// 'msgpack' or 'cbor' [default: legacy with a private key, msgpack with AES-GCM for ECIES]
+    #[arg(short, long)]
+    format: Option<String>,
+
+    /// Write the binary encrypted data to FILE, or stdout with '-'
+    #[arg(short, long = "out", value_name = "FILE")]
+    output: Option<Stream>,
   },
-  /// ECDH with AES Decryption
+  /// ECDH with AES Decryption, or ECIES only with the recipient's private key
   Ecdh_Aes_Decrypt {
-    /// hex DER-formatted public key
+    /// hex DER-formatted public key, or 'file:<path>' of the DER or PEM. Not needed for ECIES.
+    #[arg(short, long = "publicKey")]
+    public_key: Option<HexOrFile>,
+
+    /// hex DER-formatted private key, or 'file:<path>' of the DER or PEM
+    #[arg(short = 's', long = "privateKey")]
+    private_key: HexOrFile,
+
+    /// encrypted data string in hex, either a legacy msgpack or an envelope in any format
+    #[arg(required_unless_present = "input", conflicts_with = "input")]
+    data: Option<String>,
+
+    /// Read binary encrypted data from FILE, or stdin with '-'
+    #[arg(short, long = "in", value_name = "FILE")]
+    input: Option<Stream>,
+
+    /// Write the decrypted data to FILE, or stdout with '-'
+    #[arg(short, long = "out", value_name = "FILE")]
+    output: Option<Stream>,
+  },
+  /// HPKE (RFC 9180) encryption to the recipient's public key, authenticated with a PSK and/or the sender's key
+  Hpke_Seal {
+    /// hex DER-formatted public key of the recipient, or 'file:<path>' of the DER or PEM
     #[arg(short, long = "publicKey")]
-    public_key: String,
+    public_key: HexOrFile,
+
+    /// hex DER-formatted private key of the sender for the auth modes, or 'file:<path>' of the DER or PEM
+    #[arg(short = 's', long = "privateKey")]
+    private_key: Option<HexOrFile>,
+
+    /// plaintext data string
+    #[arg(required_unless_present_any = ["input", "export"], conflicts_with = "input")]
+    data: Option<String>,
+
+    /// Read plaintext data from FILE, or stdin with '-'
+    #[arg(short, long = "in", value_name = "FILE")]
+    input: Option<Stream>,
+
+    #[command(flatten)]
+    hpke: HpkeArgs,
 
-    /// hex DER-formatted private key
+    /// Write the encapsulated key followed by the binary encrypted data to FILE, or stdout with '-'
+    #[arg(short, long = "out", value_name = "FILE")]
+    output: Option<Stream>,
+  },
+  /// HPKE (RFC 9180) decryption with the recipient's private key
+  Hpke_Open {
+    /// hex DER-formatted private key of the recipient, or 'file:<path>' of the DER or PEM
     #[arg(short = 's', long = "privateKey")]
-    private_key: String,
+    private_key: HexOrFile,
+
+    /// hex DER-formatted public key of the sender for the auth modes, or 'file:<path>' of the DER or PEM
+    #[arg(short, long = "publicKey")]
+    public_key: Option<HexOrFile>,
+
+    /// encapsulated key followed by encrypted data in hex
+    #[arg(required_unless_present = "input", conflicts_with = "input")]
+    data: Option<String>,
+
+    /// Read the encapsulated key followed by binary encrypted data from FILE, or stdin with '-'
+    #[arg(short, long = "in", value_name = "FILE")]
+    input: Option<Stream>,
 
-    /// encrypted and msgpacked data string in hex
-    data: String,
+    #[command(flatten)]
+    hpke: HpkeArgs,
+
+    /// Write the decrypted data to FILE, or stdout with '-'
+    #[arg(short, long = "out", value_name = "FILE")]
+    output: Option<Stream>,
   },
 }
+
+#[derive(Debug, Args)]
+pub struct HpkeArgs {
+  /// KDF, either 'HKDF-SHA256', 'HKDF-SHA384' or 'HKDF-SHA512' [default: the KDF of the KEM]
+  #[arg(short, long)]
+  pub kdf: Option<String>,
+
+  /// AEAD, either 'AES-128-GCM', 'AES-256-GCM', 'ChaCha20Poly1305' or 'Export-only'
+  #[arg(short, long, default_value = "AES-256-GCM")]
+  pub aead: String,
+
+  /// Application info string bound to the context
+  #[arg(long, default_value = "")]
+  pub info: String,
+
+  /// Additional authenticated data string
+  #[arg(long, default_value = "")]
+  pub aad: String,
+
+  /// hex pre-shared key of at least 32 bytes for the PSK modes, or 'file:<path>' of the binary
+  #[arg(long, requires = "psk_id")]
+  pub psk: Option<HexOrFile>,
+
+  /// Identifier string of the pre-shared key
+  #[arg(long, requires = "psk")]
+  pub psk_id: Option<String>,
+
+  /// Print the secret exported from the context for the exporter context string
+  #[arg(short = 'x', long, value_name = "CONTEXT")]
+  pub export: Option<String>,
+
+  /// Length of the exported secret in bytes
+  #[arg(long, default_value = "32")]
+  pub export_len: usize,
+}
@@ -1,69 +1,83 @@
 mod config;
-mod crypto;
-mod ecc;
 mod error;
-mod key;
-mod rsa;
-mod util;
+mod io;
 
-use crate::{
-  crypto::{decrypt, encrypt, Encrypted},
+use crate::error::*;
+use base64::{engine::general_purpose, Engine as _};
+use clap::Parser;
+use config::{ClapArgs, HpkeArgs, SubCommands};
+use io::{info, print_plaintext, read_data, read_hex_data, write_key_pair, HexOrFile, Stream};
+use seceng_crypto::{
   ecc::*,
-  error::*,
-  key::BinaryKey,
+  envelope::{Encoding, Envelope, KeyAgreement, Sample05Pack},
+  hpke::{self, Kem, Psk, Suite},
+  kdf::{BinaryKey, KdfHash, KdfParams},
+  pem,
   rsa::*,
+  symmetric::{encrypt, Algorithm},
   util::*,
 };
-use clap::Parser;
-use config::{ClapArgs, SubCommands};
-use rmp_serde::{Deserializer, Serializer};
-use serde::{Deserialize, Serialize};
 
 pub fn main() -> Result<()> {
   let _ = include_str!("../Cargo.toml");
   let args = ClapArgs::parse();
 
   match &args.subcommand {
-    SubCommands::Rsa_Keygen { bits } => {
+    SubCommands::Rsa_Keygen { bits, pem, output } => {
       let rsa_keypair = RsaKeyPair::new(bits)?;
-      println!("<Generated RSA Key Pair (DER Form)>");
-      println!("Public Key:\n{}\n", rsa_keypair.to_spki_public_der()?.to_hex_string());
-      println!(
-        "Private Key:\n{}\n",
-        rsa_keypair.to_pkcs8_private_der()?.to_hex_string()
-      );
+      let (pk, sk) = (rsa_keypair.to_spki_public_der()?, rsa_keypair.to_pkcs8_private_der()?);
+      print_key_pair("RSA", &pk, &sk, *pem, output.as_ref())?;
     }
-    SubCommands::Rsa_Oaep_Encrypt { public_key, data } => {
-      let public_key = hex::decode(public_key)?;
-      let rsa_keypair = RsaKeyPair::from_spki_public_der(&public_key)?;
-      let enc_data = rsa_keypair.oaep_encrypt(data.as_bytes())?;
-      println!("<Encrypted Data (in HexString)>");
-      println!("{}", enc_data.to_hex_string());
+    SubCommands::Rsa_Oaep_Encrypt {
+      public_key,
+      data,
+      input,
+      output,
+    } => {
+      let public_key = public_key.read()?;
+      let data = read_data(data.as_deref(), input.as_ref())?;
+      let rsa_keypair = RsaKeyPair::from_public_key(&public_key)?;
+      let enc_data = rsa_keypair.oaep_encrypt(&data)?;
+      match output {
+        Some(output) => output.write(&enc_data)?,
+        None => {
+          println!("<Encrypted Data (in HexString)>");
+          println!("{}", enc_data.to_hex_string());
+        }
+      }
     }
-    SubCommands::Rsa_Oaep_Decrypt { private_key, data } => {
-      let private_key = hex::decode(private_key)?;
-      let data = hex::decode(data)?;
-      let rsa_keypair = RsaKeyPair::from_pkcs8_private_der(&private_key)?;
+    SubCommands::Rsa_Oaep_Decrypt {
+      private_key,
+      data,
+      input,
+      output,
+    } => {
+      let private_key = private_key.read()?;
+      let data = read_hex_data(data.as_deref(), input.as_ref())?;
+      let rsa_keypair = RsaKeyPair::from_private_key(&private_key)?;
       let dec_data = rsa_keypair.oaep_decrypt(&data)?;
-      println!("<Decrypted Data>");
-      println!("{}", String::from_utf8(dec_data)?);
+      match output {
+        Some(output) => output.write(&dec_data)?,
+        None => print_plaintext("Decrypted Data", &dec_data),
+      }
     }
-    SubCommands::Rsa_Oaep_Demo { data } => {
-      println!("<Input Data>\n{}\n", data);
+    SubCommands::Rsa_Oaep_Demo { data, input } => {
+      let data = read_data(data.as_deref(), input.as_ref())?;
+      print_plaintext("Input Data", &data);
       let rsa_keypair = RsaKeyPair::new(&2048)?;
       println!(
         "<Generated RSA Key Pair (DER Form)>\nPublic Key:\n{}\nPrivate Key:\n{}\n",
         rsa_keypair.to_spki_public_der()?.to_hex_string(),
         rsa_keypair.to_pkcs8_private_der()?.to_hex_string()
       );
-      let enc_data = rsa_keypair.oaep_encrypt(data.as_bytes())?;
+      let enc_data = rsa_keypair.oaep_encrypt(&data)?;
       println!("<Encrypted Data (in HexString)>\n{}\n", enc_data.to_hex_string());
       let dec_data = rsa_keypair.oaep_decrypt(&enc_data)?;
-      println!("<Decrypted Data>\n{}\n", String::from_utf8(dec_data)?);
+      print_plaintext("Decrypted Data", &dec_data);
     }
     SubCommands::Check_Ecdh => {
-      let keypair1 = EccKeyPair::<p256::NistP256>::new();
-      let keypair2 = EccKeyPair::<p256::NistP256>::new();
+      let keypair1 = EccKeyPair::<NistP256>::new();
+      let keypair2 = EccKeyPair::<NistP256>::new();
       println!(
         "<ECC Key Pair A (DER Form)>\nPublic Key:\n{}\nPrivate Key:\n{}\n",
         keypair1.to_spki_public_der()?.to_hex_string(),
@@ -85,128 +99,269 @@ pub fn main() -> Result<()> {
         bits1.to_hex_string()
       );
     }
-    SubCommands::Ecc_Keygen { curve } => {
-      let (pk, sk) = match curve.as_str() {
-        "P-256" => {
-          let kp = EccKeyPair::<p256::NistP256>::new();
-          (kp.to_spki_public_der()?, kp.to_pkcs8_private_der()?)
-        }
-        "P-384" => {
-          let kp = EccKeyPair::<p384::NistP384>::new();
-          (kp.to_spki_public_der()?, kp.to_pkcs8_private_der()?)
-        }
-        _ => bail!("Unsupported curve"),
-      };
-
-      println!(
-        "<Generated ECC Key Pair (DER Form)>\nPublic Key:\n{}\nPrivate Key:\n{}\n",
-        pk.to_hex_string(),
-        sk.to_hex_string()
-      );
+    SubCommands::Ecc_Keygen { curve, pem, output } => {
+      let kp = EccKeyPairType::new(curve)?;
+      let (pk, sk) = (kp.to_spki_public_der()?, kp.to_pkcs8_private_der()?);
+      print_key_pair("ECC", &pk, &sk, *pem, output.as_ref())?;
     }
     SubCommands::Ecdh_Aes_Encrypt {
       public_key,
       private_key,
       data,
+      input,
+      format,
+      output,
     } => {
-      let public_key = hex::decode(public_key)?;
-      let private_key = hex::decode(private_key)?;
-      let data = data.as_bytes();
+      let public_key = public_key.read()?;
+      let data = read_data(data.as_deref(), input.as_ref())?;
+      let encoding = match (format.as_deref(), private_key) {
+        (Some("legacy"), _) | (None, Some(_)) => None,
+        (Some(format), _) => Some(format.parse::<Encoding>()?),
+        (None, None) => Some(Encoding::Msgpack),
+      };
 
-      let pk = import_spki_der(&public_key)?;
-      let sk = import_pkcs8_der(&private_key)?;
+      let pk = import_public_key(&public_key)?;
+      let envelope = match private_key {
+        None => {
+          // Bare AES-CBC only in the legacy pack, which has no room for a tag
+          let cipher = match encoding {
+            None => Algorithm::AesCbc,
+            Some(_) => Algorithm::AesGcm,
+          };
+          let envelope = Envelope::seal_ecies(&data, &pk, &cipher)?;
+          let epk = envelope
+            .ka
+            .as_ref()
+            .and_then(|ka| ka.epk.as_deref())
+            .unwrap_or_default();
+          info!(
+            output,
+            "<ECIES with Ephemeral Key ({})>\nEphemeral Public Key: {}\nCipher: {}\nHKDF-Salt: {}\nHKDF-Hash: SHA-256\n",
+            pk,
+            epk.to_hex_string(),
+            cipher,
+            envelope.kdf.salt()?.to_hex_string()
+          );
+          envelope
+        }
+        Some(private_key) => {
+          let sk = import_private_key(&private_key.read()?)?;
 
-      let shared_bits = match (&pk, &sk) {
-        (EccKeyPairType::P256(pk), EccKeyPairType::P256(sk)) => pk.derive_bits(sk)?,
-        (EccKeyPairType::P384(pk), EccKeyPairType::P384(sk)) => pk.derive_bits(sk)?,
-        _ => bail!("Unsupported curve or inconsistent key type"),
-      };
+          let shared_bits = pk.derive_bits(&sk)?;
 
-      println!("<Shared Bits ({})>\n{}\n", pk, shared_bits.to_hex_string());
+          info!(output, "<Shared Bits ({})>\n{}\n", pk, shared_bits.to_hex_string());
 
-      let key = BinaryKey::try_new(&shared_bits, 32, None)?;
-      println!(
-        "<Derived AES Key>\nKey: {}\nHKDF-Salt: {}\nHKDF-Hash: SHA-256\n",
-        key.key.to_hex_string(),
-        key.salt.to_hex_string()
-      );
+          let key = BinaryKey::try_new_hkdf(&shared_bits, 32, None, None)?;
+          info!(
+            output,
+            "<Derived AES Key>\nKey: {}\nHKDF-Salt: {}\nHKDF-Hash: SHA-256\n",
+            key.key.to_hex_string(),
+            key.salt.to_hex_string()
+          );
 
-      let enc_data = encrypt(data, &key, None)?;
-      println!(
-        "<Encrypted data>\nData: {}\nInitival Vector: {}\n",
-        enc_data.data.to_hex_string(),
-        enc_data.iv.to_hex_string()
-      );
+          let enc_data = encrypt(&data, &key.key, None)?;
+          info!(
+            output,
+            "<Encrypted data>\nData: {}\nInitival Vector: {}\n",
+            enc_data.data.to_hex_string(),
+            enc_data.iv.to_hex_string()
+          );
 
-      let kdf_params = KdfParams {
-        salt: key.salt.to_hex_string(),
-        hash: "SHA-256".to_string(),
-      };
-      let encrypted_pack = EncryptedPack {
-        encrypted: EncryptedHex {
-          data: enc_data.data.to_hex_string(),
-          iv: enc_data.iv.to_hex_string(),
-        },
-        kdfParams: kdf_params,
+          let kdf_params = KdfParams::new_hkdf(&key.salt, &KdfHash::Sha256, None);
+          let ka = KeyAgreement::ecdh(Some(&pk.to_string()));
+          Envelope::new(&Algorithm::AesCbc, kdf_params, Some(ka), enc_data, None)
+        }
       };
 
-      let mut buf = Vec::new();
-      encrypted_pack.serialize(&mut Serializer::new(&mut buf).with_struct_map())?;
-
-      println!("<Msgpacked encrypted and kdf data>\n{}\n", buf.to_hex_string());
+      let (buf, title) = match encoding {
+        None => (
+          Sample05Pack::try_from(&envelope)?.to_msgpack()?,
+          "<Msgpacked encrypted and kdf data>".to_string(),
+        ),
+        Some(encoding) => (
+          envelope.encode(encoding)?,
+          format!("<Envelope in {encoding} (in HexString)>"),
+        ),
+      };
+      match output {
+        Some(output) => output.write(&buf)?,
+        None => println!("{title}\n{}\n", buf.to_hex_string()),
+      }
     }
     SubCommands::Ecdh_Aes_Decrypt {
       public_key,
       private_key,
       data,
+      input,
+      output,
     } => {
-      let public_key = hex::decode(public_key)?;
-      let private_key = hex::decode(private_key)?;
-      let data = hex::decode(data)?;
+      let private_key = private_key.read()?;
+      let data = read_hex_data(data.as_deref(), input.as_ref())?;
 
-      let pk = import_spki_der(&public_key)?;
-      let sk = import_pkcs8_der(&private_key)?;
+      let sk = import_private_key(&private_key)?;
 
-      let shared_bits = match (&pk, &sk) {
-        (EccKeyPairType::P256(pk), EccKeyPairType::P256(sk)) => pk.derive_bits(sk)?,
-        (EccKeyPairType::P384(pk), EccKeyPairType::P384(sk)) => pk.derive_bits(sk)?,
-        _ => bail!("Unsupported curve or inconsistent key type"),
+      let envelope = Envelope::decode_any(&data)?;
+      let Some(ka) = &envelope.ka else {
+        bail!("Not encrypted with a key agreement");
       };
+      let decrypted = if ka.is_ecies() {
+        info!(output, "<ECIES with Ephemeral Key ({})>\n", sk);
+        envelope.open_ecies(&sk)?
+      } else {
+        let Some(public_key) = public_key else {
+          bail!("The sender's public key is needed for ECDH");
+        };
+        let pk = import_public_key(&public_key.read()?)?;
+
+        let shared_bits = pk.derive_bits(&sk)?;
 
-      println!("<Shared Bits ({})>\n{}\n", pk, shared_bits.to_hex_string());
+        info!(output, "<Shared Bits ({})>\n{}\n", pk, shared_bits.to_hex_string());
+
+        if let Some(curve) = &ka.curve {
+          ensure!(*curve == pk.to_string(), "Encrypted with a key over {curve}, not {pk}");
+        }
+        let shared_bits = general_purpose::STANDARD.encode(&shared_bits);
+        let key = BinaryKey::try_from_kdf_params(&shared_bits, 32, &envelope.kdf)?;
+        info!(output, "<Derived AES Key>\n{}\n", key.key.to_hex_string());
 
-      let decoded = EncryptedPack::deserialize(&mut Deserializer::new(data.as_slice()))?;
-      let encrypted = Encrypted {
-        data: hex::decode(decoded.encrypted.data)?,
-        iv: hex::decode(decoded.encrypted.iv)?,
+        envelope.open(&shared_bits)?
       };
-      let key = BinaryKey::try_new(&shared_bits, 32, Some(&hex::decode(decoded.kdfParams.salt)?))?;
-      println!("<Derived AES Key>\n{}\n", key.key.to_hex_string());
+      match output {
+        Some(output) => output.write(&decrypted)?,
+        None => print_plaintext("Decrypted data", &decrypted),
+      }
+    }
+    SubCommands::Hpke_Seal {
+      public_key,
+      private_key,
+      data,
+      input,
+      hpke,
+      output,
+    } => {
+      let pk_r = import_public_key(&public_key.read()?)?;
+      let sender = private_key
+        .as_ref()
+        .map(|sk| import_private_key(&sk.read()?))
+        .transpose()?;
+      let suite = hpke_suite(hpke, &pk_r)?;
+      let psk_key = hpke.psk.as_ref().map(HexOrFile::read).transpose()?;
+      let psk = psk_key
+        .as_deref()
+        .zip(hpke.psk_id.as_deref())
+        .map(|(key, id)| Psk { key, id: id.as_bytes() });
 
-      let decrypted = decrypt(&encrypted, &key)?;
-      println!("<Decrypted data>\n{}\n", String::from_utf8(decrypted)?);
+      let (enc, mut context) = suite.setup_sender(&pk_r, hpke.info.as_bytes(), psk.as_ref(), sender.as_ref())?;
+      info!(output, "<HPKE {} Mode ({suite})>\n", context.mode());
+      print_exported(hpke, &context, output.as_ref())?;
+
+      // Without data, only the encapsulated key is sent to share the exported secret
+      let enc_data = match (data, input) {
+        (None, None) => vec![],
+        _ => context.seal(hpke.aad.as_bytes(), &read_data(data.as_deref(), input.as_ref())?)?,
+      };
+      let buf = [enc, enc_data].concat();
+      match output {
+        Some(output) => output.write(&buf)?,
+        None => println!(
+          "<Encapsulated Key and Encrypted Data (in HexString)>\n{}\n",
+          buf.to_hex_string()
+        ),
+      }
+    }
+    SubCommands::Hpke_Open {
+      private_key,
+      public_key,
+      data,
+      input,
+      hpke,
+      output,
+    } => {
+      let sk_r = import_private_key(&private_key.read()?)?;
+      let sender = public_key
+        .as_ref()
+        .map(|pk| import_public_key(&pk.read()?))
+        .transpose()?;
+      let suite = hpke_suite(hpke, &sk_r)?;
+      let psk_key = hpke.psk.as_ref().map(HexOrFile::read).transpose()?;
+      let psk = psk_key
+        .as_deref()
+        .zip(hpke.psk_id.as_deref())
+        .map(|(key, id)| Psk { key, id: id.as_bytes() });
+
+      let data = read_hex_data(data.as_deref(), input.as_ref())?;
+      ensure!(
+        data.len() >= suite.kem.enc_len(),
+        "Shorter than the encapsulated key of {}",
+        suite.kem
+      );
+      let (enc, enc_data) = data.split_at(suite.kem.enc_len());
+      let mut context = suite.setup_receiver(enc, &sk_r, hpke.info.as_bytes(), psk.as_ref(), sender.as_ref())?;
+      info!(output, "<HPKE {} Mode ({suite})>\n", context.mode());
+      print_exported(hpke, &context, output.as_ref())?;
+
+      if !enc_data.is_empty() {
+        let decrypted = context.open(hpke.aad.as_bytes(), enc_data)?;
+        match output {
+          Some(output) => output.write(&decrypted)?,
+          None => print_plaintext("Decrypted data", &decrypted),
+        }
+      }
     }
   }
 
   Ok(())
 }
 
-#[derive(Debug, PartialEq, Deserialize, Serialize)]
-#[allow(non_snake_case)]
-struct EncryptedPack {
-  encrypted: EncryptedHex,
-  kdfParams: KdfParams,
+/// Print the key pair in hex or PEM, or write it to the files in DER or PEM and print their paths
+fn print_key_pair(kind: &str, pk: &[u8], sk: &[u8], pem: bool, output: Option<&Stream>) -> Result<()> {
+  let (pk, sk, form) = if pem {
+    (
+      pem::encode(pem::PUBLIC_KEY_LABEL, pk)?.into_bytes(),
+      pem::encode(pem::PRIVATE_KEY_LABEL, sk)?.into_bytes(),
+      "PEM",
+    )
+  } else {
+    (pk.to_vec(), sk.to_vec(), "DER")
+  };
+  match output {
+    Some(output) => {
+      let (pk_path, sk_path) = write_key_pair(output, &pk, &sk)?;
+      println!(
+        "<Generated {kind} Key Pair ({form} Form)>\nPublic Key: {}\nPrivate Key: {}\n",
+        pk_path.display(),
+        sk_path.display()
+      );
+    }
+    None if pem => println!(
+      "<Generated {kind} Key Pair (PEM Form)>\n{}{}",
+      String::from_utf8_lossy(&pk),
+      String::from_utf8_lossy(&sk)
+    ),
+    None => println!(
+      "<Generated {kind} Key Pair (DER Form)>\nPublic Key:\n{}\nPrivate Key:\n{}\n",
+      pk.to_hex_string(),
+      sk.to_hex_string()
+    ),
+  }
+  Ok(())
 }
 
-#[derive(Debug, PartialEq, Deserialize, Serialize)]
-#[allow(non_snake_case)]
-struct EncryptedHex {
-  data: String,
-  iv: String,
+/// HPKE cipher suite with the KEM for the curve of the key
+fn hpke_suite(args: &HpkeArgs, key: &EccKeyPairType) -> Result<Suite> {
+  let kem = Kem::for_key(key)?;
+  let kdf = match &args.kdf {
+    Some(kdf) => kdf.parse()?,
+    None => kem.kdf(),
+  };
+  Ok(Suite::new(kem, kdf, args.aead.parse()?))
 }
 
-#[derive(Debug, PartialEq, Deserialize, Serialize)]
-struct KdfParams {
-  salt: String,
-  hash: String,
+/// Print the secret exported from the HPKE context if requested
+fn print_exported(args: &HpkeArgs, context: &hpke::Context, output: Option<&Stream>) -> Result<()> {
+  if let Some(exporter_context) = &args.export {
+    let secret = context.export(exporter_context.as_bytes(), args.export_len)?;
+    info!(output, "<Exported Secret>\n{}\n", secret.to_hex_string());
+  }
+  Ok(())
 }
@@ -1,3 +1,4 @@
+use crate::io::{HexOrFile, Stream};
 use clap::{Parser, Subcommand};
 
 #[derive(Parser, Debug)]
@@ -17,97 +18,168 @@ pub enum SubCommands {
     algorithm: String,
 
     /// Data string to be hashed
-    data: String,
+    #[arg(required_unless_present = "input", conflicts_with = "input")]
+    data: Option<String>,
+
+    /// Read data to be hashed from FILE, or stdin with '-'
+    #[arg(short, long = "in", value_name = "FILE")]
+    input: Option<Stream>,
+
+    /// Write the binary hash to FILE, or stdout with '-'
+    #[arg(short, long = "out", value_name = "FILE")]
+    output: Option<Stream>,
   },
   /// Generate hex key for HMAC generation
   Gen_Hex_Key {
     /// key size in bytes
     len: usize,
+
+    /// Write the binary key to FILE, or stdout with '-'
+    #[arg(short, long = "out", value_name = "FILE")]
+    output: Option<Stream>,
   },
   /// Generate HMAC (key length must be equal to that of hash.)
   Gen_Hmac {
-    /// Hex key of length equal to the hash size
+    /// Hex key of length equal to the hash size, or 'file:<path>' of the binary key
     #[arg(short, long)]
-    key: String,
+    key: HexOrFile,
 
     /// Name of hash function like 'SHA-256'
     #[arg(short, long, default_value = "SHA-256")]
     algorithm: String,
 
     /// Data string to be keyed-hashed
-    data: String,
+    #[arg(required_unless_present = "input", conflicts_with = "input")]
+    data: Option<String>,
+
+    /// Read data to be keyed-hashed from FILE, or stdin with '-'
+    #[arg(short, long = "in", value_name = "FILE")]
+    input: Option<Stream>,
+
+    /// Write the binary HMAC to FILE, or stdout with '-'
+    #[arg(short, long = "out", value_name = "FILE")]
+    output: Option<Stream>,
   },
   /// Verify HMAC
   Verify_Hmac {
-    /// Hex key of length equal to the hash size
+    /// Hex key of length equal to the hash size, or 'file:<path>' of the binary key
     #[arg(short, long)]
-    key: String,
+    key: HexOrFile,
 
-    /// Hex HMAC
+    /// Hex HMAC, or 'file:<path>' of the binary HMAC
     #[arg(short, long)]
-    mac: String,
+    mac: HexOrFile,
 
     /// Name of hash function like 'SHA-256'
     #[arg(short, long, default_value = "SHA-256")]
     algorithm: String,
 
     /// Data string to be keyed-hashed
-    data: String,
+    #[arg(required_unless_present = "input", conflicts_with = "input")]
+    data: Option<String>,
+
+    /// Read data to be keyed-hashed from FILE, or stdin with '-'
+    #[arg(short, long = "in", value_name = "FILE")]
+    input: Option<Stream>,
   },
   /// Generate RSA key pair
   Gen_Rsa_key {
     ///  Modulus length like 2048
     #[arg(short, long, default_value = "2048")]
     bits: usize,
+
+    /// Output the keys in PEM instead of DER
+    #[arg(long)]
+    pem: bool,
+
+    /// Write the private key to FILE and the public key to FILE.pub, in DER or PEM
+    #[arg(short, long = "out", value_name = "FILE")]
+    output: Option<Stream>,
   },
   /// Sign with RSASSA PSS
   Sign_Rsa_Pss {
-    /// hex DER-formatted private key
+    /// hex DER-formatted private key, or 'file:<path>' of the DER or PEM
     #[arg(short = 's', long = "privateKey")]
-    private_key: String,
+    private_key: HexOrFile,
 
     /// message data to be signed
-    data: String,
+    #[arg(required_unless_present = "input", conflicts_with = "input")]
+    data: Option<String>,
+
+    /// Read message data from FILE, or stdin with '-'
+    #[arg(short, long = "in", value_name = "FILE")]
+    input: Option<Stream>,
+
+    /// Write the binary signature to FILE, or stdout with '-'
+    #[arg(short, long = "out", value_name = "FILE")]
+    output: Option<Stream>,
   },
   /// Verify with RSASSA PSS
   Verify_Rsa_Pss {
-    /// hex DER-formatted public key
+    /// hex DER-formatted public key, or 'file:<path>' of the DER or PEM
     #[arg(short, long = "publicKey")]
-    public_key: String,
+    public_key: HexOrFile,
 
-    /// hex signature
+    /// hex signature, or 'file:<path>' of the binary signature
     #[arg(short = 't', long)]
-    signature: String,
+    signature: HexOrFile,
 
     /// message data
-    data: String,
+    #[arg(required_unless_present = "input", conflicts_with = "input")]
+    data: Option<String>,
+
+    /// Read message data from FILE, or stdin with '-'
+    #[arg(short, long = "in", value_name = "FILE")]
+    input: Option<Stream>,
   },
   /// Generate ECC key pair
   Gen_Ecc_key {
-    /// Curve name like P-256
+    /// Curve name, either 'P-256', 'P-384', 'P-521', 'secp256k1' or 'Ed25519'
     #[arg(default_value = "P-256")]
     curve: String,
+
+    /// Output the keys in PEM instead of DER
+    #[arg(long)]
+    pem: bool,
+
+    /// Write the private key to FILE and the public key to FILE.pub, in DER or PEM
+    #[arg(short, long = "out", value_name = "FILE")]
+    output: Option<Stream>,
   },
-  /// Sign with ECDSA
+  /// Sign with ECDSA, or EdDSA with an Ed25519 key
   Sign_Ecdsa {
-    /// hex DER-formatted private key
+    /// hex DER-formatted private key, or 'file:<path>' of the DER or PEM
     #[arg(short = 's', long = "privateKey")]
-    private_key: String,
+    private_key: HexOrFile,
 
     /// message data to be signed
-    data: String,
+    #[arg(required_unless_present = "input", conflicts_with = "input")]
+    data: Option<String>,
+
+    /// Read message data from FILE, or stdin with '-'
+    #[arg(short, long = "in", value_name = "FILE")]
+    input: Option<Stream>,
+
+    /// Write the binary signature to FILE, or stdout with '-'
+    #[arg(short, long = "out", value_name = "FILE")]
+    output: Option<Stream>,
   },
-  /// Verify with ECDSA
+  /// Verify with ECDSA, or EdDSA with an Ed25519 key
   Verify_Ecdsa {
-    /// hex DER-formatted public key
+    /// hex DER-formatted public key, or 'file:<path>' of the DER or PEM
     #[arg(short, long = "publicKey")]
-    public_key: String,
+    public_key: HexOrFile,
 
-    /// hex signature
+    /// hex signature, or 'file:<path>' of the binary signature
     #[arg(short = 't', long)]
-    signature: String,
+    signature: HexOrFile,
 
     /// message data
-    data: String,
+    #[arg(required_unless_present = "input", conflicts_with = "input")]
+    data: Option<String>,
+
+    /// Read message data from FILE, or stdin with '-'
+    #[arg(short, long = "in", value_name = "FILE")]
+    input: Option<Stream>,
   },
 }
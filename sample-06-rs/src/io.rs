@@ -0,0 +1,148 @@
+//! Input and output of binary data. Data on the command line is a string or hex, while data in files,
+//! stdin and stdout is read and written as is, so that binary payloads are preserved.
+
+use crate::error::*;
+use std::{
+  fs,
+  io::{Read, Write},
+  path::PathBuf,
+  str::FromStr,
+};
+
+/// File, or stdin or stdout with '-'
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stream {
+  Std,
+  File(PathBuf),
+}
+
+impl FromStr for Stream {
+  type Err = anyhow::Error;
+  fn from_str(s: &str) -> Result<Self> {
+    match s {
+      "" => bail!("Empty file name"),
+      "-" => Ok(Stream::Std),
+      path => Ok(Stream::File(PathBuf::from(path))),
+    }
+  }
+}
+
+impl Stream {
+  pub fn read(&self) -> Result<Vec<u8>> {
+    match self {
+      Stream::Std => {
+        let mut buf = vec![];
+        std::io::stdin().lock().read_to_end(&mut buf)?;
+        Ok(buf)
+      }
+      Stream::File(path) => fs::read(path).with_context(|| format!("Failed to read {}", path.display())),
+    }
+  }
+
+  pub fn write(&self, data: &[u8]) -> Result<()> {
+    match self {
+      Stream::Std => {
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(data)?;
+        stdout.flush()?;
+      }
+      Stream::File(path) => fs::write(path, data).with_context(|| format!("Failed to write {}", path.display()))?,
+    }
+    Ok(())
+  }
+}
+
+/// Data given as a string argument, or read from the input
+pub fn read_data(data: Option<&str>, input: Option<&Stream>) -> Result<Vec<u8>> {
+  match (data, input) {
+    (_, Some(input)) => input.read(),
+    (Some(data), None) => Ok(data.as_bytes().to_vec()),
+    (None, None) => bail!("No data given"),
+  }
+}
+
+/// Write the private key to the file and the public key to `<file>.pub`, both in DER or PEM
+pub fn write_key_pair(output: &Stream, public_der: &[u8], private_der: &[u8]) -> Result<(PathBuf, PathBuf)> {
+  let Stream::File(private_path) = output else {
+    bail!("Key pair cannot be written to stdout");
+  };
+  let mut public_path = private_path.clone().into_os_string();
+  public_path.push(".pub");
+  let public_path = PathBuf::from(public_path);
+  Stream::File(private_path.clone()).write(private_der)?;
+  Stream::File(public_path.clone()).write(public_der)?;
+  Ok((public_path, private_path.clone()))
+}
+
+/// Key, signature or MAC given in hex, or 'file:<path>' of the binary like DER, or of the PEM of a key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HexOrFile {
+  Hex(Vec<u8>),
+  File(PathBuf),
+}
+
+impl FromStr for HexOrFile {
+  type Err = anyhow::Error;
+  fn from_str(s: &str) -> Result<Self> {
+    match s.strip_prefix("file:") {
+      Some("") => bail!("Empty file name"),
+      Some(path) => Ok(HexOrFile::File(PathBuf::from(path))),
+      None => Ok(HexOrFile::Hex(
+        hex::decode(s).context("Neither a hex string nor 'file:<path>'")?,
+      )),
+    }
+  }
+}
+
+impl HexOrFile {
+  pub fn read(&self) -> Result<Vec<u8>> {
+    match self {
+      HexOrFile::Hex(bytes) => Ok(bytes.clone()),
+      HexOrFile::File(path) => Stream::File(path.clone()).read(),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_arguments() -> Result<()> {
+    assert_eq!("-".parse::<Stream>()?, Stream::Std);
+    assert_eq!("a.bin".parse::<Stream>()?, Stream::File(PathBuf::from("a.bin")));
+    assert!("".parse::<Stream>().is_err());
+    assert_eq!("00ff".parse::<HexOrFile>()?, HexOrFile::Hex(vec![0, 255]));
+    assert_eq!(
+      "file:key.der".parse::<HexOrFile>()?,
+      HexOrFile::File(PathBuf::from("key.der"))
+    );
+    assert!("key.der".parse::<HexOrFile>().is_err());
+    assert!("file:".parse::<HexOrFile>().is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn binary_files_are_kept_as_is() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("cli06-io-test-{}", std::process::id()));
+    fs::create_dir_all(&dir)?;
+    let binary = [0u8, 0xff, 0x80, b'\n', 0xc3];
+    let path = dir.join("data.bin");
+    Stream::File(path.clone()).write(&binary)?;
+    assert_eq!(read_data(None, Some(&Stream::File(path.clone())))?, binary);
+    assert_eq!(read_data(Some("text"), None)?, b"text");
+    assert!(read_data(None, None).is_err());
+    assert_eq!(HexOrFile::File(path).read()?, binary);
+
+    let key = dir.join("key.der");
+    let (public_path, private_path) = write_key_pair(&Stream::File(key.clone()), b"public", b"private")?;
+    assert_eq!(
+      (fs::read(&public_path)?, fs::read(&private_path)?),
+      (b"public".to_vec(), b"private".to_vec())
+    );
+    assert_eq!(public_path, dir.join("key.der.pub"));
+    assert!(write_key_pair(&Stream::Std, b"public", b"private").is_err());
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+  }
+}
@@ -1,156 +1,145 @@
 mod config;
-mod ecc;
 mod error;
-mod hash;
-mod rsa;
-mod util;
+mod io;
 
-use crate::{ecc::*, error::*, hash::*, rsa::*, util::*};
+use crate::error::*;
 use clap::Parser;
 use config::{ClapArgs, SubCommands};
-use ecc::import_pkcs8_der;
+use io::{read_data, write_key_pair, Stream};
+use seceng_crypto::{ecc::*, hash::HashAlgorithm, pem, rsa::*, util::*};
 
 pub fn main() -> Result<()> {
   let _ = include_str!("../Cargo.toml");
   let args = ClapArgs::parse();
 
   match &args.subcommand {
-    SubCommands::Gen_Hash { algorithm, data } => {
-      let data = data.as_bytes();
-      let digest = match algorithm.as_str() {
-        "SHA-256" => generate_hash::<sha2::Sha256>(data),
-        "SHA-384" => generate_hash::<sha2::Sha384>(data),
-        "SHA-512" => generate_hash::<sha2::Sha512>(data),
-        "SHA3-256" => generate_hash::<sha3::Sha3_256>(data),
-        "SHA3-384" => generate_hash::<sha3::Sha3_384>(data),
-        "SHA3-512" => generate_hash::<sha3::Sha3_512>(data),
-        _ => bail!("Unsupported hash algorithm: {}", algorithm),
-      };
-      println!("<Computed Hash>\n{}\n", digest.to_hex_string());
+    SubCommands::Gen_Hash {
+      algorithm,
+      data,
+      input,
+      output,
+    } => {
+      let data = read_data(data.as_deref(), input.as_ref())?;
+      let digest = algorithm.parse::<HashAlgorithm>()?.hash(&data);
+      match output {
+        Some(output) => output.write(&digest)?,
+        None => println!("<Computed Hash>\n{}\n", digest.to_hex_string()),
+      }
     }
-    SubCommands::Gen_Hex_Key { len } => {
+    SubCommands::Gen_Hex_Key { len, output } => {
       use rand::RngCore;
       let mut buf = vec![0u8; *len];
       rand::rng().fill_bytes(&mut buf);
-      println!("<Generated Hex Key>\n{}\n", buf.to_hex_string());
+      match output {
+        Some(output) => output.write(&buf)?,
+        None => println!("<Generated Hex Key>\n{}\n", buf.to_hex_string()),
+      }
     }
-    SubCommands::Gen_Hmac { key, algorithm, data } => {
-      let data = data.as_bytes();
-      let key = &hex::decode(key)?;
-      let digest = match algorithm.as_str() {
-        "SHA-256" => generate_hmac::<sha2::Sha256>(data, key),
-        "SHA-384" => generate_hmac::<sha2::Sha384>(data, key),
-        "SHA-512" => generate_hmac::<sha2::Sha512>(data, key),
-        "SHA3-256" => generate_hmac::<sha3::Sha3_256>(data, key),
-        "SHA3-384" => generate_hmac::<sha3::Sha3_384>(data, key),
-        "SHA3-512" => generate_hmac::<sha3::Sha3_512>(data, key),
-        _ => bail!("Unsupported hash algorithm: {}", algorithm),
-      }?;
-      println!(
-        "<Computed HMAC with {}>\n{}\n",
-        algorithm.as_str(),
-        digest.to_hex_string()
-      );
+    SubCommands::Gen_Hmac {
+      key,
+      algorithm,
+      data,
+      input,
+      output,
+    } => {
+      let key = key.read()?;
+      let data = read_data(data.as_deref(), input.as_ref())?;
+      let digest = algorithm.parse::<HashAlgorithm>()?.hmac(&data, &key)?;
+      match output {
+        Some(output) => output.write(&digest)?,
+        None => println!(
+          "<Computed HMAC with {}>\n{}\n",
+          algorithm.as_str(),
+          digest.to_hex_string()
+        ),
+      }
     }
     SubCommands::Verify_Hmac {
       key,
       mac,
       algorithm,
       data,
+      input,
     } => {
-      let data = data.as_bytes();
-      let key = &hex::decode(key)?;
-      let digest = match algorithm.as_str() {
-        "SHA-256" => generate_hmac::<sha2::Sha256>(data, key),
-        "SHA-384" => generate_hmac::<sha2::Sha384>(data, key),
-        "SHA-512" => generate_hmac::<sha2::Sha512>(data, key),
-        "SHA3-256" => generate_hmac::<sha3::Sha3_256>(data, key),
-        "SHA3-384" => generate_hmac::<sha3::Sha3_384>(data, key),
-        "SHA3-512" => generate_hmac::<sha3::Sha3_512>(data, key),
-        _ => bail!("Unsupported hash algorithm: {}", algorithm),
-      }?;
-      println!(
-        "<Verification result of given HMAC>\n{}\n",
-        mac == &digest.to_hex_string()
-      );
+      let key = key.read()?;
+      let mac = mac.read()?;
+      let data = read_data(data.as_deref(), input.as_ref())?;
+      let result = algorithm.parse::<HashAlgorithm>()?.verify_hmac(&data, &key, &mac)?;
+      println!("<Verification result of given HMAC>\n{}\n", result);
     }
-    SubCommands::Gen_Rsa_key { bits } => {
+    SubCommands::Gen_Rsa_key { bits, pem, output } => {
       let rsa_keypair = RsaKeyPair::new(bits)?;
-      println!("<Generated RSA Key Pair (DER Form)>");
-      println!("Public Key:\n{}\n", rsa_keypair.to_spki_public_der()?.to_hex_string());
-      println!(
-        "Private Key:\n{}\n",
-        rsa_keypair.to_pkcs8_private_der()?.to_hex_string()
-      );
+      let (pk, sk) = (rsa_keypair.to_spki_public_der()?, rsa_keypair.to_pkcs8_private_der()?);
+      print_key_pair("RSA", &pk, &sk, *pem, output.as_ref())?;
     }
-    SubCommands::Sign_Rsa_Pss { private_key, data } => {
-      let private_key = hex::decode(private_key)?;
-      let data = data.as_bytes();
-      let rsa_keypair = RsaKeyPair::from_pkcs8_private_der(&private_key)?;
-      let signature = rsa_keypair.pss_sign(data)?;
-      println!("<Generated RSASSA-PSS Signature>\n{}\n", signature.to_hex_string());
+    SubCommands::Sign_Rsa_Pss {
+      private_key,
+      data,
+      input,
+      output,
+    } => {
+      let private_key = private_key.read()?;
+      let data = read_data(data.as_deref(), input.as_ref())?;
+      let rsa_keypair = RsaKeyPair::from_private_key(&private_key)?;
+      let signature = rsa_keypair.pss_sign(&data)?;
+      match output {
+        Some(output) => output.write(&signature)?,
+        None => println!("<Generated RSASSA-PSS Signature>\n{}\n", signature.to_hex_string()),
+      }
     }
     SubCommands::Verify_Rsa_Pss {
       public_key,
-      data,
       signature,
+      data,
+      input,
     } => {
-      let public_key = hex::decode(public_key)?;
-      let data = data.as_bytes();
-      let signature = hex::decode(signature)?;
-      let rsa_keypair = RsaKeyPair::from_spki_public_der(&public_key)?;
-      let result = rsa_keypair.pss_verify(data, &signature);
+      let public_key = public_key.read()?;
+      let data = read_data(data.as_deref(), input.as_ref())?;
+      let signature = signature.read()?;
+      let rsa_keypair = RsaKeyPair::from_public_key(&public_key)?;
+      let result = rsa_keypair.pss_verify(&data, &signature);
       println!("<Verification Result of RSASSA-PSS Signature>\n{}\n", result.is_ok());
     }
-    SubCommands::Gen_Ecc_key { curve } => {
-      let (pk, sk) = match curve.as_str() {
-        "P-256" => {
-          let kp = EccKeyPair::<p256::NistP256>::new();
-          (kp.to_spki_public_der()?, kp.to_pkcs8_private_der()?)
-        }
-        "P-384" => {
-          let kp = EccKeyPair::<p384::NistP384>::new();
-          (kp.to_spki_public_der()?, kp.to_pkcs8_private_der()?)
-        }
-        _ => bail!("Unsupported curve"),
-      };
-
-      println!(
-        "<Generated ECC Key Pair (DER Form)>\nPublic Key:\n{}\nPrivate Key:\n{}\n",
-        pk.to_hex_string(),
-        sk.to_hex_string()
-      );
+    SubCommands::Gen_Ecc_key { curve, pem, output } => {
+      let kp = EccKeyPairType::new(curve)?;
+      let (pk, sk) = (kp.to_spki_public_der()?, kp.to_pkcs8_private_der()?);
+      print_key_pair("ECC", &pk, &sk, *pem, output.as_ref())?;
     }
-    SubCommands::Sign_Ecdsa { private_key, data } => {
-      let private_key = hex::decode(private_key)?;
-      let data = data.as_bytes();
-      let ecc_keypair_type = import_pkcs8_der(&private_key)?;
-      let signature = match &ecc_keypair_type {
-        EccKeyPairType::P256(kp) => kp.sign(data)?,
-        EccKeyPairType::P384(kp) => kp.sign(data)?,
-      };
+    SubCommands::Sign_Ecdsa {
+      private_key,
+      data,
+      input,
+      output,
+    } => {
+      let private_key = private_key.read()?;
+      let data = read_data(data.as_deref(), input.as_ref())?;
+      let ecc_keypair_type = import_private_key(&private_key)?;
+      let signature = ecc_keypair_type.sign(&data)?;
 
-      println!(
-        "<Generated ECDSA Signature ({})>\n{}\n",
-        ecc_keypair_type,
-        signature.to_hex_string()
-      );
+      match output {
+        Some(output) => output.write(&signature)?,
+        None => println!(
+          "<Generated {} Signature ({})>\n{}\n",
+          ecc_keypair_type.signature_scheme(),
+          ecc_keypair_type,
+          signature.to_hex_string()
+        ),
+      }
     }
     SubCommands::Verify_Ecdsa {
       public_key,
       signature,
       data,
+      input,
     } => {
-      let public_key = hex::decode(public_key)?;
-      let data = data.as_bytes();
-      let signature = hex::decode(signature)?;
-      let ecc_keypair_type = import_spki_der(&public_key)?;
-      let result = match &ecc_keypair_type {
-        EccKeyPairType::P256(kp) => kp.verify(data, &signature),
-        EccKeyPairType::P384(kp) => kp.verify(data, &signature),
-      };
+      let public_key = public_key.read()?;
+      let data = read_data(data.as_deref(), input.as_ref())?;
+      let signature = signature.read()?;
+      let ecc_keypair_type = import_public_key(&public_key)?;
+      let result = ecc_keypair_type.verify(&data, &signature);
       println!(
-        "<Verification Result of ECDSA Signature ({})>\n{}\n",
+        "<Verification Result of {} Signature ({})>\n{}\n",
+        ecc_keypair_type.signature_scheme(),
         ecc_keypair_type,
         result.is_ok()
       );
@@ -159,3 +148,37 @@ pub fn main() -> Result<()> {
 
   Ok(())
 }
+
+/// Print the key pair in hex or PEM, or write it to the files in DER or PEM and print their paths
+fn print_key_pair(kind: &str, pk: &[u8], sk: &[u8], pem: bool, output: Option<&Stream>) -> Result<()> {
+  let (pk, sk, form) = if pem {
+    (
+      pem::encode(pem::PUBLIC_KEY_LABEL, pk)?.into_bytes(),
+      pem::encode(pem::PRIVATE_KEY_LABEL, sk)?.into_bytes(),
+      "PEM",
+    )
+  } else {
+    (pk.to_vec(), sk.to_vec(), "DER")
+  };
+  match output {
+    Some(output) => {
+      let (pk_path, sk_path) = write_key_pair(output, &pk, &sk)?;
+      println!(
+        "<Generated {kind} Key Pair ({form} Form)>\nPublic Key: {}\nPrivate Key: {}\n",
+        pk_path.display(),
+        sk_path.display()
+      );
+    }
+    None if pem => println!(
+      "<Generated {kind} Key Pair (PEM Form)>\n{}{}",
+      String::from_utf8_lossy(&pk),
+      String::from_utf8_lossy(&sk)
+    ),
+    None => println!(
+      "<Generated {kind} Key Pair (DER Form)>\nPublic Key:\n{}\nPrivate Key:\n{}\n",
+      pk.to_hex_string(),
+      sk.to_hex_string()
+    ),
+  }
+  Ok(())
+}
@@ -0,0 +1 @@
+pub use anyhow::{anyhow, bail, ensure, Context, Result};
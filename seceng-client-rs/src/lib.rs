@@ -0,0 +1,8 @@
+//! Client-side configuration shared by `cli03` and `cli04`.
+//!
+//! - [`profile`]: Server profiles in `~/.config/seceng/profiles.toml` and the HTTP client of a profile
+//!
+//! Cryptography is in `seceng_crypto`, so that it does not depend on an HTTP client.
+
+pub mod error;
+pub mod profile;
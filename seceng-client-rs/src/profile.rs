@@ -0,0 +1,210 @@
+use crate::error::*;
+use serde::Deserialize;
+use std::{
+  collections::HashMap,
+  path::{Path, PathBuf},
+};
+
+pub const LOCAL_SRV: &str = "http://localhost:3000/data";
+pub const REMOTE_SRV: &str = "https://e2e.secarchlab.net/data";
+
+const PROFILES_DIR: &str = "seceng";
+const PROFILES_FILE: &str = "profiles.toml";
+
+/// Profiles file shared by cli03 and cli04, like
+///
+/// ```toml
+/// default = "staging"
+///
+/// [profiles.staging]
+/// server = "https://staging.example.com/data"
+/// ca_cert = "/path/to/ca.pem"
+/// cipher = "AES-GCM"
+/// kdf = "Argon2id"
+/// etm = true
+/// ```
+///
+/// Each client ignores the keys of the other, but unknown keys like a misspelled `iteratons` are rejected.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Profiles {
+  pub default: Option<String>,
+  #[serde(default)]
+  pub profiles: HashMap<String, Profile>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+  /// URL of the data endpoint like http://localhost:3000/data
+  pub server: Option<String>,
+  /// PEM file of CA certificates trusted in addition to the built-in roots
+  pub ca_cert: Option<PathBuf>,
+  /// Default encryption algorithm of cli03, either 'AES-CBC' or 'AES-GCM'
+  pub cipher: Option<String>,
+  /// Default password-based KDF of cli04, either 'PBKDF2', 'Argon2id' or 'scrypt'
+  pub kdf: Option<String>,
+  /// Default hash function of cli04 for PBKDF2 or HKDF
+  pub hash: Option<String>,
+  /// Default iteration count of cli04 for PBKDF2
+  pub iterations: Option<u32>,
+  /// Append HMAC-SHA-256 tag by default in cli04
+  pub etm: Option<bool>,
+}
+
+/// `$XDG_CONFIG_HOME/seceng/profiles.toml`, or `~/.config/seceng/profiles.toml`
+pub fn default_profiles_path() -> Option<PathBuf> {
+  let config_dir = match std::env::var_os("XDG_CONFIG_HOME").filter(|d| !d.is_empty()) {
+    Some(dir) => PathBuf::from(dir),
+    None => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+  };
+  Some(config_dir.join(PROFILES_DIR).join(PROFILES_FILE))
+}
+
+impl Profiles {
+  /// Load the given profiles file, or the default one if exists
+  pub fn load(path: Option<&Path>) -> Result<Self> {
+    let path = match path {
+      Some(p) => p.to_path_buf(),
+      None => match default_profiles_path() {
+        Some(p) if p.exists() => p,
+        _ => return Ok(Self::default()),
+      },
+    };
+    let content = std::fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    Self::parse(&content).with_context(|| format!("Invalid profiles file {}", path.display()))
+  }
+
+  pub fn parse(content: &str) -> Result<Self> {
+    let profiles: Self = toml::from_str(content)?;
+    if let Some(default) = &profiles.default {
+      ensure!(
+        profiles.profiles.contains_key(default),
+        "Default profile '{default}' is not defined"
+      );
+    }
+    Ok(profiles)
+  }
+
+  /// The named profile, or the default one if not named
+  pub fn select(&self, name: Option<&str>) -> Result<Profile> {
+    match name.or(self.default.as_deref()) {
+      Some(name) => self
+        .profiles
+        .get(name)
+        .cloned()
+        .ok_or_else(|| anyhow!("Unknown profile: {name}")),
+      None => Ok(Profile::default()),
+    }
+  }
+}
+
+impl Profile {
+  /// Endpoint URL, chosen by `--server`, `--remote`, the profile and localhost in this order
+  pub fn server_url(&self, server: Option<&str>, remote: bool) -> String {
+    match (server, remote, &self.server) {
+      (Some(server), _, _) => server.to_string(),
+      (None, true, _) => REMOTE_SRV.to_string(),
+      (None, false, Some(server)) => server.clone(),
+      (None, false, None) => LOCAL_SRV.to_string(),
+    }
+  }
+
+  /// HTTP client trusting the CA certificates of the profile
+  pub fn build_client(&self) -> Result<reqwest::Client> {
+    let mut builder = reqwest::Client::builder();
+    if let Some(ca_cert) = &self.ca_cert {
+      let pem = std::fs::read(ca_cert).with_context(|| format!("Failed to read {}", ca_cert.display()))?;
+      let certs = reqwest::Certificate::from_pem_bundle(&pem)?;
+      ensure!(!certs.is_empty(), "No certificate found in {}", ca_cert.display());
+      builder = builder.tls_certs_merge(certs);
+    }
+    Ok(builder.build()?)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const PROFILES: &str = r#"
+default = "staging1"
+
+[profiles.staging1]
+server = "https://staging1.example.com/data"
+cipher = "AES-GCM"
+kdf = "Argon2id"
+etm = true
+
+[profiles.staging2]
+server = "https://staging2.example.com/data"
+hash = "SHA-512"
+iterations = 4096
+"#;
+
+  const CA_CERT: &str = "-----BEGIN CERTIFICATE-----
+MIIBJTCBzKADAgECAgEBMAoGCCqGSM49BAMCMBIxEDAOBgNVBAMMB1Rlc3QgQ0Ew
+HhcNMjUwMTAxMDAwMDAwWhcNNDUwMTAxMDAwMDAwWjASMRAwDgYDVQQDDAdUZXN0
+IENBMFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAE7Fj2uYNw3dU776p4A8+Fq8Ll
+PHudwe9LplFWJ1Yf7CmPo9I1YwMXiguKf4tqmP7nO0hcmdq2MtFMrjgTa4MjkaMT
+MBEwDwYDVR0TAQH/BAUwAwEB/zAKBggqhkjOPQQDAgNIADBFAiBB0ckdLCQivlL9
+7xdgAQImq6wBK6zJ7ruWuQtVIU5LGQIhAL6dLOgtK3KOGoW8E8Ggj0cNiDb6ICHf
+Ze5tcoVqLR5u
+-----END CERTIFICATE-----
+";
+
+  #[test]
+  fn select_profile() -> Result<()> {
+    let profiles = Profiles::parse(PROFILES)?;
+
+    let default = profiles.select(None)?;
+    assert_eq!(default.cipher.as_deref(), Some("AES-GCM"));
+    assert_eq!(default.kdf.as_deref(), Some("Argon2id"));
+    assert_eq!(default.etm, Some(true));
+    assert_eq!(default.server_url(None, false), "https://staging1.example.com/data");
+    assert_eq!(default.server_url(None, true), REMOTE_SRV);
+    assert_eq!(
+      default.server_url(Some("http://127.0.0.1:8000/data"), true),
+      "http://127.0.0.1:8000/data"
+    );
+
+    let staging2 = profiles.select(Some("staging2"))?;
+    assert_eq!(staging2.cipher, None);
+    assert_eq!(staging2.kdf, None);
+    assert_eq!(staging2.hash.as_deref(), Some("SHA-512"));
+    assert_eq!(staging2.iterations, Some(4096));
+    assert_eq!(staging2.server_url(None, false), "https://staging2.example.com/data");
+
+    assert!(profiles.select(Some("production")).is_err());
+    assert_eq!(Profiles::default().select(None)?.server_url(None, false), LOCAL_SRV);
+    assert!(Profiles::parse("default = \"none\"").is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn reject_unknown_keys() {
+    let err = Profiles::parse("[profiles.staging]\niteratons = 4096\n").unwrap_err();
+    assert!(format!("{err:#}").contains("iteratons"));
+    assert!(Profiles::parse("defualt = \"staging\"").is_err());
+  }
+
+  #[test]
+  fn build_client_with_ca_cert() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("seceng-client-profile-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let ca_cert = dir.join("ca.pem");
+    std::fs::write(&ca_cert, CA_CERT)?;
+
+    let profile = Profile {
+      ca_cert: Some(ca_cert.clone()),
+      ..Default::default()
+    };
+    assert!(profile.build_client().is_ok());
+
+    std::fs::write(&ca_cert, "not a certificate")?;
+    assert!(profile.build_client().is_err());
+    std::fs::remove_dir_all(&dir)?;
+    assert!(profile.build_client().is_err());
+    Ok(())
+  }
+}
@@ -0,0 +1,161 @@
+use crate::{
+  error::*,
+  kdf::{
+    random_salt, Argon2idCost, BinaryKey, KdfAlgorithm, KdfHash, KdfParams, ScryptCost, MAX_ARGON2ID_ITERATION,
+    MAX_PBKDF2_ITERATION, MAX_SCRYPT_LOG_N,
+  },
+};
+use std::time::{Duration, Instant};
+
+/// Default target time of a password-based key derivation
+pub const DEFAULT_TARGET: Duration = Duration::from_millis(250);
+/// Probes shorter than this are too noisy to extrapolate from
+const MIN_PROBE: Duration = Duration::from_millis(20);
+const PBKDF2_PROBE_ITERATION: u32 = 1024;
+const PROBE_PASSWORD: &str = "calibration";
+const PROBE_KEY_LEN: usize = 32;
+
+/// KDF params with a fresh salt whose derivation takes about `target` on this machine.
+/// The costs of Argon2id and scrypt other than the calibrated ones are the defaults.
+pub fn calibrate(alg: &KdfAlgorithm, hash: &KdfHash, target: Duration) -> Result<KdfParams> {
+  let salt = random_salt();
+  match alg {
+    KdfAlgorithm::Pbkdf2 => Ok(KdfParams::new_pbkdf2(&salt, hash, calibrate_pbkdf2(hash, target)?)),
+    KdfAlgorithm::Argon2id => Ok(KdfParams::new_argon2id(
+      &salt,
+      &calibrate_argon2id(&Argon2idCost::default(), target)?,
+    )),
+    KdfAlgorithm::Scrypt => Ok(KdfParams::new_scrypt(
+      &salt,
+      &calibrate_scrypt(&ScryptCost::default(), target)?,
+    )),
+    KdfAlgorithm::Hkdf => bail!("HKDF is not a password KDF and has no cost to calibrate"),
+  }
+}
+
+/// Time taken to derive a key as described in the params
+pub fn measure(params: &KdfParams) -> Result<Duration> {
+  time(|| BinaryKey::try_from_kdf_params(PROBE_PASSWORD, PROBE_KEY_LEN, params).map(|_| ()))
+}
+
+/// PBKDF2 iteration count extrapolated from a probe doubled until it is long enough to measure,
+/// up to `MAX_PBKDF2_ITERATION`
+pub fn calibrate_pbkdf2(hash: &KdfHash, target: Duration) -> Result<u32> {
+  ensure!(!target.is_zero(), "Target time must be positive");
+  let mut iter = PBKDF2_PROBE_ITERATION;
+  loop {
+    let elapsed =
+      time(|| BinaryKey::try_new_pbkdf2(PROBE_PASSWORD, PROBE_KEY_LEN, None, Some(&iter), Some(hash)).map(|_| ()))?;
+    if elapsed >= MIN_PROBE.min(target) || iter > MAX_PBKDF2_ITERATION / 2 {
+      let scaled = iter as f64 * target.as_secs_f64() / elapsed.as_secs_f64().max(f64::EPSILON);
+      return Ok(scaled.round().clamp(1.0, MAX_PBKDF2_ITERATION as f64) as u32);
+    }
+    iter *= 2;
+  }
+}
+
+/// Argon2id passes extrapolated from a single pass over the memory of `base`, up to `MAX_ARGON2ID_ITERATION`.
+/// The memory is halved while a single pass exceeds the target.
+pub fn calibrate_argon2id(base: &Argon2idCost, target: Duration) -> Result<Argon2idCost> {
+  ensure!(!target.is_zero(), "Target time must be positive");
+  let min_memory = 8 * base.lanes;
+  let mut cost = Argon2idCost {
+    iterations: 1,
+    ..base.clone()
+  };
+  let elapsed = loop {
+    let elapsed = time(|| BinaryKey::try_new_argon2id(PROBE_PASSWORD, PROBE_KEY_LEN, None, Some(&cost)).map(|_| ()))?;
+    if elapsed <= target || cost.memory / 2 < min_memory {
+      break elapsed;
+    }
+    cost.memory /= 2;
+  };
+  let passes = target.as_secs_f64() / elapsed.as_secs_f64().max(f64::EPSILON);
+  cost.iterations = passes.floor().clamp(1.0, MAX_ARGON2ID_ITERATION as f64) as u32;
+  Ok(cost)
+}
+
+/// scrypt N searched by halving or doubling from `base.n`, taking the closest to the target in ratio
+pub fn calibrate_scrypt(base: &ScryptCost, target: Duration) -> Result<ScryptCost> {
+  ensure!(!target.is_zero(), "Target time must be positive");
+  ensure!(
+    base.n.is_power_of_two() && base.n > 1,
+    "scrypt cost parameter N must be a power of 2 greater than 1"
+  );
+  let measure_n = |n: u64| {
+    let cost = ScryptCost { n, ..base.clone() };
+    time(|| BinaryKey::try_new_scrypt(PROBE_PASSWORD, PROBE_KEY_LEN, None, Some(&cost)).map(|_| ()))
+  };
+  let (mut n, mut elapsed) = (base.n, measure_n(base.n)?);
+  let slower = elapsed < target;
+  loop {
+    let next = if slower { n * 2 } else { n / 2 };
+    if next < 2 || next.trailing_zeros() > MAX_SCRYPT_LOG_N {
+      break;
+    }
+    let next_elapsed = measure_n(next)?;
+    if ratio(next_elapsed, target) > ratio(elapsed, target) {
+      break;
+    }
+    (n, elapsed) = (next, next_elapsed);
+    if (next_elapsed < target) != slower {
+      break;
+    }
+  }
+  Ok(ScryptCost { n, ..base.clone() })
+}
+
+/// How far the elapsed time is from the target, regardless of the direction
+fn ratio(elapsed: Duration, target: Duration) -> f64 {
+  let r = elapsed.as_secs_f64().max(f64::EPSILON) / target.as_secs_f64();
+  r.max(1.0 / r)
+}
+
+fn time(f: impl FnOnce() -> Result<()>) -> Result<Duration> {
+  let start = Instant::now();
+  f()?;
+  Ok(start.elapsed())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn pbkdf2_iterations_grow_with_target() -> Result<()> {
+    let short = calibrate_pbkdf2(&KdfHash::Sha256, Duration::from_millis(1))?;
+    let long = calibrate_pbkdf2(&KdfHash::Sha256, Duration::from_millis(64))?;
+    assert!(short >= 1);
+    assert!(long > short, "{long} <= {short}");
+    assert!(calibrate_pbkdf2(&KdfHash::Sha256, Duration::ZERO).is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn calibrated_params_are_valid() -> Result<()> {
+    let target = Duration::from_millis(10);
+    let params = calibrate(&KdfAlgorithm::Pbkdf2, &KdfHash::Sha512, target)?;
+    params.validate()?;
+    assert_eq!(params.hash()?, KdfHash::Sha512);
+    measure(&params)?;
+    assert!(calibrate(&KdfAlgorithm::Hkdf, &KdfHash::Sha256, target).is_err());
+
+    let base = Argon2idCost {
+      memory: 64,
+      iterations: 3,
+      lanes: 1,
+    };
+    let cost = calibrate_argon2id(&base, target)?;
+    assert_eq!((cost.memory, cost.lanes), (64, 1));
+    assert!(cost.iterations >= 1);
+    KdfParams::new_argon2id(&random_salt(), &cost).validate()?;
+
+    let base = ScryptCost { n: 16, r: 8, p: 1 };
+    let cost = calibrate_scrypt(&base, target)?;
+    assert!(cost.n.is_power_of_two() && cost.n > 16);
+    assert_eq!((cost.r, cost.p), (8, 1));
+    KdfParams::new_scrypt(&random_salt(), &cost).validate()?;
+    assert!(calibrate_scrypt(&ScryptCost { n: 1000, r: 8, p: 1 }, target).is_err());
+    Ok(())
+  }
+}
@@ -0,0 +1,969 @@
+use crate::{error::*, pem};
+use crypto_common::generic_array::ArrayLength;
+use ecdsa::{
+  hazmat::{bits2field, sign_prehashed, DigestPrimitive, SignPrimitive, VerifyPrimitive},
+  signature::{Signer, Verifier},
+  PrimeCurve, Signature, SignatureSize, SigningKey, VerifyingKey,
+};
+use elliptic_curve::{
+  ecdh,
+  ff::PrimeField,
+  ops::Invert,
+  pkcs8::{
+    der::{
+      asn1::{BitStringRef, OctetStringRef},
+      Decode, Encode,
+    },
+    spki::{AlgorithmIdentifierRef, SubjectPublicKeyInfoRef},
+    AssociatedOid, DecodePrivateKey, DecodePublicKey, EncodePrivateKey, EncodePublicKey, ObjectIdentifier,
+    PrivateKeyInfo,
+  },
+  rand_core::OsRng,
+  sec1::{ModulusSize, ToEncodedPoint},
+  subtle::CtOption,
+  AffinePoint, CurveArithmetic, FieldBytesSize, PublicKey, Scalar, SecretKey,
+};
+use hmac::{Hmac, Mac};
+pub use k256::Secp256k1;
+pub use p256::NistP256;
+pub use p384::NistP384;
+pub use p521::NistP521;
+use sha2::{Digest, Sha512};
+use std::fmt::Display;
+use zeroize::Zeroizing;
+
+/// OID of X25519 in SPKI and PKCS#8 (RFC 8410)
+pub const X25519_OID: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.101.110");
+/// OID of Ed25519 in SPKI and PKCS#8 (RFC 8410)
+pub const ED25519_OID: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.101.112");
+
+/// ECC key pair for ECDH and ECDSA over the curve `C`. The private key is absent for an imported public key.
+pub struct EccKeyPair<C>
+where
+  C: CurveArithmetic,
+{
+  pub public: PublicKey<C>,
+  pub private: Option<SecretKey<C>>,
+}
+
+/// ECC key pair over a curve determined at runtime, e.g., from the OID of an imported key.
+/// X25519 is only for ECDH, and Ed25519 only for signatures.
+pub enum EccKeyPairType {
+  P256(EccKeyPair<NistP256>),
+  P384(EccKeyPair<NistP384>),
+  P521(EccKeyPair<NistP521>),
+  Secp256k1(EccKeyPair<Secp256k1>),
+  X25519(X25519KeyPair),
+  /// Boxed as its decompressed points make it much larger than the others
+  Ed25519(Box<Ed25519KeyPair>),
+}
+
+impl Display for EccKeyPairType {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      EccKeyPairType::P256(_) => write!(f, "P-256"),
+      EccKeyPairType::P384(_) => write!(f, "P-384"),
+      EccKeyPairType::P521(_) => write!(f, "P-521"),
+      EccKeyPairType::Secp256k1(_) => write!(f, "secp256k1"),
+      EccKeyPairType::X25519(_) => write!(f, "X25519"),
+      EccKeyPairType::Ed25519(_) => write!(f, "Ed25519"),
+    }
+  }
+}
+
+impl EccKeyPairType {
+  /// Generate a new key pair over the curve named like 'P-256'
+  pub fn new(curve: &str) -> Result<Self> {
+    match curve {
+      "P-256" => Ok(EccKeyPairType::P256(EccKeyPair::new())),
+      "P-384" => Ok(EccKeyPairType::P384(EccKeyPair::new())),
+      "P-521" => Ok(EccKeyPairType::P521(EccKeyPair::new())),
+      "secp256k1" => Ok(EccKeyPairType::Secp256k1(EccKeyPair::new())),
+      "X25519" => Ok(EccKeyPairType::X25519(X25519KeyPair::new())),
+      "Ed25519" => Ok(EccKeyPairType::Ed25519(Box::default())),
+      _ => bail!("Unsupported curve: {}", curve),
+    }
+  }
+
+  pub fn to_spki_public_der(&self) -> Result<Vec<u8>> {
+    match self {
+      EccKeyPairType::P256(kp) => kp.to_spki_public_der(),
+      EccKeyPairType::P384(kp) => kp.to_spki_public_der(),
+      EccKeyPairType::P521(kp) => kp.to_spki_public_der(),
+      EccKeyPairType::Secp256k1(kp) => kp.to_spki_public_der(),
+      EccKeyPairType::X25519(kp) => kp.to_spki_public_der(),
+      EccKeyPairType::Ed25519(kp) => kp.to_spki_public_der(),
+    }
+  }
+
+  pub fn to_spki_public_pem(&self) -> Result<String> {
+    pem::encode(pem::PUBLIC_KEY_LABEL, &self.to_spki_public_der()?)
+  }
+
+  pub fn to_pkcs8_private_pem(&self) -> Result<String> {
+    pem::encode(pem::PRIVATE_KEY_LABEL, &self.to_pkcs8_private_der()?)
+  }
+
+  pub fn to_pkcs8_private_der(&self) -> Result<Vec<u8>> {
+    match self {
+      EccKeyPairType::P256(kp) => kp.to_pkcs8_private_der(),
+      EccKeyPairType::P384(kp) => kp.to_pkcs8_private_der(),
+      EccKeyPairType::P521(kp) => kp.to_pkcs8_private_der(),
+      EccKeyPairType::Secp256k1(kp) => kp.to_pkcs8_private_der(),
+      EccKeyPairType::X25519(kp) => kp.to_pkcs8_private_der(),
+      EccKeyPairType::Ed25519(kp) => kp.to_pkcs8_private_der(),
+    }
+  }
+
+  /// Import the private key over the curve named like 'P-256' from its big-endian scalar,
+  /// or from the 32 bytes of the private key for X25519 and Ed25519
+  pub fn from_private_bytes(curve: &str, bytes: &[u8]) -> Result<Self> {
+    match curve {
+      "P-256" => Ok(EccKeyPairType::P256(EccKeyPair::from_private_bytes(bytes)?)),
+      "P-384" => Ok(EccKeyPairType::P384(EccKeyPair::from_private_bytes(bytes)?)),
+      "P-521" => Ok(EccKeyPairType::P521(EccKeyPair::from_private_bytes(bytes)?)),
+      "secp256k1" => Ok(EccKeyPairType::Secp256k1(EccKeyPair::from_private_bytes(bytes)?)),
+      "X25519" => Ok(EccKeyPairType::X25519(X25519KeyPair::from_private_bytes(bytes)?)),
+      "Ed25519" => Ok(EccKeyPairType::Ed25519(Box::new(Ed25519KeyPair::from_private_bytes(
+        bytes,
+      )?))),
+      _ => bail!("Unsupported curve: {}", curve),
+    }
+  }
+
+  /// Import the public key from a SEC1 point over the curve named like 'P-256'
+  pub fn from_sec1_public(curve: &str, bytes: &[u8]) -> Result<Self> {
+    match curve {
+      "P-256" => Ok(EccKeyPairType::P256(EccKeyPair::from_sec1_public(bytes)?)),
+      "P-384" => Ok(EccKeyPairType::P384(EccKeyPair::from_sec1_public(bytes)?)),
+      "P-521" => Ok(EccKeyPairType::P521(EccKeyPair::from_sec1_public(bytes)?)),
+      "secp256k1" => Ok(EccKeyPairType::Secp256k1(EccKeyPair::from_sec1_public(bytes)?)),
+      _ => bail!("Unsupported curve: {}", curve),
+    }
+  }
+
+  pub fn to_sec1_public(&self) -> Result<Vec<u8>> {
+    match self {
+      EccKeyPairType::P256(kp) => Ok(kp.to_sec1_public()),
+      EccKeyPairType::P384(kp) => Ok(kp.to_sec1_public()),
+      EccKeyPairType::P521(kp) => Ok(kp.to_sec1_public()),
+      EccKeyPairType::Secp256k1(kp) => Ok(kp.to_sec1_public()),
+      _ => bail!("No SEC1 point over {self}"),
+    }
+  }
+
+  /// ECDH shared bits. Both key pairs must be over the same curve.
+  pub fn derive_bits(&self, other: &EccKeyPairType) -> Result<Vec<u8>> {
+    match (self, other) {
+      (EccKeyPairType::P256(a), EccKeyPairType::P256(b)) => a.derive_bits(b),
+      (EccKeyPairType::P384(a), EccKeyPairType::P384(b)) => a.derive_bits(b),
+      (EccKeyPairType::P521(a), EccKeyPairType::P521(b)) => a.derive_bits(b),
+      (EccKeyPairType::Secp256k1(a), EccKeyPairType::Secp256k1(b)) => a.derive_bits(b),
+      (EccKeyPairType::X25519(a), EccKeyPairType::X25519(b)) => a.derive_bits(b),
+      _ => bail!("Unsupported curve or inconsistent key type"),
+    }
+  }
+
+  /// ECDSA signature in the `r || s` form, or Ed25519 signature
+  pub fn sign(&self, data: &[u8]) -> Result<Vec<u8>> {
+    match self {
+      EccKeyPairType::P256(kp) => kp.sign(data),
+      EccKeyPairType::P384(kp) => kp.sign(data),
+      EccKeyPairType::P521(kp) => kp.sign(data),
+      EccKeyPairType::Secp256k1(kp) => kp.sign(data),
+      EccKeyPairType::Ed25519(kp) => kp.sign(data),
+      EccKeyPairType::X25519(_) => bail!("X25519 is only for ECDH"),
+    }
+  }
+
+  pub fn verify(&self, data: &[u8], signature: &[u8]) -> Result<()> {
+    match self {
+      EccKeyPairType::P256(kp) => kp.verify(data, signature),
+      EccKeyPairType::P384(kp) => kp.verify(data, signature),
+      EccKeyPairType::P521(kp) => kp.verify(data, signature),
+      EccKeyPairType::Secp256k1(kp) => kp.verify(data, signature),
+      EccKeyPairType::Ed25519(kp) => kp.verify(data, signature),
+      EccKeyPairType::X25519(_) => bail!("X25519 is only for ECDH"),
+    }
+  }
+
+  /// Name of the signature scheme over the curve
+  pub fn signature_scheme(&self) -> &'static str {
+    match self {
+      EccKeyPairType::Ed25519(_) => "EdDSA",
+      _ => "ECDSA",
+    }
+  }
+}
+
+impl<C> Default for EccKeyPair<C>
+where
+  C: CurveArithmetic + AssociatedOid + EcdsaCurve,
+  <C as elliptic_curve::CurveArithmetic>::AffinePoint: elliptic_curve::sec1::FromEncodedPoint<C>,
+  <C as elliptic_curve::Curve>::FieldBytesSize: elliptic_curve::sec1::ModulusSize,
+  <C as elliptic_curve::CurveArithmetic>::AffinePoint: elliptic_curve::sec1::ToEncodedPoint<C>,
+{
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl<C> EccKeyPair<C>
+where
+  C: CurveArithmetic + AssociatedOid + EcdsaCurve,
+  <C as elliptic_curve::CurveArithmetic>::AffinePoint: elliptic_curve::sec1::FromEncodedPoint<C>,
+  <C as elliptic_curve::Curve>::FieldBytesSize: elliptic_curve::sec1::ModulusSize,
+  <C as elliptic_curve::CurveArithmetic>::AffinePoint: elliptic_curve::sec1::ToEncodedPoint<C>,
+{
+  /// Generate a new key pair
+  pub fn new() -> Self {
+    let mut rng = OsRng;
+    let private_key = SecretKey::random(&mut rng);
+    let public_key = private_key.public_key();
+
+    Self {
+      public: public_key,
+      private: Some(private_key),
+    }
+  }
+
+  pub fn to_spki_public_der(&self) -> Result<Vec<u8>> {
+    let der = self.public.to_public_key_der()?.as_bytes().to_vec();
+    Ok(der)
+  }
+
+  pub fn to_pkcs8_private_der(&self) -> Result<Vec<u8>> {
+    if self.private.is_none() {
+      bail!("No private key");
+    }
+    let der = self.private.as_ref().unwrap().to_pkcs8_der()?.as_bytes().to_vec();
+    Ok(der)
+  }
+
+  pub fn to_spki_public_pem(&self) -> Result<String> {
+    pem::encode(pem::PUBLIC_KEY_LABEL, &self.to_spki_public_der()?)
+  }
+
+  pub fn to_pkcs8_private_pem(&self) -> Result<String> {
+    pem::encode(pem::PRIVATE_KEY_LABEL, &self.to_pkcs8_private_der()?)
+  }
+
+  pub fn from_spki_public_der(der: &[u8]) -> Result<EccKeyPair<C>> {
+    let public_key = PublicKey::<C>::from_public_key_der(der)?;
+    Ok(EccKeyPair {
+      public: public_key,
+      private: None,
+    })
+  }
+
+  pub fn from_pkcs8_private_der(der: &[u8]) -> Result<EccKeyPair<C>> {
+    let private_key = SecretKey::<C>::from_pkcs8_der(der)?;
+    let public_key = private_key.public_key();
+    Ok(EccKeyPair {
+      public: public_key,
+      private: Some(private_key),
+    })
+  }
+
+  /// Key pair from the big-endian private scalar, which must be nonzero and less than the order
+  pub fn from_private_bytes(bytes: &[u8]) -> Result<EccKeyPair<C>> {
+    let private_key = SecretKey::<C>::from_slice(bytes).map_err(|e| anyhow!(e))?;
+    let public_key = private_key.public_key();
+    Ok(EccKeyPair {
+      public: public_key,
+      private: Some(private_key),
+    })
+  }
+
+  pub fn from_sec1_public(bytes: &[u8]) -> Result<EccKeyPair<C>> {
+    let public_key = PublicKey::<C>::from_sec1_bytes(bytes).map_err(|e| anyhow!(e))?;
+    Ok(EccKeyPair {
+      public: public_key,
+      private: None,
+    })
+  }
+
+  /// Public key as an uncompressed SEC1 point
+  pub fn to_sec1_public(&self) -> Vec<u8> {
+    self.public.to_encoded_point(false).as_bytes().to_vec()
+  }
+
+  /// ECDH shared bits from the private key of either key pair and the public key of the other
+  pub fn derive_bits(&self, other: &EccKeyPair<C>) -> Result<Vec<u8>> {
+    if self.private.is_none() && other.private.is_none() {
+      bail!("No private key");
+    }
+    let shared_secret = match &self.private {
+      Some(private_key) => ecdh::diffie_hellman(private_key.to_nonzero_scalar(), other.public.as_affine()),
+      None => ecdh::diffie_hellman(
+        other.private.as_ref().unwrap().to_nonzero_scalar(),
+        self.public.as_affine(),
+      ),
+    };
+
+    let raw_bits = shared_secret.raw_secret_bytes().as_slice();
+    Ok(raw_bits.to_vec())
+  }
+
+  /// ECDSA signature in the fixed-size `r || s` form
+  pub fn sign(&self, data: &[u8]) -> Result<Vec<u8>> {
+    if self.private.is_none() {
+      bail!("No private key");
+    }
+    C::ecdsa_sign(self.private.as_ref().unwrap(), data)
+  }
+
+  pub fn verify(&self, data: &[u8], signature: &[u8]) -> Result<()> {
+    C::ecdsa_verify(&self.public, data, signature)
+  }
+}
+
+/// Curve of ECDSA with the hash of its size, i.e., SHA-256 for P-256 and secp256k1, SHA-384 for P-384,
+/// and SHA-512 for P-521
+pub trait EcdsaCurve: PrimeCurve + CurveArithmetic {
+  fn ecdsa_sign(private_key: &SecretKey<Self>, data: &[u8]) -> Result<Vec<u8>>;
+  fn ecdsa_verify(public_key: &PublicKey<Self>, data: &[u8], signature: &[u8]) -> Result<()>;
+}
+
+/// Deterministic ECDSA (RFC 6979) with the digest associated with the curve
+fn sign_with_digest<C>(private_key: &SecretKey<C>, data: &[u8]) -> Vec<u8>
+where
+  C: PrimeCurve + CurveArithmetic + DigestPrimitive,
+  Scalar<C>: Invert<Output = CtOption<Scalar<C>>> + SignPrimitive<C>,
+  SignatureSize<C>: ArrayLength<u8>,
+{
+  let signature: Signature<C> = SigningKey::from(private_key).sign(data);
+  signature.to_vec()
+}
+
+fn verify_with_digest<C>(public_key: &PublicKey<C>, data: &[u8], signature: &Signature<C>) -> Result<()>
+where
+  C: PrimeCurve + CurveArithmetic + DigestPrimitive,
+  AffinePoint<C>: VerifyPrimitive<C>,
+  FieldBytesSize<C>: ModulusSize,
+  SignatureSize<C>: ArrayLength<u8>,
+{
+  VerifyingKey::from(public_key)
+    .verify(data, signature)
+    .map_err(|e| anyhow!(e))
+}
+
+impl EcdsaCurve for NistP256 {
+  fn ecdsa_sign(private_key: &SecretKey<Self>, data: &[u8]) -> Result<Vec<u8>> {
+    Ok(sign_with_digest(private_key, data))
+  }
+
+  fn ecdsa_verify(public_key: &PublicKey<Self>, data: &[u8], signature: &[u8]) -> Result<()> {
+    verify_with_digest(public_key, data, &Signature::try_from(signature)?)
+  }
+}
+
+impl EcdsaCurve for NistP384 {
+  fn ecdsa_sign(private_key: &SecretKey<Self>, data: &[u8]) -> Result<Vec<u8>> {
+    Ok(sign_with_digest(private_key, data))
+  }
+
+  fn ecdsa_verify(public_key: &PublicKey<Self>, data: &[u8], signature: &[u8]) -> Result<()> {
+    verify_with_digest(public_key, data, &Signature::try_from(signature)?)
+  }
+}
+
+/// P-521 has no digest associated in the generic ECDSA, and `p521` signs with a random nonce.
+/// So it is signed here with the nonce of RFC 6979 over SHA-512 as the other curves.
+impl EcdsaCurve for NistP521 {
+  fn ecdsa_sign(private_key: &SecretKey<Self>, data: &[u8]) -> Result<Vec<u8>> {
+    let h1 = Sha512::digest(data);
+    let k = rfc6979_p521_nonce(&private_key.to_bytes(), &h1)?;
+    let (signature, _) =
+      sign_prehashed::<NistP521, _>(&private_key.to_nonzero_scalar(), k, &bits2field::<NistP521>(&h1)?)?;
+    Ok(signature.to_vec())
+  }
+
+  fn ecdsa_verify(public_key: &PublicKey<Self>, data: &[u8], signature: &[u8]) -> Result<()> {
+    let verifying_key = p521::ecdsa::VerifyingKey::from(VerifyingKey::from(public_key));
+    let signature = p521::ecdsa::Signature::try_from(signature)?;
+    verifying_key.verify(data, &signature).map_err(|e| anyhow!(e))
+  }
+}
+
+/// Nonce of RFC 6979 section 3.2 for P-521 with HMAC-SHA-512, which `rfc6979` cannot give
+/// since the digest is shorter than the order
+fn rfc6979_p521_nonce(x: &[u8], h1: &[u8]) -> Result<p521::Scalar> {
+  const RLEN: usize = 66;
+  let hmac = |key: &[u8], parts: &[&[u8]]| -> Result<Zeroizing<Vec<u8>>> {
+    let mut mac = Hmac::<Sha512>::new_from_slice(key).map_err(|e| anyhow!(e))?;
+    for part in parts {
+      mac.update(part);
+    }
+    Ok(Zeroizing::new(mac.finalize().into_bytes().to_vec()))
+  };
+  // bits2octets(h1) is h1 itself left-padded, as the digest of 512 bits is less than the order
+  let mut h = [0u8; RLEN];
+  h[RLEN - h1.len()..].copy_from_slice(h1);
+  let mut v = Zeroizing::new(vec![0x01u8; 64]);
+  let mut k = hmac(&[0x00; 64], &[&v, &[0x00], x, &h])?;
+  v = hmac(&k, &[&v])?;
+  k = hmac(&k, &[&v, &[0x01], x, &h])?;
+  v = hmac(&k, &[&v])?;
+  loop {
+    let mut t = Zeroizing::new(vec![]);
+    while t.len() < RLEN {
+      v = hmac(&k, &[&v])?;
+      t.extend_from_slice(&v);
+    }
+    // bits2int(T) is the leftmost 521 bits, i.e., the first 66 bytes shifted right by 7 bits
+    let mut candidate = p521::FieldBytes::default();
+    candidate[0] = t[0] >> 7;
+    for i in 1..RLEN {
+      candidate[i] = (t[i - 1] << 1) | (t[i] >> 7);
+    }
+    let nonce = Option::<p521::Scalar>::from(p521::Scalar::from_repr(candidate));
+    if let Some(nonce) = nonce.filter(|n| !bool::from(n.is_zero())) {
+      return Ok(nonce);
+    }
+    k = hmac(&k, &[&v, &[0x00]])?;
+    v = hmac(&k, &[&v])?;
+  }
+}
+
+/// Signatures of secp256k1 are made with the low `s`, while those with the high `s` are also accepted
+/// as in the other curves, which `k256` alone would reject
+impl EcdsaCurve for Secp256k1 {
+  fn ecdsa_sign(private_key: &SecretKey<Self>, data: &[u8]) -> Result<Vec<u8>> {
+    Ok(sign_with_digest(private_key, data))
+  }
+
+  fn ecdsa_verify(public_key: &PublicKey<Self>, data: &[u8], signature: &[u8]) -> Result<()> {
+    let signature = Signature::<Secp256k1>::try_from(signature)?;
+    let signature = signature.normalize_s().unwrap_or(signature);
+    verify_with_digest(public_key, data, &signature)
+  }
+}
+
+/// X25519 key pair for ECDH (RFC 7748). The private key is absent for an imported public key.
+pub struct X25519KeyPair {
+  pub public: x25519_dalek::PublicKey,
+  pub private: Option<x25519_dalek::StaticSecret>,
+}
+
+impl Default for X25519KeyPair {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl X25519KeyPair {
+  pub fn new() -> Self {
+    Self::from_secret(x25519_dalek::StaticSecret::random_from_rng(OsRng))
+  }
+
+  fn from_secret(private_key: x25519_dalek::StaticSecret) -> Self {
+    Self {
+      public: x25519_dalek::PublicKey::from(&private_key),
+      private: Some(private_key),
+    }
+  }
+
+  /// Key pair from the 32 bytes of the private key, which is clamped on use
+  pub fn from_private_bytes(bytes: &[u8]) -> Result<Self> {
+    let bytes: [u8; 32] = bytes
+      .try_into()
+      .map_err(|_| anyhow!("Invalid X25519 private key length"))?;
+    Ok(Self::from_secret(x25519_dalek::StaticSecret::from(bytes)))
+  }
+
+  /// Public key from the 32 bytes of the u-coordinate
+  pub fn from_public_bytes(bytes: &[u8]) -> Result<Self> {
+    let bytes: [u8; 32] = bytes
+      .try_into()
+      .map_err(|_| anyhow!("Invalid X25519 public key length"))?;
+    Ok(Self {
+      public: x25519_dalek::PublicKey::from(bytes),
+      private: None,
+    })
+  }
+
+  pub fn to_spki_public_der(&self) -> Result<Vec<u8>> {
+    let spki = SubjectPublicKeyInfoRef {
+      algorithm: curve25519_algorithm(X25519_OID),
+      subject_public_key: BitStringRef::from_bytes(self.public.as_bytes())?,
+    };
+    Ok(spki.to_der()?)
+  }
+
+  pub fn to_pkcs8_private_der(&self) -> Result<Vec<u8>> {
+    let Some(private_key) = &self.private else {
+      bail!("No private key");
+    };
+    let bytes = Zeroizing::new(private_key.to_bytes());
+    // The private key is an OCTET STRING wrapped in the privateKey OCTET STRING (RFC 8410)
+    let inner = Zeroizing::new(OctetStringRef::new(bytes.as_slice())?.to_der()?);
+    Ok(PrivateKeyInfo::new(curve25519_algorithm(X25519_OID), &inner).to_der()?)
+  }
+
+  pub fn from_spki_public_der(der: &[u8]) -> Result<Self> {
+    let spki = SubjectPublicKeyInfoRef::from_der(der)?;
+    ensure!(
+      spki.algorithm == curve25519_algorithm(X25519_OID),
+      "Not an X25519 public key"
+    );
+    let bytes = spki
+      .subject_public_key
+      .as_bytes()
+      .ok_or_else(|| anyhow!("Invalid X25519 public key"))?;
+    Self::from_public_bytes(bytes)
+  }
+
+  pub fn from_pkcs8_private_der(der: &[u8]) -> Result<Self> {
+    let pki = PrivateKeyInfo::from_der(der)?;
+    ensure!(
+      pki.algorithm == curve25519_algorithm(X25519_OID),
+      "Not an X25519 private key"
+    );
+    let key_pair = Self::from_private_bytes(OctetStringRef::from_der(pki.private_key)?.as_bytes())?;
+    if let Some(public_key) = pki.public_key {
+      ensure!(
+        public_key == key_pair.public.as_bytes(),
+        "Inconsistent X25519 public key"
+      );
+    }
+    Ok(key_pair)
+  }
+
+  /// Shared bits from the private key of either key pair and the public key of the other.
+  /// The all-zero output of a small-order public key is rejected as in RFC 7748 Section 6.1.
+  pub fn derive_bits(&self, other: &X25519KeyPair) -> Result<Vec<u8>> {
+    let shared_secret = match (&self.private, &other.private) {
+      (Some(private_key), _) => private_key.diffie_hellman(&other.public),
+      (None, Some(private_key)) => private_key.diffie_hellman(&self.public),
+      (None, None) => bail!("No private key"),
+    };
+    ensure!(shared_secret.was_contributory(), "All-zero X25519 shared secret");
+    Ok(shared_secret.as_bytes().to_vec())
+  }
+}
+
+/// Ed25519 key pair for EdDSA signatures (RFC 8032). The private key is absent for an imported public key.
+pub struct Ed25519KeyPair {
+  pub public: ed25519_dalek::VerifyingKey,
+  pub private: Option<ed25519_dalek::SigningKey>,
+}
+
+impl Default for Ed25519KeyPair {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Ed25519KeyPair {
+  pub fn new() -> Self {
+    Self::from_signing_key(ed25519_dalek::SigningKey::generate(&mut OsRng))
+  }
+
+  fn from_signing_key(private_key: ed25519_dalek::SigningKey) -> Self {
+    Self {
+      public: private_key.verifying_key(),
+      private: Some(private_key),
+    }
+  }
+
+  /// Key pair from the 32 bytes of the private key, i.e., the seed
+  pub fn from_private_bytes(bytes: &[u8]) -> Result<Self> {
+    let bytes: [u8; 32] = bytes
+      .try_into()
+      .map_err(|_| anyhow!("Invalid Ed25519 private key length"))?;
+    Ok(Self::from_signing_key(ed25519_dalek::SigningKey::from_bytes(&bytes)))
+  }
+
+  pub fn to_spki_public_der(&self) -> Result<Vec<u8>> {
+    Ok(self.public.to_public_key_der()?.as_bytes().to_vec())
+  }
+
+  pub fn to_pkcs8_private_der(&self) -> Result<Vec<u8>> {
+    let Some(private_key) = &self.private else {
+      bail!("No private key");
+    };
+    Ok(private_key.to_pkcs8_der()?.as_bytes().to_vec())
+  }
+
+  pub fn from_spki_public_der(der: &[u8]) -> Result<Self> {
+    Ok(Self {
+      public: ed25519_dalek::VerifyingKey::from_public_key_der(der)?,
+      private: None,
+    })
+  }
+
+  pub fn from_pkcs8_private_der(der: &[u8]) -> Result<Self> {
+    Ok(Self::from_signing_key(ed25519_dalek::SigningKey::from_pkcs8_der(der)?))
+  }
+
+  pub fn sign(&self, data: &[u8]) -> Result<Vec<u8>> {
+    let Some(private_key) = &self.private else {
+      bail!("No private key");
+    };
+    Ok(private_key.sign(data).to_bytes().to_vec())
+  }
+
+  /// Verify the signature, rejecting small-order public keys and non-canonical signatures
+  pub fn verify(&self, data: &[u8], signature: &[u8]) -> Result<()> {
+    let signature = ed25519_dalek::Signature::from_slice(signature)?;
+    self.public.verify_strict(data, &signature).map_err(|e| anyhow!(e))
+  }
+}
+
+/// Algorithm identifier of X25519 or Ed25519, which takes no parameters
+fn curve25519_algorithm(oid: ObjectIdentifier) -> AlgorithmIdentifierRef<'static> {
+  AlgorithmIdentifierRef { oid, parameters: None }
+}
+
+/// Import a PKCS#8 private key, detecting the curve from its OID
+pub fn import_pkcs8_der(der: &[u8]) -> Result<EccKeyPairType> {
+  let pki = PrivateKeyInfo::from_der(der)?;
+  match pki.algorithm.oid {
+    X25519_OID => return Ok(EccKeyPairType::X25519(X25519KeyPair::from_pkcs8_private_der(der)?)),
+    ED25519_OID => {
+      return Ok(EccKeyPairType::Ed25519(Box::new(
+        Ed25519KeyPair::from_pkcs8_private_der(der)?,
+      )))
+    }
+    _ => {}
+  }
+  let algorithm = pki.algorithm.oid;
+  let parameters = pki.algorithm.parameters_oid()?;
+  match (algorithm, parameters) {
+    (elliptic_curve::ALGORITHM_OID, NistP256::OID) => {
+      let k: EccKeyPair<NistP256> = EccKeyPair::from_pkcs8_private_der(der).map_err(|e| anyhow!(e))?;
+      Ok(EccKeyPairType::P256(k))
+    }
+    (elliptic_curve::ALGORITHM_OID, NistP384::OID) => {
+      let k: EccKeyPair<NistP384> = EccKeyPair::from_pkcs8_private_der(der).map_err(|e| anyhow!(e))?;
+      Ok(EccKeyPairType::P384(k))
+    }
+    (elliptic_curve::ALGORITHM_OID, NistP521::OID) => {
+      let k: EccKeyPair<NistP521> = EccKeyPair::from_pkcs8_private_der(der).map_err(|e| anyhow!(e))?;
+      Ok(EccKeyPairType::P521(k))
+    }
+    (elliptic_curve::ALGORITHM_OID, Secp256k1::OID) => {
+      let k: EccKeyPair<Secp256k1> = EccKeyPair::from_pkcs8_private_der(der).map_err(|e| anyhow!(e))?;
+      Ok(EccKeyPairType::Secp256k1(k))
+    }
+    _ => {
+      bail!("Unsupported algorithm");
+    }
+  }
+}
+
+/// Import an SPKI public key, detecting the curve from its OID
+pub fn import_spki_der(der: &[u8]) -> Result<EccKeyPairType> {
+  let spki = SubjectPublicKeyInfoRef::from_der(der)?;
+  match spki.algorithm.oid {
+    X25519_OID => return Ok(EccKeyPairType::X25519(X25519KeyPair::from_spki_public_der(der)?)),
+    ED25519_OID => {
+      return Ok(EccKeyPairType::Ed25519(Box::new(Ed25519KeyPair::from_spki_public_der(
+        der,
+      )?)))
+    }
+    _ => {}
+  }
+  let algorithm = spki.algorithm.oid;
+  let parameters = spki.algorithm.parameters_oid()?;
+  match (algorithm, parameters) {
+    (elliptic_curve::ALGORITHM_OID, NistP256::OID) => {
+      let k: EccKeyPair<NistP256> = EccKeyPair::from_spki_public_der(der).map_err(|e| anyhow!(e))?;
+      Ok(EccKeyPairType::P256(k))
+    }
+    (elliptic_curve::ALGORITHM_OID, NistP384::OID) => {
+      let k: EccKeyPair<NistP384> = EccKeyPair::from_spki_public_der(der).map_err(|e| anyhow!(e))?;
+      Ok(EccKeyPairType::P384(k))
+    }
+    (elliptic_curve::ALGORITHM_OID, NistP521::OID) => {
+      let k: EccKeyPair<NistP521> = EccKeyPair::from_spki_public_der(der).map_err(|e| anyhow!(e))?;
+      Ok(EccKeyPairType::P521(k))
+    }
+    (elliptic_curve::ALGORITHM_OID, Secp256k1::OID) => {
+      let k: EccKeyPair<Secp256k1> = EccKeyPair::from_spki_public_der(der).map_err(|e| anyhow!(e))?;
+      Ok(EccKeyPairType::Secp256k1(k))
+    }
+    _ => {
+      bail!("Unsupported algorithm");
+    }
+  }
+}
+
+/// Import a private key in PEM, DER or hex of DER, either of PKCS#8 or SEC1, detecting the curve
+pub fn import_private_key(input: &[u8]) -> Result<EccKeyPairType> {
+  import_pkcs8_der(&pem::private_key_der(input)?)
+}
+
+/// Import a public key in PEM, DER or hex of DER, detecting the curve
+pub fn import_public_key(input: &[u8]) -> Result<EccKeyPairType> {
+  import_spki_der(&pem::public_key_der(input)?)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::util::*;
+
+  #[test]
+  fn oid() {
+    let keypair = EccKeyPair::<p256::NistP256>::new();
+    let public_der = keypair.to_spki_public_der().unwrap();
+    let private_der = keypair.to_pkcs8_private_der().unwrap();
+    let k = import_pkcs8_der(&private_der).unwrap();
+    assert!(matches!(k, EccKeyPairType::P256(_)));
+    let k = import_spki_der(&public_der).unwrap();
+    assert!(matches!(k, EccKeyPairType::P256(_)));
+
+    let keypair = EccKeyPair::<p384::NistP384>::new();
+    let public_der = keypair.to_spki_public_der().unwrap();
+    let private_der = keypair.to_pkcs8_private_der().unwrap();
+    let k = import_pkcs8_der(&private_der).unwrap();
+    assert!(matches!(k, EccKeyPairType::P384(_)));
+    let k = import_spki_der(&public_der).unwrap();
+    assert!(matches!(k, EccKeyPairType::P384(_)));
+  }
+
+  #[test]
+  fn test_keygen() {
+    let keypair = EccKeyPair::<p256::NistP256>::new();
+    let public_der = keypair.to_spki_public_der().unwrap();
+    let private_der = keypair.to_pkcs8_private_der().unwrap();
+
+    let keypair_from_public_der = EccKeyPair::<p256::NistP256>::from_spki_public_der(&public_der).unwrap();
+    let keypair_from_private_der = EccKeyPair::<p256::NistP256>::from_pkcs8_private_der(&private_der).unwrap();
+
+    let public_der2 = keypair_from_public_der.to_spki_public_der().unwrap();
+    let public_der3 = keypair_from_private_der.to_spki_public_der().unwrap();
+    assert_eq!(public_der, public_der2);
+    assert_eq!(public_der, public_der3);
+
+    let private_der2 = keypair_from_private_der.to_pkcs8_private_der().unwrap();
+    assert_eq!(private_der, private_der2);
+  }
+
+  #[test]
+  fn test_key_vector() {
+    let private_key = "308193020100301306072a8648ce3d020106082a8648ce3d0301070479307702010104207922cff3b9e5910b212890ac9ff654f36c746d9549f6a57b0a513f995434453ea00a06082a8648ce3d030107a14403420004bfbfb4b020abfc135b32ace230ea5d3bc14c57c9751379d647c77128ffa0b71c4837e8185a3bdc4df8936763de63d14c935b538c9b45a2acd773b4731378f53f";
+    let public_key = "3059301306072a8648ce3d020106082a8648ce3d03010703420004bfbfb4b020abfc135b32ace230ea5d3bc14c57c9751379d647c77128ffa0b71c4837e8185a3bdc4df8936763de63d14c935b538c9b45a2acd773b4731378f53f";
+
+    let keypair_from_private_der =
+      EccKeyPair::<p256::NistP256>::from_pkcs8_private_der(&hex::decode(private_key).unwrap()).unwrap();
+    let keypair_from_public_der =
+      EccKeyPair::<p256::NistP256>::from_spki_public_der(&hex::decode(public_key).unwrap()).unwrap();
+
+    let public_der2 = keypair_from_public_der.to_spki_public_der().unwrap();
+    let public_der3 = keypair_from_private_der.to_spki_public_der().unwrap();
+    assert_eq!(hex::decode(public_key).unwrap(), public_der2);
+    assert_eq!(public_der2, public_der3);
+  }
+
+  #[test]
+  fn test_ecdh() {
+    let keypair1 = EccKeyPair::<p256::NistP256>::new();
+    let keypair2 = EccKeyPair::<p256::NistP256>::new();
+
+    let bits1 = keypair1.derive_bits(&keypair2).unwrap();
+    let bits2 = keypair2.derive_bits(&keypair1).unwrap();
+    assert_eq!(bits1, bits2);
+  }
+
+  #[test]
+  fn test_ecdh_vector() {
+    let public_key1 = "3059301306072a8648ce3d020106082a8648ce3d03010703420004eda2be155a365f3a66aa86bb18f16afeb5ac2b77cabcc04fd77d5646d0ebe6718600914749d322ad6bcd15b955fa51866f62ed8b1c78bf2fad7788d99868c0e1";
+    let private_key1 = "308193020100301306072a8648ce3d020106082a8648ce3d03010704793077020101042010f0c0660d9a51ced7218d31156b388a7442b34a3fb3f12fb0836fec5f569c31a00a06082a8648ce3d030107a14403420004eda2be155a365f3a66aa86bb18f16afeb5ac2b77cabcc04fd77d5646d0ebe6718600914749d322ad6bcd15b955fa51866f62ed8b1c78bf2fad7788d99868c0e1";
+
+    let public_key2 = "3059301306072a8648ce3d020106082a8648ce3d03010703420004b8b4132b8191cf03f7ae3d542d7a3f201db1f1c2eecda7e3f0afff5b3a327d6e1276b531acbd05ca95005e5de077ef14f5eb714c065b913d33a5436d8b654cba";
+    let private_key2 = "308193020100301306072a8648ce3d020106082a8648ce3d03010704793077020101042008c608249387c17c78ad50fe00b14929b74f7b4f729bbfee16d5aef986dc9caca00a06082a8648ce3d030107a14403420004b8b4132b8191cf03f7ae3d542d7a3f201db1f1c2eecda7e3f0afff5b3a327d6e1276b531acbd05ca95005e5de077ef14f5eb714c065b913d33a5436d8b654cba";
+
+    let bits1 = EccKeyPair::<p256::NistP256>::from_spki_public_der(&hex::decode(public_key1).unwrap())
+      .unwrap()
+      .derive_bits(&EccKeyPair::<p256::NistP256>::from_pkcs8_private_der(&hex::decode(private_key2).unwrap()).unwrap())
+      .unwrap();
+    let bits2 = EccKeyPair::<p256::NistP256>::from_pkcs8_private_der(&hex::decode(private_key1).unwrap())
+      .unwrap()
+      .derive_bits(&EccKeyPair::<p256::NistP256>::from_spki_public_der(&hex::decode(public_key2).unwrap()).unwrap())
+      .unwrap();
+    assert_eq!(bits1, bits2);
+    assert_eq!(
+      "c69bed8ba4a68db213ed4d92e59d113abf6ff6cb811313abe7b7bc39c75d1602",
+      &bits1.to_hex_string()
+    );
+  }
+
+  #[test]
+  fn test_ecdsa() {
+    let keypair = EccKeyPair::<p256::NistP256>::new();
+    let data = "hello".as_bytes();
+    let signature = keypair.sign(data).unwrap();
+    keypair.verify(data, &signature).unwrap();
+  }
+
+  #[test]
+  fn p521_signatures_are_deterministic() -> Result<()> {
+    // RFC 6979 A.2.7, ECDSA over P-521 with SHA-512
+    let x = hex::decode(
+      "00fad06daa62ba3b25d2fb40133da757205de67f5bb0018fee8c86e1b68c7e75caa896eb32f1f47c70855836a6d16fcc1466f6d8fbec67db89ec0c08b0e996b83538",
+    )?;
+    let vectors = [
+      (
+        "sample",
+        "00c328fafcbd79dd77850370c46325d987cb525569fb63c5d3bc53950e6d4c5f174e25a1ee9017b5d450606add152b534931d7d4e8455cc91f9b15bf05ec36e377fa",
+        "00617cce7cf5064806c467f678d3b4080d6f1cc50af26ca209417308281b68af282623eaa63e5b5c0723d8b8c37ff0777b1a20f8ccb1dccc43997f1ee0e44da4a67a",
+      ),
+      (
+        "test",
+        "013e99020abf5cee7525d16b69b229652ab6bdf2affcaef38773b4b7d08725f10cdb93482fdcc54edcee91eca4166b2a7c6265ef0ce2bd7051b7cef945babd47ee6d",
+        "01fbd0013c674aa79cb39849527916ce301c66ea7ce8b80682786ad60f98f7e78a19ca69eff5c57400e3b3a0ad66ce0978214d13baf4e9ac60752f7b155e2de4dce3",
+      ),
+    ];
+    let private_key = SecretKey::<NistP521>::from_slice(&x)?;
+    for (message, r, s) in vectors {
+      let signature = NistP521::ecdsa_sign(&private_key, message.as_bytes())?;
+      assert_eq!(hex::encode(&signature), format!("{r}{s}"), "{message}");
+      NistP521::ecdsa_verify(&private_key.public_key(), message.as_bytes(), &signature)?;
+    }
+    Ok(())
+  }
+
+  #[test]
+  fn test_keypair_type_dispatch() -> Result<()> {
+    let a = EccKeyPairType::new("P-384")?;
+    let b = EccKeyPairType::new("P-384")?;
+    assert_eq!(a.to_string(), "P-384");
+    let b_public = import_spki_der(&b.to_spki_public_der()?)?;
+    let a_public = import_spki_der(&a.to_spki_public_der()?)?;
+    assert_eq!(a.derive_bits(&b_public)?, b.derive_bits(&a_public)?);
+
+    let data = b"hello";
+    let signature = a.sign(data)?;
+    assert!(a_public.verify(data, &signature).is_ok());
+    assert!(b_public.verify(data, &signature).is_err());
+
+    let c = EccKeyPairType::new("P-256")?;
+    assert!(a.derive_bits(&c).is_err());
+    assert!(EccKeyPairType::new("P-192").is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn test_keypair_type_curves() -> Result<()> {
+    for curve in ["P-256", "P-384", "P-521", "secp256k1"] {
+      let a = EccKeyPairType::new(curve)?;
+      let b = EccKeyPairType::new(curve)?;
+      assert_eq!(a.to_string(), curve);
+      let a_private = import_pkcs8_der(&a.to_pkcs8_private_der()?)?;
+      let a_public = import_spki_der(&a.to_spki_public_der()?)?;
+      let b_public = EccKeyPairType::from_sec1_public(curve, &b.to_sec1_public()?)?;
+      assert_eq!(a_private.to_string(), curve);
+      assert_eq!(a_private.derive_bits(&b_public)?, b.derive_bits(&a_public)?);
+
+      let data = b"hello";
+      let signature = a_private.sign(data)?;
+      assert!(a_public.verify(data, &signature).is_ok());
+      assert_eq!(a_private.sign(data)?, signature, "{curve} signing is not deterministic");
+      assert!(a_public.verify(b"other", &signature).is_err());
+      assert!(b_public.verify(data, &signature).is_err());
+    }
+    let p521 = EccKeyPairType::new("P-521")?;
+    assert_eq!(p521.sign(b"hello")?.len(), 132);
+    assert!(p521.derive_bits(&EccKeyPairType::new("secp256k1")?).is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn x25519_rfc7748_vectors() -> Result<()> {
+    // Section 5.2, where the high bit of the second u-coordinate is masked
+    let vectors = [
+      (
+        "a546e36bf0527c9d3b16154b82465edd62144c0ac1fc5a18506a2244ba449ac4",
+        "e6db6867583030db3594c1a424b15f7c726624ec26b3353b10a903a6d0ab1c4c",
+        "c3da55379de9c6908e94ea4df28d084f32eccf03491c71f754b4075577a28552",
+      ),
+      (
+        "4b66e9d4d1b4673c5ad22691957d6af5c11b6421e0ea01d42ca4169e7918ba0d",
+        "e5210f12786811d3f4b7959d0538ae2c31dbe7106fc03c3efc4cd549c715a493",
+        "95cbde9476e8907d7aade45cb4b873f88b595a68799fa152e6f8f7647aac7957",
+      ),
+    ];
+    for (scalar, u, output) in vectors {
+      let k = X25519KeyPair::from_private_bytes(&hex::decode(scalar)?)?;
+      let u = X25519KeyPair::from_public_bytes(&hex::decode(u)?)?;
+      assert_eq!(k.derive_bits(&u)?.to_hex_string(), output);
+    }
+
+    // Section 6.1, with the keys in the PKCS#8 and SPKI DER of RFC 8410 as written by OpenSSL
+    let alice = import_pkcs8_der(&hex::decode(
+      "302e020100300506032b656e0422042077076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a",
+    )?)?;
+    let bob_public_der = "302a300506032b656e032100de9edb7d7b7dc1b4d35b61c2ece435373f8343c85b78674dadfc7e146f882b4f";
+    let bob = EccKeyPairType::from_private_bytes(
+      "X25519",
+      &hex::decode("5dab087e624a8a4b79e17f8b83800ee66f3bb1292618b6fd1c2f8b27ff88e0eb")?,
+    )?;
+    assert_eq!(
+      alice.to_spki_public_der()?.to_hex_string(),
+      "302a300506032b656e0321008520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a"
+    );
+    assert_eq!(bob.to_spki_public_der()?.to_hex_string(), bob_public_der);
+    let shared = "4a5d9d5ba4ce2de1728e3bf480350f25e07e21c947d19e3376f09b3c1e161742";
+    let bob_public = import_spki_der(&hex::decode(bob_public_der)?)?;
+    assert_eq!(alice.derive_bits(&bob_public)?.to_hex_string(), shared);
+    assert_eq!(
+      bob
+        .derive_bits(&import_spki_der(&alice.to_spki_public_der()?)?)?
+        .to_hex_string(),
+      shared
+    );
+
+    let der = alice.to_pkcs8_private_der()?;
+    assert_eq!(import_pkcs8_der(&der)?.to_pkcs8_private_der()?, der);
+    assert!(alice.sign(b"hello").is_err());
+    let zero = EccKeyPairType::X25519(X25519KeyPair::from_public_bytes(&[0u8; 32])?);
+    assert!(alice.derive_bits(&zero).is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn ed25519_rfc8032_vectors() -> Result<()> {
+    // Tests 1 to 3 of Section 7.1
+    let vectors = [
+      (
+        "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60",
+        "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a",
+        "",
+        "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e065224901555fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b",
+      ),
+      (
+        "4ccd089b28ff96da9db6c346ec114e0f5b8a319f35aba624da8cf6ed4fb8a6fb",
+        "3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c",
+        "72",
+        "92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00",
+      ),
+      (
+        "c5aa8df43f9f837bedb7442f31dcb7b166d38535076f094b85ce3a2e0b4458f7",
+        "fc51cd8e6218a1a38da47ed00230f0580816ed13ba3303ac5deb911548908025",
+        "af82",
+        "6291d657deec24024827e69c3abe01a30ce548a284743a445e3680d7db5ac3ac18ff9b538d16f290ae67f760984dc6594a7c15e9716ed28dc027beceea1ec40a",
+      ),
+    ];
+    for (secret, public, message, signature) in vectors {
+      // PKCS#8 DER of RFC 8410 as written by OpenSSL
+      let sk = import_pkcs8_der(&hex::decode(format!("302e020100300506032b657004220420{secret}"))?)?;
+      let pk = import_spki_der(&hex::decode(format!("302a300506032b6570032100{public}"))?)?;
+      assert_eq!(sk.to_spki_public_der()?, pk.to_spki_public_der()?);
+      let message = hex::decode(message)?;
+      assert_eq!(sk.sign(&message)?.to_hex_string(), signature);
+      assert!(pk.verify(&message, &hex::decode(signature)?).is_ok());
+      assert!(pk.verify(b"other", &hex::decode(signature)?).is_err());
+
+      let der = sk.to_pkcs8_private_der()?;
+      assert_eq!(import_pkcs8_der(&der)?.sign(&message)?.to_hex_string(), signature);
+    }
+    let ed25519 = EccKeyPairType::new("Ed25519")?;
+    assert_eq!(ed25519.signature_scheme(), "EdDSA");
+    assert!(ed25519.derive_bits(&EccKeyPairType::new("Ed25519")?).is_err());
+    assert!(ed25519.derive_bits(&EccKeyPairType::new("X25519")?).is_err());
+    Ok(())
+  }
+}
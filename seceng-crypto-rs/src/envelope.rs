@@ -0,0 +1,937 @@
+use crate::{
+  ecc::{import_spki_der, EccKeyPairType},
+  error::*,
+  kdf::{BinaryKey, KdfAlgorithm, KdfHash, KdfParams, ITERATION},
+  keywrap::{random_dek, KeyWrapAlgorithm, DEK_LEN, KW_OVERHEAD},
+  symmetric::{encrypt_then_mac, verify_then_decrypt, Algorithm, Encrypted, CBC_IV_LEN, GCM_NONCE_LEN, KEY_LEN},
+};
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{fmt::Display, str::FromStr};
+use zeroize::Zeroizing;
+
+/// Current version of the envelope, recorded as `v`
+pub const ENVELOPE_VERSION: u32 = 1;
+const BLOCK_LEN: usize = 16;
+const GCM_TAG_LEN: usize = 16;
+const MAC_LEN: usize = 32;
+/// Prefix of the HKDF `info` of ECIES, followed by both public keys
+const ECIES_INFO_PREFIX: &[u8] = b"seceng-ecies";
+
+/// Versioned self-describing ciphertext shared by all the clients.
+/// Binary fields are Base64 strings in JSON, and native byte strings in msgpack and CBOR.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Envelope {
+  pub v: u32,
+  /// Symmetric cipher like `AES-CBC`
+  pub cipher: String,
+  /// How the key was derived from the password, master secret or shared bits
+  pub kdf: KdfParams,
+  /// Key agreement that produced the input of the KDF, if any
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub ka: Option<KeyAgreement>,
+  /// Random data key wrapped under the key derived by the KDF, if any
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub kw: Option<WrappedKey>,
+  #[serde(with = "bytes")]
+  pub iv: Vec<u8>,
+  #[serde(with = "bytes")]
+  pub data: Vec<u8>,
+  /// HMAC-SHA-256 over IV || data for encrypt-then-MAC
+  #[serde(default, skip_serializing_if = "Option::is_none", with = "opt_bytes")]
+  pub mac: Option<Vec<u8>>,
+}
+
+/// Key agreement parameters of the envelope
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct KeyAgreement {
+  /// `ECDH` between static keys, or `ECIES` with an ephemeral key of the sender
+  pub alg: String,
+  /// Curve like `P-256`. Absent in envelopes upgraded from the original sample-05.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub curve: Option<String>,
+  /// Ephemeral public key of the sender in SPKI DER for ECIES
+  #[serde(default, skip_serializing_if = "Option::is_none", with = "opt_bytes")]
+  pub epk: Option<Vec<u8>>,
+}
+
+impl KeyAgreement {
+  pub fn ecdh(curve: Option<&str>) -> Self {
+    Self {
+      alg: "ECDH".to_string(),
+      curve: curve.map(|c| c.to_string()),
+      epk: None,
+    }
+  }
+
+  pub fn ecies(curve: &str, epk: &[u8]) -> Self {
+    Self {
+      alg: "ECIES".to_string(),
+      curve: Some(curve.to_string()),
+      epk: Some(epk.to_vec()),
+    }
+  }
+
+  pub fn is_ecies(&self) -> bool {
+    self.alg == "ECIES"
+  }
+}
+
+/// Data key wrapped under a key-encryption key for envelope encryption
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct WrappedKey {
+  /// Key wrap like `AES-KW`
+  pub alg: String,
+  #[serde(with = "bytes")]
+  pub key: Vec<u8>,
+}
+
+impl WrappedKey {
+  pub fn alg(&self) -> Result<KeyWrapAlgorithm> {
+    self.alg.parse()
+  }
+}
+
+/// Serialization format of the envelope
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+  Json,
+  Msgpack,
+  Cbor,
+}
+
+impl FromStr for Encoding {
+  type Err = anyhow::Error;
+  fn from_str(s: &str) -> Result<Self> {
+    match s {
+      "json" => Ok(Encoding::Json),
+      "msgpack" => Ok(Encoding::Msgpack),
+      "cbor" => Ok(Encoding::Cbor),
+      _ => bail!("Unsupported encoding: {}", s),
+    }
+  }
+}
+
+impl Display for Encoding {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Encoding::Json => write!(f, "json"),
+      Encoding::Msgpack => write!(f, "msgpack"),
+      Encoding::Cbor => write!(f, "cbor"),
+    }
+  }
+}
+
+impl Envelope {
+  pub fn new(
+    cipher: &Algorithm,
+    kdf: KdfParams,
+    ka: Option<KeyAgreement>,
+    encrypted: Encrypted,
+    mac: Option<Vec<u8>>,
+  ) -> Self {
+    Self {
+      v: ENVELOPE_VERSION,
+      cipher: cipher.to_string(),
+      kdf,
+      ka,
+      kw: None,
+      iv: encrypted.iv,
+      data: encrypted.data,
+      mac,
+    }
+  }
+
+  pub fn cipher(&self) -> Result<Algorithm> {
+    self.cipher.parse()
+  }
+
+  pub fn encrypted(&self) -> Encrypted {
+    Encrypted {
+      data: self.data.clone(),
+      iv: self.iv.clone(),
+    }
+  }
+
+  /// Check that every field is consistent with the others, as done on decode
+  pub fn validate(&self) -> Result<()> {
+    ensure!(self.v == ENVELOPE_VERSION, "Unsupported envelope version: {}", self.v);
+    match self.cipher()? {
+      Algorithm::AesCbc => {
+        ensure!(self.iv.len() == CBC_IV_LEN, "Invalid IV length for AES-CBC");
+        ensure!(
+          !self.data.is_empty() && self.data.len().is_multiple_of(BLOCK_LEN),
+          "AES-CBC data must be a positive multiple of the block length"
+        );
+      }
+      Algorithm::AesGcm => {
+        ensure!(self.iv.len() == GCM_NONCE_LEN, "Invalid nonce length for AES-GCM");
+        ensure!(self.data.len() >= GCM_TAG_LEN, "AES-GCM data is shorter than its tag");
+        ensure!(self.mac.is_none(), "AES-GCM takes no separate MAC");
+      }
+    }
+    if let Some(mac) = &self.mac {
+      ensure!(mac.len() == MAC_LEN, "Invalid MAC length for HMAC-SHA-256");
+    }
+    self.kdf.validate()?;
+    if let Some(ka) = &self.ka {
+      ensure!(
+        matches!(ka.alg.as_str(), "ECDH" | "ECIES"),
+        "Unsupported key agreement: {}",
+        ka.alg
+      );
+      if let Some(curve) = &ka.curve {
+        ensure!(
+          matches!(curve.as_str(), "P-256" | "P-384" | "P-521" | "secp256k1" | "X25519"),
+          "Unsupported curve: {}",
+          curve
+        );
+      }
+      match (ka.is_ecies(), &ka.epk) {
+        (true, Some(epk)) => {
+          let epk = import_spki_der(epk).context("Invalid ephemeral public key")?;
+          ensure!(
+            ka.curve.as_deref() == Some(epk.to_string().as_str()),
+            "Ephemeral public key is not over the curve of ECIES"
+          );
+          ensure!(
+            self.kdf.info.is_none(),
+            "ECIES takes no HKDF info other than the public keys"
+          );
+        }
+        (true, None) => bail!("ECIES takes the ephemeral public key"),
+        (false, Some(_)) => bail!("ECDH takes no ephemeral public key"),
+        (false, None) => {}
+      }
+      ensure!(
+        self.kdf.algorithm()? == KdfAlgorithm::Hkdf,
+        "Shared bits of key agreement must be expanded by HKDF"
+      );
+    }
+    if let Some(kw) = &self.kw {
+      kw.alg()?;
+      ensure!(
+        kw.key.len() == DEK_LEN + KW_OVERHEAD,
+        "Invalid length of the wrapped data key"
+      );
+      ensure!(self.ka.is_none(), "Wrapped data key takes no key agreement");
+    }
+    Ok(())
+  }
+
+  pub fn encode(&self, encoding: Encoding) -> Result<Vec<u8>> {
+    Ok(match encoding {
+      Encoding::Json => serde_json::to_vec(self)?,
+      Encoding::Msgpack => rmp_serde::to_vec_named(self)?,
+      Encoding::Cbor => {
+        let mut buf = Vec::new();
+        ciborium::into_writer(self, &mut buf)?;
+        buf
+      }
+    })
+  }
+
+  /// Decode and validate the envelope, rejecting unknown fields and trailing bytes
+  pub fn decode(bytes: &[u8], encoding: Encoding) -> Result<Self> {
+    let envelope = Self::parse(bytes, encoding)?;
+    envelope.validate()?;
+    Ok(envelope)
+  }
+
+  /// Decode an envelope in any of the encodings, or upgrade a msgpack pack of the original sample-05
+  pub fn decode_any(bytes: &[u8]) -> Result<Self> {
+    for encoding in [Encoding::Json, Encoding::Msgpack, Encoding::Cbor] {
+      if let Ok(envelope) = Self::parse(bytes, encoding) {
+        envelope.validate()?;
+        return Ok(envelope);
+      }
+    }
+    Self::upgrade_msgpack(bytes).context("Neither an envelope nor a legacy sample-05 pack")
+  }
+
+  fn parse(bytes: &[u8], encoding: Encoding) -> Result<Self> {
+    let mut rest = bytes;
+    let envelope = match encoding {
+      Encoding::Json => return Ok(serde_json::from_slice(bytes)?),
+      Encoding::Msgpack => Self::deserialize(&mut rmp_serde::Deserializer::new(&mut rest))?,
+      Encoding::Cbor => ciborium::from_reader(&mut rest)?,
+    };
+    ensure!(rest.is_empty(), "Trailing bytes after the {encoding} envelope");
+    Ok(envelope)
+  }
+
+  pub fn to_json_value(&self) -> Result<Value> {
+    Ok(serde_json::to_value(self)?)
+  }
+
+  pub fn from_json_value(value: &Value) -> Result<Self> {
+    let envelope = Self::deserialize(value)?;
+    envelope.validate()?;
+    Ok(envelope)
+  }
+
+  /// Whether the JSON object is an envelope rather than a legacy object
+  pub fn is_envelope(value: &Value) -> bool {
+    value.get("v").is_some()
+  }
+
+  /// Read an object from the json server, either an envelope or a legacy object of sample-03 or sample-04.
+  /// The `id` assigned by the server is ignored.
+  pub fn upgrade_json(value: &Value) -> Result<Self> {
+    let mut value = value.clone();
+    if let Some(object) = value.as_object_mut() {
+      object.remove("id");
+    }
+    if Self::is_envelope(&value) {
+      Self::from_json_value(&value)
+    } else if value.get("kdfParams").is_some() {
+      Sample04Object::deserialize(&value)?.try_into()
+    } else if value.get("salt").is_some() {
+      Sample03Object::deserialize(&value)?.try_into()
+    } else {
+      bail!("Not an encrypted object")
+    }
+  }
+
+  /// Upgrade a msgpack pack of the original sample-05
+  pub fn upgrade_msgpack(bytes: &[u8]) -> Result<Self> {
+    rmp_serde::from_slice::<Sample05Pack>(bytes)?.try_into()
+  }
+
+  /// Encrypt the data with a key derived as described in `kdf`, with encrypt-then-MAC if `etm`.
+  /// `secret` is a password, or a master secret in Base64 for HKDF.
+  pub fn seal(data: &[u8], secret: &str, kdf: &KdfParams, cipher: &Algorithm, etm: bool) -> Result<Self> {
+    let (encrypted, mac) = if etm {
+      ensure!(*cipher == Algorithm::AesCbc, "Encrypt-then-MAC is only for AES-CBC");
+      let (enc_key, mac_key) = BinaryKey::try_etm_from_kdf_params(secret, kdf)?;
+      let (encrypted, mac) = encrypt_then_mac(data, &enc_key.key, &mac_key, None)?;
+      (encrypted, Some(mac))
+    } else {
+      let key = BinaryKey::try_from_kdf_params(secret, KEY_LEN, kdf)?;
+      (cipher.encrypt(data, &key.key, None)?, None)
+    };
+    let envelope = Self::new(cipher, kdf.clone(), None, encrypted, mac);
+    envelope.validate()?;
+    Ok(envelope)
+  }
+
+  /// Encrypt the data with a random data key, and wrap it under a key-encryption key derived as described in `kdf`.
+  /// Rotating the secret then only needs `rewrap`, leaving the data as is.
+  pub fn seal_wrapped(
+    data: &[u8],
+    secret: &str,
+    kdf: &KdfParams,
+    cipher: &Algorithm,
+    etm: bool,
+    wrap: &KeyWrapAlgorithm,
+  ) -> Result<Self> {
+    let dek = random_dek();
+    let (encrypted, mac) = if etm {
+      ensure!(*cipher == Algorithm::AesCbc, "Encrypt-then-MAC is only for AES-CBC");
+      let (enc_key, mac_key) = split_dek(&dek)?;
+      let (encrypted, mac) = encrypt_then_mac(data, &enc_key.key, &mac_key, None)?;
+      (encrypted, Some(mac))
+    } else {
+      (cipher.encrypt(data, &dek, None)?, None)
+    };
+    let mut envelope = Self::new(cipher, kdf.clone(), None, encrypted, mac);
+    envelope.kw = Some(wrap_dek(&dek, secret, kdf, wrap)?);
+    envelope.validate()?;
+    Ok(envelope)
+  }
+
+  /// Unwrap the data key under the key-encryption key derived from the secret
+  pub fn unwrap_dek(&self, secret: &str) -> Result<Zeroizing<Vec<u8>>> {
+    let kw = self.kw.as_ref().context("No wrapped data key")?;
+    let kek = BinaryKey::try_kek_from_kdf_params(secret, &self.kdf)?;
+    kw.alg()?.unwrap(&kek.key, &kw.key)
+  }
+
+  /// Wrap the data key again under a key-encryption key derived from the new secret as described in `kdf`.
+  /// The ciphertext is kept as is.
+  pub fn rewrap(&self, secret: &str, new_secret: &str, kdf: &KdfParams) -> Result<Self> {
+    self.validate()?;
+    let dek = self.unwrap_dek(secret)?;
+    let alg = self.kw.as_ref().context("No wrapped data key")?.alg()?;
+    let envelope = Self {
+      kdf: kdf.clone(),
+      kw: Some(wrap_dek(&dek, new_secret, kdf, &alg)?),
+      ..self.clone()
+    };
+    envelope.validate()?;
+    Ok(envelope)
+  }
+
+  /// Encrypt the data to the recipient's public key by ECIES, so that the sender needs no long-term key pair.
+  /// The key is expanded by HKDF-SHA-256 from the ECDH shared bits of a fresh ephemeral key pair,
+  /// with both public keys bound into the `info`, and the ephemeral public key is recorded in `ka`.
+  pub fn seal_ecies(data: &[u8], recipient: &EccKeyPairType, cipher: &Algorithm) -> Result<Self> {
+    let ephemeral = EccKeyPairType::new(&recipient.to_string())?;
+    let shared_bits = Zeroizing::new(recipient.derive_bits(&ephemeral)?);
+    let epk = ephemeral.to_spki_public_der()?;
+    let info = ecies_info(&epk, &recipient.to_spki_public_der()?);
+    let key = BinaryKey::try_new_hkdf_with_info(&shared_bits, KEY_LEN, None, Some(&KdfHash::Sha256), &info)?;
+    let kdf = KdfParams::new_hkdf(&key.salt, &KdfHash::Sha256, None);
+    let ka = KeyAgreement::ecies(&recipient.to_string(), &epk);
+    let envelope = Self::new(cipher, kdf, Some(ka), cipher.encrypt(data, &key.key, None)?, None);
+    envelope.validate()?;
+    Ok(envelope)
+  }
+
+  /// Decrypt the data encrypted by ECIES with only the recipient's private key
+  pub fn open_ecies(&self, recipient: &EccKeyPairType) -> Result<Vec<u8>> {
+    self.validate()?;
+    let Some(KeyAgreement { epk: Some(epk), .. }) = self.ka.as_ref().filter(|ka| ka.is_ecies()) else {
+      bail!("Not encrypted by ECIES");
+    };
+    let ephemeral = import_spki_der(epk)?;
+    ensure!(
+      ephemeral.to_string() == recipient.to_string(),
+      "Encrypted to a key over {ephemeral}, not {recipient}"
+    );
+    let shared_bits = Zeroizing::new(ephemeral.derive_bits(recipient)?);
+    let info = ecies_info(epk, &recipient.to_spki_public_der()?);
+    let key = BinaryKey::try_new_hkdf_with_info(
+      &shared_bits,
+      KEY_LEN,
+      Some(&self.kdf.salt()?),
+      Some(&self.kdf.hash()?),
+      &info,
+    )?;
+    self.cipher()?.decrypt(&self.encrypted(), &key.key)
+  }
+
+  /// Decrypt the data, verifying the MAC first if any.
+  /// `secret` is a password, or a master secret or shared bits of key agreement in Base64 for HKDF.
+  pub fn open(&self, secret: &str) -> Result<Vec<u8>> {
+    Ok(self.open_with_derived_key(secret)?.0)
+  }
+
+  /// `open` also giving the key derived from the secret as described in `kdf` for inspection,
+  /// i.e., the key-encryption key if the data key is wrapped, and the encryption key otherwise
+  pub fn open_with_derived_key(&self, secret: &str) -> Result<(Vec<u8>, BinaryKey)> {
+    self.validate()?;
+    ensure!(
+      !self.ka.as_ref().is_some_and(|ka| ka.is_ecies()),
+      "ECIES is opened with the recipient's private key"
+    );
+    if let Some(kw) = &self.kw {
+      let kek = BinaryKey::try_kek_from_kdf_params(secret, &self.kdf)?;
+      let dek = kw.alg()?.unwrap(&kek.key, &kw.key)?;
+      let data = match &self.mac {
+        Some(mac) => {
+          let (enc_key, mac_key) = split_dek(&dek)?;
+          verify_then_decrypt(&self.encrypted(), mac, &enc_key.key, &mac_key)?
+        }
+        None => self.cipher()?.decrypt(&self.encrypted(), &dek)?,
+      };
+      return Ok((data, kek));
+    }
+    match &self.mac {
+      Some(mac) => {
+        let (enc_key, mac_key) = BinaryKey::try_etm_from_kdf_params(secret, &self.kdf)?;
+        let data = verify_then_decrypt(&self.encrypted(), mac, &enc_key.key, &mac_key)?;
+        Ok((data, enc_key))
+      }
+      None => {
+        let key = BinaryKey::try_from_kdf_params(secret, KEY_LEN, &self.kdf)?;
+        let data = self.cipher()?.decrypt(&self.encrypted(), &key.key)?;
+        Ok((data, key))
+      }
+    }
+  }
+}
+
+/// HKDF `info` of ECIES binding the ephemeral and the recipient's public keys in SPKI DER,
+/// each prefixed by its length in two bytes
+fn ecies_info(epk: &[u8], recipient: &[u8]) -> Vec<u8> {
+  let mut info = ECIES_INFO_PREFIX.to_vec();
+  for key in [epk, recipient] {
+    info.extend_from_slice(&(key.len() as u16).to_be_bytes());
+    info.extend_from_slice(key);
+  }
+  info
+}
+
+/// Encryption and MAC keys split from the data key for encrypt-then-MAC
+pub fn split_dek(dek: &[u8]) -> Result<(BinaryKey, Zeroizing<Vec<u8>>)> {
+  BinaryKey {
+    key: dek.to_vec(),
+    salt: vec![],
+  }
+  .split_etm()
+}
+
+fn wrap_dek(dek: &[u8], secret: &str, kdf: &KdfParams, wrap: &KeyWrapAlgorithm) -> Result<WrappedKey> {
+  let kek = BinaryKey::try_kek_from_kdf_params(secret, kdf)?;
+  Ok(WrappedKey {
+    alg: wrap.to_string(),
+    key: wrap.wrap(&kek.key, dek)?,
+  })
+}
+
+/// Object posted by the original sample-03. The key is derived by PBKDF2-SHA256 with 2048 iterations.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Sample03Object {
+  pub data: String,
+  pub iv: String,
+  pub salt: String,
+  /// Absent for AES-CBC
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub alg: Option<String>,
+}
+
+impl TryFrom<Sample03Object> for Envelope {
+  type Error = anyhow::Error;
+  fn try_from(object: Sample03Object) -> Result<Self> {
+    let cipher = match &object.alg {
+      Some(alg) => alg.parse()?,
+      None => Algorithm::AesCbc,
+    };
+    let kdf = KdfParams::new_pbkdf2(&decode_base64(&object.salt)?, &KdfHash::Sha256, ITERATION);
+    let encrypted = Encrypted {
+      data: decode_base64(&object.data)?,
+      iv: decode_base64(&object.iv)?,
+    };
+    let envelope = Envelope::new(&cipher, kdf, None, encrypted, None);
+    envelope.validate()?;
+    Ok(envelope)
+  }
+}
+
+impl TryFrom<&Envelope> for Sample03Object {
+  type Error = anyhow::Error;
+  fn try_from(envelope: &Envelope) -> Result<Self> {
+    let kdf = &envelope.kdf;
+    ensure!(
+      kdf.algorithm()? == KdfAlgorithm::Pbkdf2
+        && kdf.hash()? == KdfHash::Sha256
+        && kdf.iterationCount == Some(ITERATION as usize),
+      "Sample-03 objects take only PBKDF2-SHA256 with {ITERATION} iterations"
+    );
+    ensure!(
+      envelope.mac.is_none() && envelope.ka.is_none() && envelope.kw.is_none(),
+      "Sample-03 objects take no MAC, key agreement or wrapped key"
+    );
+    let cipher = envelope.cipher()?;
+    Ok(Self {
+      data: encode_base64(&envelope.data),
+      iv: encode_base64(&envelope.iv),
+      salt: kdf.salt.clone(),
+      alg: (cipher != Algorithm::AesCbc).then(|| cipher.to_string()),
+    })
+  }
+}
+
+/// Object posted by sample-04, always encrypted by AES-CBC
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Sample04Object {
+  pub data: String,
+  pub iv: String,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub mac: Option<String>,
+  pub kdfParams: KdfParams,
+}
+
+impl TryFrom<Sample04Object> for Envelope {
+  type Error = anyhow::Error;
+  fn try_from(object: Sample04Object) -> Result<Self> {
+    let encrypted = Encrypted {
+      data: decode_base64(&object.data)?,
+      iv: decode_base64(&object.iv)?,
+    };
+    let mac = object.mac.as_deref().map(decode_base64).transpose()?;
+    let envelope = Envelope::new(&Algorithm::AesCbc, object.kdfParams, None, encrypted, mac);
+    envelope.validate()?;
+    Ok(envelope)
+  }
+}
+
+impl TryFrom<&Envelope> for Sample04Object {
+  type Error = anyhow::Error;
+  fn try_from(envelope: &Envelope) -> Result<Self> {
+    ensure!(
+      envelope.cipher()? == Algorithm::AesCbc && envelope.ka.is_none() && envelope.kw.is_none(),
+      "Sample-04 objects take only AES-CBC without key agreement or wrapped key"
+    );
+    Ok(Self {
+      data: encode_base64(&envelope.data),
+      iv: encode_base64(&envelope.iv),
+      mac: envelope.mac.as_deref().map(encode_base64),
+      kdfParams: envelope.kdf.clone(),
+    })
+  }
+}
+
+/// Msgpack pack of the original sample-05, with binary fields in hex.
+/// The key is expanded from the ECDH shared bits by HKDF with an empty `info`, and the data is encrypted by AES-CBC.
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Sample05Pack {
+  pub encrypted: Sample05Encrypted,
+  pub kdfParams: Sample05KdfParams,
+  /// Ephemeral public key in SPKI DER for ECIES, whose `info` binds both public keys instead
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub epk: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Sample05Encrypted {
+  pub data: String,
+  pub iv: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Sample05KdfParams {
+  pub salt: String,
+  pub hash: String,
+}
+
+impl Sample05Pack {
+  pub fn to_msgpack(&self) -> Result<Vec<u8>> {
+    Ok(rmp_serde::to_vec_named(self)?)
+  }
+}
+
+impl TryFrom<Sample05Pack> for Envelope {
+  type Error = anyhow::Error;
+  fn try_from(pack: Sample05Pack) -> Result<Self> {
+    let hash = pack.kdfParams.hash.parse::<KdfHash>()?;
+    let kdf = KdfParams::new_hkdf(&hex::decode(&pack.kdfParams.salt)?, &hash, None);
+    let encrypted = Encrypted {
+      data: hex::decode(&pack.encrypted.data)?,
+      iv: hex::decode(&pack.encrypted.iv)?,
+    };
+    let ka = match &pack.epk {
+      Some(epk) => {
+        let epk = hex::decode(epk)?;
+        KeyAgreement::ecies(&import_spki_der(&epk)?.to_string(), &epk)
+      }
+      None => KeyAgreement::ecdh(None),
+    };
+    let envelope = Envelope::new(&Algorithm::AesCbc, kdf, Some(ka), encrypted, None);
+    envelope.validate()?;
+    Ok(envelope)
+  }
+}
+
+impl TryFrom<&Envelope> for Sample05Pack {
+  type Error = anyhow::Error;
+  fn try_from(envelope: &Envelope) -> Result<Self> {
+    ensure!(
+      envelope.cipher()? == Algorithm::AesCbc
+        && envelope.mac.is_none()
+        && envelope.kw.is_none()
+        && envelope.kdf.algorithm()? == KdfAlgorithm::Hkdf
+        && envelope.kdf.info.is_none(),
+      "Sample-05 packs take only AES-CBC with a key by HKDF without info"
+    );
+    Ok(Self {
+      encrypted: Sample05Encrypted {
+        data: hex::encode(&envelope.data),
+        iv: hex::encode(&envelope.iv),
+      },
+      kdfParams: Sample05KdfParams {
+        salt: hex::encode(envelope.kdf.salt()?),
+        hash: envelope.kdf.hash()?.to_string(),
+      },
+      epk: envelope.ka.as_ref().and_then(|ka| ka.epk.as_ref()).map(hex::encode),
+    })
+  }
+}
+
+fn decode_base64(s: &str) -> Result<Vec<u8>> {
+  Ok(general_purpose::STANDARD.decode(s)?)
+}
+
+fn encode_base64(b: &[u8]) -> String {
+  general_purpose::STANDARD.encode(b)
+}
+
+/// Base64 string in human-readable formats, and native byte string otherwise
+mod bytes {
+  use base64::{engine::general_purpose, Engine as _};
+  use serde::{de, Deserialize, Deserializer, Serializer};
+
+  pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    if serializer.is_human_readable() {
+      serializer.serialize_str(&general_purpose::STANDARD.encode(bytes))
+    } else {
+      serializer.serialize_bytes(bytes)
+    }
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    if deserializer.is_human_readable() {
+      let s = String::deserialize(deserializer)?;
+      general_purpose::STANDARD.decode(s).map_err(de::Error::custom)
+    } else {
+      deserializer.deserialize_byte_buf(BytesVisitor)
+    }
+  }
+
+  struct BytesVisitor;
+
+  impl de::Visitor<'_> for BytesVisitor {
+    type Value = Vec<u8>;
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      f.write_str("a byte string")
+    }
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+      Ok(v.to_vec())
+    }
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+      Ok(v)
+    }
+  }
+}
+
+mod opt_bytes {
+  use serde::{Deserializer, Serializer};
+
+  pub fn serialize<S: Serializer>(bytes: &Option<Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error> {
+    match bytes {
+      Some(bytes) => super::bytes::serialize(bytes, serializer),
+      None => serializer.serialize_none(),
+    }
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error> {
+    super::bytes::deserialize(deserializer).map(Some)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::kdf::{random_salt, HkdfInfo};
+  use serde_json::json;
+
+  const DATA: &[u8] = b"hello my super secret world!!!";
+
+  fn sample_envelopes() -> Result<Vec<Envelope>> {
+    let pbkdf2 = KdfParams::new_pbkdf2(&random_salt(), &KdfHash::Sha256, ITERATION);
+    let master = general_purpose::STANDARD.encode([0x42u8; 32]);
+    let info = HkdfInfo::try_new("notes", 1)?;
+    let hkdf = KdfParams::new_hkdf(&random_salt(), &KdfHash::Sha384, Some(&info));
+    Ok(vec![
+      Envelope::seal(DATA, "password", &pbkdf2, &Algorithm::AesCbc, false)?,
+      Envelope::seal(DATA, "password", &pbkdf2, &Algorithm::AesGcm, false)?,
+      Envelope::seal(DATA, &master, &hkdf, &Algorithm::AesCbc, true)?,
+    ])
+  }
+
+  #[test]
+  fn envelope_round_trip() -> Result<()> {
+    for envelope in sample_envelopes()? {
+      for encoding in [Encoding::Json, Encoding::Msgpack, Encoding::Cbor] {
+        let encoded = envelope.encode(encoding)?;
+        assert_eq!(Envelope::decode(&encoded, encoding)?, envelope);
+        assert_eq!(Envelope::decode_any(&encoded)?, envelope);
+      }
+    }
+    let [cbc, gcm, etm] = <[Envelope; 3]>::try_from(sample_envelopes()?).unwrap();
+    assert_eq!(cbc.open("password")?, DATA);
+    assert_eq!(gcm.open("password")?, DATA);
+    assert!(gcm.open("wrong password").is_err());
+    assert_eq!(etm.open(&general_purpose::STANDARD.encode([0x42u8; 32]))?, DATA);
+    assert!(Envelope::seal(DATA, "password", &cbc.kdf, &Algorithm::AesGcm, true).is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn envelope_is_strictly_validated() -> Result<()> {
+    let envelope = sample_envelopes()?.remove(0);
+    let json = envelope.to_json_value()?;
+
+    let mut unknown = json.clone();
+    unknown["extra"] = json!(1);
+    assert!(Envelope::from_json_value(&unknown).is_err());
+    let mut unknown = json.clone();
+    unknown["kdf"]["extra"] = json!(1);
+    assert!(Envelope::from_json_value(&unknown).is_err());
+    let mut version = json.clone();
+    version["v"] = json!(2);
+    assert!(Envelope::from_json_value(&version).is_err());
+
+    let mut bad_iv = envelope.clone();
+    bad_iv.iv.pop();
+    assert!(Envelope::decode(&bad_iv.encode(Encoding::Cbor)?, Encoding::Cbor).is_err());
+    let mut gcm_mac = sample_envelopes()?.remove(1);
+    gcm_mac.mac = Some(vec![0u8; 32]);
+    assert!(Envelope::decode(&gcm_mac.encode(Encoding::Msgpack)?, Encoding::Msgpack).is_err());
+    let mut mixed_kdf = envelope.clone();
+    mixed_kdf.kdf.n = Some(1024);
+    assert!(mixed_kdf.validate().is_err());
+    let mut ka = envelope.clone();
+    ka.ka = Some(KeyAgreement::ecdh(Some("P-256")));
+    assert!(ka.validate().is_err());
+
+    for encoding in [Encoding::Json, Encoding::Msgpack, Encoding::Cbor] {
+      let mut encoded = envelope.encode(encoding)?;
+      encoded.push(0);
+      assert!(Envelope::decode(&encoded, encoding).is_err());
+    }
+    Ok(())
+  }
+
+  #[test]
+  fn wrapped_data_key_is_rewrapped_without_touching_data() -> Result<()> {
+    let master = general_purpose::STANDARD.encode([0x42u8; 32]);
+    let new_master = general_purpose::STANDARD.encode([0x43u8; 32]);
+    let info = HkdfInfo::try_new("notes", 1)?;
+    let hkdf = KdfParams::new_hkdf(&random_salt(), &KdfHash::Sha256, Some(&info));
+    let pbkdf2 = KdfParams::new_pbkdf2(&random_salt(), &KdfHash::Sha256, ITERATION);
+    let etm = Envelope::seal_wrapped(DATA, &master, &hkdf, &Algorithm::AesCbc, true, &KeyWrapAlgorithm::AesKw)?;
+    let gcm = Envelope::seal_wrapped(
+      DATA,
+      "password",
+      &pbkdf2,
+      &Algorithm::AesGcm,
+      false,
+      &KeyWrapAlgorithm::AesKwp,
+    )?;
+    for encoding in [Encoding::Json, Encoding::Msgpack, Encoding::Cbor] {
+      assert_eq!(Envelope::decode(&etm.encode(encoding)?, encoding)?, etm);
+    }
+    assert_eq!(etm.open(&master)?, DATA);
+    assert_eq!(gcm.open("password")?, DATA);
+    assert!(etm.open(&new_master).is_err());
+    assert!(Sample04Object::try_from(&etm).is_err());
+
+    // The data key differs from the KEK, which is bound to its own purpose by the HKDF info
+    let kek = BinaryKey::try_kek_from_kdf_params(&master, &hkdf)?;
+    let enc_key = BinaryKey::try_from_kdf_params(&master, KEY_LEN, &hkdf)?;
+    assert_ne!(kek.key, enc_key.key);
+    assert_ne!(etm.unwrap_dek(&master)?.as_slice(), kek.key.as_slice());
+    let (data, derived) = etm.open_with_derived_key(&master)?;
+    assert_eq!((data.as_slice(), &derived.key), (DATA, &kek.key));
+
+    let kdf = KdfParams::new_hkdf(&random_salt(), &KdfHash::Sha256, Some(&HkdfInfo::try_new("notes", 2)?));
+    let rewrapped = etm.rewrap(&master, &new_master, &kdf)?;
+    assert_eq!(
+      (&rewrapped.iv, &rewrapped.data, &rewrapped.mac),
+      (&etm.iv, &etm.data, &etm.mac)
+    );
+    assert_ne!(rewrapped.kw, etm.kw);
+    assert_eq!(rewrapped.open(&new_master)?, DATA);
+    assert!(rewrapped.open(&master).is_err());
+    assert!(etm.rewrap(&new_master, &master, &kdf).is_err());
+    assert!(sample_envelopes()?[0]
+      .rewrap("password", "new password", &pbkdf2)
+      .is_err());
+
+    let mut truncated = etm.clone();
+    truncated.kw.as_mut().unwrap().key.pop();
+    assert!(truncated.validate().is_err());
+    let mut unknown = etm.clone();
+    unknown.kw.as_mut().unwrap().alg = "AES-SIV".to_string();
+    assert!(unknown.validate().is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn ecies_needs_only_the_recipient_private_key() -> Result<()> {
+    for curve in ["P-256", "P-384", "P-521", "secp256k1"] {
+      let recipient = EccKeyPairType::new(curve)?;
+      let public = import_spki_der(&recipient.to_spki_public_der()?)?;
+      for cipher in [Algorithm::AesCbc, Algorithm::AesGcm] {
+        let envelope = Envelope::seal_ecies(DATA, &public, &cipher)?;
+        for encoding in [Encoding::Json, Encoding::Msgpack, Encoding::Cbor] {
+          assert_eq!(Envelope::decode_any(&envelope.encode(encoding)?)?, envelope);
+        }
+        assert_eq!(envelope.open_ecies(&recipient)?, DATA);
+        assert!(envelope.open("").is_err());
+      }
+      let envelope = Envelope::seal_ecies(DATA, &public, &Algorithm::AesCbc)?;
+      let pack = Sample05Pack::try_from(&envelope)?;
+      assert_eq!(Envelope::decode_any(&pack.to_msgpack()?)?, envelope);
+
+      let gcm = Envelope::seal_ecies(DATA, &public, &Algorithm::AesGcm)?;
+      assert!(gcm.open_ecies(&EccKeyPairType::new(curve)?).is_err());
+      // The ephemeral key is bound into the info, so that it cannot be swapped
+      let mut swapped = gcm.clone();
+      swapped.ka.as_mut().unwrap().epk = Some(EccKeyPairType::new(curve)?.to_spki_public_der()?);
+      assert!(swapped.open_ecies(&recipient).is_err());
+    }
+    assert!(
+      Envelope::seal_ecies(DATA, &EccKeyPairType::new("P-256")?, &Algorithm::AesGcm)?
+        .open_ecies(&EccKeyPairType::new("P-384")?)
+        .is_err()
+    );
+
+    let envelope = Envelope::seal_ecies(DATA, &EccKeyPairType::new("P-256")?, &Algorithm::AesGcm)?;
+    let mut no_epk = envelope.clone();
+    no_epk.ka.as_mut().unwrap().epk = None;
+    assert!(no_epk.validate().is_err());
+    let mut ecdh_epk = envelope.clone();
+    ecdh_epk.ka.as_mut().unwrap().alg = "ECDH".to_string();
+    assert!(ecdh_epk.validate().is_err());
+    let mut wrong_curve = envelope.clone();
+    wrong_curve.ka.as_mut().unwrap().curve = Some("P-384".to_string());
+    assert!(wrong_curve.validate().is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn upgrade_sample03_object() -> Result<()> {
+    // The vector of the original sample-03
+    let record = json!({
+      "id": 1,
+      "data": "EoeSsv5BFr6s1jZh3iMM1Pxa+wA4UxQnM30J2027kJU=",
+      "iv": "zuwTPW7nrWon6nEhyrzzxA==",
+      "salt": "jbfL016yS9RUb8Sf+6m+Pm2L1Io7u1SpqHsr+R6RTu4=",
+    });
+    let envelope = Envelope::upgrade_json(&record)?;
+    assert_eq!(envelope.cipher()?, Algorithm::AesCbc);
+    assert_eq!(envelope.open("my secret key")?, DATA);
+
+    let object = Sample03Object::try_from(&envelope)?;
+    let mut record = record;
+    record.as_object_mut().unwrap().remove("id");
+    assert_eq!(serde_json::to_value(object)?, record);
+    assert!(Sample03Object::try_from(&sample_envelopes()?.remove(2)).is_err());
+    assert!(Envelope::upgrade_json(&json!({"id": 2, "data": "plain"})).is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn upgrade_sample04_object() -> Result<()> {
+    let etm = sample_envelopes()?.remove(2);
+    let record = serde_json::to_value(Sample04Object::try_from(&etm)?)?;
+    assert!(record.get("mac").is_some());
+    assert_eq!(Envelope::upgrade_json(&record)?, etm);
+    assert!(Sample04Object::try_from(&sample_envelopes()?.remove(1)).is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn upgrade_sample05_pack() -> Result<()> {
+    // The vector of the original sample-05, whose key is expanded from the shared bits by HKDF
+    let pack = Sample05Pack {
+      encrypted: Sample05Encrypted {
+        data: "ea581e08b09f990ea4b68cb5fc119e773fb4103399cb15c6f5991b50daafe6e0".to_string(),
+        iv: "ceec133d6ee7ad6a27ea7121cabcf3c4".to_string(),
+      },
+      kdfParams: Sample05KdfParams {
+        salt: "8db7cbd35eb24bd4546fc49ffba9be3e6d8bd48a3bbb54a9a87b2bf91e914eee".to_string(),
+        hash: "SHA-256".to_string(),
+      },
+      epk: None,
+    };
+    let envelope = Envelope::decode_any(&pack.to_msgpack()?)?;
+    assert_eq!(envelope.ka, Some(KeyAgreement::ecdh(None)));
+    assert_eq!(
+      envelope.open(&general_purpose::STANDARD.encode(b"my secret key"))?,
+      DATA
+    );
+    assert_eq!(Sample05Pack::try_from(&envelope)?, pack);
+    assert!(Envelope::decode_any(b"not an envelope").is_err());
+    Ok(())
+  }
+}
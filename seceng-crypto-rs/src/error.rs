@@ -0,0 +1,22 @@
+pub use anyhow::{anyhow, bail, ensure, Context, Result};
+use std::fmt::Display;
+
+/// Errors that must be distinguishable from generic decryption failures
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CryptoError {
+  /// MAC tag mismatch, i.e., the record has been tampered with or the key is wrong
+  InvalidMac,
+}
+
+impl Display for CryptoError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      CryptoError::InvalidMac => write!(
+        f,
+        "MAC verification failed: data has been tampered with or key is wrong"
+      ),
+    }
+  }
+}
+
+impl std::error::Error for CryptoError {}
@@ -6,8 +6,81 @@ use digest::{
   Digest, HashMarker, Mac,
 };
 use hmac::Hmac;
+use sha2::{Sha256, Sha384, Sha512};
+use sha3::{Sha3_256, Sha3_384, Sha3_512};
+use std::{fmt::Display, str::FromStr};
+use subtle::ConstantTimeEq;
 use typenum::{Le, NonZero, U256};
 
+/// Hash function of SHA-2 and SHA-3 families, named like 'SHA-256' and 'SHA3-256'
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HashAlgorithm {
+  #[default]
+  Sha256,
+  Sha384,
+  Sha512,
+  Sha3_256,
+  Sha3_384,
+  Sha3_512,
+}
+
+impl FromStr for HashAlgorithm {
+  type Err = anyhow::Error;
+  fn from_str(s: &str) -> Result<Self> {
+    match s {
+      "SHA-256" => Ok(HashAlgorithm::Sha256),
+      "SHA-384" => Ok(HashAlgorithm::Sha384),
+      "SHA-512" => Ok(HashAlgorithm::Sha512),
+      "SHA3-256" => Ok(HashAlgorithm::Sha3_256),
+      "SHA3-384" => Ok(HashAlgorithm::Sha3_384),
+      "SHA3-512" => Ok(HashAlgorithm::Sha3_512),
+      _ => bail!("Unsupported hash algorithm: {}", s),
+    }
+  }
+}
+
+impl Display for HashAlgorithm {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      HashAlgorithm::Sha256 => write!(f, "SHA-256"),
+      HashAlgorithm::Sha384 => write!(f, "SHA-384"),
+      HashAlgorithm::Sha512 => write!(f, "SHA-512"),
+      HashAlgorithm::Sha3_256 => write!(f, "SHA3-256"),
+      HashAlgorithm::Sha3_384 => write!(f, "SHA3-384"),
+      HashAlgorithm::Sha3_512 => write!(f, "SHA3-512"),
+    }
+  }
+}
+
+impl HashAlgorithm {
+  pub fn hash(&self, data: &[u8]) -> Vec<u8> {
+    match self {
+      HashAlgorithm::Sha256 => generate_hash::<Sha256>(data),
+      HashAlgorithm::Sha384 => generate_hash::<Sha384>(data),
+      HashAlgorithm::Sha512 => generate_hash::<Sha512>(data),
+      HashAlgorithm::Sha3_256 => generate_hash::<Sha3_256>(data),
+      HashAlgorithm::Sha3_384 => generate_hash::<Sha3_384>(data),
+      HashAlgorithm::Sha3_512 => generate_hash::<Sha3_512>(data),
+    }
+  }
+
+  pub fn hmac(&self, data: &[u8], key: &[u8]) -> Result<Vec<u8>> {
+    match self {
+      HashAlgorithm::Sha256 => generate_hmac::<Sha256>(data, key),
+      HashAlgorithm::Sha384 => generate_hmac::<Sha384>(data, key),
+      HashAlgorithm::Sha512 => generate_hmac::<Sha512>(data, key),
+      HashAlgorithm::Sha3_256 => generate_hmac::<Sha3_256>(data, key),
+      HashAlgorithm::Sha3_384 => generate_hmac::<Sha3_384>(data, key),
+      HashAlgorithm::Sha3_512 => generate_hmac::<Sha3_512>(data, key),
+    }
+  }
+
+  /// Verify an HMAC tag in constant time
+  pub fn verify_hmac(&self, data: &[u8], key: &[u8], mac: &[u8]) -> Result<bool> {
+    Ok(bool::from(self.hmac(data, key)?.ct_eq(mac)))
+  }
+}
+
 pub fn generate_hash<D>(data: &[u8]) -> Vec<u8>
 where
   D: Digest,
@@ -36,8 +109,6 @@ where
 mod tests {
   use super::*;
   use hex_literal::hex;
-  use sha2::{Sha256, Sha384, Sha512};
-  use sha3::{Sha3_256, Sha3_384, Sha3_512};
 
   #[test]
   fn test_generate_hash() {
@@ -93,4 +164,23 @@ mod tests {
     );
     assert_eq!(generate_hmac::<Sha3_512>(data, key).unwrap(), hex!("6379a3fdebee97d298ba4a1ac63379e81e90b70277ec2770c48f841777789bee5c1f49c33812af4ac5d478413e5c0ffe89dabbea5f46c9f3acdb8952992b9202"));
   }
+
+  #[test]
+  fn test_hash_algorithm_dispatch() -> Result<()> {
+    let data = "abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq".as_bytes();
+    let key = "luchse sind halt tolle katzen".as_bytes();
+    for name in ["SHA-256", "SHA-384", "SHA-512", "SHA3-256", "SHA3-384", "SHA3-512"] {
+      let alg = name.parse::<HashAlgorithm>()?;
+      assert_eq!(alg.to_string(), name);
+      let mac = alg.hmac(data, key)?;
+      assert!(alg.verify_hmac(data, key, &mac)?);
+      assert!(!alg.verify_hmac(data, key, &mac[1..])?);
+    }
+    assert_eq!(
+      HashAlgorithm::Sha3_256.hash(data),
+      hex!("41c0dba2a9d6240849100376a8235e2c82e1b9998a999e21db32dd97496d3376")
+    );
+    assert!("MD5".parse::<HashAlgorithm>().is_err());
+    Ok(())
+  }
 }
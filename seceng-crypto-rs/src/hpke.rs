@@ -0,0 +1,814 @@
+//! Hybrid Public Key Encryption (RFC 9180) with DHKEM(P-256, HKDF-SHA256) and DHKEM(P-384, HKDF-SHA384),
+//! built on the ECDH of [`EccKeyPairType`]. The mode is determined by the inputs, i.e., a pre-shared key
+//! for the PSK modes and the sender's key pair for the authenticated modes.
+
+use crate::{ecc::EccKeyPairType, error::*};
+use aes_gcm::{
+  aead::{Aead as _, Payload},
+  Aes128Gcm, Aes256Gcm, KeyInit,
+};
+use chacha20poly1305::ChaCha20Poly1305;
+use hkdf::Hkdf;
+use sha2::{Sha256, Sha384, Sha512};
+use std::{fmt::Display, str::FromStr};
+use zeroize::Zeroizing;
+
+const VERSION_LABEL: &[u8] = b"HPKE-v1";
+/// Nonce length of all the AEADs
+const NONCE_LEN: usize = 12;
+/// Minimum length of a pre-shared key, which must have 32 bytes of entropy
+const MIN_PSK_LEN: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+  Base,
+  Psk,
+  Auth,
+  AuthPsk,
+}
+
+impl Display for Mode {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Mode::Base => write!(f, "Base"),
+      Mode::Psk => write!(f, "PSK"),
+      Mode::Auth => write!(f, "Auth"),
+      Mode::AuthPsk => write!(f, "AuthPSK"),
+    }
+  }
+}
+
+impl Mode {
+  fn new(psk: bool, auth: bool) -> Self {
+    match (psk, auth) {
+      (false, false) => Mode::Base,
+      (true, false) => Mode::Psk,
+      (false, true) => Mode::Auth,
+      (true, true) => Mode::AuthPsk,
+    }
+  }
+
+  fn id(&self) -> u8 {
+    match self {
+      Mode::Base => 0,
+      Mode::Psk => 1,
+      Mode::Auth => 2,
+      Mode::AuthPsk => 3,
+    }
+  }
+}
+
+/// Key encapsulation mechanism, determined by the curve of the recipient's key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kem {
+  P256HkdfSha256,
+  P384HkdfSha384,
+}
+
+impl Display for Kem {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Kem::P256HkdfSha256 => write!(f, "DHKEM(P-256, HKDF-SHA256)"),
+      Kem::P384HkdfSha384 => write!(f, "DHKEM(P-384, HKDF-SHA384)"),
+    }
+  }
+}
+
+impl Kem {
+  pub fn for_key(key: &EccKeyPairType) -> Result<Self> {
+    match key {
+      EccKeyPairType::P256(_) => Ok(Kem::P256HkdfSha256),
+      EccKeyPairType::P384(_) => Ok(Kem::P384HkdfSha384),
+      _ => bail!("Unsupported curve for HPKE: {key}"),
+    }
+  }
+
+  pub fn id(&self) -> u16 {
+    match self {
+      Kem::P256HkdfSha256 => 0x0010,
+      Kem::P384HkdfSha384 => 0x0011,
+    }
+  }
+
+  pub fn curve(&self) -> &'static str {
+    match self {
+      Kem::P256HkdfSha256 => "P-256",
+      Kem::P384HkdfSha384 => "P-384",
+    }
+  }
+
+  /// Length of the encapsulated key, i.e., an uncompressed point
+  pub fn enc_len(&self) -> usize {
+    1 + 2 * self.secret_len()
+  }
+
+  /// KDF of the KEM, which is also a natural choice for the key schedule
+  pub fn kdf(&self) -> Kdf {
+    match self {
+      Kem::P256HkdfSha256 => Kdf::HkdfSha256,
+      Kem::P384HkdfSha384 => Kdf::HkdfSha384,
+    }
+  }
+
+  /// Length of the private key, the DH output and the shared secret, which are equal over these curves
+  fn secret_len(&self) -> usize {
+    match self {
+      Kem::P256HkdfSha256 => 32,
+      Kem::P384HkdfSha384 => 48,
+    }
+  }
+
+  fn suite_id(&self) -> Vec<u8> {
+    [b"KEM".as_slice(), &self.id().to_be_bytes()].concat()
+  }
+
+  /// Deterministic key pair from the input keying material of at least the length of the private key
+  pub fn derive_key_pair(&self, ikm: &[u8]) -> Result<EccKeyPairType> {
+    ensure!(ikm.len() >= self.secret_len(), "Input keying material is too short");
+    let (kdf, suite_id) = (self.kdf(), self.suite_id());
+    let prk = kdf.labeled_extract(&suite_id, b"", b"dkp_prk", ikm);
+    // Rejection sampling of the scalar, whose bitmask is 0xff for P-256 and P-384
+    for counter in 0..=u8::MAX {
+      let bytes = kdf.labeled_expand(&suite_id, &prk, b"candidate", &[counter], self.secret_len())?;
+      if let Ok(key) = EccKeyPairType::from_private_bytes(self.curve(), &bytes) {
+        return Ok(key);
+      }
+    }
+    bail!("Failed to derive a key pair")
+  }
+
+  fn extract_and_expand(&self, dh: &[u8], kem_context: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+    let (kdf, suite_id) = (self.kdf(), self.suite_id());
+    let prk = kdf.labeled_extract(&suite_id, b"", b"eae_prk", dh);
+    kdf.labeled_expand(&suite_id, &prk, b"shared_secret", kem_context, self.secret_len())
+  }
+
+  /// Shared secret and the encapsulated key, authenticated by the sender's key pair if given
+  fn encap(
+    &self,
+    pk_r: &EccKeyPairType,
+    ephemeral: &EccKeyPairType,
+    sender: Option<&EccKeyPairType>,
+  ) -> Result<(Zeroizing<Vec<u8>>, Vec<u8>)> {
+    let enc = ephemeral.to_sec1_public()?;
+    let mut dh = Zeroizing::new(ephemeral.derive_bits(pk_r)?);
+    let mut kem_context = [enc.as_slice(), &pk_r.to_sec1_public()?].concat();
+    if let Some(sender) = sender {
+      dh.extend(sender.derive_bits(pk_r)?);
+      kem_context.extend(sender.to_sec1_public()?);
+    }
+    Ok((self.extract_and_expand(&dh, &kem_context)?, enc))
+  }
+
+  fn decap(&self, enc: &[u8], sk_r: &EccKeyPairType, sender: Option<&EccKeyPairType>) -> Result<Zeroizing<Vec<u8>>> {
+    ensure!(enc.len() == self.enc_len(), "Invalid length of the encapsulated key");
+    let pk_e = EccKeyPairType::from_sec1_public(self.curve(), enc)?;
+    let mut dh = Zeroizing::new(sk_r.derive_bits(&pk_e)?);
+    let mut kem_context = [enc, &sk_r.to_sec1_public()?].concat();
+    if let Some(sender) = sender {
+      dh.extend(sk_r.derive_bits(sender)?);
+      kem_context.extend(sender.to_sec1_public()?);
+    }
+    self.extract_and_expand(&dh, &kem_context)
+  }
+}
+
+/// Key derivation function of the key schedule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Kdf {
+  #[default]
+  HkdfSha256,
+  HkdfSha384,
+  HkdfSha512,
+}
+
+impl FromStr for Kdf {
+  type Err = anyhow::Error;
+  fn from_str(s: &str) -> Result<Self> {
+    match s {
+      "HKDF-SHA256" => Ok(Kdf::HkdfSha256),
+      "HKDF-SHA384" => Ok(Kdf::HkdfSha384),
+      "HKDF-SHA512" => Ok(Kdf::HkdfSha512),
+      _ => bail!("Unsupported KDF for HPKE: {}", s),
+    }
+  }
+}
+
+impl Display for Kdf {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Kdf::HkdfSha256 => write!(f, "HKDF-SHA256"),
+      Kdf::HkdfSha384 => write!(f, "HKDF-SHA384"),
+      Kdf::HkdfSha512 => write!(f, "HKDF-SHA512"),
+    }
+  }
+}
+
+impl Kdf {
+  pub fn id(&self) -> u16 {
+    match self {
+      Kdf::HkdfSha256 => 0x0001,
+      Kdf::HkdfSha384 => 0x0002,
+      Kdf::HkdfSha512 => 0x0003,
+    }
+  }
+
+  fn hash_len(&self) -> usize {
+    match self {
+      Kdf::HkdfSha256 => 32,
+      Kdf::HkdfSha384 => 48,
+      Kdf::HkdfSha512 => 64,
+    }
+  }
+
+  fn labeled_extract(&self, suite_id: &[u8], salt: &[u8], label: &[u8], ikm: &[u8]) -> Zeroizing<Vec<u8>> {
+    let ikm = Zeroizing::new([VERSION_LABEL, suite_id, label, ikm].concat());
+    Zeroizing::new(match self {
+      Kdf::HkdfSha256 => Hkdf::<Sha256>::extract(Some(salt), &ikm).0.to_vec(),
+      Kdf::HkdfSha384 => Hkdf::<Sha384>::extract(Some(salt), &ikm).0.to_vec(),
+      Kdf::HkdfSha512 => Hkdf::<Sha512>::extract(Some(salt), &ikm).0.to_vec(),
+    })
+  }
+
+  fn labeled_expand(
+    &self,
+    suite_id: &[u8],
+    prk: &[u8],
+    label: &[u8],
+    info: &[u8],
+    len: usize,
+  ) -> Result<Zeroizing<Vec<u8>>> {
+    ensure!(len <= 255 * self.hash_len(), "Too long output of {self}");
+    let info = [&(len as u16).to_be_bytes(), VERSION_LABEL, suite_id, label, info].concat();
+    let mut okm = Zeroizing::new(vec![0u8; len]);
+    match self {
+      Kdf::HkdfSha256 => Hkdf::<Sha256>::from_prk(prk)
+        .map_err(|e| anyhow!(e))?
+        .expand(&info, &mut okm),
+      Kdf::HkdfSha384 => Hkdf::<Sha384>::from_prk(prk)
+        .map_err(|e| anyhow!(e))?
+        .expand(&info, &mut okm),
+      Kdf::HkdfSha512 => Hkdf::<Sha512>::from_prk(prk)
+        .map_err(|e| anyhow!(e))?
+        .expand(&info, &mut okm),
+    }
+    .map_err(|e| anyhow!(e))?;
+    Ok(okm)
+  }
+}
+
+/// AEAD of the context. With `ExportOnly`, the context only exports secrets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Aead {
+  Aes128Gcm,
+  #[default]
+  Aes256Gcm,
+  ChaCha20Poly1305,
+  ExportOnly,
+}
+
+impl FromStr for Aead {
+  type Err = anyhow::Error;
+  fn from_str(s: &str) -> Result<Self> {
+    match s {
+      "AES-128-GCM" => Ok(Aead::Aes128Gcm),
+      "AES-256-GCM" => Ok(Aead::Aes256Gcm),
+      "ChaCha20Poly1305" => Ok(Aead::ChaCha20Poly1305),
+      "Export-only" => Ok(Aead::ExportOnly),
+      _ => bail!("Unsupported AEAD for HPKE: {}", s),
+    }
+  }
+}
+
+impl Display for Aead {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Aead::Aes128Gcm => write!(f, "AES-128-GCM"),
+      Aead::Aes256Gcm => write!(f, "AES-256-GCM"),
+      Aead::ChaCha20Poly1305 => write!(f, "ChaCha20Poly1305"),
+      Aead::ExportOnly => write!(f, "Export-only"),
+    }
+  }
+}
+
+impl Aead {
+  pub fn id(&self) -> u16 {
+    match self {
+      Aead::Aes128Gcm => 0x0001,
+      Aead::Aes256Gcm => 0x0002,
+      Aead::ChaCha20Poly1305 => 0x0003,
+      Aead::ExportOnly => 0xffff,
+    }
+  }
+
+  fn key_len(&self) -> usize {
+    match self {
+      Aead::Aes128Gcm => 16,
+      Aead::Aes256Gcm | Aead::ChaCha20Poly1305 => 32,
+      Aead::ExportOnly => 0,
+    }
+  }
+
+  fn seal(&self, key: &[u8], nonce: &[u8; NONCE_LEN], aad: &[u8], pt: &[u8]) -> Result<Vec<u8>> {
+    let payload = Payload { msg: pt, aad };
+    match self {
+      Aead::Aes128Gcm => Aes128Gcm::new_from_slice(key)?
+        .encrypt(nonce.into(), payload)
+        .map_err(|e| anyhow!(e)),
+      Aead::Aes256Gcm => Aes256Gcm::new_from_slice(key)?
+        .encrypt(nonce.into(), payload)
+        .map_err(|e| anyhow!(e)),
+      Aead::ChaCha20Poly1305 => ChaCha20Poly1305::new_from_slice(key)?
+        .encrypt(nonce.into(), payload)
+        .map_err(|e| anyhow!(e)),
+      Aead::ExportOnly => bail!("{self} cannot seal"),
+    }
+  }
+
+  fn open(&self, key: &[u8], nonce: &[u8; NONCE_LEN], aad: &[u8], ct: &[u8]) -> Result<Vec<u8>> {
+    let payload = Payload { msg: ct, aad };
+    let opened = match self {
+      Aead::Aes128Gcm => Aes128Gcm::new_from_slice(key)?.decrypt(nonce.into(), payload).ok(),
+      Aead::Aes256Gcm => Aes256Gcm::new_from_slice(key)?.decrypt(nonce.into(), payload).ok(),
+      Aead::ChaCha20Poly1305 => ChaCha20Poly1305::new_from_slice(key)?
+        .decrypt(nonce.into(), payload)
+        .ok(),
+      Aead::ExportOnly => bail!("{self} cannot open"),
+    };
+    opened.ok_or_else(|| anyhow!("Failed to authenticate the encrypted data"))
+  }
+}
+
+/// Pre-shared key and its identifier for the PSK and auth-PSK modes
+#[derive(Debug, Clone, Copy)]
+pub struct Psk<'a> {
+  pub key: &'a [u8],
+  pub id: &'a [u8],
+}
+
+/// Cipher suite of the KEM, the KDF and the AEAD
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Suite {
+  pub kem: Kem,
+  pub kdf: Kdf,
+  pub aead: Aead,
+}
+
+impl Display for Suite {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}, {}, {}", self.kem, self.kdf, self.aead)
+  }
+}
+
+impl Suite {
+  pub const fn new(kem: Kem, kdf: Kdf, aead: Aead) -> Self {
+    Self { kem, kdf, aead }
+  }
+
+  fn id(&self) -> Vec<u8> {
+    [
+      b"HPKE".as_slice(),
+      &self.kem.id().to_be_bytes(),
+      &self.kdf.id().to_be_bytes(),
+      &self.aead.id().to_be_bytes(),
+    ]
+    .concat()
+  }
+
+  /// Encapsulated key and the context of the sender to the recipient's public key `pk_r`.
+  /// `sender` is the sender's key pair for the authenticated modes.
+  pub fn setup_sender(
+    &self,
+    pk_r: &EccKeyPairType,
+    info: &[u8],
+    psk: Option<&Psk>,
+    sender: Option<&EccKeyPairType>,
+  ) -> Result<(Vec<u8>, Context)> {
+    let ephemeral = EccKeyPairType::new(self.kem.curve())?;
+    self.setup_sender_with(pk_r, &ephemeral, info, psk, sender)
+  }
+
+  fn setup_sender_with(
+    &self,
+    pk_r: &EccKeyPairType,
+    ephemeral: &EccKeyPairType,
+    info: &[u8],
+    psk: Option<&Psk>,
+    sender: Option<&EccKeyPairType>,
+  ) -> Result<(Vec<u8>, Context)> {
+    self.ensure_curve(pk_r, sender)?;
+    let (shared_secret, enc) = self.kem.encap(pk_r, ephemeral, sender)?;
+    let context = self.key_schedule(Mode::new(psk.is_some(), sender.is_some()), &shared_secret, info, psk)?;
+    Ok((enc, context))
+  }
+
+  /// Context of the recipient with the private key `sk_r` for the encapsulated key `enc`.
+  /// `sender` is the sender's public key for the authenticated modes.
+  pub fn setup_receiver(
+    &self,
+    enc: &[u8],
+    sk_r: &EccKeyPairType,
+    info: &[u8],
+    psk: Option<&Psk>,
+    sender: Option<&EccKeyPairType>,
+  ) -> Result<Context> {
+    self.ensure_curve(sk_r, sender)?;
+    let shared_secret = self.kem.decap(enc, sk_r, sender)?;
+    self.key_schedule(Mode::new(psk.is_some(), sender.is_some()), &shared_secret, info, psk)
+  }
+
+  fn ensure_curve(&self, recipient: &EccKeyPairType, sender: Option<&EccKeyPairType>) -> Result<()> {
+    for key in std::iter::once(recipient).chain(sender) {
+      ensure!(Kem::for_key(key)? == self.kem, "Key over {key} for {}", self.kem);
+    }
+    Ok(())
+  }
+
+  fn key_schedule(&self, mode: Mode, shared_secret: &[u8], info: &[u8], psk: Option<&Psk>) -> Result<Context> {
+    let (key, id) = match psk {
+      Some(psk) => {
+        ensure!(psk.key.len() >= MIN_PSK_LEN, "PSK must be at least {MIN_PSK_LEN} bytes");
+        ensure!(!psk.id.is_empty(), "PSK ID must not be empty");
+        (psk.key, psk.id)
+      }
+      None => (b"".as_slice(), b"".as_slice()),
+    };
+    let (kdf, suite_id) = (self.kdf, self.id());
+    let psk_id_hash = kdf.labeled_extract(&suite_id, b"", b"psk_id_hash", id);
+    let info_hash = kdf.labeled_extract(&suite_id, b"", b"info_hash", info);
+    let context = [&[mode.id()], psk_id_hash.as_slice(), &info_hash].concat();
+    let secret = kdf.labeled_extract(&suite_id, shared_secret, b"secret", key);
+    Ok(Context {
+      mode,
+      suite: *self,
+      key: kdf.labeled_expand(&suite_id, &secret, b"key", &context, self.aead.key_len())?,
+      base_nonce: kdf
+        .labeled_expand(&suite_id, &secret, b"base_nonce", &context, NONCE_LEN)?
+        .as_slice()
+        .try_into()?,
+      exporter_secret: kdf.labeled_expand(&suite_id, &secret, b"exp", &context, kdf.hash_len())?,
+      seq: 0,
+    })
+  }
+}
+
+/// Encryption context of the sender or the recipient. Messages must be opened in the order of sealing.
+pub struct Context {
+  mode: Mode,
+  suite: Suite,
+  key: Zeroizing<Vec<u8>>,
+  base_nonce: [u8; NONCE_LEN],
+  exporter_secret: Zeroizing<Vec<u8>>,
+  seq: u64,
+}
+
+impl Context {
+  pub fn mode(&self) -> Mode {
+    self.mode
+  }
+
+  pub fn seal(&mut self, aad: &[u8], pt: &[u8]) -> Result<Vec<u8>> {
+    let ct = self.suite.aead.seal(&self.key, &self.nonce(), aad, pt)?;
+    self.seq = self
+      .seq
+      .checked_add(1)
+      .ok_or_else(|| anyhow!("Sequence number overflow"))?;
+    Ok(ct)
+  }
+
+  /// Open the next message. The sequence number is advanced only on success.
+  pub fn open(&mut self, aad: &[u8], ct: &[u8]) -> Result<Vec<u8>> {
+    let pt = self.suite.aead.open(&self.key, &self.nonce(), aad, ct)?;
+    self.seq = self
+      .seq
+      .checked_add(1)
+      .ok_or_else(|| anyhow!("Sequence number overflow"))?;
+    Ok(pt)
+  }
+
+  /// Secret of `len` bytes bound to the context and `exporter_context`
+  pub fn export(&self, exporter_context: &[u8], len: usize) -> Result<Zeroizing<Vec<u8>>> {
+    let kdf = self.suite.kdf;
+    kdf.labeled_expand(&self.suite.id(), &self.exporter_secret, b"sec", exporter_context, len)
+  }
+
+  fn nonce(&self) -> [u8; NONCE_LEN] {
+    let mut nonce = self.base_nonce;
+    for (n, s) in nonce[NONCE_LEN - 8..].iter_mut().zip(self.seq.to_be_bytes()) {
+      *n ^= s;
+    }
+    nonce
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const INFO: &[u8] = b"Ode on a Grecian Urn";
+  const PSK: Psk = Psk {
+    key: &hex_literal::hex!("0247fd33b913760fa1fa51e1892d9f307fbe65eb171e8132c2af18555a738b82"),
+    id: b"Ennyn Durin aran Moria",
+  };
+  const PT: &[u8] = b"Beauty is truth, truth beauty";
+  const EXPORTER_CONTEXTS: [&[u8]; 3] = [b"", b"\x00", b"TestContext"];
+
+  struct Vector {
+    suite: Suite,
+    mode: Mode,
+    ikm_e: &'static str,
+    ikm_r: &'static str,
+    ikm_s: Option<&'static str>,
+    pk_rm: &'static str,
+    enc: &'static str,
+    shared_secret: &'static str,
+    key: &'static str,
+    base_nonce: &'static str,
+    exporter_secret: &'static str,
+    /// Ciphertexts of `PT` with the AAD `Count-<seq>` by the sequence number
+    cts: &'static [(u64, &'static str)],
+    /// Exported secrets of 32 bytes for `EXPORTER_CONTEXTS`
+    exports: [&'static str; 3],
+  }
+
+  /// Appendix A.3.1-A.3.4 of RFC 9180, DHKEM(P-256, HKDF-SHA256), HKDF-SHA256, AES-128-GCM in the base, PSK, auth
+  /// and auth-PSK modes, and A.5.1 with ChaCha20Poly1305 in the base mode
+  const RFC9180_VECTORS: &[Vector] = &[
+    Vector {
+      suite: Suite::new(Kem::P256HkdfSha256, Kdf::HkdfSha256, Aead::Aes128Gcm),
+      mode: Mode::Base,
+      ikm_e: "4270e54ffd08d79d5928020af4686d8f6b7d35dbe470265f1f5aa22816ce860e",
+      ikm_r: "668b37171f1072f3cf12ea8a236a45df23fc13b82af3609ad1e354f6ef817550",
+      ikm_s: None,
+      pk_rm: "04fe8c19ce0905191ebc298a9245792531f26f0cece2460639e8bc39cb7f706a826a779b4cf969b8a0e539c7f62fb3d30ad6aa8f80e30f1d128aafd68a2ce72ea0",
+      enc: "04a92719c6195d5085104f469a8b9814d5838ff72b60501e2c4466e5e67b325ac98536d7b61a1af4b78e5b7f951c0900be863c403ce65c9bfcb9382657222d18c4",
+      shared_secret: "c0d26aeab536609a572b07695d933b589dcf363ff9d93c93adea537aeabb8cb8",
+      key: "868c066ef58aae6dc589b6cfdd18f97e",
+      base_nonce: "4e0bc5018beba4bf004cca59",
+      exporter_secret: "14ad94af484a7ad3ef40e9f3be99ecc6fa9036df9d4920548424df127ee0d99f",
+      cts: &[
+        (0, "5ad590bb8baa577f8619db35a36311226a896e7342a6d836d8b7bcd2f20b6c7f9076ac232e3ab2523f39513434"),
+        (1, "fa6f037b47fc21826b610172ca9637e82d6e5801eb31cbd3748271affd4ecb06646e0329cbdf3c3cd655b28e82"),
+        (255, "2ad71c85bf3f45c6eca301426289854b31448bcf8a8ccb1deef3ebd87f60848aa53c538c30a4dac71d619ee2cd"),
+        (256, "10f179686aa2caec1758c8e554513f16472bd0a11e2a907dde0b212cbe87d74f367f8ffe5e41cd3e9962a6afb2"),
+      ],
+      exports: [
+        "5e9bc3d236e1911d95e65b576a8a86d478fb827e8bdfe77b741b289890490d4d",
+        "6cff87658931bda83dc857e6353efe4987a201b849658d9b047aab4cf216e796",
+        "d8f1ea7942adbba7412c6d431c62d01371ea476b823eb697e1f6e6cae1dab85a",
+      ],
+    },
+    Vector {
+      suite: Suite::new(Kem::P256HkdfSha256, Kdf::HkdfSha256, Aead::Aes128Gcm),
+      mode: Mode::Psk,
+      ikm_e: "2afa611d8b1a7b321c761b483b6a053579afa4f767450d3ad0f84a39fda587a6",
+      ikm_r: "d42ef874c1913d9568c9405407c805baddaffd0898a00f1e84e154fa787b2429",
+      ikm_s: None,
+      pk_rm: "040d97419ae99f13007a93996648b2674e5260a8ebd2b822e84899cd52d87446ea394ca76223b76639eccdf00e1967db10ade37db4e7db476261fcc8df97c5ffd1",
+      enc: "04305d35563527bce037773d79a13deabed0e8e7cde61eecee403496959e89e4d0ca701726696d1485137ccb5341b3c1c7aaee90a4a02449725e744b1193b53b5f",
+      shared_secret: "2e783ad86a1beae03b5749e0f3f5e9bb19cb7eb382f2fb2dd64c99f15ae0661b",
+      key: "55d9eb9d26911d4c514a990fa8d57048",
+      base_nonce: "b595dc6b2d7e2ed23af529b1",
+      exporter_secret: "895a723a1eab809804973a53c0ee18ece29b25a7555a4808277ad2651d66d705",
+      cts: &[
+        (0, "90c4deb5b75318530194e4bb62f890b019b1397bbf9d0d6eb918890e1fb2be1ac2603193b60a49c2126b75d0eb"),
+        (1, "9e223384a3620f4a75b5a52f546b7262d8826dea18db5a365feb8b997180b22d72dc1287f7089a1073a7102c27"),
+        (255, "cdc541253111ed7a424eea5134dc14fc5e8293ab3b537668b8656789628e45894e5bb873c968e3b7cdcbb654a4"),
+        (256, "faf985208858b1253b97b60aecd28bc18737b58d1242370e7703ec33b73a4c31a1afee300e349adef9015bbbfd"),
+      ],
+      exports: [
+        "a115a59bf4dd8dc49332d6a0093af8efca1bcbfd3627d850173f5c4a55d0c185",
+        "4517eaede0669b16aac7c92d5762dd459c301fa10e02237cd5aeb9be969430c4",
+        "164e02144d44b607a7722e58b0f4156e67c0c2874d74cf71da6ca48a4cbdc5e0",
+      ],
+    },
+    Vector {
+      suite: Suite::new(Kem::P256HkdfSha256, Kdf::HkdfSha256, Aead::Aes128Gcm),
+      mode: Mode::Auth,
+      ikm_e: "798d82a8d9ea19dbc7f2c6dfa54e8a6706f7cdc119db0813dacf8440ab37c857",
+      ikm_r: "7bc93bde8890d1fb55220e7f3b0c107ae7e6eda35ca4040bb6651284bf0747ee",
+      ikm_s: Some("874baa0dcf93595a24a45a7f042e0d22d368747daaa7e19f80a802af19204ba8"),
+      pk_rm: "04423e363e1cd54ce7b7573110ac121399acbc9ed815fae03b72ffbd4c18b01836835c5a09513f28fc971b7266cfde2e96afe84bb0f266920e82c4f53b36e1a78d",
+      enc: "042224f3ea800f7ec55c03f29fc9865f6ee27004f818fcbdc6dc68932c1e52e15b79e264a98f2c535ef06745f3d308624414153b22c7332bc1e691cb4af4d53454",
+      shared_secret: "d4aea336439aadf68f9348880aa358086f1480e7c167b6ef15453ba69b94b44f",
+      key: "19aa8472b3fdc530392b0e54ca17c0f5",
+      base_nonce: "b390052d26b67a5b8a8fcaa4",
+      exporter_secret: "f152759972660eb0e1db880835abd5de1c39c8e9cd269f6f082ed80e28acb164",
+      cts: &[
+        (0, "82ffc8c44760db691a07c5627e5fc2c08e7a86979ee79b494a17cc3405446ac2bdb8f265db4a099ed3289ffe19"),
+        (1, "b0a705a54532c7b4f5907de51c13dffe1e08d55ee9ba59686114b05945494d96725b239468f1229e3966aa1250"),
+        (255, "4a319462eaedee37248b4d985f64f4f863d31913fe9e30b6e13136053b69fe5d70853c84c60a84bb5495d5a678"),
+        (256, "28e874512f8940fafc7d06135e7589f6b4198bc0f3a1c64702e72c9e6abaf9f05cb0d2f11b03a517898815c934"),
+      ],
+      exports: [
+        "837e49c3ff629250c8d80d3c3fb957725ed481e59e2feb57afd9fe9a8c7c4497",
+        "594213f9018d614b82007a7021c3135bda7b380da4acd9ab27165c508640dbda",
+        "14fe634f95ca0d86e15247cca7de7ba9b73c9b9deb6437e1c832daf7291b79d5",
+      ],
+    },
+    Vector {
+      suite: Suite::new(Kem::P256HkdfSha256, Kdf::HkdfSha256, Aead::Aes128Gcm),
+      mode: Mode::AuthPsk,
+      ikm_e: "3c1fceb477ec954c8d58ef3249e4bb4c38241b5925b95f7486e4d9f1d0d35fbb",
+      ikm_r: "abcc2da5b3fa81d8aabd91f7f800a8ccf60ec37b1b585a5d1d1ac77f258b6cca",
+      ikm_s: Some("6262031f040a9db853edd6f91d2272596eabbc78a2ed2bd643f770ecd0f19b82"),
+      pk_rm: "04d824d7e897897c172ac8a9e862e4bd820133b8d090a9b188b8233a64dfbc5f725aa0aa52c8462ab7c9188f1c4872f0c99087a867e8a773a13df48a627058e1b3",
+      enc: "046a1de3fc26a3d43f4e4ba97dbe24f7e99181136129c48fbe872d4743e2b131357ed4f29a7b317dc22509c7b00991ae990bf65f8b236700c82ab7c11a84511401",
+      shared_secret: "d4c27698391db126f1612d9e91a767f10b9b19aa17e1695549203f0df7d9aebe",
+      key: "4d567121d67fae1227d90e11585988fb",
+      base_nonce: "67c9d05330ca21e5116ecda6",
+      exporter_secret: "3f479020ae186788e4dfd4a42a21d24f3faabb224dd4f91c2b2e5e9524ca27b2",
+      cts: &[
+        (0, "b9f36d58d9eb101629a3e5a7b63d2ee4af42b3644209ab37e0a272d44365407db8e655c72e4fa46f4ff81b9246"),
+        (1, "51788c4e5d56276771032749d015d3eea651af0c7bb8e3da669effffed299ea1f641df621af65579c10fc09736"),
+        (255, "6de25ceadeaec572fbaa25eda2558b73c383fe55106abaec24d518ef6724a7ce698f83ecdc53e640fe214d2f42"),
+        (256, "f380e19d291e12c5e378b51feb5cd50f6d00df6cb2af8393794c4df342126c2e29633fe7e8ce49587531affd4d"),
+      ],
+      exports: [
+        "595ce0eff405d4b3bb1d08308d70a4e77226ce11766e0a94c4fdb5d90025c978",
+        "110472ee0ae328f57ef7332a9886a1992d2c45b9b8d5abc9424ff68630f7d38d",
+        "18ee4d001a9d83a4c67e76f88dd747766576cac438723bad0700a910a4d717e6",
+      ],
+    },
+    Vector {
+      suite: Suite::new(Kem::P256HkdfSha256, Kdf::HkdfSha256, Aead::ChaCha20Poly1305),
+      mode: Mode::Base,
+      ikm_e: "f1f1a3bc95416871539ecb51c3a8f0cf608afb40fbbe305c0a72819d35c33f1f",
+      ikm_r: "61092f3f56994dd424405899154a9918353e3e008171517ad576b900ddb275e7",
+      ikm_s: None,
+      pk_rm: "04a697bffde9405c992883c5c439d6cc358170b51af72812333b015621dc0f40bad9bb726f68a5c013806a790ec716ab8669f84f6b694596c2987cf35baba2a006",
+      enc: "04c07836a0206e04e31d8ae99bfd549380b072a1b1b82e563c935c095827824fc1559eac6fb9e3c70cd3193968994e7fe9781aa103f5b50e934b5b2f387e381291",
+      shared_secret: "806520f82ef0b03c823b7fc524b6b55a088f566b9751b89551c170f4113bd850",
+      key: "a8f45490a92a3b04d1dbf6cf2c3939ad8bfc9bfcb97c04bffe116730c9dfe3fc",
+      base_nonce: "726b4390ed2209809f58c693",
+      exporter_secret: "4f9bd9b3a8db7d7c3a5b9d44fdc1f6e37d5d77689ade5ec44a7242016e6aa205",
+      cts: &[
+        (0, "6469c41c5c81d3aa85432531ecf6460ec945bde1eb428cb2fedf7a29f5a685b4ccb0d057f03ea2952a27bb458b"),
+        (1, "f1564199f7e0e110ec9c1bcdde332177fc35c1adf6e57f8d1df24022227ffa8716862dbda2b1dc546c9d114374"),
+        (255, "8f2814a2c548b3be50259713c6724009e092d37789f6856553d61df23ebc079235f710e6af3c3ca6eaba7c7c6c"),
+        (256, "b45b69d419a9be7219d8c94365b89ad6951caf4576ea4774ea40e9b7047a09d6537d1aa2f7c12d6ae4b729b4d0"),
+      ],
+      exports: [
+        "9b13c510416ac977b553bf1741018809c246a695f45eff6d3b0356dbefe1e660",
+        "6c8b7be3a20a5684edecb4253619d9051ce8583baf850e0cb53c402bdcaf8ebb",
+        "477a50d804c7c51941f69b8e32fe8288386ee1a84905fe4938d58972f24ac938",
+      ],
+    },
+  ];
+
+  /// Not in RFC 9180, which has no vectors of DHKEM(P-384, HKDF-SHA384): the auth mode and the export-only AEAD
+  /// over P-384. Generated by this module, so they only guard against regressions.
+  const SUPPLEMENTARY_VECTORS: &[Vector] = &[
+    Vector {
+      suite: Suite::new(Kem::P384HkdfSha384, Kdf::HkdfSha384, Aead::Aes256Gcm),
+      mode: Mode::Auth,
+      ikm_e: "3c1fceb477ec954c8d58ef3249e4bb4c38241b5925b95f7486e4d9f1d0d35fbb4270e54ffd08d79d5928020af4686d8f6b7d35dbe470265f1f5aa22816ce860e",
+      ikm_r: "668b37171f1072f3cf12ea8a236a45df23fc13b82af3609ad1e354f6ef817550874baa0dcf93595a24a45a7f042e0d22d368747daaa7e19f80a802af19204ba8",
+      ikm_s: Some("874baa0dcf93595a24a45a7f042e0d22d368747daaa7e19f80a802af19204ba83c1fceb477ec954c8d58ef3249e4bb4c38241b5925b95f7486e4d9f1d0d35fbb"),
+      pk_rm: "0430e645e6444561d3a5c28cc854851fbe72f47030787f5604a86f2e1a95f788ca51dde5e6facb697ee6e75e9e51d8138bb5911233d68e1d4534380cf015da6a549e73c3a0594e8d81148d820b14e1f02091121f18e956d0d120953db725137d73",
+      enc: "04c05e22dec827897650ad203764cd0d7db0dc3bec7b2501b2dc4b6202d8a72506d4c5644c1e997aa6215ec5c543605a77c4379e36cf8d3f22bfa05bc528b6953c921071f766e8d9c24e5eea09f41131498762d2cea4ae00812cfc7e78afe1c329",
+      shared_secret: "2a6979eadd8a2ca68dd09b1db87178398121b4a86f16f8a7bc19ccc824f9c11e6eeb0d9c368b4512266d104af12fff1d",
+      key: "6b72255b16c71ebef03626deaf12b9fb0625de761c235dc9796cf67030f812d6",
+      base_nonce: "66cc5c327aa1df5b98620668",
+      exporter_secret: "5c8218cebfb9c332aabf1deb56b2c3fb5a25711301c1533d50c8765329122e5c5f77a6143622f32b6607d0583cc62a04",
+      cts: &[
+        (0, "23bae2a51b1df0da1725b9ed6cf2c1445090ac571b185d28d662d7ada54ab2f940d633ba4fe2ef181293c5e574"),
+        (1, "4beb1dc9ba8e83a632ca96fa05eea3b49b668ca0baa6e7328197277cfe15ee3d6b0d90c466e777d5cb26df1491"),
+        (255, "50c9d3a6be324579c98d56a8213a833bdcab85e0925ad20a29c3af14c344e0fb204e3a7ea095e29d93c0314454"),
+        (256, "6c52bebcda9da12f048c3e7130acaf09d99f0888ce093299af839a11d36f4fe4d74c48c75b9916485c48abd621"),
+      ],
+      exports: [
+        "381dff981cf4dd75b909ac7622fb59913bd5a5a63974e17070635a9530ee3ff7",
+        "24d1eb2385d788e6682ebf5d58f523c62790d3701dcfaaf57496cbbb275369f1",
+        "69b1b0933f115424402818e1761cf626f29ea4a23b4d1ff20906170e52e978d2",
+      ],
+    },
+    Vector {
+      suite: Suite::new(Kem::P384HkdfSha384, Kdf::HkdfSha512, Aead::ExportOnly),
+      mode: Mode::Psk,
+      ikm_e: "4270e54ffd08d79d5928020af4686d8f6b7d35dbe470265f1f5aa22816ce860e3c1fceb477ec954c8d58ef3249e4bb4c38241b5925b95f7486e4d9f1d0d35fbb",
+      ikm_r: "668b37171f1072f3cf12ea8a236a45df23fc13b82af3609ad1e354f6ef817550874baa0dcf93595a24a45a7f042e0d22d368747daaa7e19f80a802af19204ba8",
+      ikm_s: None,
+      pk_rm: "0430e645e6444561d3a5c28cc854851fbe72f47030787f5604a86f2e1a95f788ca51dde5e6facb697ee6e75e9e51d8138bb5911233d68e1d4534380cf015da6a549e73c3a0594e8d81148d820b14e1f02091121f18e956d0d120953db725137d73",
+      enc: "044814511d1fe07aeea2686290a4b3c37a1f1ae8fde3834f7c52b441d7b98f542a0f04278ca7e0dd11c448c359dbf0ad1185e962b8d3346945ec823f8cc25fa13992b89e75fca59ea73322e71cf31b54ef37b063dffd001ccba246a793e79c173d",
+      shared_secret: "0fe4df279cff01e6e231d7e7d272fdeb1195344e5dc72884fd5d27ba9b57fa534f5f74f868242983c69a52a857206b3a",
+      key: "",
+      base_nonce: "e7057c05dc29cff1cf0ed8aa",
+      exporter_secret: "328232ec91ab6f93edd8ad56567ca27d2dd0784a9b775d2d99e906d5b41bec6e9c712faa1c6c16034771d042cebcdccae8c5e3386512f5bac9fb9b3e665d024b",
+      cts: &[],
+      exports: [
+        "600d864b7daa1d71bdb08acd1273f5f87fc1b63de703830e0f96450bcd15fe65",
+        "8f5a9027f6c3d204481aa2f285bd2564ff941883f7b42556d94ba1d9a9d24fe3",
+        "07c8df7699cccb3e2ee995d1dc7b34755774bfcda741da8ae114feffb71ae3f1",
+      ],
+    },
+  ];
+
+  fn check(v: &Vector) -> Result<()> {
+    let kem = v.suite.kem;
+    let sk_r = kem.derive_key_pair(&hex::decode(v.ikm_r)?)?;
+    assert_eq!(hex::encode(sk_r.to_sec1_public()?), v.pk_rm);
+    let pk_r = EccKeyPairType::from_sec1_public(kem.curve(), &sk_r.to_sec1_public()?)?;
+    let sender = v.ikm_s.map(|ikm| kem.derive_key_pair(&hex::decode(ikm)?)).transpose()?;
+    let sender_public = match &sender {
+      Some(sender) => Some(EccKeyPairType::from_sec1_public(
+        kem.curve(),
+        &sender.to_sec1_public()?,
+      )?),
+      None => None,
+    };
+    let psk = matches!(v.mode, Mode::Psk | Mode::AuthPsk).then_some(&PSK);
+
+    let ephemeral = kem.derive_key_pair(&hex::decode(v.ikm_e)?)?;
+    let (shared_secret, _) = kem.encap(&pk_r, &ephemeral, sender.as_ref())?;
+    assert_eq!(hex::encode(&shared_secret), v.shared_secret);
+    let (enc, mut sender_ctx) = v
+      .suite
+      .setup_sender_with(&pk_r, &ephemeral, INFO, psk, sender.as_ref())?;
+    assert_eq!(hex::encode(&enc), v.enc);
+    assert_eq!(sender_ctx.mode(), v.mode);
+    let mut receiver_ctx = v.suite.setup_receiver(&enc, &sk_r, INFO, psk, sender_public.as_ref())?;
+    for ctx in [&sender_ctx, &receiver_ctx] {
+      assert_eq!(hex::encode(&ctx.key), v.key);
+      assert_eq!(hex::encode(ctx.base_nonce), v.base_nonce);
+      assert_eq!(hex::encode(&ctx.exporter_secret), v.exporter_secret);
+    }
+
+    if v.suite.aead == Aead::ExportOnly {
+      assert!(sender_ctx.seal(b"", PT).is_err());
+    } else {
+      for seq in 0..=256 {
+        let aad = format!("Count-{seq}");
+        let ct = sender_ctx.seal(aad.as_bytes(), PT)?;
+        if let Some((_, expected)) = v.cts.iter().find(|(s, _)| *s == seq) {
+          assert_eq!(hex::encode(&ct), *expected, "seq {seq}");
+        }
+        assert_eq!(receiver_ctx.open(aad.as_bytes(), &ct)?, PT);
+      }
+    }
+    for (context, expected) in EXPORTER_CONTEXTS.iter().zip(v.exports) {
+      assert_eq!(hex::encode(sender_ctx.export(context, 32)?), expected);
+      assert_eq!(hex::encode(receiver_ctx.export(context, 32)?), expected);
+    }
+    Ok(())
+  }
+
+  #[test]
+  fn rfc9180_test_vectors() -> Result<()> {
+    for v in RFC9180_VECTORS {
+      check(v)?;
+    }
+    Ok(())
+  }
+
+  #[test]
+  fn supplementary_test_vectors() -> Result<()> {
+    for v in SUPPLEMENTARY_VECTORS {
+      check(v)?;
+    }
+    Ok(())
+  }
+
+  #[test]
+  fn open_detects_wrong_inputs() -> Result<()> {
+    let suite = Suite::new(Kem::P384HkdfSha384, Kdf::HkdfSha384, Aead::ChaCha20Poly1305);
+    let recipient = EccKeyPairType::new("P-384")?;
+    let sender = EccKeyPairType::new("P-384")?;
+    let other = EccKeyPairType::new("P-384")?;
+    let (enc, mut ctx) = suite.setup_sender(&recipient, INFO, Some(&PSK), Some(&sender))?;
+    assert_eq!((ctx.mode(), enc.len()), (Mode::AuthPsk, suite.kem.enc_len()));
+    let ct = ctx.seal(b"aad", PT)?;
+
+    let other_psk = Psk { id: b"other", ..PSK };
+    assert!(suite
+      .setup_receiver(&enc, &recipient, INFO, Some(&PSK), Some(&other))?
+      .open(b"aad", &ct)
+      .is_err());
+    assert!(suite
+      .setup_receiver(&enc, &recipient, INFO, Some(&other_psk), Some(&sender))?
+      .open(b"aad", &ct)
+      .is_err());
+    assert!(suite
+      .setup_receiver(&enc, &recipient, b"other", Some(&PSK), Some(&sender))?
+      .open(b"aad", &ct)
+      .is_err());
+
+    // The sequence number is not advanced by a failure
+    let mut receiver_ctx = suite.setup_receiver(&enc, &recipient, INFO, Some(&PSK), Some(&sender))?;
+    let mut tampered = ct.clone();
+    tampered[0] ^= 1;
+    assert!(receiver_ctx.open(b"aad", &tampered).is_err());
+    assert!(receiver_ctx.open(b"other", &ct).is_err());
+    assert_eq!(receiver_ctx.open(b"aad", &ct)?, PT);
+
+    let short_psk = Psk { key: &[0u8; 16], ..PSK };
+    assert!(suite.setup_sender(&recipient, INFO, Some(&short_psk), None).is_err());
+    let p256 = EccKeyPairType::new("P-256")?;
+    assert!(suite.setup_sender(&p256, INFO, None, None).is_err());
+    assert!(suite.setup_sender(&recipient, INFO, None, Some(&p256)).is_err());
+    assert!(suite.setup_receiver(&enc[1..], &recipient, INFO, None, None).is_err());
+    assert!(suite.kem.derive_key_pair(&[0u8; 32]).is_err());
+    assert!(Kem::for_key(&EccKeyPairType::new("X25519")?).is_err());
+    Ok(())
+  }
+}
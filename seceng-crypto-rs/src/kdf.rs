@@ -0,0 +1,940 @@
+use crate::error::*;
+use base64::{engine::general_purpose, Engine as _};
+use hkdf::Hkdf;
+use pbkdf2::pbkdf2_hmac;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Sha256, Sha384, Sha512};
+use std::{fmt::Display, str::FromStr};
+use zeroize::{Zeroize, Zeroizing};
+
+const SALT_LEN: usize = 32;
+/// Default PBKDF2 iteration count of the original samples
+pub const ITERATION: u32 = 2048;
+const ETM_KEY_LEN: usize = 32;
+const KEK_LEN: usize = 32;
+const ETM_ENC_INFO: &[u8] = b"encrypt-then-mac AES-256-CBC key";
+const ETM_MAC_INFO: &[u8] = b"encrypt-then-mac HMAC-SHA-256 key";
+/// Upper bounds of the costs in `KdfParams`, so that a crafted record cannot make a derivation run for hours
+/// or exhaust the memory
+pub const MAX_PBKDF2_ITERATION: u32 = 10_000_000;
+/// Argon2id memory size in KiB, i.e., 1 GiB
+pub const MAX_ARGON2ID_MEMORY: u32 = 1 << 20;
+pub const MAX_ARGON2ID_ITERATION: u32 = 1024;
+pub const MAX_ARGON2ID_LANES: u32 = 64;
+/// scrypt N of 1 GiB of memory with r = 8
+pub const MAX_SCRYPT_LOG_N: u32 = 20;
+pub const MAX_SCRYPT_R: u32 = 32;
+pub const MAX_SCRYPT_P: u32 = 16;
+/// Domain of the HKDF `info`, kept as that of cli04 so that existing records can be decrypted
+const HKDF_INFO_DOMAIN: &str = "seceng-cli04";
+
+/// Key derivation function recorded as `algorithm` in `KdfParams`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KdfAlgorithm {
+  Pbkdf2,
+  Hkdf,
+  Argon2id,
+  Scrypt,
+}
+
+impl FromStr for KdfAlgorithm {
+  type Err = anyhow::Error;
+  fn from_str(s: &str) -> Result<Self> {
+    match s {
+      "PBKDF2" => Ok(KdfAlgorithm::Pbkdf2),
+      "HKDF" => Ok(KdfAlgorithm::Hkdf),
+      "Argon2id" => Ok(KdfAlgorithm::Argon2id),
+      "scrypt" => Ok(KdfAlgorithm::Scrypt),
+      _ => bail!("Unsupported KDF: {}", s),
+    }
+  }
+}
+
+impl Display for KdfAlgorithm {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      KdfAlgorithm::Pbkdf2 => write!(f, "PBKDF2"),
+      KdfAlgorithm::Hkdf => write!(f, "HKDF"),
+      KdfAlgorithm::Argon2id => write!(f, "Argon2id"),
+      KdfAlgorithm::Scrypt => write!(f, "scrypt"),
+    }
+  }
+}
+
+/// Hash function for PBKDF2 and HKDF, recorded as `hash` in `KdfParams`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KdfHash {
+  #[default]
+  Sha256,
+  Sha384,
+  Sha512,
+}
+
+impl FromStr for KdfHash {
+  type Err = anyhow::Error;
+  fn from_str(s: &str) -> Result<Self> {
+    match s {
+      "SHA-256" => Ok(KdfHash::Sha256),
+      "SHA-384" => Ok(KdfHash::Sha384),
+      "SHA-512" => Ok(KdfHash::Sha512),
+      _ => bail!("Unsupported hash for KDF: {}", s),
+    }
+  }
+}
+
+impl Display for KdfHash {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      KdfHash::Sha256 => write!(f, "SHA-256"),
+      KdfHash::Sha384 => write!(f, "SHA-384"),
+      KdfHash::Sha512 => write!(f, "SHA-512"),
+    }
+  }
+}
+
+/// Cost parameters of Argon2id
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Argon2idCost {
+  /// Memory size in KiB
+  pub memory: u32,
+  /// Number of passes over the memory
+  pub iterations: u32,
+  /// Degree of parallelism
+  pub lanes: u32,
+}
+
+impl Default for Argon2idCost {
+  fn default() -> Self {
+    Self {
+      memory: argon2::Params::DEFAULT_M_COST,
+      iterations: argon2::Params::DEFAULT_T_COST,
+      lanes: argon2::Params::DEFAULT_P_COST,
+    }
+  }
+}
+
+/// Cost parameters of scrypt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScryptCost {
+  /// CPU/memory cost N, a power of 2
+  pub n: u64,
+  /// Block size r
+  pub r: u32,
+  /// Parallelization p
+  pub p: u32,
+}
+
+impl Default for ScryptCost {
+  fn default() -> Self {
+    Self {
+      n: 1 << scrypt::Params::RECOMMENDED_LOG_N,
+      r: scrypt::Params::RECOMMENDED_R,
+      p: scrypt::Params::RECOMMENDED_P,
+    }
+  }
+}
+
+/// Purpose of a key derived from a master secret, bound into the HKDF `info`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyPurpose {
+  Enc,
+  Mac,
+  Subkey,
+  Wrap,
+}
+
+impl Display for KeyPurpose {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      KeyPurpose::Enc => write!(f, "enc"),
+      KeyPurpose::Mac => write!(f, "mac"),
+      KeyPurpose::Subkey => write!(f, "subkey"),
+      KeyPurpose::Wrap => write!(f, "wrap"),
+    }
+  }
+}
+
+/// Context of HKDF stored as `info` in `KdfParams`.
+/// Keys for each purpose are expanded with `seceng-cli04/v<version>/<purpose>/<label>` as the HKDF `info`,
+/// where the label comes last so that the encoding is unambiguous for any label.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct HkdfInfo {
+  pub label: String,
+  pub version: u32,
+}
+
+impl HkdfInfo {
+  pub fn try_new(label: &str, version: u32) -> Result<Self> {
+    ensure!(!label.is_empty(), "HKDF label must not be empty");
+    ensure!(version > 0, "HKDF info version must be positive");
+    Ok(Self {
+      label: label.to_string(),
+      version,
+    })
+  }
+
+  pub fn encode(&self, purpose: KeyPurpose) -> Vec<u8> {
+    format!("{HKDF_INFO_DOMAIN}/v{}/{purpose}/{}", self.version, self.label).into_bytes()
+  }
+}
+
+/// Self-describing KDF parameters stored along with the encrypted data.
+/// Records from the original sample-04 have no `algorithm`, which is then inferred from `iterationCount`.
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct KdfParams {
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub algorithm: Option<String>,
+  pub salt: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub hash: Option<String>,
+  /// PBKDF2 iteration count, or Argon2id passes
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub iterationCount: Option<usize>,
+  /// Argon2id memory size in KiB
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub memoryCost: Option<u32>,
+  /// Argon2id degree of parallelism
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub lanes: Option<u32>,
+  /// scrypt CPU/memory cost
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub n: Option<u64>,
+  /// scrypt block size
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub r: Option<u32>,
+  /// scrypt parallelization
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub p: Option<u32>,
+  /// HKDF context. Keys are expanded with an empty `info` if absent, as in the original sample-04.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub info: Option<HkdfInfo>,
+}
+
+impl KdfParams {
+  pub fn algorithm(&self) -> Result<KdfAlgorithm> {
+    match &self.algorithm {
+      Some(alg) => alg.parse(),
+      None if self.iterationCount.is_some() => Ok(KdfAlgorithm::Pbkdf2),
+      None => Ok(KdfAlgorithm::Hkdf),
+    }
+  }
+
+  /// Hash function of PBKDF2 or HKDF. SHA-256 if absent.
+  pub fn hash(&self) -> Result<KdfHash> {
+    match &self.hash {
+      Some(hash) => hash.parse(),
+      None => Ok(KdfHash::default()),
+    }
+  }
+
+  /// Salt decoded from Base64
+  pub fn salt(&self) -> Result<Vec<u8>> {
+    Ok(general_purpose::STANDARD.decode(&self.salt)?)
+  }
+
+  /// Strictly check that the params are complete for their algorithm, carry nothing of other algorithms
+  /// and have no cost beyond the upper bounds
+  pub fn validate(&self) -> Result<()> {
+    const MAX_ITER: usize = MAX_PBKDF2_ITERATION as usize;
+    let algorithm = self.algorithm()?;
+    ensure!(!self.salt()?.is_empty(), "KDF salt must not be empty");
+    let (argon2id, scrypt) = (
+      self.memoryCost.is_some() || self.lanes.is_some(),
+      self.n.is_some() || self.r.is_some() || self.p.is_some(),
+    );
+    match algorithm {
+      KdfAlgorithm::Pbkdf2 | KdfAlgorithm::Hkdf => {
+        self.hash()?;
+        ensure!(!argon2id && !scrypt, "{algorithm} takes no Argon2id or scrypt cost");
+      }
+      KdfAlgorithm::Argon2id | KdfAlgorithm::Scrypt => {
+        ensure!(self.hash.is_none(), "{algorithm} takes no hash");
+      }
+    }
+    ensure!(
+      algorithm == KdfAlgorithm::Hkdf || self.info.is_none(),
+      "{algorithm} takes no HKDF info"
+    );
+    match algorithm {
+      KdfAlgorithm::Pbkdf2 => ensure!(
+        matches!(self.iterationCount, Some(1..=MAX_ITER)),
+        "PBKDF2 iteration count must be from 1 to {MAX_PBKDF2_ITERATION}"
+      ),
+      KdfAlgorithm::Hkdf => ensure!(self.iterationCount.is_none(), "HKDF takes no iteration count"),
+      KdfAlgorithm::Argon2id => {
+        let (Some(iterations), Some(memory), Some(lanes), false) =
+          (self.iterationCount, self.memoryCost, self.lanes, scrypt)
+        else {
+          bail!("Argon2id requires iterationCount, memoryCost and lanes only");
+        };
+        ensure!(
+          iterations <= MAX_ARGON2ID_ITERATION as usize,
+          "Argon2id iteration count must not exceed {MAX_ARGON2ID_ITERATION}"
+        );
+        ensure!(
+          memory <= MAX_ARGON2ID_MEMORY,
+          "Argon2id memory cost must not exceed {MAX_ARGON2ID_MEMORY} KiB"
+        );
+        ensure!(
+          lanes <= MAX_ARGON2ID_LANES,
+          "Argon2id lanes must not exceed {MAX_ARGON2ID_LANES}"
+        );
+      }
+      KdfAlgorithm::Scrypt => {
+        let (Some(n), Some(r), Some(p), false, None) = (self.n, self.r, self.p, argon2id, self.iterationCount) else {
+          bail!("scrypt requires n, r and p only");
+        };
+        ensure!(
+          n.is_power_of_two() && n > 1,
+          "scrypt cost parameter N must be a power of 2 greater than 1"
+        );
+        ensure!(
+          n.trailing_zeros() <= MAX_SCRYPT_LOG_N,
+          "scrypt cost parameter N must not exceed 2^{MAX_SCRYPT_LOG_N}"
+        );
+        ensure!(r <= MAX_SCRYPT_R, "scrypt block size r must not exceed {MAX_SCRYPT_R}");
+        ensure!(
+          p <= MAX_SCRYPT_P,
+          "scrypt parallelization p must not exceed {MAX_SCRYPT_P}"
+        );
+      }
+    }
+    Ok(())
+  }
+
+  pub fn new_pbkdf2(salt: &[u8], hash: &KdfHash, iter: u32) -> Self {
+    Self {
+      salt: general_purpose::STANDARD.encode(salt),
+      hash: Some(hash.to_string()),
+      iterationCount: Some(iter as usize),
+      ..Default::default()
+    }
+  }
+
+  pub fn new_hkdf(salt: &[u8], hash: &KdfHash, info: Option<&HkdfInfo>) -> Self {
+    Self {
+      salt: general_purpose::STANDARD.encode(salt),
+      hash: Some(hash.to_string()),
+      info: info.cloned(),
+      ..Default::default()
+    }
+  }
+
+  pub fn new_argon2id(salt: &[u8], cost: &Argon2idCost) -> Self {
+    Self {
+      algorithm: Some(KdfAlgorithm::Argon2id.to_string()),
+      salt: general_purpose::STANDARD.encode(salt),
+      iterationCount: Some(cost.iterations as usize),
+      memoryCost: Some(cost.memory),
+      lanes: Some(cost.lanes),
+      ..Default::default()
+    }
+  }
+
+  pub fn new_scrypt(salt: &[u8], cost: &ScryptCost) -> Self {
+    Self {
+      algorithm: Some(KdfAlgorithm::Scrypt.to_string()),
+      salt: general_purpose::STANDARD.encode(salt),
+      n: Some(cost.n),
+      r: Some(cost.r),
+      p: Some(cost.p),
+      ..Default::default()
+    }
+  }
+}
+
+/// Derived key with its salt. The key is zeroized on drop.
+pub struct BinaryKey {
+  pub key: Vec<u8>,
+  pub salt: Vec<u8>,
+}
+
+impl Drop for BinaryKey {
+  fn drop(&mut self) {
+    self.key.zeroize();
+  }
+}
+
+impl BinaryKey {
+  /// Derive a key from a password by PBKDF2. A random salt is used if `salt` is `None`.
+  pub fn try_new_pbkdf2(
+    password: &str,
+    len: usize,
+    salt: Option<&[u8]>,
+    iter: Option<&u32>,
+    hash: Option<&KdfHash>,
+  ) -> Result<Self> {
+    let salt = salt_or_random(salt);
+    let iter = match iter {
+      Some(v) => v,
+      None => &ITERATION,
+    };
+    ensure!(*iter > 0, "PBKDF2 iteration count must be positive");
+
+    let mut key_bin = vec![Default::default(); len];
+
+    match hash.unwrap_or(&KdfHash::Sha256) {
+      KdfHash::Sha256 => pbkdf2_hmac::<Sha256>(password.as_bytes(), &salt, *iter, &mut key_bin),
+      KdfHash::Sha384 => pbkdf2_hmac::<Sha384>(password.as_bytes(), &salt, *iter, &mut key_bin),
+      KdfHash::Sha512 => pbkdf2_hmac::<Sha512>(password.as_bytes(), &salt, *iter, &mut key_bin),
+    }
+
+    Ok(Self { key: key_bin, salt })
+  }
+
+  /// Derive a key from a master secret by HKDF with an empty `info`
+  pub fn try_new_hkdf(master: &[u8], len: usize, salt: Option<&[u8]>, hash: Option<&KdfHash>) -> Result<Self> {
+    Self::try_new_hkdf_with_info(master, len, salt, hash, b"")
+  }
+
+  /// Derive a key from a master secret by HKDF with the given `info`
+  pub fn try_new_hkdf_with_info(
+    master: &[u8],
+    len: usize,
+    salt: Option<&[u8]>,
+    hash: Option<&KdfHash>,
+    info: &[u8],
+  ) -> Result<Self> {
+    let salt = salt_or_random(salt);
+    let okm = hkdf_expand(master, Some(&salt), info, hash.unwrap_or(&KdfHash::Sha256), len)?;
+
+    Ok(Self {
+      key: okm.to_vec(),
+      salt,
+    })
+  }
+
+  /// Derive a key from a password by Argon2id (RFC 9106, version 0x13)
+  pub fn try_new_argon2id(
+    password: &str,
+    len: usize,
+    salt: Option<&[u8]>,
+    cost: Option<&Argon2idCost>,
+  ) -> Result<Self> {
+    let salt = salt_or_random(salt);
+    let cost = cost.cloned().unwrap_or_default();
+    let params = argon2::Params::new(cost.memory, cost.iterations, cost.lanes, Some(len)).map_err(|e| anyhow!(e))?;
+    let argon2 = argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params);
+
+    let mut key_bin = vec![Default::default(); len];
+    argon2
+      .hash_password_into(password.as_bytes(), &salt, &mut key_bin)
+      .map_err(|e| anyhow!(e))?;
+
+    Ok(Self { key: key_bin, salt })
+  }
+
+  /// Derive a key from a password by scrypt (RFC 7914)
+  pub fn try_new_scrypt(password: &str, len: usize, salt: Option<&[u8]>, cost: Option<&ScryptCost>) -> Result<Self> {
+    let salt = salt_or_random(salt);
+    let cost = cost.cloned().unwrap_or_default();
+    ensure!(
+      cost.n.is_power_of_two() && cost.n > 1,
+      "scrypt cost parameter N must be a power of 2 greater than 1"
+    );
+    let params = scrypt::Params::new(cost.n.trailing_zeros() as u8, cost.r, cost.p, len).map_err(|e| anyhow!(e))?;
+
+    let mut key_bin = vec![Default::default(); len];
+    scrypt::scrypt(password.as_bytes(), &salt, &params, &mut key_bin).map_err(|e| anyhow!(e))?;
+
+    Ok(Self { key: key_bin, salt })
+  }
+
+  /// Re-derive a key exactly as described in the stored KDF params.
+  /// `secret` is a password for PBKDF2, Argon2id and scrypt, or a master secret in Base64 for HKDF.
+  pub fn try_from_kdf_params(secret: &str, len: usize, params: &KdfParams) -> Result<Self> {
+    let salt = params.salt()?;
+    match params.algorithm()? {
+      KdfAlgorithm::Pbkdf2 => {
+        let iter = params.iterationCount.context("PBKDF2 iteration count is missing")?;
+        let iter = u32::try_from(iter).context("PBKDF2 iteration count is too large")?;
+        Self::try_new_pbkdf2(secret, len, Some(&salt), Some(&iter), Some(&params.hash()?))
+      }
+      KdfAlgorithm::Hkdf => {
+        ensure!(
+          params.iterationCount.is_none(),
+          "Invalid KDF params. HKDF takes no iteration count."
+        );
+        let hash = params.hash()?;
+        let master = Zeroizing::new(general_purpose::STANDARD.decode(secret)?);
+        match &params.info {
+          Some(info) => {
+            let info = info.encode(KeyPurpose::Enc);
+            Self::try_new_hkdf_with_info(&master, len, Some(&salt), Some(&hash), &info)
+          }
+          None => Self::try_new_hkdf(&master, len, Some(&salt), Some(&hash)),
+        }
+      }
+      KdfAlgorithm::Argon2id => {
+        let cost = Argon2idCost {
+          memory: params.memoryCost.context("Argon2id memory cost is missing")?,
+          iterations: u32::try_from(params.iterationCount.context("Argon2id iteration count is missing")?)
+            .context("Argon2id iteration count is too large")?,
+          lanes: params.lanes.context("Argon2id lanes are missing")?,
+        };
+        Self::try_new_argon2id(secret, len, Some(&salt), Some(&cost))
+      }
+      KdfAlgorithm::Scrypt => {
+        let cost = ScryptCost {
+          n: params.n.context("scrypt cost parameter N is missing")?,
+          r: params.r.context("scrypt block size r is missing")?,
+          p: params.p.context("scrypt parallelization p is missing")?,
+        };
+        Self::try_new_scrypt(secret, len, Some(&salt), Some(&cost))
+      }
+    }
+  }
+
+  /// Encryption and MAC keys for encrypt-then-MAC as described in the stored KDF params.
+  /// With an HKDF context, both are expanded directly from the master secret for their own purposes.
+  /// Otherwise, they are split from the derived key by `split_etm`.
+  pub fn try_etm_from_kdf_params(secret: &str, params: &KdfParams) -> Result<(BinaryKey, Zeroizing<Vec<u8>>)> {
+    let (KdfAlgorithm::Hkdf, Some(info)) = (params.algorithm()?, &params.info) else {
+      return Self::try_from_kdf_params(secret, ETM_KEY_LEN, params)?.split_etm();
+    };
+    let enc_key = Self::try_from_kdf_params(secret, ETM_KEY_LEN, params)?;
+    let master = Zeroizing::new(general_purpose::STANDARD.decode(secret)?);
+    let mac_key = Self::try_new_hkdf_with_info(
+      &master,
+      ETM_KEY_LEN,
+      Some(&params.salt()?),
+      Some(&params.hash()?),
+      &info.encode(KeyPurpose::Mac),
+    )?;
+    Ok((enc_key, Zeroizing::new(mac_key.key.clone())))
+  }
+
+  /// Key-encryption key wrapping a random data key, as described in the stored KDF params.
+  /// With an HKDF context, it is expanded from the master secret for its own purpose.
+  pub fn try_kek_from_kdf_params(secret: &str, params: &KdfParams) -> Result<BinaryKey> {
+    let (KdfAlgorithm::Hkdf, Some(info)) = (params.algorithm()?, &params.info) else {
+      return Self::try_from_kdf_params(secret, KEK_LEN, params);
+    };
+    let master = Zeroizing::new(general_purpose::STANDARD.decode(secret)?);
+    Self::try_new_hkdf_with_info(
+      &master,
+      KEK_LEN,
+      Some(&params.salt()?),
+      Some(&params.hash()?),
+      &info.encode(KeyPurpose::Wrap),
+    )
+  }
+
+  pub fn salt_to_base64(&self) -> String {
+    general_purpose::STANDARD.encode(&self.salt)
+  }
+
+  /// Split the derived key into an AES-256 encryption key and an HMAC-SHA-256 key for encrypt-then-MAC.
+  /// Both are expanded from `self.key` by HKDF-SHA256 with distinct info, so that they are independent.
+  pub fn split_etm(&self) -> Result<(BinaryKey, Zeroizing<Vec<u8>>)> {
+    let hkdf = Hkdf::<Sha256>::new(None, &self.key);
+    let mut enc_key = vec![0u8; ETM_KEY_LEN];
+    let mut mac_key = Zeroizing::new(vec![0u8; ETM_KEY_LEN]);
+    hkdf.expand(ETM_ENC_INFO, &mut enc_key).map_err(|e| anyhow!(e))?;
+    hkdf.expand(ETM_MAC_INFO, &mut mac_key).map_err(|e| anyhow!(e))?;
+
+    Ok((
+      BinaryKey {
+        key: enc_key,
+        salt: self.salt.clone(),
+      },
+      mac_key,
+    ))
+  }
+}
+
+/// Derive a named sub-master secret from a master secret.
+/// No salt is used so that the same label always gives the same sub-master secret.
+pub fn derive_subkey(master: &[u8], len: usize, hash: &KdfHash, info: &HkdfInfo) -> Result<Zeroizing<Vec<u8>>> {
+  hkdf_expand(master, None, &info.encode(KeyPurpose::Subkey), hash, len)
+}
+
+fn hkdf_expand(ikm: &[u8], salt: Option<&[u8]>, info: &[u8], hash: &KdfHash, len: usize) -> Result<Zeroizing<Vec<u8>>> {
+  let mut okm = Zeroizing::new(vec![Default::default(); len]);
+  match hash {
+    KdfHash::Sha256 => Hkdf::<Sha256>::new(salt, ikm).expand(info, &mut okm),
+    KdfHash::Sha384 => Hkdf::<Sha384>::new(salt, ikm).expand(info, &mut okm),
+    KdfHash::Sha512 => Hkdf::<Sha512>::new(salt, ikm).expand(info, &mut okm),
+  }
+  .map_err(|e| anyhow!(e))?;
+  Ok(okm)
+}
+
+/// Generate a random salt
+pub fn random_salt() -> Vec<u8> {
+  let mut buf = vec![0u8; SALT_LEN];
+  rand::rng().fill_bytes(&mut buf);
+  buf
+}
+
+fn salt_or_random(salt: Option<&[u8]>) -> Vec<u8> {
+  match salt {
+    Some(v) => v.to_vec(),
+    None => random_salt(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use hex_literal::hex;
+
+  #[test]
+  fn gen_binary_key_with_salt() -> Result<()> {
+    // pbkdf2
+    let salt = hex!("dc04deff5a33c22df3aa82085f9c2d0f5477af73cd500dfe53162d70ba096a03").as_slice();
+
+    let binary_key = BinaryKey::try_new_pbkdf2("password", 32, Some(salt), None, None)?;
+    assert_eq!(
+      binary_key.key.as_slice(),
+      hex!("bf3d09d429fbf71bbb384a6421447da32096ff8a010c7042d3e29194237792d2")
+    );
+    assert_eq!(
+      binary_key.salt_to_base64(),
+      "3ATe/1ozwi3zqoIIX5wtD1R3r3PNUA3+UxYtcLoJagM="
+    );
+
+    // hkdf
+    let mut ikm = Vec::with_capacity(32);
+    for i in 0..32 {
+      ikm.push(i as u8)
+    }
+    let binary_key = BinaryKey::try_new_hkdf(&ikm, 144, Some(&ikm), None)?;
+
+    let test_vector = "fJHB6pVraz09Ognk2NRFR/DKdsK0cnFQORjocdWbv6YaAV7m9LmrZhT2O8v1yBEZXBbEaqiRfV59VGWVd5L685jh6IHoZWoTN50i8JLMogXrnB/mvCSwLEMjY4dTxbHspz88XS+94aKvl/Hql9+IGfnOWNAcb6brgCEoD1rb7pmYT2FzIVk3qLWNTO2QtTl1";
+    assert_eq!(
+      binary_key.key.as_slice(),
+      general_purpose::STANDARD.decode(test_vector)?
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn gen_binary_key_without_salt() -> Result<()> {
+    let binary_key = BinaryKey::try_new_pbkdf2("password", 32, None, None, None)?;
+
+    let salt = binary_key.salt.clone();
+
+    let binary_key_new = BinaryKey::try_new_pbkdf2("password", 32, Some(&salt), None, None)?;
+
+    assert_eq!(binary_key.key, binary_key_new.key);
+    assert_eq!(salt, binary_key_new.salt);
+    Ok(())
+  }
+
+  #[test]
+  fn pbkdf2_hkdf_hash_test_vector() -> Result<()> {
+    let salt = hex!("dc04deff5a33c22df3aa82085f9c2d0f5477af73cd500dfe53162d70ba096a03").as_slice();
+    let expected = [
+      (
+        KdfHash::Sha256,
+        hex!("de429e2ca64925cfb02a301f58f77aace78b7f5203aba40ad03950a4be7264ed"),
+      ),
+      (
+        KdfHash::Sha384,
+        hex!("f4bd3882602f264084b4574c5488a0023563503dcc5c1127d3940a5707e2ef48"),
+      ),
+      (
+        KdfHash::Sha512,
+        hex!("86e1e635c43414f69ccdf1bf90aeceea24a35496b9e6edef7fbae52921853ccd"),
+      ),
+    ];
+    for (hash, key) in expected {
+      let binary_key = BinaryKey::try_new_pbkdf2("password", 32, Some(salt), Some(&4096), Some(&hash))?;
+      assert_eq!(binary_key.key.as_slice(), key);
+    }
+
+    let ikm = (0..32u8).collect::<Vec<_>>();
+    let expected = [
+      (
+        KdfHash::Sha256,
+        hex!("7c91c1ea956b6b3d3d3a09e4d8d44547f0ca76c2b47271503918e871d59bbfa6"),
+      ),
+      (
+        KdfHash::Sha384,
+        hex!("e22863d1b2f70503a65a0939c4361d41bf77cd402fade97471add201081e2ce9"),
+      ),
+      (
+        KdfHash::Sha512,
+        hex!("4d776fbc7c5fe27f14aac4e29f8324ca1549ccd3f766179f69a1f1403274deaa"),
+      ),
+    ];
+    for (hash, key) in expected {
+      let binary_key = BinaryKey::try_new_hkdf(&ikm, 32, Some(&ikm), Some(&hash))?;
+      assert_eq!(binary_key.key.as_slice(), key);
+    }
+    Ok(())
+  }
+
+  #[test]
+  fn kdf_params_round_trip_matrix() -> Result<()> {
+    let master_bin = [0x0bu8; 32];
+    let master = general_purpose::STANDARD.encode(master_bin);
+    for hash in ["SHA-256", "SHA-384", "SHA-512"] {
+      let hash = hash.parse::<KdfHash>()?;
+      for iter in [1u32, 1000, 2048, 5000] {
+        let binary_key = BinaryKey::try_new_pbkdf2("password", 32, None, Some(&iter), Some(&hash))?;
+        let params = KdfParams::new_pbkdf2(&binary_key.salt, &hash, iter);
+        let params: KdfParams = serde_json::from_str(&serde_json::to_string(&params)?)?;
+        let rederived = BinaryKey::try_from_kdf_params("password", 32, &params)?;
+        assert_eq!(rederived.key, binary_key.key, "PBKDF2 {hash} {iter}");
+
+        // Any other iteration count or hash must give another key
+        let other = KdfParams::new_pbkdf2(&binary_key.salt, &hash, iter + 1);
+        assert_ne!(
+          BinaryKey::try_from_kdf_params("password", 32, &other)?.key,
+          binary_key.key
+        );
+      }
+
+      let binary_key = BinaryKey::try_new_hkdf(&master_bin, 32, None, Some(&hash))?;
+      let params = KdfParams::new_hkdf(&binary_key.salt, &hash, None);
+      let params: KdfParams = serde_json::from_str(&serde_json::to_string(&params)?)?;
+      let rederived = BinaryKey::try_from_kdf_params(&master, 32, &params)?;
+      assert_eq!(rederived.key, binary_key.key, "HKDF {hash}");
+    }
+
+    let keys = ["SHA-256", "SHA-384", "SHA-512"]
+      .iter()
+      .map(|h| {
+        let params = KdfParams::new_pbkdf2(&[0u8; 3], &h.parse()?, 10);
+        Ok(BinaryKey::try_from_kdf_params("password", 32, &params)?.key.clone())
+      })
+      .collect::<Result<Vec<_>>>()?;
+    assert_ne!(keys[0], keys[1]);
+    assert_ne!(keys[1], keys[2]);
+
+    let params: KdfParams = serde_json::from_str(r#"{"salt":"AAAA","hash":"SHA-1","iterationCount":10}"#)?;
+    assert!(BinaryKey::try_from_kdf_params("password", 32, &params).is_err());
+    let params: KdfParams = serde_json::from_str(r#"{"salt":"AAAA","hash":"SHA-256","iterationCount":0}"#)?;
+    assert!(BinaryKey::try_from_kdf_params("password", 32, &params).is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn argon2id_test_vector() -> Result<()> {
+    // Cross-checked with OpenSSL's Argon2id: m = 64 KiB, t = 2, p = 1, salt "somesalt"
+    let cost = Argon2idCost {
+      memory: 64,
+      iterations: 2,
+      lanes: 1,
+    };
+    let binary_key = BinaryKey::try_new_argon2id("password", 32, Some(b"somesalt"), Some(&cost))?;
+    assert_eq!(
+      binary_key.key.as_slice(),
+      hex!("16a1a498734609dd01456da406de9f3d9da93e6c86c300a12fc1465214ce4922")
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn scrypt_test_vector() -> Result<()> {
+    // RFC 7914 Section 12, the second vector
+    let cost = ScryptCost { n: 1024, r: 8, p: 16 };
+    let binary_key = BinaryKey::try_new_scrypt("password", 64, Some(b"NaCl"), Some(&cost))?;
+    assert_eq!(binary_key.key.as_slice(), hex!("fdbabe1c9d3472007856e7190d01e9fe7c6ad7cbc8237830e77376634b3731622eaf30d92e22a3886ff109279d9830dac727afb94a83ee6d8360cbdfa2cc0640"));
+
+    let cost = ScryptCost { n: 1000, r: 8, p: 1 };
+    assert!(BinaryKey::try_new_scrypt("password", 32, None, Some(&cost)).is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn rederive_from_kdf_params() -> Result<()> {
+    let cost = Argon2idCost {
+      memory: 256,
+      iterations: 1,
+      lanes: 2,
+    };
+    let binary_key = BinaryKey::try_new_argon2id("password", 32, None, Some(&cost))?;
+    let params = KdfParams::new_argon2id(&binary_key.salt, &cost);
+    let json = serde_json::to_string(&params)?;
+    let params: KdfParams = serde_json::from_str(&json)?;
+    assert_eq!(params.algorithm()?, KdfAlgorithm::Argon2id);
+    assert_eq!(
+      BinaryKey::try_from_kdf_params("password", 32, &params)?.key,
+      binary_key.key
+    );
+    assert_ne!(
+      BinaryKey::try_from_kdf_params("passw0rd", 32, &params)?.key,
+      binary_key.key
+    );
+
+    let cost = ScryptCost { n: 16, r: 1, p: 1 };
+    let binary_key = BinaryKey::try_new_scrypt("password", 32, None, Some(&cost))?;
+    let params = KdfParams::new_scrypt(&binary_key.salt, &cost);
+    let json = serde_json::to_string(&params)?;
+    let params: KdfParams = serde_json::from_str(&json)?;
+    assert_eq!(params.algorithm()?, KdfAlgorithm::Scrypt);
+    assert_eq!(
+      BinaryKey::try_from_kdf_params("password", 32, &params)?.key,
+      binary_key.key
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn legacy_kdf_params() -> Result<()> {
+    let params: KdfParams = serde_json::from_str(r#"{"salt":"AAAA","hash":"SHA-256","iterationCount":2048}"#)?;
+    assert_eq!(params.algorithm()?, KdfAlgorithm::Pbkdf2);
+    let params: KdfParams = serde_json::from_str(r#"{"salt":"AAAA","hash":"SHA-256"}"#)?;
+    assert_eq!(params.algorithm()?, KdfAlgorithm::Hkdf);
+    let params: KdfParams = serde_json::from_str(r#"{"salt":"AAAA","algorithm":"bcrypt"}"#)?;
+    assert!(params.algorithm().is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn validate_kdf_params() -> Result<()> {
+    let salt = random_salt();
+    KdfParams::new_pbkdf2(&salt, &KdfHash::Sha256, ITERATION).validate()?;
+    KdfParams::new_hkdf(&salt, &KdfHash::Sha512, None).validate()?;
+    KdfParams::new_argon2id(&salt, &Argon2idCost::default()).validate()?;
+    KdfParams::new_scrypt(&salt, &ScryptCost::default()).validate()?;
+    let legacy: KdfParams = serde_json::from_str(r#"{"salt":"AAAA","hash":"SHA-256","iterationCount":2048}"#)?;
+    legacy.validate()?;
+
+    let invalid = [
+      KdfParams::new_pbkdf2(b"", &KdfHash::Sha256, ITERATION),
+      KdfParams::new_pbkdf2(&salt, &KdfHash::Sha256, 0),
+      KdfParams {
+        lanes: Some(1),
+        ..KdfParams::new_pbkdf2(&salt, &KdfHash::Sha256, ITERATION)
+      },
+      KdfParams {
+        info: Some(HkdfInfo::try_new("label", 1)?),
+        ..KdfParams::new_argon2id(&salt, &Argon2idCost::default())
+      },
+      KdfParams {
+        hash: Some("SHA-256".to_string()),
+        ..KdfParams::new_scrypt(&salt, &ScryptCost::default())
+      },
+      KdfParams::new_scrypt(&salt, &ScryptCost { n: 1000, r: 8, p: 1 }),
+      KdfParams::new_pbkdf2(&salt, &KdfHash::Sha256, MAX_PBKDF2_ITERATION + 1),
+      KdfParams {
+        iterationCount: Some(MAX_ARGON2ID_ITERATION as usize + 1),
+        ..KdfParams::new_argon2id(&salt, &Argon2idCost::default())
+      },
+      KdfParams {
+        memoryCost: Some(MAX_ARGON2ID_MEMORY + 1),
+        ..KdfParams::new_argon2id(&salt, &Argon2idCost::default())
+      },
+      KdfParams {
+        lanes: Some(MAX_ARGON2ID_LANES + 1),
+        ..KdfParams::new_argon2id(&salt, &Argon2idCost::default())
+      },
+      KdfParams::new_scrypt(
+        &salt,
+        &ScryptCost {
+          n: 2 << MAX_SCRYPT_LOG_N,
+          r: 8,
+          p: 1,
+        },
+      ),
+      KdfParams::new_scrypt(
+        &salt,
+        &ScryptCost {
+          n: 16,
+          r: MAX_SCRYPT_R + 1,
+          p: 1,
+        },
+      ),
+      KdfParams::new_scrypt(
+        &salt,
+        &ScryptCost {
+          n: 16,
+          r: 8,
+          p: MAX_SCRYPT_P + 1,
+        },
+      ),
+    ];
+    for params in invalid {
+      assert!(params.validate().is_err(), "{params:?}");
+    }
+    assert!(serde_json::from_str::<KdfParams>(r#"{"salt":"AAAA","unknown":1}"#).is_err());
+
+    // Costs beyond u32 are rejected rather than truncated
+    let huge = KdfParams {
+      iterationCount: Some(u32::MAX as usize + 2),
+      ..KdfParams::new_argon2id(&salt, &Argon2idCost::default())
+    };
+    assert!(huge.validate().is_err());
+    assert!(BinaryKey::try_from_kdf_params("password", 32, &huge).is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn hkdf_info_binds_label_purpose_and_version() -> Result<()> {
+    // Vectors computed independently by pyca/cryptography
+    let master = general_purpose::STANDARD.encode((0..32u8).collect::<Vec<_>>());
+    let salt = (32..64u8).collect::<Vec<_>>();
+    let info = HkdfInfo::try_new("record-1", 1)?;
+    assert_eq!(info.encode(KeyPurpose::Enc), b"seceng-cli04/v1/enc/record-1");
+
+    let params = KdfParams::new_hkdf(&salt, &KdfHash::Sha256, Some(&info));
+    let params: KdfParams = serde_json::from_str(&serde_json::to_string(&params)?)?;
+    assert_eq!(params.info.as_ref(), Some(&info));
+    let (enc_key, mac_key) = BinaryKey::try_etm_from_kdf_params(&master, &params)?;
+    assert_eq!(
+      enc_key.key,
+      hex!("5c75950ef933b96c4801a4da7f90737b512abbc984ca474160fcf258407b5c14")
+    );
+    assert_eq!(
+      *mac_key,
+      hex!("b3f8eb18d1fa4a6930a243c21e5ef99ddbefd576bb271ae09f31ec4bbdde0a85")
+    );
+    assert_eq!(BinaryKey::try_from_kdf_params(&master, 32, &params)?.key, enc_key.key);
+
+    let v2 = KdfParams::new_hkdf(&salt, &KdfHash::Sha256, Some(&HkdfInfo::try_new("record-1", 2)?));
+    assert_eq!(
+      BinaryKey::try_from_kdf_params(&master, 32, &v2)?.key,
+      hex!("dcd113e820132c8a7cc8e95228a7a6f7a87dafb24ffe74ef4c49a9f7dab53a30")
+    );
+    let legacy = KdfParams::new_hkdf(&salt, &KdfHash::Sha256, None);
+    assert_ne!(BinaryKey::try_from_kdf_params(&master, 32, &legacy)?.key, enc_key.key);
+    assert!(!serde_json::to_string(&legacy)?.contains("info"));
+
+    assert!(HkdfInfo::try_new("", 1).is_err());
+    assert!(HkdfInfo::try_new("record-1", 0).is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn derive_subkey_works() -> Result<()> {
+    let master = (0..32u8).collect::<Vec<_>>();
+    let subkey = derive_subkey(&master, 32, &KdfHash::Sha256, &HkdfInfo::try_new("team-a", 1)?)?;
+    assert_eq!(
+      *subkey,
+      hex!("9b256e951b2126e8ad2effafda1cfefbf95815be6b1e64e63ae058b946e7ea5a")
+    );
+    let subkey = derive_subkey(&master, 64, &KdfHash::Sha512, &HkdfInfo::try_new("team-a", 3)?)?;
+    assert_eq!(
+      *subkey,
+      hex!(
+        "f3f328e52bc4d195c2e7ef1eca5d5e56da1b3b9c8b45b6cc74f8c81f74e58bee"
+        "488626fb2a4c77f0dfcfdbfd5c7b3379ae531e504ebc432e8e8488d3a01f80b9"
+      )
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn split_etm_keys() -> Result<()> {
+    let binary_key = BinaryKey::try_new_pbkdf2("password", 32, None, None, None)?;
+    let (enc_key, mac_key) = binary_key.split_etm()?;
+    assert_eq!(enc_key.key.len(), 32);
+    assert_eq!(mac_key.len(), 32);
+    assert_ne!(enc_key.key, *mac_key);
+    assert_ne!(enc_key.key, binary_key.key);
+    assert_eq!(enc_key.salt, binary_key.salt);
+
+    let (enc_key2, mac_key2) = binary_key.split_etm()?;
+    assert_eq!(enc_key.key, enc_key2.key);
+    assert_eq!(mac_key, mac_key2);
+    Ok(())
+  }
+}
@@ -0,0 +1,191 @@
+//! AES key wrap (RFC 3394) and AES key wrap with padding (RFC 5649) for envelope encryption.
+//! The key-encryption key (KEK) is AES-128, AES-192 or AES-256 by its length.
+
+use crate::error::*;
+use aes::{Aes128, Aes192, Aes256};
+use aes_kw::Kek;
+use rand::RngCore;
+use std::{fmt::Display, str::FromStr};
+use zeroize::Zeroizing;
+
+/// Length of a random data-encryption key, that is, an AES-256 key
+pub const DEK_LEN: usize = 32;
+/// Length of the integrity check value prepended by the key wrap
+pub const KW_OVERHEAD: usize = 8;
+
+/// Key wrap algorithm, recorded as `alg` of the wrapped key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyWrapAlgorithm {
+  /// RFC 3394, for keys of a multiple of 64 bits
+  #[default]
+  AesKw,
+  /// RFC 5649, for keys of any length
+  AesKwp,
+}
+
+impl FromStr for KeyWrapAlgorithm {
+  type Err = anyhow::Error;
+  fn from_str(s: &str) -> Result<Self> {
+    match s {
+      "AES-KW" => Ok(KeyWrapAlgorithm::AesKw),
+      "AES-KWP" => Ok(KeyWrapAlgorithm::AesKwp),
+      _ => bail!("Unsupported key wrap: {}", s),
+    }
+  }
+}
+
+impl Display for KeyWrapAlgorithm {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      KeyWrapAlgorithm::AesKw => write!(f, "AES-KW"),
+      KeyWrapAlgorithm::AesKwp => write!(f, "AES-KWP"),
+    }
+  }
+}
+
+impl KeyWrapAlgorithm {
+  pub fn wrap(&self, kek: &[u8], key: &[u8]) -> Result<Vec<u8>> {
+    match kek.len() {
+      16 => self.wrap_with(&Kek::<Aes128>::try_from(kek)?, key),
+      24 => self.wrap_with(&Kek::<Aes192>::try_from(kek)?, key),
+      32 => self.wrap_with(&Kek::<Aes256>::try_from(kek)?, key),
+      len => bail!("Invalid KEK length: {len}"),
+    }
+  }
+
+  /// Unwrap the key, failing if the integrity check does not pass, i.e., with a wrong KEK or a modified key
+  pub fn unwrap(&self, kek: &[u8], wrapped: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+    match kek.len() {
+      16 => self.unwrap_with(&Kek::<Aes128>::try_from(kek)?, wrapped),
+      24 => self.unwrap_with(&Kek::<Aes192>::try_from(kek)?, wrapped),
+      32 => self.unwrap_with(&Kek::<Aes256>::try_from(kek)?, wrapped),
+      len => bail!("Invalid KEK length: {len}"),
+    }
+  }
+
+  fn wrap_with<A: KekCipher>(&self, kek: &Kek<A>, key: &[u8]) -> Result<Vec<u8>> {
+    match self {
+      KeyWrapAlgorithm::AesKw => kek.wrap_vec(key),
+      KeyWrapAlgorithm::AesKwp => kek.wrap_with_padding_vec(key),
+    }
+    .map_err(|e| anyhow!("{self} failed: {e}"))
+  }
+
+  fn unwrap_with<A: KekCipher>(&self, kek: &Kek<A>, wrapped: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+    match self {
+      KeyWrapAlgorithm::AesKw => kek.unwrap_vec(wrapped),
+      KeyWrapAlgorithm::AesKwp => kek.unwrap_with_padding_vec(wrapped),
+    }
+    .map(Zeroizing::new)
+    .map_err(|e| anyhow!("{self} failed: {e}"))
+  }
+}
+
+/// Block ciphers usable as the KEK
+trait KekCipher:
+  aes::cipher::KeyInit
+  + aes::cipher::BlockCipher
+  + aes::cipher::BlockSizeUser<BlockSize = aes::cipher::consts::U16>
+  + aes::cipher::BlockEncrypt
+  + aes::cipher::BlockDecrypt
+{
+}
+
+impl KekCipher for Aes128 {}
+impl KekCipher for Aes192 {}
+impl KekCipher for Aes256 {}
+
+/// Generate a random data-encryption key
+pub fn random_dek() -> Zeroizing<Vec<u8>> {
+  let mut dek = Zeroizing::new(vec![0u8; DEK_LEN]);
+  rand::rng().fill_bytes(&mut dek);
+  dek
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use hex_literal::hex;
+
+  #[test]
+  fn aes_kw_rfc3394_vectors() -> Result<()> {
+    let kek = hex!("000102030405060708090A0B0C0D0E0F101112131415161718191A1B1C1D1E1F");
+    let data = hex!("00112233445566778899AABBCCDDEEFF000102030405060708090A0B0C0D0E0F");
+    // Sections 4.1 to 4.6 of RFC 3394
+    let vectors: [(&[u8], &[u8], &[u8]); 6] = [
+      (
+        &kek[..16],
+        &data[..16],
+        &hex!("1FA68B0A8112B447AEF34BD8FB5A7B829D3E862371D2CFE5"),
+      ),
+      (
+        &kek[..24],
+        &data[..16],
+        &hex!("96778B25AE6CA435F92B5B97C050AED2468AB8A17AD84E5D"),
+      ),
+      (
+        &kek,
+        &data[..16],
+        &hex!("64E8C3F9CE0F5BA263E9777905818A2A93C8191E7D6E8AE7"),
+      ),
+      (
+        &kek[..24],
+        &data[..24],
+        &hex!("031D33264E15D33268F24EC260743EDCE1C6C7DDEE725A936BA814915C6762D2"),
+      ),
+      (
+        &kek,
+        &data[..24],
+        &hex!("A8F9BC1612C68B3FF6E6F4FBE30E71E4769C8B80A32CB8958CD5D17D6B254DA1"),
+      ),
+      (
+        &kek,
+        &data,
+        &hex!("28C9F404C4B810F4CBCCB35CFB87F8263F5786E2D80ED326CBC7F0E71A99F43BFB988B9B7A02DD21"),
+      ),
+    ];
+    let kw = KeyWrapAlgorithm::AesKw;
+    for (kek, key, wrapped) in vectors {
+      assert_eq!(kw.wrap(kek, key)?, wrapped);
+      assert_eq!(kw.unwrap(kek, wrapped)?.as_slice(), key);
+    }
+    Ok(())
+  }
+
+  #[test]
+  fn aes_kwp_rfc5649_vectors() -> Result<()> {
+    let kek = hex!("5840df6e29b02af1ab493b705bf16ea1ae8338f4dcc176a8");
+    // Section 6 of RFC 5649, where a key of 7 octets is wrapped into a single block
+    let vectors: [(&[u8], &[u8]); 2] = [
+      (
+        &hex!("c37b7e6492584340bed12207808941155068f738"),
+        &hex!("138bdeaa9b8fa7fc61f97742e72248ee5ae6ae5360d1ae6a5f54f373fa543b6a"),
+      ),
+      (&hex!("466f7250617369"), &hex!("afbeb0f07dfbf5419200f2ccb50bb24f")),
+    ];
+    let kwp = KeyWrapAlgorithm::AesKwp;
+    for (key, wrapped) in vectors {
+      assert_eq!(kwp.wrap(&kek, key)?, wrapped);
+      assert_eq!(kwp.unwrap(&kek, wrapped)?.as_slice(), key);
+    }
+    Ok(())
+  }
+
+  #[test]
+  fn unwrap_detects_wrong_kek_and_tampering() -> Result<()> {
+    let (kek, other) = ([1u8; 32], [2u8; 32]);
+    let dek = random_dek();
+    for alg in [KeyWrapAlgorithm::AesKw, KeyWrapAlgorithm::AesKwp] {
+      let wrapped = alg.wrap(&kek, &dek)?;
+      assert_eq!(wrapped.len(), DEK_LEN + KW_OVERHEAD);
+      assert!(alg.unwrap(&other, &wrapped).is_err());
+      let mut tampered = wrapped.clone();
+      tampered[KW_OVERHEAD] ^= 1;
+      assert!(alg.unwrap(&kek, &tampered).is_err());
+      assert_eq!(alg.to_string().parse::<KeyWrapAlgorithm>()?, alg);
+    }
+    assert!(KeyWrapAlgorithm::AesKw.wrap(&kek, &dek[..20]).is_err());
+    assert!(KeyWrapAlgorithm::AesKw.wrap(&kek[..20], &dek).is_err());
+    Ok(())
+  }
+}
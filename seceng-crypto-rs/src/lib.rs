@@ -0,0 +1,29 @@
+//! Cryptographic primitives shared by the Rust samples of the lecture.
+//!
+//! - [`symmetric`]: AES-256-CBC, AES-256-GCM and encrypt-then-MAC
+//! - [`kdf`]: PBKDF2, HKDF, Argon2id and scrypt with self-describing parameters
+//! - [`calibrate`]: Costs of the password KDFs calibrated to a target derivation time
+//! - [`hash`]: SHA-2/SHA-3 hashes and HMAC
+//! - [`rsa`]: RSA-OAEP encryption and RSASSA-PSS signatures
+//! - [`ecc`]: ECDH and ECDSA over P-256, P-384, P-521 and secp256k1, X25519 and Ed25519
+//! - [`pem`]: PEM of keys, and keys given in PEM, raw DER or hex including the legacy PKCS#1 and SEC1
+//! - [`hpke`]: Hybrid Public Key Encryption (RFC 9180) over P-256 and P-384 in all the four modes
+//! - [`keywrap`]: AES key wrap (RFC 3394) and AES key wrap with padding (RFC 5649)
+//! - [`stream`]: Chunked AES-256-GCM in the manner of STREAM for large files
+//! - [`envelope`]: Versioned ciphertext envelope in JSON, msgpack or CBOR, readable from the legacy formats
+//!
+//! The CLIs `cli03` to `cli06` are thin front ends of this crate.
+
+pub mod calibrate;
+pub mod ecc;
+pub mod envelope;
+pub mod error;
+pub mod hash;
+pub mod hpke;
+pub mod kdf;
+pub mod keywrap;
+pub mod pem;
+pub mod rsa;
+pub mod stream;
+pub mod symmetric;
+pub mod util;
@@ -1,20 +1,20 @@
-use crate::error::*;
+use crate::{error::*, pem};
 use rsa::{
   pkcs8::{DecodePrivateKey, DecodePublicKey, EncodePrivateKey, EncodePublicKey},
   pss::{BlindedSigningKey, Signature, VerifyingKey},
+  rand_core::OsRng,
   signature::{RandomizedSigner, SignatureEncoding, Verifier},
   Oaep, RsaPrivateKey, RsaPublicKey,
-  rand_core::OsRng
 };
 use sha2::Sha256;
 
+/// RSA key pair for RSA-OAEP and RSASSA-PSS with SHA-256. The private key is absent for an imported public key.
 #[derive(Debug)]
 pub struct RsaKeyPair {
   pub public: RsaPublicKey,
   pub private: Option<RsaPrivateKey>,
 }
 
-#[allow(dead_code)]
 impl RsaKeyPair {
   pub fn new(bits: &usize) -> Result<Self> {
     let mut rng = OsRng;
@@ -56,6 +56,24 @@ impl RsaKeyPair {
     })
   }
 
+  pub fn to_spki_public_pem(&self) -> Result<String> {
+    pem::encode(pem::PUBLIC_KEY_LABEL, &self.to_spki_public_der()?)
+  }
+
+  pub fn to_pkcs8_private_pem(&self) -> Result<String> {
+    pem::encode(pem::PRIVATE_KEY_LABEL, &self.to_pkcs8_private_der()?)
+  }
+
+  /// Import the public key in PEM, DER or hex of DER
+  pub fn from_public_key(input: &[u8]) -> Result<RsaKeyPair> {
+    Self::from_spki_public_der(&pem::public_key_der(input)?)
+  }
+
+  /// Import the private key in PEM, DER or hex of DER, either of PKCS#8 or PKCS#1
+  pub fn from_private_key(input: &[u8]) -> Result<RsaKeyPair> {
+    Self::from_pkcs8_private_der(&pem::private_key_der(input)?)
+  }
+
   pub fn oaep_encrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
     let mut rng = OsRng;
     let padding = Oaep::new::<sha2::Sha256>();
@@ -91,7 +109,6 @@ impl RsaKeyPair {
 #[cfg(test)]
 mod tests {
   use super::*;
-  use crate::util::*;
 
   #[test]
   fn test_keygen() {
@@ -137,11 +154,4 @@ mod tests {
 
     assert_eq!(plaintext.to_string(), plaintext2);
   }
-
-  #[test]
-  fn test_hex() {
-    let v: [u8; 3] = [0x01, 0x02, 0x03];
-    let s = v.to_vec().to_hex_string();
-    assert_eq!(s, "010203")
-  }
 }
@@ -0,0 +1,291 @@
+//! Chunked AEAD for files too large to encrypt in memory, in the manner of the STREAM construction.
+//!
+//! The plaintext is split into chunks of a fixed length, each of which is encrypted by AES-256-GCM with the nonce
+//! `prefix (7 bytes) || counter (4 bytes, big endian) || last (1 byte)`, where `last` is 1 only for the final chunk.
+//! The final chunk is shorter than the others, and is empty if the plaintext is a multiple of the chunk length.
+//! Reordered chunks fail by the counter, and a stream truncated at a chunk boundary fails by the flag.
+//! The header is the associated data of every chunk, so that its KDF params and chunk length are authenticated.
+
+use crate::{error::*, kdf::KdfParams, symmetric::KEY_LEN};
+use aes_gcm::{aead::AeadInPlace, Aes256Gcm, KeyInit, Nonce};
+use base64::{engine::general_purpose, Engine as _};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::io::{ErrorKind, Read, Write};
+
+/// Magic bytes at the beginning of a stream, followed by the header length (4 bytes, big endian) and the header
+pub const STREAM_MAGIC: &[u8; 8] = b"SECENGST";
+/// Current version of the stream, recorded as `v`
+pub const STREAM_VERSION: u32 = 1;
+/// Default plaintext length of a chunk
+pub const DEFAULT_CHUNK_LEN: u32 = 64 * 1024;
+/// Upper bound of the chunk length, which bounds the memory used to decrypt an untrusted stream
+pub const MAX_CHUNK_LEN: u32 = 16 * 1024 * 1024;
+const NONCE_PREFIX_LEN: usize = 7;
+const TAG_LEN: usize = 16;
+const MAX_HEADER_LEN: usize = 64 * 1024;
+
+/// Header of the stream, authenticated as the associated data of every chunk
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct StreamHeader {
+  pub v: u32,
+  /// Only `AES-GCM` for now
+  pub cipher: String,
+  /// How the key was derived from the password or master secret
+  pub kdf: KdfParams,
+  /// Plaintext length of a chunk
+  pub chunk: u32,
+  /// Random nonce prefix in Base64
+  pub nonce: String,
+}
+
+impl StreamHeader {
+  /// Header with a random nonce prefix
+  pub fn new(kdf: &KdfParams, chunk: u32) -> Result<Self> {
+    let mut prefix = [0u8; NONCE_PREFIX_LEN];
+    rand::rng().fill_bytes(&mut prefix);
+    let header = Self {
+      v: STREAM_VERSION,
+      cipher: "AES-GCM".to_string(),
+      kdf: kdf.clone(),
+      chunk,
+      nonce: general_purpose::STANDARD.encode(prefix),
+    };
+    header.validate()?;
+    Ok(header)
+  }
+
+  pub fn validate(&self) -> Result<()> {
+    ensure!(self.v == STREAM_VERSION, "Unsupported stream version: {}", self.v);
+    ensure!(self.cipher == "AES-GCM", "Unsupported stream cipher: {}", self.cipher);
+    ensure!(
+      (1..=MAX_CHUNK_LEN).contains(&self.chunk),
+      "Chunk length must be between 1 and {MAX_CHUNK_LEN}"
+    );
+    ensure!(self.prefix()?.len() == NONCE_PREFIX_LEN, "Invalid nonce prefix length");
+    self.kdf.validate()
+  }
+
+  fn prefix(&self) -> Result<Vec<u8>> {
+    Ok(general_purpose::STANDARD.decode(&self.nonce)?)
+  }
+
+  /// Magic bytes, header length and header, which are also the associated data
+  pub fn encode(&self) -> Result<Vec<u8>> {
+    let json = serde_json::to_vec(self)?;
+    Ok([STREAM_MAGIC.as_slice(), &(json.len() as u32).to_be_bytes(), &json].concat())
+  }
+
+  /// Read the header from the beginning of the stream, returning it with its raw bytes as the associated data
+  pub fn read(reader: &mut impl Read) -> Result<(Self, Vec<u8>)> {
+    let mut prelude = [0u8; 12];
+    reader.read_exact(&mut prelude).context("Not a stream")?;
+    ensure!(prelude.starts_with(STREAM_MAGIC), "Not a stream");
+    let len = u32::from_be_bytes(prelude[8..].try_into()?) as usize;
+    ensure!(len <= MAX_HEADER_LEN, "Stream header is too long");
+    let mut json = vec![0u8; len];
+    reader.read_exact(&mut json).context("Stream header is truncated")?;
+    let header: Self = serde_json::from_slice(&json)?;
+    header.validate()?;
+    Ok((header, [prelude.as_slice(), &json].concat()))
+  }
+
+  fn nonce(&self, counter: u32, last: bool) -> Result<[u8; 12]> {
+    let mut nonce = [0u8; 12];
+    nonce[..NONCE_PREFIX_LEN].copy_from_slice(&self.prefix()?);
+    nonce[NONCE_PREFIX_LEN..11].copy_from_slice(&counter.to_be_bytes());
+    nonce[11] = last as u8;
+    Ok(nonce)
+  }
+}
+
+/// Encrypt the reader into the writer chunk by chunk after writing the header, and return the plaintext length.
+/// Only a chunk is held in memory at a time.
+pub fn encrypt_stream(
+  reader: &mut impl Read,
+  writer: &mut impl Write,
+  key: &[u8],
+  header: &StreamHeader,
+) -> Result<u64> {
+  ensure!(key.len() == KEY_LEN, "Invalid key length for AES-256");
+  header.validate()?;
+  let cipher = Aes256Gcm::new_from_slice(key).map_err(|e| anyhow!(e))?;
+  let aad = header.encode()?;
+  writer.write_all(&aad)?;
+
+  let chunk = header.chunk as usize;
+  let mut buf = Vec::with_capacity(chunk + TAG_LEN);
+  let mut total = 0u64;
+  for counter in 0..=u32::MAX {
+    buf.resize(chunk, 0);
+    let len = read_full(reader, &mut buf)?;
+    buf.truncate(len);
+    let last = len < chunk;
+    let nonce = header.nonce(counter, last)?;
+    cipher
+      .encrypt_in_place(Nonce::from_slice(&nonce), &aad, &mut buf)
+      .map_err(|e| anyhow!(e))?;
+    writer.write_all(&buf)?;
+    total += len as u64;
+    if last {
+      writer.flush()?;
+      return Ok(total);
+    }
+  }
+  bail!("Too many chunks")
+}
+
+/// Decrypt the chunks following the header into the writer, and return the plaintext length.
+/// Chunks are written as soon as each is authenticated, so the output must be discarded on error.
+pub fn decrypt_stream(
+  reader: &mut impl Read,
+  writer: &mut impl Write,
+  key: &[u8],
+  header: &StreamHeader,
+  aad: &[u8],
+) -> Result<u64> {
+  ensure!(key.len() == KEY_LEN, "Invalid key length for AES-256");
+  let cipher = Aes256Gcm::new_from_slice(key).map_err(|e| anyhow!(e))?;
+
+  let chunk = header.chunk as usize + TAG_LEN;
+  let mut buf = Vec::with_capacity(chunk);
+  let mut total = 0u64;
+  for counter in 0..=u32::MAX {
+    buf.resize(chunk, 0);
+    let len = read_full(reader, &mut buf)?;
+    buf.truncate(len);
+    let last = len < chunk;
+    ensure!(len >= TAG_LEN, "Stream is truncated at chunk {counter}");
+    let nonce = header.nonce(counter, last)?;
+    cipher
+      .decrypt_in_place(Nonce::from_slice(&nonce), aad, &mut buf)
+      .map_err(|_| {
+        anyhow!("Failed to authenticate chunk {counter}. The stream is truncated, reordered or modified.")
+      })?;
+    writer.write_all(&buf)?;
+    total += buf.len() as u64;
+    if last {
+      writer.flush()?;
+      return Ok(total);
+    }
+  }
+  bail!("Too many chunks")
+}
+
+/// Fill the buffer unless the reader reaches its end, and return the length read
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> Result<usize> {
+  let mut len = 0;
+  while len < buf.len() {
+    match reader.read(&mut buf[len..]) {
+      Ok(0) => break,
+      Ok(n) => len += n,
+      Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+      Err(e) => return Err(e.into()),
+    }
+  }
+  Ok(len)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::kdf::{random_salt, KdfHash};
+
+  const KEY: [u8; KEY_LEN] = [7u8; KEY_LEN];
+  const CHUNK: u32 = 32;
+
+  fn header() -> Result<StreamHeader> {
+    StreamHeader::new(&KdfParams::new_hkdf(&random_salt(), &KdfHash::Sha256, None), CHUNK)
+  }
+
+  fn encrypt(data: &[u8], header: &StreamHeader) -> Result<Vec<u8>> {
+    let mut encrypted = vec![];
+    encrypt_stream(&mut &data[..], &mut encrypted, &KEY, header)?;
+    Ok(encrypted)
+  }
+
+  fn decrypt(encrypted: &[u8], key: &[u8]) -> Result<Vec<u8>> {
+    let mut reader = encrypted;
+    let (header, aad) = StreamHeader::read(&mut reader)?;
+    let mut decrypted = vec![];
+    decrypt_stream(&mut reader, &mut decrypted, key, &header, &aad)?;
+    Ok(decrypted)
+  }
+
+  #[test]
+  fn stream_round_trip() -> Result<()> {
+    let header = header()?;
+    let head_len = header.encode()?.len();
+    for len in [0, 1, 31, 32, 33, 64, 100] {
+      let data = (0..len).map(|i| i as u8).collect::<Vec<_>>();
+      let encrypted = encrypt(&data, &header)?;
+      // A tag per chunk, including the final one that is empty for a multiple of the chunk length
+      let chunks = len / CHUNK as usize + 1;
+      assert_eq!(encrypted.len(), head_len + len + chunks * TAG_LEN);
+      assert_eq!(decrypt(&encrypted, &KEY)?, data);
+    }
+    assert!(decrypt(&encrypt(b"data", &header)?, &[8u8; KEY_LEN]).is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn stream_detects_truncation_reordering_and_tampering() -> Result<()> {
+    let header = header()?;
+    let head_len = header.encode()?.len();
+    let data = [0x55u8; 100];
+    let encrypted = encrypt(&data, &header)?;
+    let full = CHUNK as usize + TAG_LEN;
+    let chunk = |i: usize| &encrypted[head_len + i * full..(head_len + (i + 1) * full).min(encrypted.len())];
+
+    // Truncated at a chunk boundary and in the middle of a chunk
+    assert!(decrypt(&encrypted[..head_len + 3 * full], &KEY).is_err());
+    assert!(decrypt(&encrypted[..head_len + 2 * full], &KEY).is_err());
+    assert!(decrypt(&encrypted[..encrypted.len() - 1], &KEY).is_err());
+    // Reordered, duplicated and appended chunks
+    let reordered = [&encrypted[..head_len], chunk(1), chunk(0), chunk(2), chunk(3)].concat();
+    assert!(decrypt(&reordered, &KEY).is_err());
+    let duplicated = [&encrypted[..head_len], chunk(0), chunk(0), chunk(1), chunk(2), chunk(3)].concat();
+    assert!(decrypt(&duplicated, &KEY).is_err());
+    assert!(decrypt(&[encrypted.as_slice(), &[0u8]].concat(), &KEY).is_err());
+    // Modified chunk and header
+    let mut modified = encrypted.clone();
+    modified[head_len + 1] ^= 1;
+    assert!(decrypt(&modified, &KEY).is_err());
+    let other = StreamHeader {
+      chunk: CHUNK + 1,
+      ..header.clone()
+    };
+    let swapped = [other.encode()?.as_slice(), &encrypted[head_len..]].concat();
+    assert!(decrypt(&swapped, &KEY).is_err());
+    assert!(decrypt(b"SECENGST", &KEY).is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn stream_header_is_validated() -> Result<()> {
+    let header = header()?;
+    assert!(StreamHeader {
+      chunk: 0,
+      ..header.clone()
+    }
+    .validate()
+    .is_err());
+    assert!(StreamHeader {
+      chunk: MAX_CHUNK_LEN + 1,
+      ..header.clone()
+    }
+    .validate()
+    .is_err());
+    assert!(StreamHeader { v: 2, ..header.clone() }.validate().is_err());
+    assert!(StreamHeader {
+      nonce: "AAAA".to_string(),
+      ..header.clone()
+    }
+    .validate()
+    .is_err());
+    let (read, aad) = StreamHeader::read(&mut header.encode()?.as_slice())?;
+    assert_eq!((read, aad), (header.clone(), header.encode()?));
+    Ok(())
+  }
+}
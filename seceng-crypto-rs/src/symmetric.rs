@@ -0,0 +1,345 @@
+use crate::error::*;
+use aes::cipher::{
+  block_padding::Pkcs7,
+  generic_array::{
+    typenum::{U16, U32},
+    GenericArray,
+  },
+  BlockDecryptMut, BlockEncryptMut, KeyIvInit,
+};
+use aes_gcm::{aead::Aead, Aes256Gcm, KeyInit, Nonce};
+use base64::{engine::general_purpose, Engine as _};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::Sha256;
+use std::{fmt::Display, str::FromStr};
+type Aes256CbcEnc = cbc::Encryptor<aes::Aes256>;
+type Aes256CbcDec = cbc::Decryptor<aes::Aes256>;
+type HmacSha256 = Hmac<Sha256>;
+
+/// Key length of AES-256
+pub const KEY_LEN: usize = 32;
+/// IV length of AES-CBC
+pub const CBC_IV_LEN: usize = 16;
+/// Nonce length of AES-GCM
+pub const GCM_NONCE_LEN: usize = 12;
+
+/// Symmetric encryption algorithm, recorded as `alg` in the posted object
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Algorithm {
+  /// AES-256-CBC with PKCS#7 padding (unauthenticated, legacy default)
+  #[default]
+  AesCbc,
+  /// AES-256-GCM with 96-bit nonce and 128-bit tag appended to the ciphertext
+  AesGcm,
+}
+
+impl FromStr for Algorithm {
+  type Err = anyhow::Error;
+  fn from_str(s: &str) -> Result<Self> {
+    match s {
+      "AES-CBC" => Ok(Algorithm::AesCbc),
+      "AES-GCM" => Ok(Algorithm::AesGcm),
+      _ => bail!("Unsupported algorithm: {}", s),
+    }
+  }
+}
+
+impl Display for Algorithm {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Algorithm::AesCbc => write!(f, "AES-CBC"),
+      Algorithm::AesGcm => write!(f, "AES-GCM"),
+    }
+  }
+}
+
+impl Algorithm {
+  pub fn encrypt(&self, data: &[u8], key: &[u8], iv: Option<&[u8]>) -> Result<Encrypted> {
+    match self {
+      Algorithm::AesCbc => encrypt(data, key, iv),
+      Algorithm::AesGcm => encrypt_gcm(data, key, iv),
+    }
+  }
+
+  pub fn decrypt(&self, encrypted: &Encrypted, key: &[u8]) -> Result<Vec<u8>> {
+    match self {
+      Algorithm::AesCbc => decrypt(encrypted, key),
+      Algorithm::AesGcm => decrypt_gcm(encrypted, key),
+    }
+  }
+}
+
+/// Ciphertext with its IV (or nonce for AES-GCM)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Encrypted {
+  pub data: Vec<u8>,
+  pub iv: Vec<u8>,
+}
+
+impl Encrypted {
+  pub fn data_to_base64(&self) -> String {
+    general_purpose::STANDARD.encode(&self.data)
+  }
+  pub fn iv_to_base64(&self) -> String {
+    general_purpose::STANDARD.encode(&self.iv)
+  }
+}
+
+/// Encrypt with AES-256-CBC and PKCS#7 padding. A random IV is used if `iv` is `None`.
+pub fn encrypt(data: &[u8], key: &[u8], iv: Option<&[u8]>) -> Result<Encrypted> {
+  ensure!(key.len() == KEY_LEN, "Invalid key length for AES-256");
+  let key_array: &GenericArray<u8, U32> = GenericArray::from_slice(key);
+  let iv = random_or_given(iv, CBC_IV_LEN);
+  ensure!(iv.len() == CBC_IV_LEN, "Invalid IV length for AES-CBC");
+  let iv: &GenericArray<u8, U16> = GenericArray::from_slice(&iv);
+
+  let encrypted = Aes256CbcEnc::new(key_array, iv).encrypt_padded_vec_mut::<Pkcs7>(data);
+
+  Ok(Encrypted {
+    data: encrypted,
+    iv: iv.to_vec(),
+  })
+}
+
+/// Decrypt with AES-256-CBC and remove the PKCS#7 padding
+pub fn decrypt(encrypted: &Encrypted, key: &[u8]) -> Result<Vec<u8>> {
+  ensure!(key.len() == KEY_LEN, "Invalid key length for AES-256");
+  ensure!(encrypted.iv.len() == CBC_IV_LEN, "Invalid IV length for AES-CBC");
+  let key_array: &GenericArray<u8, U32> = GenericArray::from_slice(key);
+  let iv: &GenericArray<u8, U16> = GenericArray::from_slice(&encrypted.iv);
+  Aes256CbcDec::new(key_array, iv)
+    .decrypt_padded_vec_mut::<Pkcs7>(&encrypted.data)
+    .map_err(|e| anyhow!(e))
+}
+
+/// Encrypt with AES-256-GCM. The authentication tag is appended to `data`.
+pub fn encrypt_gcm(data: &[u8], key: &[u8], iv: Option<&[u8]>) -> Result<Encrypted> {
+  let iv = random_or_given(iv, GCM_NONCE_LEN);
+  ensure!(iv.len() == GCM_NONCE_LEN, "Invalid nonce length for AES-GCM");
+
+  let cipher = Aes256Gcm::new_from_slice(key).map_err(|e| anyhow!(e))?;
+  let encrypted = cipher.encrypt(Nonce::from_slice(&iv), data).map_err(|e| anyhow!(e))?;
+
+  Ok(Encrypted { data: encrypted, iv })
+}
+
+/// Decrypt with AES-256-GCM, failing if the ciphertext, nonce or tag has been modified.
+pub fn decrypt_gcm(encrypted: &Encrypted, key: &[u8]) -> Result<Vec<u8>> {
+  ensure!(encrypted.iv.len() == GCM_NONCE_LEN, "Invalid nonce length for AES-GCM");
+  let cipher = Aes256Gcm::new_from_slice(key).map_err(|e| anyhow!(e))?;
+  cipher
+    .decrypt(Nonce::from_slice(&encrypted.iv), encrypted.data.as_slice())
+    .map_err(|_| anyhow!("Failed to authenticate the encrypted data"))
+}
+
+/// Encrypt by AES-CBC and then compute HMAC-SHA-256 over IV || ciphertext.
+/// The keys must be independent, e.g., split by `BinaryKey::split_etm`.
+pub fn encrypt_then_mac(
+  data: &[u8],
+  enc_key: &[u8],
+  mac_key: &[u8],
+  iv: Option<&[u8]>,
+) -> Result<(Encrypted, Vec<u8>)> {
+  let encrypted = encrypt(data, enc_key, iv)?;
+  let mac = new_mac(&encrypted, mac_key)?.finalize().into_bytes().to_vec();
+  Ok((encrypted, mac))
+}
+
+/// Verify the HMAC-SHA-256 tag in constant time, and decrypt by AES-CBC only if it is valid.
+/// A tag mismatch is reported as `CryptoError::InvalidMac` instead of a padding error.
+pub fn verify_then_decrypt(encrypted: &Encrypted, mac: &[u8], enc_key: &[u8], mac_key: &[u8]) -> Result<Vec<u8>> {
+  new_mac(encrypted, mac_key)?
+    .verify_slice(mac)
+    .map_err(|_| CryptoError::InvalidMac)?;
+  decrypt(encrypted, enc_key)
+}
+
+fn new_mac(encrypted: &Encrypted, mac_key: &[u8]) -> Result<HmacSha256> {
+  let mut mac = <HmacSha256 as Mac>::new_from_slice(mac_key)?;
+  mac.update(&encrypted.iv);
+  mac.update(&encrypted.data);
+  Ok(mac)
+}
+
+fn random_or_given(iv: Option<&[u8]>, len: usize) -> Vec<u8> {
+  match iv {
+    Some(v) => v.to_vec(),
+    None => {
+      let mut iv = vec![0u8; len];
+      rand::rng().fill_bytes(&mut iv);
+      iv
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::kdf::BinaryKey;
+  use hex_literal::hex;
+
+  #[test]
+  fn aes_cbc_works() -> Result<()> {
+    let data = b"secret".as_slice();
+    let key = BinaryKey::try_new_pbkdf2("password", 32, None, None, None)?;
+    let encrypted = encrypt(data, &key.key, None)?;
+    let decrypted = decrypt(&encrypted, &key.key)?;
+
+    assert_eq!(&decrypted, data);
+    Ok(())
+  }
+
+  #[test]
+  fn aes_cbc_test_vector() -> Result<()> {
+    let data = b"hello my super secret world!!!";
+    let salt = general_purpose::STANDARD.decode("jbfL016yS9RUb8Sf+6m+Pm2L1Io7u1SpqHsr+R6RTu4=")?;
+    let key = BinaryKey::try_new_pbkdf2("my secret key", 32, Some(&salt), None, None)?;
+    let iv = general_purpose::STANDARD.decode("zuwTPW7nrWon6nEhyrzzxA==")?;
+    let encrypted_data = general_purpose::STANDARD.decode("EoeSsv5BFr6s1jZh3iMM1Pxa+wA4UxQnM30J2027kJU=")?;
+
+    let encrypted = encrypt(data, &key.key, Some(&iv))?;
+
+    assert_eq!(encrypted.data, encrypted_data);
+    assert_eq!(iv, encrypted.iv);
+
+    let dec = decrypt(
+      &Encrypted {
+        data: encrypted_data,
+        iv,
+      },
+      &key.key,
+    )?;
+
+    assert_eq!(data.as_slice(), &dec);
+    Ok(())
+  }
+
+  #[test]
+  fn aes_cbc_hkdf_test_vector() -> Result<()> {
+    // The vector of the original sample-05, whose key is derived by HKDF from a raw master secret
+    let data = b"hello my super secret world!!!";
+    let salt = hex!("8db7cbd35eb24bd4546fc49ffba9be3e6d8bd48a3bbb54a9a87b2bf91e914eee");
+    let key = BinaryKey::try_new_hkdf(b"my secret key", 32, Some(&salt), None)?;
+
+    let iv = hex!("ceec133d6ee7ad6a27ea7121cabcf3c4");
+    let encrypted_data = hex!("ea581e08b09f990ea4b68cb5fc119e773fb4103399cb15c6f5991b50daafe6e0");
+
+    let encrypted = encrypt(data, &key.key, Some(&iv))?;
+    assert_eq!(encrypted.data, encrypted_data);
+    assert_eq!(decrypt(&encrypted, &key.key)?, data);
+    Ok(())
+  }
+
+  #[test]
+  fn aes_cbc_rejects_invalid_lengths() {
+    assert!(encrypt(b"data", &[0u8; 16], None).is_err());
+    assert!(encrypt(b"data", &[0u8; 32], Some(&[0u8; 12])).is_err());
+    let encrypted = Encrypted {
+      data: vec![0u8; 16],
+      iv: vec![0u8; 8],
+    };
+    assert!(decrypt(&encrypted, &[0u8; 32]).is_err());
+  }
+
+  #[test]
+  fn aes_gcm_works() -> Result<()> {
+    let data = b"secret".as_slice();
+    let key = BinaryKey::try_new_pbkdf2("password", 32, None, None, None)?;
+    let encrypted = encrypt_gcm(data, &key.key, None)?;
+    assert_eq!(encrypted.iv.len(), GCM_NONCE_LEN);
+    assert_eq!(encrypted.data.len(), data.len() + 16);
+
+    let decrypted = decrypt_gcm(&encrypted, &key.key)?;
+    assert_eq!(&decrypted, data);
+    Ok(())
+  }
+
+  #[test]
+  fn aes_gcm_detects_tampering() -> Result<()> {
+    let data = b"hello my super secret world!!!";
+    let key = BinaryKey::try_new_pbkdf2("password", 32, None, None, None)?;
+    let mut encrypted = encrypt_gcm(data, &key.key, None)?;
+    encrypted.data[0] ^= 0x01;
+    assert!(decrypt_gcm(&encrypted, &key.key).is_err());
+
+    let wrong_key = BinaryKey::try_new_pbkdf2("wrong password", 32, Some(&key.salt), None, None)?;
+    encrypted.data[0] ^= 0x01;
+    assert!(decrypt_gcm(&encrypted, &wrong_key.key).is_err());
+    assert!(decrypt_gcm(&encrypted, &key.key).is_ok());
+    Ok(())
+  }
+
+  #[test]
+  fn aes_gcm_test_vector() -> Result<()> {
+    // NIST GCM test case 14 (AES-256, zero key/nonce, one zero block)
+    let iv = [0u8; GCM_NONCE_LEN];
+    let encrypted = encrypt_gcm(&[0u8; 16], &[0u8; 32], Some(&iv))?;
+    assert_eq!(
+      encrypted.data,
+      hex!("cea7403d4d606b6e074ec5d3baf39d18d0d1c8a799996bf0265b98b5d48ab919")
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn algorithm_dispatch() -> Result<()> {
+    let data = b"hello".as_slice();
+    let key = BinaryKey::try_new_pbkdf2("password", 32, None, None, None)?;
+    for alg in ["AES-CBC", "AES-GCM"] {
+      let alg = alg.parse::<Algorithm>()?;
+      let encrypted = alg.encrypt(data, &key.key, None)?;
+      assert_eq!(alg.decrypt(&encrypted, &key.key)?, data);
+    }
+    assert!("AES-ECB".parse::<Algorithm>().is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn aes_cbc_etm_works() -> Result<()> {
+    let data = b"secret".as_slice();
+    let key = BinaryKey::try_new_pbkdf2("password", 32, None, None, None)?;
+    let (enc_key, mac_key) = key.split_etm()?;
+    let (encrypted, mac) = encrypt_then_mac(data, &enc_key.key, &mac_key, None)?;
+    assert_eq!(mac.len(), 32);
+
+    let decrypted = verify_then_decrypt(&encrypted, &mac, &enc_key.key, &mac_key)?;
+    assert_eq!(&decrypted, data);
+
+    // The plain CBC key must differ from the one used inside encrypt-then-MAC
+    assert!(decrypt(&encrypted, &key.key).map_or(true, |d| d != data));
+    Ok(())
+  }
+
+  #[test]
+  fn aes_cbc_etm_detects_tampering() -> Result<()> {
+    let data = b"hello my super secret world!!!";
+    let key = BinaryKey::try_new_pbkdf2("password", 32, None, None, None)?;
+    let (enc_key, mac_key) = key.split_etm()?;
+    let (encrypted, mac) = encrypt_then_mac(data, &enc_key.key, &mac_key, None)?;
+
+    let is_invalid_mac = |e: anyhow::Error| matches!(e.downcast_ref::<CryptoError>(), Some(CryptoError::InvalidMac));
+
+    let mut tampered = encrypted.clone();
+    tampered.data[0] ^= 0x01;
+    assert!(is_invalid_mac(
+      verify_then_decrypt(&tampered, &mac, &enc_key.key, &mac_key).unwrap_err()
+    ));
+
+    tampered.data[0] ^= 0x01;
+    tampered.iv[0] ^= 0x01;
+    assert!(is_invalid_mac(
+      verify_then_decrypt(&tampered, &mac, &enc_key.key, &mac_key).unwrap_err()
+    ));
+
+    let mut wrong_mac = mac.clone();
+    wrong_mac[31] ^= 0x80;
+    assert!(is_invalid_mac(
+      verify_then_decrypt(&encrypted, &wrong_mac, &enc_key.key, &mac_key).unwrap_err()
+    ));
+    assert!(is_invalid_mac(
+      verify_then_decrypt(&encrypted, &mac[..16], &enc_key.key, &mac_key).unwrap_err()
+    ));
+    Ok(())
+  }
+}
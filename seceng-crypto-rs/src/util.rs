@@ -0,0 +1,29 @@
+/// Lowercase hex encoding of binary data
+pub trait ToHexString {
+  fn to_hex_string(&self) -> String;
+}
+
+impl ToHexString for Vec<u8> {
+  fn to_hex_string(&self) -> String {
+    hex::encode(self)
+  }
+}
+
+impl ToHexString for &[u8] {
+  fn to_hex_string(&self) -> String {
+    hex::encode(self)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_hex() {
+    let v: [u8; 3] = [0x01, 0x02, 0x03];
+    let s = v.to_vec().to_hex_string();
+    assert_eq!(s, "010203");
+    assert_eq!(v.as_slice().to_hex_string(), "010203");
+  }
+}
@@ -0,0 +1,772 @@
+//! Known-answer tests driven by the vector files under `tests/vectors`.
+//!
+//! - `cavp/*.rsp`: AES-256-CBC in the NIST CAVP response format
+//! - `wycheproof/*.blb`: upstream Wycheproof vectors of ECDSA, HMAC, HKDF and AES-GCM, in the blobby format
+//!   RustCrypto extracts them into
+//! - `generated/*.json`: supplementary vectors of the other primitives in the Wycheproof schema, generated by
+//!   `generate.py`, including vectors that must be rejected
+//!
+//! Every vector runs against the public API of the crate, and any mismatch fails the test.
+
+use elliptic_curve::{pkcs8::EncodePrivateKey, SecretKey};
+use seceng_crypto::{
+  ecc::{import_pkcs8_der, import_spki_der, EccKeyPairType, NistP256, NistP384, NistP521, Secp256k1},
+  error::*,
+  hash::HashAlgorithm,
+  kdf::{BinaryKey, KdfHash},
+  rsa::RsaKeyPair,
+  symmetric::{decrypt, decrypt_gcm, encrypt, encrypt_gcm, Encrypted},
+};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer};
+use std::{
+  collections::HashMap,
+  fmt::Debug,
+  fs,
+  path::{Path, PathBuf},
+};
+
+fn vectors(dir: &str) -> Result<Vec<PathBuf>> {
+  let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/vectors").join(dir);
+  let mut paths = fs::read_dir(&dir)?
+    .map(|e| e.map(|e| e.path()))
+    .collect::<Result<Vec<_>, _>>()?;
+  paths.sort();
+  Ok(paths)
+}
+
+/// Supplementary vector files generated by `generate.py`
+fn generated(prefix: &str) -> Result<Vec<PathBuf>> {
+  let paths: Vec<_> = vectors("generated")?
+    .into_iter()
+    .filter(|p| p.file_name().unwrap().to_string_lossy().starts_with(prefix))
+    .collect();
+  ensure!(!paths.is_empty(), "No vector file of {prefix}");
+  Ok(paths)
+}
+
+fn from_hex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+  let s = String::deserialize(deserializer)?;
+  hex::decode(s).map_err(serde::de::Error::custom)
+}
+
+/* ---------------------------------------------------------------------------------- */
+// Generated vectors in the Wycheproof schema
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TestFile<P, T> {
+  algorithm: String,
+  number_of_tests: usize,
+  test_groups: Vec<TestGroup<P, T>>,
+}
+
+#[derive(Deserialize)]
+struct TestGroup<P, T> {
+  #[serde(flatten)]
+  params: P,
+  tests: Vec<TestCase<T>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TestCase<T> {
+  tc_id: u32,
+  comment: String,
+  #[serde(default)]
+  flags: Vec<String>,
+  result: Expected,
+  #[serde(flatten)]
+  fields: T,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum Expected {
+  Valid,
+  Invalid,
+  Acceptable,
+}
+
+/// Outcome of a vector the API did not reject
+#[derive(Debug)]
+enum Outcome {
+  Accepted,
+  WrongOutput,
+  /// Parameters the crate does not implement, e.g., labels of RSA-OAEP
+  Unsupported,
+}
+
+fn matches(actual: &[u8], expected: &[u8]) -> Outcome {
+  if actual == expected {
+    Outcome::Accepted
+  } else {
+    Outcome::WrongOutput
+  }
+}
+
+/// Whether the outcome meets the expectation, or `None` for unsupported parameters
+fn verdict(expected: &Expected, outcome: &Result<Outcome>) -> Option<bool> {
+  match (expected, outcome) {
+    (_, Ok(Outcome::Unsupported)) => None,
+    (Expected::Valid, Ok(Outcome::Accepted)) => Some(true),
+    (Expected::Invalid, Err(_)) => Some(true),
+    (Expected::Acceptable, Ok(Outcome::Accepted) | Err(_)) => Some(true),
+    _ => Some(false),
+  }
+}
+
+/// Run `check` over all vectors of a file, where `Err` means the API rejected the vector.
+/// Valid vectors must be accepted with the expected output, and invalid ones must be rejected.
+fn run<P, T>(path: &Path, check: impl Fn(&str, &P, &T) -> Result<Outcome>) -> Result<()>
+where
+  P: DeserializeOwned,
+  T: DeserializeOwned,
+{
+  let name = path.file_name().unwrap().to_string_lossy();
+  let file: TestFile<P, T> = serde_json::from_str(&fs::read_to_string(path)?)?;
+  let (mut count, mut skipped, mut failures) = (0, 0, vec![]);
+  for group in &file.test_groups {
+    for tc in &group.tests {
+      count += 1;
+      let outcome = check(&file.algorithm, &group.params, &tc.fields);
+      let Some(passed) = verdict(&tc.result, &outcome) else {
+        skipped += 1;
+        continue;
+      };
+      if !passed {
+        failures.push(format!(
+          "{name} tcId {} ({}, flags {:?}): expected {:?}, got {:?}",
+          tc.tc_id, tc.comment, tc.flags, tc.result, outcome
+        ));
+      }
+    }
+  }
+  ensure!(
+    count == file.number_of_tests,
+    "{name}: {count} vectors found but numberOfTests is {}",
+    file.number_of_tests
+  );
+  ensure!(count > skipped, "{name}: all vectors are unsupported");
+  ensure!(
+    failures.is_empty(),
+    "{} of {count} vectors failed:\n{}",
+    failures.len(),
+    failures.join("\n")
+  );
+  println!("{name}: {} passed, {skipped} skipped", count - skipped);
+  Ok(())
+}
+
+/// `SHA-256` from the algorithm names like `HMACSHA256`, `HKDF-SHA-256` and `PBKDF2-HMACSHA256`
+fn hash_name(algorithm: &str) -> String {
+  let hash = algorithm.find("SHA").map(|i| &algorithm[i..]).unwrap_or(algorithm);
+  hash.replace("SHA-", "SHA").replace("SHA", "SHA-")
+}
+
+#[derive(Deserialize)]
+struct Ignored {}
+
+#[derive(Deserialize)]
+struct AesCbcTest {
+  #[serde(deserialize_with = "from_hex")]
+  key: Vec<u8>,
+  #[serde(deserialize_with = "from_hex")]
+  iv: Vec<u8>,
+  #[serde(deserialize_with = "from_hex")]
+  msg: Vec<u8>,
+  #[serde(deserialize_with = "from_hex")]
+  ct: Vec<u8>,
+}
+
+#[test]
+fn aes_cbc_pkcs5() -> Result<()> {
+  for path in generated("aes_cbc_pkcs5")? {
+    run(&path, |_, _: &Ignored, t: &AesCbcTest| {
+      if t.key.len() != 32 {
+        return Ok(Outcome::Unsupported);
+      }
+      let encrypted = Encrypted {
+        data: t.ct.clone(),
+        iv: t.iv.clone(),
+      };
+      let decrypted = decrypt(&encrypted, &t.key)?;
+      if decrypted != t.msg {
+        return Ok(Outcome::WrongOutput);
+      }
+      Ok(matches(&encrypt(&t.msg, &t.key, Some(&t.iv))?.data, &t.ct))
+    })?;
+  }
+  Ok(())
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MacGroup {
+  tag_size: usize,
+}
+
+#[derive(Deserialize)]
+struct MacTest {
+  #[serde(deserialize_with = "from_hex")]
+  key: Vec<u8>,
+  #[serde(deserialize_with = "from_hex")]
+  msg: Vec<u8>,
+  #[serde(deserialize_with = "from_hex")]
+  tag: Vec<u8>,
+}
+
+#[test]
+fn hmac() -> Result<()> {
+  for path in generated("hmac_")? {
+    run(&path, |alg, g: &MacGroup, t: &MacTest| {
+      let Ok(hash) = hash_name(alg).parse::<HashAlgorithm>() else {
+        return Ok(Outcome::Unsupported);
+      };
+      let full = hash.hmac(&t.msg, &t.key)?;
+      if g.tag_size == full.len() * 8 {
+        ensure!(hash.verify_hmac(&t.msg, &t.key, &t.tag)?, "HMAC verification failed");
+        return Ok(Outcome::Accepted);
+      }
+      // Truncated tags are compared by the caller of `hmac`
+      ensure!(t.tag.len() * 8 == g.tag_size, "Tag size mismatch");
+      ensure!(full[..t.tag.len()] == t.tag, "HMAC verification failed");
+      Ok(Outcome::Accepted)
+    })?;
+  }
+  Ok(())
+}
+
+#[derive(Deserialize)]
+struct HkdfTest {
+  #[serde(deserialize_with = "from_hex")]
+  ikm: Vec<u8>,
+  #[serde(deserialize_with = "from_hex")]
+  salt: Vec<u8>,
+  #[serde(deserialize_with = "from_hex")]
+  info: Vec<u8>,
+  size: usize,
+  #[serde(deserialize_with = "from_hex")]
+  okm: Vec<u8>,
+}
+
+#[test]
+fn hkdf() -> Result<()> {
+  for path in generated("hkdf_")? {
+    run(&path, |alg, _: &Ignored, t: &HkdfTest| {
+      let Ok(hash) = hash_name(alg).parse::<KdfHash>() else {
+        return Ok(Outcome::Unsupported);
+      };
+      let key = BinaryKey::try_new_hkdf_with_info(&t.ikm, t.size, Some(&t.salt), Some(&hash), &t.info)?;
+      Ok(matches(&key.key, &t.okm))
+    })?;
+  }
+  Ok(())
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PbkdfTest {
+  #[serde(deserialize_with = "from_hex")]
+  password: Vec<u8>,
+  #[serde(deserialize_with = "from_hex")]
+  salt: Vec<u8>,
+  iteration_count: u32,
+  dk_len: usize,
+  #[serde(deserialize_with = "from_hex")]
+  dk: Vec<u8>,
+}
+
+#[test]
+fn pbkdf2() -> Result<()> {
+  for path in generated("pbkdf2_")? {
+    run(&path, |alg, _: &Ignored, t: &PbkdfTest| {
+      // Passwords are strings in the API
+      let (Ok(hash), Ok(password)) = (hash_name(alg).parse::<KdfHash>(), std::str::from_utf8(&t.password)) else {
+        return Ok(Outcome::Unsupported);
+      };
+      let key = BinaryKey::try_new_pbkdf2(password, t.dk_len, Some(&t.salt), Some(&t.iteration_count), Some(&hash))?;
+      Ok(matches(&key.key, &t.dk))
+    })?;
+  }
+  Ok(())
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RsaOaepGroup {
+  sha: String,
+  mgf_sha: String,
+  #[serde(deserialize_with = "from_hex")]
+  private_key_pkcs8: Vec<u8>,
+}
+
+#[derive(Deserialize)]
+struct RsaOaepTest {
+  #[serde(deserialize_with = "from_hex")]
+  msg: Vec<u8>,
+  #[serde(deserialize_with = "from_hex")]
+  ct: Vec<u8>,
+  #[serde(deserialize_with = "from_hex")]
+  label: Vec<u8>,
+}
+
+#[test]
+fn rsa_oaep() -> Result<()> {
+  for path in generated("rsa_oaep_")? {
+    run(&path, |_, g: &RsaOaepGroup, t: &RsaOaepTest| {
+      if g.sha != "SHA-256" || g.mgf_sha != "SHA-256" || !t.label.is_empty() {
+        return Ok(Outcome::Unsupported);
+      }
+      let keypair = RsaKeyPair::from_pkcs8_private_der(&g.private_key_pkcs8)?;
+      Ok(matches(&keypair.oaep_decrypt(&t.ct)?, &t.msg))
+    })?;
+  }
+  Ok(())
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RsaPssGroup {
+  sha: String,
+  mgf_sha: String,
+  s_len: usize,
+  #[serde(deserialize_with = "from_hex")]
+  public_key_der: Vec<u8>,
+}
+
+#[derive(Deserialize)]
+struct SignatureTest {
+  #[serde(deserialize_with = "from_hex")]
+  msg: Vec<u8>,
+  #[serde(deserialize_with = "from_hex")]
+  sig: Vec<u8>,
+}
+
+#[test]
+fn rsa_pss() -> Result<()> {
+  for path in generated("rsa_pss_")? {
+    run(&path, |_, g: &RsaPssGroup, t: &SignatureTest| {
+      if g.sha != "SHA-256" || g.mgf_sha != "SHA-256" || g.s_len != 32 {
+        return Ok(Outcome::Unsupported);
+      }
+      RsaKeyPair::from_spki_public_der(&g.public_key_der)?.pss_verify(&t.msg, &t.sig)?;
+      Ok(Outcome::Accepted)
+    })?;
+  }
+  Ok(())
+}
+
+#[derive(Deserialize)]
+struct EcPublicKey {
+  curve: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EcdsaGroup {
+  public_key: EcPublicKey,
+  #[serde(deserialize_with = "from_hex")]
+  public_key_der: Vec<u8>,
+  sha: String,
+}
+
+#[test]
+fn ecdsa_p1363() -> Result<()> {
+  for path in generated("ecdsa_")? {
+    run(&path, |_, g: &EcdsaGroup, t: &SignatureTest| {
+      // The hash is fixed by the curve in the API
+      match (g.public_key.curve.as_str(), g.sha.as_str()) {
+        ("secp256r1" | "secp256k1", "SHA-256") | ("secp384r1", "SHA-384") | ("secp521r1", "SHA-512") => (),
+        _ => return Ok(Outcome::Unsupported),
+      }
+      import_spki_der(&g.public_key_der)?.verify(&t.msg, &t.sig)?;
+      Ok(Outcome::Accepted)
+    })?;
+  }
+  Ok(())
+}
+
+#[derive(Deserialize)]
+struct EcdhGroup {
+  curve: String,
+}
+
+#[derive(Deserialize)]
+struct EcdhTest {
+  #[serde(deserialize_with = "from_hex")]
+  public: Vec<u8>,
+  #[serde(deserialize_with = "from_hex")]
+  private: Vec<u8>,
+  #[serde(deserialize_with = "from_hex")]
+  shared: Vec<u8>,
+}
+
+/// PKCS#8 DER of a big-endian private scalar, which may have leading zeros
+fn private_key_der(curve: &str, scalar: &[u8]) -> Result<Vec<u8>> {
+  let scalar = &scalar[scalar.iter().take_while(|b| **b == 0).count()..];
+  let padded = |size: usize| -> Result<Vec<u8>> {
+    ensure!(scalar.len() <= size, "Private key too long");
+    Ok([vec![0u8; size - scalar.len()], scalar.to_vec()].concat())
+  };
+  let der = match curve {
+    "secp256r1" => SecretKey::<NistP256>::from_slice(&padded(32)?)?.to_pkcs8_der()?,
+    "secp384r1" => SecretKey::<NistP384>::from_slice(&padded(48)?)?.to_pkcs8_der()?,
+    "secp521r1" => SecretKey::<NistP521>::from_slice(&padded(66)?)?.to_pkcs8_der()?,
+    "secp256k1" => SecretKey::<Secp256k1>::from_slice(&padded(32)?)?.to_pkcs8_der()?,
+    _ => bail!("Unsupported curve {curve}"),
+  };
+  Ok(der.as_bytes().to_vec())
+}
+
+#[test]
+fn ecdh() -> Result<()> {
+  for path in generated("ecdh_")? {
+    run(&path, |_, g: &EcdhGroup, t: &EcdhTest| {
+      if !matches!(g.curve.as_str(), "secp256r1" | "secp384r1" | "secp521r1" | "secp256k1") {
+        return Ok(Outcome::Unsupported);
+      }
+      let private = import_pkcs8_der(&private_key_der(&g.curve, &t.private)?)?;
+      let shared = import_spki_der(&t.public)?.derive_bits(&private)?;
+      Ok(matches(&shared, &t.shared))
+    })?;
+  }
+  Ok(())
+}
+
+/* ---------------------------------------------------------------------------------- */
+// Upstream Wycheproof in the blobby format
+
+/// Reader of the blobby format: a table of deduplicated blobs followed by the entries, each prefixed by
+/// a variable-length quantity that is either an index into the table or the length of the bytes
+struct Blobby<'a> {
+  data: &'a [u8],
+  pos: usize,
+}
+
+impl Blobby<'_> {
+  fn byte(&mut self) -> Result<u8> {
+    let b = *self.data.get(self.pos).context("Truncated blobby file")?;
+    self.pos += 1;
+    Ok(b)
+  }
+
+  fn vlq(&mut self) -> Result<usize> {
+    let mut b = self.byte()?;
+    let mut value = usize::from(b & 0x7f);
+    while b & 0x80 != 0 {
+      b = self.byte()?;
+      value = ((value + 1) << 7) + usize::from(b & 0x7f);
+    }
+    Ok(value)
+  }
+
+  fn bytes(&mut self, len: usize) -> Result<Vec<u8>> {
+    let bytes = self
+      .data
+      .get(self.pos..self.pos + len)
+      .context("Truncated blobby file")?;
+    self.pos += len;
+    Ok(bytes.to_vec())
+  }
+}
+
+/// Rows of `fields` blobs in `wycheproof/{name}.blb`
+fn blobs(name: &str, fields: usize) -> Result<Vec<Vec<Vec<u8>>>> {
+  let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(format!("tests/vectors/wycheproof/{name}.blb"));
+  let data = fs::read(path)?;
+  let mut reader = Blobby { data: &data, pos: 0 };
+  let dedup = (0..reader.vlq()?)
+    .map(|_| {
+      let len = reader.vlq()?;
+      reader.bytes(len)
+    })
+    .collect::<Result<Vec<_>>>()?;
+  let mut entries = vec![];
+  while reader.pos < data.len() {
+    let value = reader.vlq()?;
+    entries.push(match value & 1 {
+      1 => dedup.get(value >> 1).context("Invalid blobby index")?.clone(),
+      _ => reader.bytes(value >> 1)?,
+    });
+  }
+  ensure!(
+    !entries.is_empty() && entries.len() % fields == 0,
+    "{name}: {} blobs are not rows of {fields}",
+    entries.len()
+  );
+  Ok(entries.chunks(fields).map(<[_]>::to_vec).collect())
+}
+
+/// Run `check` over all rows of a blobby file. If `flagged`, the last blob of a row is 1 for a vector that
+/// must be accepted and 0 for one that must be rejected, and otherwise all vectors must be accepted.
+fn run_blobs(name: &str, fields: usize, flagged: bool, check: impl Fn(&[Vec<u8>]) -> Result<Outcome>) -> Result<()> {
+  let rows = blobs(name, fields)?;
+  let (mut skipped, mut failures) = (0, vec![]);
+  for (i, row) in rows.iter().enumerate() {
+    let (row, expected) = match row.split_last() {
+      Some((flag, row)) if flagged => (
+        row,
+        if flag[..] == [1] {
+          Expected::Valid
+        } else {
+          Expected::Invalid
+        },
+      ),
+      _ => (&row[..], Expected::Valid),
+    };
+    let outcome = check(row);
+    let Some(passed) = verdict(&expected, &outcome) else {
+      skipped += 1;
+      continue;
+    };
+    if !passed {
+      failures.push(format!("{name} row {i}: expected {expected:?}, got {outcome:?}"));
+    }
+  }
+  ensure!(rows.len() > skipped, "{name}: all vectors are unsupported");
+  ensure!(
+    failures.is_empty(),
+    "{} of {} vectors failed:\n{}",
+    failures.len(),
+    rows.len(),
+    failures.join("\n")
+  );
+  println!("{name}: {} passed, {skipped} skipped", rows.len() - skipped);
+  Ok(())
+}
+
+/// Coordinate of `size` bytes from a big-endian integer, which may have leading zeros or be shorter
+fn coordinate(bytes: &[u8], size: usize) -> Result<Vec<u8>> {
+  let bytes = &bytes[bytes.iter().take_while(|b| **b == 0).count()..];
+  ensure!(bytes.len() <= size, "Coordinate too long");
+  Ok([vec![0u8; size - bytes.len()], bytes.to_vec()].concat())
+}
+
+/// `r || s` form of a DER signature
+fn p1363_signature(curve: &str, der: &[u8]) -> Result<Vec<u8>> {
+  let bytes = match curve {
+    "P-256" => ecdsa::Signature::<NistP256>::from_der(der)?.to_vec(),
+    "P-384" => ecdsa::Signature::<NistP384>::from_der(der)?.to_vec(),
+    "P-521" => ecdsa::Signature::<NistP521>::from_der(der)?.to_vec(),
+    "secp256k1" => ecdsa::Signature::<Secp256k1>::from_der(der)?.to_vec(),
+    _ => bail!("Unsupported curve {curve}"),
+  };
+  Ok(bytes)
+}
+
+#[test]
+fn wycheproof_ecdsa() -> Result<()> {
+  let files = [
+    ("ecdsa_secp256r1_sha256", "P-256", 32),
+    ("ecdsa_secp384r1_sha384", "P-384", 48),
+    ("ecdsa_secp521r1_sha512", "P-521", 66),
+    ("ecdsa_secp256k1_sha256", "secp256k1", 32),
+  ];
+  for (name, curve, size) in files {
+    run_blobs(name, 5, true, |row| {
+      let [wx, wy, msg, sig] = row else {
+        bail!("Malformed row");
+      };
+      let point = [vec![0x04], coordinate(wx, size)?, coordinate(wy, size)?].concat();
+      let public = EccKeyPairType::from_sec1_public(curve, &point)?;
+      // A signature that is not strict DER is rejected as well
+      public.verify(msg, &p1363_signature(curve, sig)?)?;
+      Ok(Outcome::Accepted)
+    })?;
+  }
+  Ok(())
+}
+
+#[test]
+fn wycheproof_hmac() -> Result<()> {
+  for (name, hash) in [
+    ("hmac_sha256", HashAlgorithm::Sha256),
+    ("hmac_sha384", HashAlgorithm::Sha384),
+    ("hmac_sha512", HashAlgorithm::Sha512),
+  ] {
+    run_blobs(name, 3, false, |row| {
+      let [key, msg, tag] = row else {
+        bail!("Malformed row");
+      };
+      // Tags may be truncated to the half
+      let full = hash.hmac(msg, key)?;
+      ensure!(tag.len() <= full.len(), "Tag too long");
+      // The upstream vectors of modified tags are not in the blobby files, so a full tag is checked with a flipped
+      // bit as well
+      if tag.len() == full.len() {
+        let mut modified = tag.clone();
+        modified[0] ^= 0x01;
+        if !hash.verify_hmac(msg, key, tag)? || hash.verify_hmac(msg, key, &modified)? {
+          return Ok(Outcome::WrongOutput);
+        }
+      }
+      Ok(matches(&full[..tag.len()], tag))
+    })?;
+  }
+  Ok(())
+}
+
+#[test]
+fn wycheproof_hkdf() -> Result<()> {
+  for (name, hash, hash_len) in [
+    ("hkdf_sha256", KdfHash::Sha256, 32),
+    ("hkdf_sha384", KdfHash::Sha384, 48),
+    ("hkdf_sha512", KdfHash::Sha512, 64),
+  ] {
+    run_blobs(name, 4, false, |row| {
+      let [ikm, salt, info, okm] = row else {
+        bail!("Malformed row");
+      };
+      let key = BinaryKey::try_new_hkdf_with_info(ikm, okm.len(), Some(salt), Some(&hash), info)?;
+      Ok(matches(&key.key, okm))
+    })?;
+    // The invalid upstream vectors, whose size exceeds 255 * HashLen, are not in the blobby files
+    let too_long = BinaryKey::try_new_hkdf_with_info(b"ikm", 255 * hash_len + 1, None, Some(&hash), b"");
+    ensure!(too_long.is_err(), "{name}: output of 255 * HashLen + 1 bytes accepted");
+  }
+  Ok(())
+}
+
+#[test]
+fn wycheproof_aes_gcm() -> Result<()> {
+  run_blobs("aes_gcm_256", 6, true, |row| {
+    let [key, nonce, aad, msg, ct] = row else {
+      bail!("Malformed row");
+    };
+    // The API has no associated data
+    if !aad.is_empty() {
+      return Ok(Outcome::Unsupported);
+    }
+    let encrypted = Encrypted {
+      data: ct.clone(),
+      iv: nonce.clone(),
+    };
+    if decrypt_gcm(&encrypted, key)? != *msg {
+      return Ok(Outcome::WrongOutput);
+    }
+    Ok(matches(&encrypt_gcm(msg, key, Some(nonce))?.data, ct))
+  })
+}
+
+/* ---------------------------------------------------------------------------------- */
+// NIST CAVP
+
+#[derive(Debug, Default)]
+struct CavpVector {
+  section: String,
+  fields: HashMap<String, String>,
+}
+
+impl CavpVector {
+  fn hex(&self, name: &str) -> Result<Vec<u8>> {
+    let value = self.fields.get(name).ok_or_else(|| anyhow!("No {name} in {self:?}"))?;
+    Ok(hex::decode(value)?)
+  }
+}
+
+/// Vectors of a `.rsp` file, i.e., `KEY = ...` lines separated by blank lines under `[ENCRYPT]` or `[DECRYPT]`
+fn parse_rsp(text: &str) -> Vec<CavpVector> {
+  let (mut vectors, mut section, mut current) = (vec![], String::new(), HashMap::new());
+  for line in text.lines().map(str::trim).chain([""]) {
+    if line.is_empty() {
+      if current.contains_key("COUNT") {
+        vectors.push(CavpVector {
+          section: section.clone(),
+          fields: std::mem::take(&mut current),
+        });
+      }
+      current.clear();
+    } else if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+      section = name.to_string();
+    } else if let Some((key, value)) = line.split_once('=').filter(|_| !line.starts_with('#')) {
+      current.insert(key.trim().to_string(), value.trim().to_string());
+    }
+  }
+  vectors
+}
+
+/// AES-CBC without padding on top of the PKCS#7 API. `encrypt` appends the padding block,
+/// which is the encryption of an empty message chained from the last ciphertext block.
+fn check_aes_cbc(v: &CavpVector) -> Result<()> {
+  let (key, iv) = (v.hex("KEY")?, v.hex("IV")?);
+  let (plaintext, ciphertext) = (v.hex("PLAINTEXT")?, v.hex("CIPHERTEXT")?);
+  match v.section.as_str() {
+    "ENCRYPT" => {
+      let encrypted = encrypt(&plaintext, &key, Some(&iv))?;
+      ensure!(encrypted.data.len() == ciphertext.len() + 16, "Unexpected padding");
+      ensure!(encrypted.data.starts_with(&ciphertext), "Ciphertext mismatch");
+    }
+    "DECRYPT" => {
+      let last = if ciphertext.is_empty() {
+        &iv[..]
+      } else {
+        &ciphertext[ciphertext.len() - 16..]
+      };
+      let padding = encrypt(&[], &key, Some(last))?.data;
+      let encrypted = Encrypted {
+        data: [ciphertext, padding].concat(),
+        iv,
+      };
+      ensure!(decrypt(&encrypted, &key)? == plaintext, "Plaintext mismatch");
+    }
+    section => bail!("Unknown section {section}"),
+  }
+  Ok(())
+}
+
+#[test]
+fn aes_cbc_cavp() -> Result<()> {
+  for path in vectors("cavp")? {
+    let name = path.file_name().unwrap().to_string_lossy().to_string();
+    let vectors = parse_rsp(&fs::read_to_string(&path)?);
+    ensure!(!vectors.is_empty(), "{name}: no vectors");
+    let (mut skipped, mut failures) = (0, vec![]);
+    for v in &vectors {
+      // Only AES-256 is implemented
+      if v.hex("KEY")?.len() != 32 {
+        skipped += 1;
+        continue;
+      }
+      if let Err(e) = check_aes_cbc(v) {
+        failures.push(format!("{name} [{}] COUNT {}: {e}", v.section, v.fields["COUNT"]));
+      }
+    }
+    ensure!(
+      failures.is_empty(),
+      "{} of {} vectors failed:\n{}",
+      failures.len(),
+      vectors.len(),
+      failures.join("\n")
+    );
+    println!("{name}: {} passed, {skipped} skipped", vectors.len() - skipped);
+  }
+  Ok(())
+}
+
+/// The harness itself must notice a wrong answer
+#[test]
+fn harness_detects_mismatch() -> Result<()> {
+  let path = generated("hkdf_sha256")?.remove(0);
+  let mut file: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path)?)?;
+  let tc = &mut file["testGroups"][0]["tests"][0];
+  let okm = tc["okm"].as_str().context("No okm")?.to_string();
+  let mut modified = hex::decode(&okm)?;
+  modified[0] ^= 0x01;
+  let modified = hex::encode(modified);
+  assert_ne!(modified, okm);
+  tc["okm"] = modified.into();
+  let tampered = std::env::temp_dir().join(format!("kat-{}-hkdf.json", std::process::id()));
+  fs::write(&tampered, file.to_string())?;
+  let res = run(&tampered, |_, _: &Ignored, t: &HkdfTest| {
+    let key = BinaryKey::try_new_hkdf_with_info(&t.ikm, t.size, Some(&t.salt), Some(&KdfHash::Sha256), &t.info)?;
+    Ok(matches(&key.key, &t.okm))
+  });
+  fs::remove_file(&tampered)?;
+  assert!(res.is_err());
+
+  let mut v = parse_rsp(&fs::read_to_string(vectors("cavp")?.remove(0))?).remove(0);
+  let ciphertext = v.fields.insert("CIPHERTEXT".to_string(), "00".repeat(16));
+  assert_ne!(ciphertext.as_deref(), Some("00".repeat(16).as_str()));
+  assert!(check_aes_cbc(&v).is_err());
+  Ok(())
+}
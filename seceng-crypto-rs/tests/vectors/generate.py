@@ -0,0 +1,698 @@
+#!/usr/bin/env python3
+"""Generate the known-answer vectors under this directory.
+
+The expected values are computed by an independent implementation, OpenSSL through
+pyca/cryptography (and hashlib), never by seceng-crypto-rs itself. Published vectors
+(SP 800-38A, AESAVS, RFC 5869, RFC 7914, RFC 4231 and RFC 5903) are embedded below and
+checked against OpenSSL before they are written.
+
+The JSON files under generated/ are not Wycheproof vectors. They are a supplement to
+the upstream Wycheproof vectors under wycheproof/, covering the primitives and forms
+those lack, like ECDH, RSA-OAEP, RSA-PSS, PBKDF2 and ECDSA in the r || s form, in the
+Wycheproof schema (testGroups / tests with tcId, comment, flags and result of "valid",
+"invalid" or "acceptable").
+
+    $ pip install cryptography
+    $ python3 generate.py
+
+RSA and EC keys, ECDSA nonces and the random messages change on every run.
+"""
+
+import hashlib
+import hmac
+import json
+import random
+from pathlib import Path
+
+from cryptography.hazmat.primitives import hashes, serialization
+from cryptography.hazmat.primitives.asymmetric import ec, padding, rsa
+from cryptography.hazmat.primitives.asymmetric.utils import decode_dss_signature
+from cryptography.hazmat.primitives.ciphers import Cipher, algorithms, modes
+from cryptography.hazmat.primitives.kdf.hkdf import HKDF
+
+HERE = Path(__file__).parent
+RNG = random.Random(20250101)
+HASHES = {"SHA-256": hashes.SHA256, "SHA-384": hashes.SHA384, "SHA-512": hashes.SHA512}
+HASHLIB = {"SHA-256": "sha256", "SHA-384": "sha384", "SHA-512": "sha512"}
+
+
+def rand_bytes(n):
+  return bytes(RNG.getrandbits(8) for _ in range(n))
+
+
+def flip(b, i, bit=0x01):
+  b = bytearray(b)
+  b[i] ^= bit
+  return bytes(b)
+
+
+class Tests:
+  def __init__(self):
+    self.tc_id = 0
+
+  def case(self, comment, result, flags=(), **fields):
+    self.tc_id += 1
+    case = {"tcId": self.tc_id, "comment": comment, "flags": list(flags)}
+    case.update({k: v.hex() if isinstance(v, bytes) else v for k, v in fields.items()})
+    case["result"] = result
+    return case
+
+
+def write_generated(name, algorithm, schema, groups, notes):
+  body = {
+    "algorithm": algorithm,
+    "schema": schema,
+    "generatorVersion": "seceng-crypto-rs/generate.py",
+    "numberOfTests": sum(len(g["tests"]) for g in groups),
+    "header": [
+      "Test vectors in the Wycheproof schema generated by tests/vectors/generate.py.",
+      "Expected values are computed by OpenSSL via pyca/cryptography.",
+    ],
+    "notes": notes,
+    "testGroups": groups,
+  }
+  path = HERE / "generated" / name
+  path.parent.mkdir(exist_ok=True)
+  path.write_text(json.dumps(body, indent=2) + "\n")
+
+
+##############################
+# AES-256-CBC (CAVP .rsp)
+
+def aes_cbc_raw(key, iv, data):
+  ctx = Cipher(algorithms.AES(key), modes.CBC(iv)).encryptor()
+  return ctx.update(data) + ctx.finalize()
+
+
+# AESAVS Appendix B (GFSbox), plaintexts and the published AES-256 ciphertexts
+GFSBOX256 = [
+  ("014730f80ac625fe84f026c60bfd547d", "5c9d844ed46f9885085e5d6a4f94c7d7"),
+  ("0b24af36193ce4665f2825d7b4749c98", "a9ff75bd7cf6613d3731c77c3b6d0c04"),
+  ("761c1fe41a18acf20d241650611d90f1", "623a52fcea5d443e48d9181ab32c7421"),
+  ("8a560769d605868ad80d819bdba03771", "38f2c7ae10612415d27ca190d27da8b4"),
+  ("91fbef2d15a97816060bee1feaa49afe", "1bc704f1bce135ceb810341b216d7abe"),
+]
+
+# SP 800-38A F.2.5 CBC-AES256.Encrypt
+SP800_38A_KEY = "603deb1015ca71be2b73aef0857d77811f352c073b6108d72d9810a30914dff4"
+SP800_38A_IV = "000102030405060708090a0b0c0d0e0f"
+SP800_38A_PT = (
+  "6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51"
+  "30c81c46a35ce411e5fbc1191a0a52eff69f2445df4f9b17ad2b417be66c3710"
+)
+SP800_38A_CT = (
+  "f58c4c04d6e5f1ba779eabfb5f7bfbd69cfc4e967edb808d679f777bc6702c7d"
+  "39f23369a9d9bacfa530e26304231461b2eb05e2c39be9fcda6c19078c6a9d1b"
+)
+
+
+def write_rsp(name, title, vectors):
+  lines = [f"# CAVS 11.1 style vectors: {title}", "# Generated by tests/vectors/generate.py", ""]
+  for section in ("ENCRYPT", "DECRYPT"):
+    lines += [f"[{section}]", ""]
+    for count, (key, iv, pt, ct) in enumerate(vectors):
+      assert aes_cbc_raw(bytes.fromhex(key), bytes.fromhex(iv), bytes.fromhex(pt)).hex() == ct
+      lines += [f"COUNT = {count}", f"KEY = {key}", f"IV = {iv}"]
+      if section == "ENCRYPT":
+        lines += [f"PLAINTEXT = {pt}", f"CIPHERTEXT = {ct}", ""]
+      else:
+        lines += [f"CIPHERTEXT = {ct}", f"PLAINTEXT = {pt}", ""]
+  path = HERE / "cavp" / name
+  path.parent.mkdir(exist_ok=True)
+  path.write_text("\n".join(lines))
+
+
+def aes_cbc_cavp():
+  zero = "00" * 16
+  gfsbox = [("00" * 32, zero, pt, ct) for pt, ct in GFSBOX256]
+  write_rsp("CBCGFSbox256.rsp", "AESAVS GFSbox, AES-256 CBC", gfsbox)
+
+  def bits(n, total):
+    return (((1 << n) - 1) << (total - n)).to_bytes(total // 8, "big").hex()
+
+  key_zero = bytes(32)
+  varkey = []
+  for n in range(1, 257):
+    key = bits(n, 256)
+    varkey.append((key, zero, zero, aes_cbc_raw(bytes.fromhex(key), bytes(16), bytes(16)).hex()))
+  write_rsp("CBCVarKey256.rsp", "AESAVS VarKey, AES-256 CBC", varkey)
+
+  vartxt = []
+  for n in range(1, 129):
+    pt = bits(n, 128)
+    vartxt.append(("00" * 32, zero, pt, aes_cbc_raw(key_zero, bytes(16), bytes.fromhex(pt)).hex()))
+  write_rsp("CBCVarTxt256.rsp", "AESAVS VarTxt, AES-256 CBC", vartxt)
+
+  mmt = [(SP800_38A_KEY, SP800_38A_IV, SP800_38A_PT, SP800_38A_CT)]
+  for blocks in range(1, 11):
+    key, iv, pt = rand_bytes(32), rand_bytes(16), rand_bytes(16 * blocks)
+    mmt.append((key.hex(), iv.hex(), pt.hex(), aes_cbc_raw(key, iv, pt).hex()))
+  write_rsp("CBCMMT256.rsp", "SP 800-38A F.2.5 and multi-block messages, AES-256 CBC", mmt)
+
+
+##############################
+# AES-256-CBC with PKCS#7 padding
+
+def pkcs7(data):
+  n = 16 - len(data) % 16
+  return data + bytes([n]) * n
+
+
+def aes_cbc_pkcs5():
+  t = Tests()
+  tests = []
+  for size in [0, 1, 2, 15, 16, 17, 31, 32, 33, 47, 48, 64, 100]:
+    key, iv, msg = rand_bytes(32), rand_bytes(16), rand_bytes(size)
+    tests.append(t.case(f"message size {size}", "valid", key=key, iv=iv, msg=msg, ct=aes_cbc_raw(key, iv, pkcs7(msg))))
+
+  def bad(comment, padded, flags=("BadPadding",)):
+    key, iv = rand_bytes(32), rand_bytes(16)
+    tests.append(t.case(comment, "invalid", flags, key=key, iv=iv, msg=b"", ct=aes_cbc_raw(key, iv, padded)))
+
+  body = rand_bytes(13)
+  bad("padding byte 0", rand_bytes(16)[:15] + b"\x00")
+  bad("padding byte 17", rand_bytes(16)[:15] + b"\x11")
+  bad("padding byte 255", rand_bytes(16)[:15] + b"\xff")
+  bad("inconsistent padding 03 02 03", body + b"\x03\x02\x03")
+  bad("inconsistent padding 02 03 03", body + b"\x02\x03\x03")
+  bad("modified first byte of a full padding block", b"\x0f" + b"\x10" * 15)
+  bad("no padding block", rand_bytes(32)[:31] + b"\x21")
+  bad("padding 0x0f after 0x10", b"\x10" * 15 + b"\x0f")
+
+  key, iv = rand_bytes(32), rand_bytes(16)
+  ct = aes_cbc_raw(key, iv, pkcs7(rand_bytes(20)))
+  tests.append(t.case("truncated ciphertext", "invalid", ["WrongSize"], key=key, iv=iv, msg=b"", ct=ct[:-1]))
+  tests.append(t.case("empty ciphertext", "invalid", ["WrongSize"], key=key, iv=iv, msg=b"", ct=b""))
+  tests.append(t.case("ciphertext with an extra byte", "invalid", ["WrongSize"], key=key, iv=iv, msg=b"", ct=ct + b"\x00"))
+
+  groups = [{"type": "IndCpaTest", "keySize": 256, "ivSize": 128, "tests": tests}]
+  write_generated(
+    "aes_cbc_pkcs5_test.json",
+    "AES-CBC-PKCS5",
+    "ind_cpa_test_schema.json",
+    groups,
+    {"BadPadding": "The plaintext has an invalid PKCS#7 padding", "WrongSize": "The ciphertext is not whole blocks"},
+  )
+
+
+##############################
+# HMAC
+
+# RFC 4231 test cases 1, 2, 3, 4, 6 and 7 (case 5 is a truncated tag)
+RFC4231 = [
+  ("0b" * 20, b"Hi There".hex()),
+  (b"Jefe".hex(), b"what do ya want for nothing?".hex()),
+  ("aa" * 20, "dd" * 50),
+  ("0102030405060708090a0b0c0d0e0f10111213141516171819", "cd" * 50),
+  ("aa" * 131, b"Test Using Larger Than Block-Size Key - Hash Key First".hex()),
+  (
+    "aa" * 131,
+    b"This is a test using a larger than block-size key and a larger than block-size data. "
+    b"The key needs to be hashed before being used by the HMAC algorithm.".hex(),
+  ),
+]
+RFC4231_SHA256 = [
+  "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7",
+  "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+  "773ea91e36800e46854db8ebd09181a72959098b3ef8c122d9635514ced565fe",
+  "82558a389a443c0ea4cc819899f2083a85f0faa3e578f8077a2e3ff46729665b",
+  "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54",
+  "9b09ffa71b942fcb27635fbcd5b0e944bfdc63644f0713938a7f51535c3a35e2",
+]
+
+
+def hmac_tests(name):
+  h = HASHLIB[name]
+  size = hashlib.new(h).digest_size
+  t = Tests()
+  full, truncated = [], []
+  for i, (key, msg) in enumerate(RFC4231):
+    tag = hmac.new(bytes.fromhex(key), bytes.fromhex(msg), h).digest()
+    if name == "SHA-256":
+      assert tag.hex() == RFC4231_SHA256[i]
+    full.append(t.case(f"RFC 4231 test case {i + 1 if i < 4 else i + 2}", "valid", key=key, msg=msg, tag=tag.hex()))
+  for msg_size in [0, 1, 16, 64, 127, 128, 129, 300]:
+    key, msg = rand_bytes(size), rand_bytes(msg_size)
+    full.append(t.case(f"message size {msg_size}", "valid", key=key, msg=msg, tag=hmac.new(key, msg, h).digest()))
+  for key_size in [0, 1, 64, 128, 129]:
+    key, msg = rand_bytes(key_size), rand_bytes(32)
+    full.append(t.case(f"key size {key_size}", "valid", key=key, msg=msg, tag=hmac.new(key, msg, h).digest()))
+
+  key, msg = rand_bytes(size), rand_bytes(32)
+  tag = hmac.new(key, msg, h).digest()
+  full.append(t.case("flipped bit 0 in tag", "invalid", ["ModifiedTag"], key=key, msg=msg, tag=flip(tag, 0)))
+  full.append(t.case("flipped bit 7 in tag", "invalid", ["ModifiedTag"], key=key, msg=msg, tag=flip(tag, 0, 0x80)))
+  full.append(t.case("flipped last bit in tag", "invalid", ["ModifiedTag"], key=key, msg=msg, tag=flip(tag, -1, 0x80)))
+  full.append(t.case("tag of zeros", "invalid", ["ModifiedTag"], key=key, msg=msg, tag=bytes(size)))
+  full.append(t.case("truncated tag", "invalid", ["TruncatedTag"], key=key, msg=msg, tag=tag[:-1]))
+  full.append(t.case("empty tag", "invalid", ["TruncatedTag"], key=key, msg=msg, tag=b""))
+  full.append(t.case("tag with an extra byte", "invalid", ["ModifiedTag"], key=key, msg=msg, tag=tag + b"\x00"))
+  full.append(t.case("tag of a modified message", "invalid", ["ModifiedTag"], key=key, msg=flip(msg, 3), tag=tag))
+  full.append(t.case("tag of another key", "invalid", ["ModifiedTag"], key=flip(key, 0), msg=msg, tag=tag))
+
+  for msg_size in [0, 16, 64]:
+    key, msg = rand_bytes(size), rand_bytes(msg_size)
+    tag = hmac.new(key, msg, h).digest()[:16]
+    truncated.append(t.case(f"message size {msg_size}", "valid", key=key, msg=msg, tag=tag))
+  truncated.append(t.case("flipped bit 0 in tag", "invalid", ["ModifiedTag"], key=key, msg=msg, tag=flip(tag, 0)))
+  truncated.append(t.case("flipped last bit in tag", "invalid", ["ModifiedTag"], key=key, msg=msg, tag=flip(tag, -1, 0x80)))
+
+  groups = [
+    {"type": "MacTest", "keySize": size * 8, "tagSize": size * 8, "tests": full},
+    {"type": "MacTest", "keySize": size * 8, "tagSize": 128, "tests": truncated},
+  ]
+  write_generated(
+    f"hmac_{h}_test.json",
+    f"HMAC{name}",
+    "mac_test_schema.json",
+    groups,
+    {"ModifiedTag": "The tag has been modified", "TruncatedTag": "The tag is shorter than the tag size"},
+  )
+
+
+##############################
+# HKDF
+
+# RFC 5869 A.1 to A.3
+RFC5869 = [
+  (
+    "0b" * 22,
+    "000102030405060708090a0b0c",
+    "f0f1f2f3f4f5f6f7f8f9",
+    42,
+    "3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf34007208d5b887185865",
+  ),
+  (
+    bytes(range(0x00, 0x50)).hex(),
+    bytes(range(0x60, 0xB0)).hex(),
+    bytes(range(0xB0, 0x100)).hex(),
+    82,
+    "b11e398dc80327a1c8e7f78c596a49344f012eda2d4efad8a050cc4c19afa97c59045a99cac7827271cb41c65e590e09"
+    "da3275600c2f09b8367793a9aca3db71cc30c58179ec3e87c14c01d5c1f3434f1d87",
+  ),
+  ("0b" * 22, "", "", 42, "8da4e775a563c18f715f802a063c5a31b8a11f5c5ee1879ec3454e5f3c738d2d9d201395faa4b61a96c8"),
+]
+
+
+def hkdf(name, ikm, salt, info, size):
+  return HKDF(HASHES[name](), size, salt or None, info).derive(ikm)
+
+
+def hkdf_tests(name):
+  size = HASHES[name].digest_size
+  t = Tests()
+  tests = []
+  if name == "SHA-256":
+    for i, (ikm, salt, info, l, okm) in enumerate(RFC5869):
+      assert hkdf(name, bytes.fromhex(ikm), bytes.fromhex(salt), bytes.fromhex(info), l).hex() == okm
+      tests.append(t.case(f"RFC 5869 A.{i + 1}", "valid", ikm=ikm, salt=salt, info=info, size=l, okm=okm))
+  for ikm_size, salt_size, info_size, l in [(16, 0, 0, 16), (32, 32, 0, 32), (32, 16, 16, 64), (64, 64, 64, size * 4), (20, 20, 20, 1)]:
+    ikm, salt, info = rand_bytes(ikm_size), rand_bytes(salt_size), rand_bytes(info_size)
+    comment = f"ikm {ikm_size}, salt {salt_size}, info {info_size}, size {l}"
+    tests.append(t.case(comment, "valid", ikm=ikm, salt=salt, info=info, size=l, okm=hkdf(name, ikm, salt, info, l)))
+  ikm, salt, info = rand_bytes(32), rand_bytes(16), rand_bytes(8)
+  tests.append(t.case("maximal size", "valid", ikm=ikm, salt=salt, info=info, size=255 * size, okm=hkdf(name, ikm, salt, info, 255 * size)))
+  tests.append(t.case("size too large", "invalid", ["SizeTooLarge"], ikm=ikm, salt=salt, info=info, size=255 * size + 1, okm=""))
+  groups = [{"type": "HkdfTest", "keySize": 256, "tests": tests}]
+  write_generated(
+    f"hkdf_{HASHLIB[name]}_test.json",
+    f"HKDF-{name}",
+    "hkdf_test_schema.json",
+    groups,
+    {"SizeTooLarge": "The size of the output exceeds 255 * HashLen"},
+  )
+
+
+##############################
+# PBKDF2
+
+# RFC 7914 section 11, PBKDF2-HMAC-SHA256
+RFC7914 = [
+  (b"passwd", b"salt", 1, 64, "55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc49ca9cccf179b645991664b39d77ef317c71b845b1e30bd509112041d3a19783"),
+  (b"Password", b"NaCl", 80000, 64, "4ddcd8f60b98be21830cee5ef22701f9641a4418d04c0414aeff08876b34ab56a1d425a1225833549adb841b51c9b3176a272bdebba1d078478f62b397f33c8d"),
+]
+
+# Passwords and salts of RFC 6070, whose published outputs are for HMAC-SHA1, which the crate does not implement.
+# The outputs with SHA-256/384/512 are computed by OpenSSL, so they are not RFC 6070 vectors.
+RFC6070 = [
+  (b"password", b"salt", 1, 20),
+  (b"password", b"salt", 2, 20),
+  (b"password", b"salt", 4096, 20),
+  (b"passwordPASSWORDpassword", b"saltSALTsaltSALTsaltSALTsaltSALTsalt", 4096, 25),
+  (b"pass\0word", b"sa\0lt", 4096, 16),
+]
+
+
+def pbkdf2_tests(name):
+  h = HASHLIB[name]
+  t = Tests()
+  tests = []
+  if name == "SHA-256":
+    for i, (password, salt, c, l, dk) in enumerate(RFC7914):
+      assert hashlib.pbkdf2_hmac(h, password, salt, c, l).hex() == dk
+      tests.append(t.case(f"RFC 7914 section 11, vector {i + 1}", "valid", password=password, salt=salt, iterationCount=c, dkLen=l, dk=dk))
+  for password, salt, c, l in RFC6070:
+    dk = hashlib.pbkdf2_hmac(h, password, salt, c, l)
+    tests.append(t.case(f"input of RFC 6070, {c} iterations, output by OpenSSL", "valid", password=password, salt=salt, iterationCount=c, dkLen=l, dk=dk))
+  for l in [1, 32, HASHES[name].digest_size + 1, 100]:
+    password, salt = RNG.choice([b"", b"correct horse battery staple", "パスワード".encode()]), rand_bytes(16)
+    dk = hashlib.pbkdf2_hmac(h, password, salt, 10, l)
+    tests.append(t.case(f"output size {l}", "valid", password=password, salt=salt, iterationCount=10, dkLen=l, dk=dk))
+  tests.append(t.case("zero iterations", "invalid", ["ZeroIterations"], password=b"password", salt=b"salt", iterationCount=0, dkLen=32, dk=b""))
+  groups = [{"type": "PbkdfTest", "tests": tests}]
+  write_generated(
+    f"pbkdf2_hmac{h}_test.json",
+    f"PBKDF2-HMAC{name}",
+    "pbkdf_test_schema.json",
+    groups,
+    {"ZeroIterations": "The iteration count must be positive"},
+  )
+
+
+##############################
+# RSA-OAEP and RSASSA-PSS with SHA-256
+
+def i2osp(x, n):
+  return x.to_bytes(n, "big")
+
+
+def mgf1(seed, length, h=hashlib.sha256):
+  out = b""
+  for counter in range((length + h().digest_size - 1) // h().digest_size):
+    out += h(seed + counter.to_bytes(4, "big")).digest()
+  return out[:length]
+
+
+def xor(a, b):
+  return bytes(x ^ y for x, y in zip(a, b))
+
+
+def rsa_oaep():
+  key = rsa.generate_private_key(public_exponent=65537, key_size=2048)
+  pub = key.public_key().public_numbers()
+  n, e, k = pub.n, pub.e, 256
+  oaep = padding.OAEP(mgf=padding.MGF1(hashes.SHA256()), algorithm=hashes.SHA256(), label=None)
+  t = Tests()
+  tests = []
+  for size in [0, 1, 2, 20, 32, 100, 189, 190]:
+    msg = rand_bytes(size)
+    ct = key.public_key().encrypt(msg, oaep)
+    assert key.decrypt(ct, oaep) == msg
+    tests.append(t.case(f"message size {size}", "valid", msg=msg, ct=ct, label=b""))
+
+  def raw(em):
+    return i2osp(pow(int.from_bytes(em, "big"), e, n), k)
+
+  def encode(msg, first=b"\x00", lhash=hashlib.sha256(b"").digest(), sep=b"\x01"):
+    ps = bytes(k - len(msg) - 2 * 32 - 2)
+    db = lhash + ps + sep + msg
+    seed = rand_bytes(32)
+    masked_db = xor(db, mgf1(seed, len(db)))
+    return first + xor(seed, mgf1(masked_db, 32)) + masked_db
+
+  msg = b"Wycheproof-style"
+  tests.append(t.case("raw encoding of a valid message", "valid", msg=msg, ct=raw(encode(msg)), label=b""))
+  ct = key.public_key().encrypt(msg, oaep)
+  tests.append(t.case("flipped bit in ciphertext", "invalid", ["InvalidCiphertext"], msg=msg, ct=flip(ct, 128), label=b""))
+  tests.append(t.case("first byte of EM is 1", "invalid", ["InvalidOaepPadding"], msg=msg, ct=raw(encode(msg, first=b"\x01")), label=b""))
+  tests.append(t.case("wrong label hash", "invalid", ["InvalidOaepPadding"], msg=msg, ct=raw(encode(msg, lhash=hashlib.sha256(b"x").digest())), label=b""))
+  tests.append(t.case("separator 0x02", "invalid", ["InvalidOaepPadding"], msg=msg, ct=raw(encode(msg, sep=b"\x02")), label=b""))
+  tests.append(t.case("no separator", "invalid", ["InvalidOaepPadding"], msg=msg, ct=raw(encode(msg, sep=b"\x00")), label=b""))
+  tests.append(t.case("ciphertext is 0", "invalid", ["InvalidCiphertext"], msg=b"", ct=bytes(k), label=b""))
+  tests.append(t.case("ciphertext is n", "invalid", ["InvalidCiphertext"], msg=b"", ct=i2osp(n, k), label=b""))
+  tests.append(t.case("ciphertext with a leading zero", "invalid", ["InvalidCiphertext"], msg=msg, ct=b"\x00" + ct, label=b""))
+  tests.append(t.case("truncated ciphertext", "invalid", ["InvalidCiphertext"], msg=msg, ct=ct[1:], label=b""))
+  tests.append(t.case("empty ciphertext", "invalid", ["InvalidCiphertext"], msg=msg, ct=b"", label=b""))
+
+  der = key.private_bytes(serialization.Encoding.DER, serialization.PrivateFormat.PKCS8, serialization.NoEncryption())
+  groups = [
+    {
+      "type": "RsaesOaepDecrypt",
+      "keySize": 2048,
+      "sha": "SHA-256",
+      "mgf": "MGF1",
+      "mgfSha": "SHA-256",
+      "privateKeyPkcs8": der.hex(),
+      "tests": tests,
+    }
+  ]
+  write_generated(
+    "rsa_oaep_2048_sha256_mgf1sha256_test.json",
+    "RSAES-OAEP",
+    "rsaes_oaep_decrypt_schema.json",
+    groups,
+    {
+      "InvalidCiphertext": "The ciphertext is not a valid RSA ciphertext of the key",
+      "InvalidOaepPadding": "The ciphertext decrypts to an invalid OAEP encoding",
+    },
+  )
+
+
+def rsa_pss():
+  key = rsa.generate_private_key(public_exponent=65537, key_size=2048)
+  numbers = key.private_numbers()
+  n, d, k = numbers.public_numbers.n, numbers.d, 256
+  t = Tests()
+  tests = []
+
+  def sign(msg, salt_len=32, mgf=hashes.SHA256(), h=hashes.SHA256()):
+    return key.sign(msg, padding.PSS(mgf=padding.MGF1(mgf), salt_length=salt_len), h)
+
+  for size in [0, 1, 32, 100, 1000]:
+    msg = rand_bytes(size)
+    tests.append(t.case(f"message size {size}", "valid", msg=msg, sig=sign(msg)))
+
+  def raw(em):
+    return i2osp(pow(int.from_bytes(em, "big"), d, n), k)
+
+  def encode(msg, salt_len=32, trailer=b"\xbc"):
+    em_len = k
+    m_hash = hashlib.sha256(msg).digest()
+    salt = rand_bytes(salt_len)
+    h = hashlib.sha256(bytes(8) + m_hash + salt).digest()
+    db = bytes(em_len - salt_len - 32 - 2) + b"\x01" + salt
+    masked = bytearray(xor(db, mgf1(h, len(db))))
+    masked[0] &= 0x7F
+    return bytes(masked) + h + trailer
+
+  msg = b"Wycheproof-style"
+  sig = sign(msg)
+  tests.append(t.case("raw encoding of a valid signature", "valid", msg=msg, sig=raw(encode(msg))))
+  tests.append(t.case("modified message", "invalid", ["ModifiedSignature"], msg=flip(msg, 0), sig=sig))
+  tests.append(t.case("flipped bit in signature", "invalid", ["ModifiedSignature"], msg=msg, sig=flip(sig, 100)))
+  tests.append(t.case("salt length 20", "invalid", ["WrongSaltLength"], msg=msg, sig=sign(msg, 20)))
+  tests.append(t.case("salt length 0", "invalid", ["WrongSaltLength"], msg=msg, sig=sign(msg, 0)))
+  tests.append(t.case("MGF1 with SHA-1", "invalid", ["WrongMgf"], msg=msg, sig=sign(msg, mgf=hashes.SHA1())))
+  tests.append(t.case("hashed with SHA-384", "invalid", ["WrongHash"], msg=msg, sig=sign(msg, h=hashes.SHA384())))
+  tests.append(t.case("trailer 0xbd", "invalid", ["InvalidPssPadding"], msg=msg, sig=raw(encode(msg, trailer=b"\xbd"))))
+  tests.append(t.case("leftmost bit of EM is set", "invalid", ["InvalidPssPadding"], msg=msg, sig=raw(flip(encode(msg), 0, 0x80))))
+  tests.append(t.case("signature is 0", "invalid", ["InvalidSignature"], msg=msg, sig=bytes(k)))
+  tests.append(t.case("signature is n", "invalid", ["InvalidSignature"], msg=msg, sig=i2osp(n, k)))
+  tests.append(t.case("signature with a leading zero", "invalid", ["InvalidSignature"], msg=msg, sig=b"\x00" + sig))
+  tests.append(t.case("truncated signature", "invalid", ["InvalidSignature"], msg=msg, sig=sig[:-1]))
+  tests.append(t.case("empty signature", "invalid", ["InvalidSignature"], msg=msg, sig=b""))
+
+  der = key.public_key().public_bytes(serialization.Encoding.DER, serialization.PublicFormat.SubjectPublicKeyInfo)
+  groups = [
+    {
+      "type": "RsassaPssVerify",
+      "keySize": 2048,
+      "sha": "SHA-256",
+      "mgf": "MGF1",
+      "mgfSha": "SHA-256",
+      "sLen": 32,
+      "publicKeyDer": der.hex(),
+      "tests": tests,
+    }
+  ]
+  write_generated(
+    "rsa_pss_2048_sha256_mgf1_32_test.json",
+    "RSASSA-PSS",
+    "rsassa_pss_verify_schema.json",
+    groups,
+    {
+      "ModifiedSignature": "The signature or the message has been modified",
+      "WrongSaltLength": "The salt length differs from sLen",
+      "WrongMgf": "The MGF hash differs from mgfSha",
+      "WrongHash": "The message hash differs from sha",
+      "InvalidPssPadding": "The signature decodes to an invalid PSS encoding",
+      "InvalidSignature": "The signature is not a valid RSA signature of the key",
+    },
+  )
+
+
+##############################
+# ECDSA (P1363) and ECDH over P-256, P-384, P-521 and secp256k1
+
+CURVES = {
+  "secp256r1": (ec.SECP256R1, hashes.SHA256, 32, 0xFFFFFFFF00000000FFFFFFFFFFFFFFFFBCE6FAADA7179E84F3B9CAC2FC632551),
+  "secp384r1": (
+    ec.SECP384R1,
+    hashes.SHA384,
+    48,
+    0xFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFC7634D81F4372DDF581A0DB248B0A77AECEC196ACCC52973,
+  ),
+  "secp521r1": (
+    ec.SECP521R1,
+    hashes.SHA512,
+    66,
+    0x01FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFA51868783BF2F966B7FCC0148F709A5D03BB5C9B8899C47AEBB6FB71E91386409,
+  ),
+  "secp256k1": (ec.SECP256K1, hashes.SHA256, 32, 0xFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEBAAEDCE6AF48A03BBFD25E8CD0364141),
+}
+
+
+def spki(public_key):
+  return public_key.public_bytes(serialization.Encoding.DER, serialization.PublicFormat.SubjectPublicKeyInfo)
+
+
+def ecdsa(curve_name):
+  curve, h, size, order = CURVES[curve_name]
+  key = ec.generate_private_key(curve())
+  t = Tests()
+  tests = []
+
+  def sign(msg):
+    r, s = decode_dss_signature(key.sign(msg, ec.ECDSA(h())))
+    return r, s
+
+  def p1363(r, s):
+    return r.to_bytes(size, "big") + s.to_bytes(size, "big")
+
+  for msg_size in [0, 1, 32, 100, 1000]:
+    msg = rand_bytes(msg_size)
+    tests.append(t.case(f"message size {msg_size}", "valid", msg=msg, sig=p1363(*sign(msg))))
+  msg = b"Wycheproof-style"
+  r, s = sign(msg)
+  tests.append(t.case("s replaced by n - s", "valid", ["SignatureMalleability"], msg=msg, sig=p1363(r, order - s)))
+  tests.append(t.case("modified message", "invalid", ["ModifiedSignature"], msg=flip(msg, 0), sig=p1363(r, s)))
+  tests.append(t.case("flipped bit in r", "invalid", ["ModifiedSignature"], msg=msg, sig=p1363(r ^ 1, s)))
+  tests.append(t.case("flipped bit in s", "invalid", ["ModifiedSignature"], msg=msg, sig=p1363(r, s ^ 1)))
+  tests.append(t.case("r and s swapped", "invalid", ["ModifiedSignature"], msg=msg, sig=p1363(s, r)))
+  tests.append(t.case("r = 0", "invalid", ["InvalidSignature"], msg=msg, sig=p1363(0, s)))
+  tests.append(t.case("s = 0", "invalid", ["InvalidSignature"], msg=msg, sig=p1363(r, 0)))
+  tests.append(t.case("r = n", "invalid", ["InvalidSignature"], msg=msg, sig=p1363(order, s)))
+  tests.append(t.case("s = n", "invalid", ["InvalidSignature"], msg=msg, sig=p1363(r, order)))
+  tests.append(t.case("truncated signature", "invalid", ["InvalidEncoding"], msg=msg, sig=p1363(r, s)[:-1]))
+  tests.append(t.case("signature with an extra byte", "invalid", ["InvalidEncoding"], msg=msg, sig=p1363(r, s) + b"\x00"))
+  tests.append(t.case("empty signature", "invalid", ["InvalidEncoding"], msg=msg, sig=b""))
+
+  groups = [
+    {
+      "type": "EcdsaP1363Verify",
+      "publicKey": {"curve": curve_name, "keySize": curve.key_size},
+      "publicKeyDer": spki(key.public_key()).hex(),
+      "sha": f"SHA-{h.digest_size * 8}",
+      "tests": tests,
+    }
+  ]
+  write_generated(
+    f"ecdsa_{curve_name}_sha{h.digest_size * 8}_p1363_test.json",
+    "ECDSA",
+    "ecdsa_p1363_verify_schema.json",
+    groups,
+    {
+      "SignatureMalleability": "(r, n - s) is also a valid signature",
+      "ModifiedSignature": "The signature or the message has been modified",
+      "InvalidSignature": "r or s is out of the range [1, n - 1]",
+      "InvalidEncoding": "The signature is not 2 * size bytes",
+    },
+  )
+
+
+# RFC 5903 section 8.1 to 8.3: (i, r, x of g^ir), the private keys of the initiator and the responder and the shared
+# secret
+RFC5903 = {
+  "secp256r1": (
+    "c88f01f510d9ac3f70a292daa2316de544e9aab8afe84049c62a9c57862d1433",
+    "c6ef9c5d78ae012a011164acb397ce2088685d8f06bf9be0b283ab46476bee53",
+    "d6840f6b42f6edafd13116e0e12565202fef8e9ece7dce03812464d04b9442de",
+  ),
+  "secp384r1": (
+    "099f3c7034d4a2c699884d73a375a67f7624ef7c6b3c0f160647b67414dce655e35b538041e649ee3faef896783ab194",
+    "41cb0779b4bdb85d47846725fbec3c9430fab46cc8dc5060855cc9bda0aa2942e0308312916b8ed2960e4bd55a7448fc",
+    "11187331c279962d93d604243fd592cb9d0a926f422e47187521287e7156c5c4d603135569b9e9d09cf5d4a270f59746",
+  ),
+  "secp521r1": (
+    "0037ade9319a89f4dabdb3ef411aaccca5123c61acab57b5393dce47608172a095aa85a30fe1c2952c6771d937ba9777f5957b2639bab072462f68c27a57382d4a52",
+    "0145ba99a847af43793fdd0e872e7cdfa16be30fdc780f97bccc3f078380201e9c677d600b343757a3bdbf2a3163e4c2f869cca7458aa4a4effc311f5cb151685eb9",
+    "01144c7d79ae6956bc8edb8e7c787c4521cb086fa64407f97894e5e6b2d79b04d1427e73ca4baa240a34786859810c06b3c715a3a8cc3151f2bee417996d19f3ddea",
+  ),
+}
+
+
+def ecdh(curve_name):
+  curve, _, size, _ = CURVES[curve_name]
+  other = "secp384r1" if curve_name == "secp256r1" else "secp256r1"
+  t = Tests()
+  tests = []
+
+  def case(comment, result, flags, public, private, shared):
+    scalar = private.private_numbers().private_value.to_bytes(size, "big")
+    tests.append(t.case(comment, result, flags, public=public, private=scalar, shared=shared))
+
+  if curve_name in RFC5903:
+    i, r, shared = RFC5903[curve_name]
+    a, b = ec.derive_private_key(int(i, 16), curve()), ec.derive_private_key(int(r, 16), curve())
+    assert a.exchange(ec.ECDH(), b.public_key()).hex() == shared
+    case(f"RFC 5903 section 8.{list(RFC5903).index(curve_name) + 1}", "valid", [], spki(b.public_key()), a, shared)
+  for _ in range(4):
+    a, b = ec.generate_private_key(curve()), ec.generate_private_key(curve())
+    case("normal case", "valid", [], spki(b.public_key()), a, a.exchange(ec.ECDH(), b.public_key()))
+
+  a, b = ec.generate_private_key(curve()), ec.generate_private_key(curve())
+  shared = a.exchange(ec.ECDH(), b.public_key())
+  public = spki(b.public_key())
+  compressed_point = b.public_key().public_bytes(serialization.Encoding.X962, serialization.PublicFormat.CompressedPoint)
+  uncompressed_point = b.public_key().public_bytes(serialization.Encoding.X962, serialization.PublicFormat.UncompressedPoint)
+  prefix = public[: -len(uncompressed_point)]
+  case("compressed public key", "acceptable", ["CompressedPublic"], reencode(prefix, compressed_point), a, shared)
+  case("point not on the curve", "invalid", ["InvalidPublic"], public[:-1] + bytes([public[-1] ^ 1]), a, b"")
+  case("x and y swapped", "invalid", ["InvalidPublic"], prefix + b"\x04" + uncompressed_point[1 + size :] + uncompressed_point[1 : 1 + size], a, b"")
+  case("point at infinity", "invalid", ["InvalidPublic"], reencode(prefix, b"\x00"), a, b"")
+  case("truncated point", "invalid", ["InvalidPublic"], reencode(prefix, uncompressed_point[:-1]), a, b"")
+  c = ec.generate_private_key(CURVES[other][0]())
+  case(f"public key on {other}", "invalid", ["WrongCurve"], spki(c.public_key()), a, b"")
+
+  groups = [{"type": "EcdhTest", "curve": curve_name, "encoding": "asn", "tests": tests}]
+  write_generated(
+    f"ecdh_{curve_name}_test.json",
+    "ECDH",
+    "ecdh_test_schema.json",
+    groups,
+    {
+      "CompressedPublic": "The public key is a compressed point, which may be rejected",
+      "InvalidPublic": "The public key is not a point on the curve",
+      "WrongCurve": "The public key is on another curve",
+    },
+  )
+
+
+def der_length(n):
+  if n < 0x80:
+    return bytes([n])
+  body = n.to_bytes((n.bit_length() + 7) // 8, "big")
+  return bytes([0x80 | len(body)]) + body
+
+
+def reencode(prefix, point):
+  """SPKI with the given point, reusing the AlgorithmIdentifier of `prefix`"""
+  # prefix: SEQUENCE hdr, AlgorithmIdentifier, BIT STRING hdr, unused bits
+  algorithm = prefix[2 : 2 + 2 + prefix[3]] if prefix[1] < 0x80 else prefix[3 : 3 + 2 + prefix[4]]
+  bit_string = b"\x03" + der_length(len(point) + 1) + b"\x00" + point
+  body = algorithm + bit_string
+  return b"\x30" + der_length(len(body)) + body
+
+
+if __name__ == "__main__":
+  aes_cbc_cavp()
+  aes_cbc_pkcs5()
+  for name in HASHES:
+    hmac_tests(name)
+    hkdf_tests(name)
+    pbkdf2_tests(name)
+  rsa_oaep()
+  rsa_pss()
+  for curve_name in CURVES:
+    ecdsa(curve_name)
+    ecdh(curve_name)